@@ -1,10 +1,14 @@
 app-title = Cosmic money
 about = About
 view = View
+edit = Edit
+undo = Undo
+redo = Redo
 
 save = Save
 cancel = Cancel
 add = Add
+delete = Delete
 color-scheme-name = placeholder
 save-current-color-scheme = save placeholder
 
@@ -14,12 +18,22 @@ welcome-message = Setup your app
 currency-message = Choose your default currency
 welcome-income-categories = Income categories
 welcome-expense-categories = Expense categories
+set-budget = Set budget
+monthly-budget = Budget for this month
+budgeted = Budgeted
+rolled-over = rolled over
+spent = Spent
+remaining = Remaining
+overspent = Overspent
+rollover = Roll over unused amount
+no-rollover = No rollover
 welcome-initial-accounts = Initial accounts
 setup = Setup
 
 page_accounts = Accounts
 page_categories = Categories
 page_transactions = Transactions
+page_recurring = Recurring
 page_stats = Statistics
 page_settings = Settings
 balance = Balance
@@ -32,6 +46,7 @@ account-description = Account description
 bank-account = Bank account
 initial-value = Initial value
 no-elements = No elements in this list
+loading = Loading…
 
 view-settings = View settings
 add-category = Add category
@@ -44,11 +59,30 @@ expense-categories = Expense categories
 
 transactions = Transactions
 add-transaction = Add transaction
+edit-transaction = Edit transaction
+delete-transaction-confirm = Delete this transaction? This cannot be undone.
 category = Category
 amount = Amount
+invalid-amount = Enter a valid amount
+missing-account-or-category = Choose an account and a category
+transfer-same-account = Choose two different accounts for a transfer
 income = Income
 expense = Expense
+transfer = Transfer
+from-account = From account
+to-account = To account
 note = Note
+payee = Payee
+payees = Payees
+tag = Tag
+tags = Tags
+tags-placeholder = vacation-2026, reimbursable
+all-tags = All tags
+split = Split
+add-split-line = Add split line
+unassigned = Unassigned
+split-mismatch = Split lines must add up to the transaction amount
+split-missing-category = Every split line needs a category
 not-found = Not found
 date = Date
 
@@ -65,4 +99,195 @@ month-10 = October
 month-11 = November
 month-12 = December
 
-currency = Currency
\ No newline at end of file
+currency = Currency
+reporting-currency = Reporting currency
+exchange-rates = Exchange rates
+from-currency = From
+to-currency = To
+rate = Rate
+add-exchange-rate = Add exchange rate
+net-worth = Net worth
+recurring-transactions = Recurring transactions
+add-recurring = Add recurring transaction
+post = Post
+skip = Skip
+next-date = Next
+first-occurrence = First occurrence
+frequency = Frequency
+frequency-daily = Daily
+frequency-weekly = Weekly
+frequency-monthly = Monthly
+frequency-yearly = Yearly
+repeat-every = Repeat every
+day-of-month = Day of month
+ends = Ends
+ends-never = Never ends
+ends-on-date = Ends on
+ends-after-count = After a number of times
+occurrences = Occurrences
+recurring-ended = Ended
+occurrences-left = { $count ->
+    [one] 1 occurrence left
+   *[other] { $count } occurrences left
+}
+every-n-days = { $interval ->
+    [one] Every day
+   *[other] Every { $interval } days
+}
+every-n-weeks = { $interval ->
+    [one] Every week
+   *[other] Every { $interval } weeks
+}
+every-n-months = { $interval ->
+    [one] Every month on day { $day }
+   *[other] Every { $interval } months on day { $day }
+}
+every-n-years = { $interval ->
+    [one] Every year
+   *[other] Every { $interval } years
+}
+parent-category = Parent category
+no-parent = No parent
+total-with-subcategories = Total with subcategories
+account-type = Account type
+account-type-checking = Checking
+account-type-savings = Savings
+account-type-credit-card = Credit card
+account-type-cash = Cash
+account-type-investment = Investment
+account-type-loan = Loan
+amount-owed = Amount owed
+archive = Archive
+unarchive = Unarchive
+archived = archived
+merge-into = Move its transactions, budgets and subcategories to
+no-merge-category = Add another category of the same type first: its transactions have to be moved somewhere.
+reconcile = Reconcile
+last-reconciled = Last reconciled
+statement-date = Statement date
+statement-balance = Statement balance
+statement-amount-owed = Amount owed on the statement
+cleared-balance = Cleared balance
+difference = Difference
+finish-reconciliation = Finish reconciliation
+status = Status
+uncleared = Uncleared
+cleared = Cleared
+reconciled = Reconciled
+page_goals = Goals
+add-goal = Add goal
+goal-name = Goal name
+target-amount = Target amount
+target-date = Target date
+goal-source = Track progress with
+manual-contributions = Manual contributions
+saved = Saved
+goal-reached = Goal reached
+needed-per-month = Needed per month
+projected-completion = Projected completion
+no-contributions-yet = No contributions yet, the completion date can't be projected
+contribution = Contribution
+add-contribution = Add contribution
+remaining-balance = Remaining balance
+loan-terms = { $rate }% interest, { $payments } payments, { $frequency }
+next-payment = Next payment
+principal = principal
+interest = interest
+payment = Payment
+paid = Paid
+loan-paid-off = Paid off
+post-payment = Post payment
+show-schedule = Show schedule
+hide-schedule = Hide schedule
+interest-rate = Annual interest rate (%)
+number-of-payments = Number of payments
+payment-account = Paid from
+interest-category = Interest category
+first-payment = First payment
+loan-terms-missing = A loan needs a number of payments, the account it is paid from and an interest category.
+page_securities = Securities
+add-security = Add security
+symbol = Symbol
+security-name = Name
+security = Security
+show-prices = Show prices
+hide-prices = Hide prices
+price = Price
+add-price = Add price
+no-prices = No prices recorded yet, the latest trade price is used instead
+import-prices = Import prices from a CSV file of date,price lines
+import = Import
+import-prices-error = Line { $line } is not a date,price pair
+import-prices-done = Imported { $count } prices
+trade-buy = Buy
+trade-sell = Sell
+trade-dividend = Dividend
+trade-kind = Trade
+add-trade = Add trade
+quantity = Quantity
+fees = Fees
+market-value = Market value
+cost-basis = Cost basis
+cost-basis-method = Cost basis method
+cost-basis-fifo = First in, first out
+cost-basis-average = Average cost
+unrealized-gain = Unrealized gain
+realized-gain = Realized gain
+dividends = Dividends
+
+transaction-history = Transaction history
+no-history = No changes recorded
+created = Created
+updated = Updated
+deleted = Deleted
+transaction-type = Type
+
+ledgers = Ledgers
+ledger-name = Ledger name
+create-ledger = Create ledger
+open = Open
+open-ledger = open
+delete-ledger-confirm = Delete the ledger { $name } and all of its data?
+ledger-locked = This ledger is locked
+ledger-locked-message = Enter the passphrase of { $name } to open it.
+passphrase = Passphrase
+unlock = Unlock
+encryption = Encryption
+ledger-encrypted = The open ledger is encrypted with a passphrase.
+ledger-not-encrypted = The open ledger is not encrypted.
+new-passphrase = New passphrase
+confirm-passphrase = Confirm passphrase
+set-passphrase = Encrypt ledger
+change-passphrase = Change passphrase
+remove-passphrase = Remove passphrase
+backups = Backups
+keep-backups = A backup is taken whenever the ledger is opened. The newest one of each of the last days, weeks and months is kept:
+backup-days = Days
+backup-weeks = Weeks
+backup-months = Months
+back-up = Back up
+backup-done = Backup written to { $path }
+show-backups = Show backups
+hide-backups = Hide backups
+no-backups = No backups of this ledger yet
+before-migration = before an update
+backup-summary = { $accounts } accounts, { $categories } categories, { $transactions } transactions
+restore = Restore
+restore-backup-confirm = Replace the open ledger with the backup of { $date }? The ledger is backed up first.
+
+ledger-corrupt = This ledger is damaged
+ledger-corrupt-message = { $name } cannot be read anymore. Restore one of its backups or start a new ledger; the damaged file is kept.
+migration-failed = This ledger could not be updated
+migration-failed-message = { $name } could not be updated for this version of the app. It was backed up before the update, so you can restore a backup or start a new ledger.
+data-dir-read-only = The data directory is read-only
+data-dir-read-only-message = Ledgers cannot be saved. Make the data directory writable, then try again.
+data-dir-missing = The data directory could not be found
+data-dir-missing-message = Ledgers are kept under your home directory, which could not be found. Make sure it is set, then start the app again.
+ledger-not-opened = This ledger could not be opened
+ledger-not-opened-message = { $name } could not be opened. Try again, restore one of its backups or start a new ledger.
+try-again = Try again
+restore-backup = Restore a backup
+start-new-ledger = Start a new ledger
+
+search-transactions = Search notes, payees and categories
+no-search-results = No transactions match the search
@@ -0,0 +1,23 @@
+CREATE TEMP TABLE IF NOT EXISTS migration_context (reporting_currency_id INTEGER);
+CREATE TEMP VIEW legacy_factor AS
+SELECT COALESCE((
+  SELECT CASE currency.scale WHEN 0 THEN 1 WHEN 1 THEN 10 WHEN 3 THEN 1000 ELSE 100 END
+  FROM currency
+  WHERE currency.id = (SELECT reporting_currency_id FROM migration_context)
+), 100) AS factor;
+
+ALTER TABLE money_transaction ADD COLUMN amount_real REAL NOT NULL DEFAULT 0;
+UPDATE money_transaction
+SET amount_real = CAST(amount AS REAL) / (SELECT factor FROM legacy_factor);
+ALTER TABLE money_transaction DROP COLUMN amount;
+ALTER TABLE money_transaction RENAME COLUMN amount_real TO amount;
+
+ALTER TABLE account ADD COLUMN initial_balance_real REAL NOT NULL DEFAULT 0;
+UPDATE account
+SET initial_balance_real = CAST(initial_balance AS REAL) / (SELECT factor FROM legacy_factor);
+ALTER TABLE account DROP COLUMN initial_balance;
+ALTER TABLE account RENAME COLUMN initial_balance_real TO initial_balance;
+
+DROP VIEW legacy_factor;
+
+ALTER TABLE currency DROP COLUMN scale;
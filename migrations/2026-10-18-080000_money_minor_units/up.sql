@@ -0,0 +1,30 @@
+-- Amounts are stored as integer minor units of their currency (e.g. cents),
+-- the scale of a currency being its number of decimal places.
+ALTER TABLE currency ADD COLUMN scale INTEGER NOT NULL DEFAULT 2;
+UPDATE currency SET scale = 0 WHERE symbol IN ('JPY', 'KRW', 'ISK', 'CLP', 'VND');
+
+-- Existing amounts are in the reporting currency chosen in the settings,
+-- which the store hands over in the temporary `migration_context` table.
+-- Without it, as when migrating outside the app, two decimal places are
+-- assumed.
+CREATE TEMP TABLE IF NOT EXISTS migration_context (reporting_currency_id INTEGER);
+CREATE TEMP VIEW legacy_factor AS
+SELECT COALESCE((
+  SELECT CASE currency.scale WHEN 0 THEN 1 WHEN 1 THEN 10 WHEN 3 THEN 1000 ELSE 100 END
+  FROM currency
+  WHERE currency.id = (SELECT reporting_currency_id FROM migration_context)
+), 100) AS factor;
+
+ALTER TABLE account ADD COLUMN initial_balance_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE account
+SET initial_balance_minor = CAST(ROUND(initial_balance * (SELECT factor FROM legacy_factor)) AS INTEGER);
+ALTER TABLE account DROP COLUMN initial_balance;
+ALTER TABLE account RENAME COLUMN initial_balance_minor TO initial_balance;
+
+ALTER TABLE money_transaction ADD COLUMN amount_minor BIGINT NOT NULL DEFAULT 0;
+UPDATE money_transaction
+SET amount_minor = CAST(ROUND(amount * (SELECT factor FROM legacy_factor)) AS INTEGER);
+ALTER TABLE money_transaction DROP COLUMN amount;
+ALTER TABLE money_transaction RENAME COLUMN amount_minor TO amount;
+
+DROP VIEW legacy_factor;
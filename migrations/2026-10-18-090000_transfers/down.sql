@@ -0,0 +1,19 @@
+DELETE FROM money_transaction WHERE transfer_peer IS NOT NULL;
+
+CREATE TABLE money_transaction_old (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  bank_account INTEGER NOT NULL,
+  transaction_category INTEGER NOT NULL,
+  description VARCHAR NOT NULL,
+  transaction_date DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  is_expense BOOLEAN DEFAULT TRUE NOT NULL,
+  amount BIGINT NOT NULL DEFAULT 0,
+  FOREIGN KEY (bank_account) REFERENCES account(id),
+  FOREIGN KEY (transaction_category) REFERENCES category(id)
+);
+
+INSERT INTO money_transaction_old (id, bank_account, transaction_category, description, transaction_date, is_expense, amount)
+  SELECT id, bank_account, transaction_category, description, transaction_date, is_expense, amount FROM money_transaction;
+
+DROP TABLE money_transaction;
+ALTER TABLE money_transaction_old RENAME TO money_transaction;
@@ -0,0 +1,21 @@
+-- Transfers have no category, so `transaction_category` becomes nullable.
+-- The two rows of a transfer point at each other through `transfer_peer`.
+CREATE TABLE money_transaction_new (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  bank_account INTEGER NOT NULL,
+  transaction_category INTEGER,
+  description VARCHAR NOT NULL,
+  transaction_date DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  is_expense BOOLEAN DEFAULT TRUE NOT NULL,
+  amount BIGINT NOT NULL DEFAULT 0,
+  transfer_peer INTEGER,
+  FOREIGN KEY (bank_account) REFERENCES account(id),
+  FOREIGN KEY (transaction_category) REFERENCES category(id),
+  FOREIGN KEY (transfer_peer) REFERENCES money_transaction(id)
+);
+
+INSERT INTO money_transaction_new (id, bank_account, transaction_category, description, transaction_date, is_expense, amount)
+  SELECT id, bank_account, transaction_category, description, transaction_date, is_expense, amount FROM money_transaction;
+
+DROP TABLE money_transaction;
+ALTER TABLE money_transaction_new RENAME TO money_transaction;
@@ -0,0 +1,3 @@
+DROP INDEX exchange_rate_lookup;
+DROP TABLE exchange_rate;
+ALTER TABLE account DROP COLUMN currency_id;
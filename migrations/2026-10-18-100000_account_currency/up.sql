@@ -0,0 +1,15 @@
+-- Accounts created before this migration have no currency and keep using
+-- the reporting currency chosen in the settings.
+ALTER TABLE account ADD COLUMN currency_id INTEGER REFERENCES currency(id);
+
+CREATE TABLE exchange_rate (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  from_currency INTEGER NOT NULL,
+  to_currency INTEGER NOT NULL,
+  rate DOUBLE NOT NULL,
+  rate_date DATE NOT NULL,
+  FOREIGN KEY (from_currency) REFERENCES currency(id),
+  FOREIGN KEY (to_currency) REFERENCES currency(id)
+);
+
+CREATE INDEX exchange_rate_lookup ON exchange_rate (from_currency, to_currency, rate_date);
@@ -0,0 +1,19 @@
+-- A rule that repeats an income or expense. `next_date` is the next
+-- occurrence to post and becomes NULL once the rule has run out, either
+-- past `end_date` or after `remaining_occurrences` reached zero.
+CREATE TABLE recurring_transaction (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  bank_account INTEGER NOT NULL,
+  transaction_category INTEGER NOT NULL,
+  description VARCHAR NOT NULL,
+  is_expense BOOLEAN DEFAULT TRUE NOT NULL,
+  amount BIGINT NOT NULL DEFAULT 0,
+  frequency VARCHAR NOT NULL,
+  frequency_interval INTEGER NOT NULL DEFAULT 1,
+  day_of_month INTEGER,
+  next_date DATE,
+  end_date DATE,
+  remaining_occurrences INTEGER,
+  FOREIGN KEY (bank_account) REFERENCES account(id),
+  FOREIGN KEY (transaction_category) REFERENCES category(id)
+);
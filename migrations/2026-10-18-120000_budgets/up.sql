@@ -0,0 +1,12 @@
+-- One budget per category and month. `budget_month` is the first day of the
+-- month and `amount` is in minor units of the reporting currency. With
+-- `rollover` set, whatever was left unspent in the previous month is added.
+CREATE TABLE budget (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  budget_category INTEGER NOT NULL,
+  budget_month DATE NOT NULL,
+  amount BIGINT NOT NULL DEFAULT 0,
+  rollover BOOLEAN DEFAULT FALSE NOT NULL,
+  FOREIGN KEY (budget_category) REFERENCES category(id),
+  UNIQUE (budget_category, budget_month)
+);
@@ -0,0 +1,3 @@
+DROP INDEX transaction_tag_by_tag;
+DROP TABLE transaction_tag;
+DROP TABLE tag;
@@ -0,0 +1,14 @@
+CREATE TABLE tag (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  name VARCHAR NOT NULL UNIQUE
+);
+
+CREATE TABLE transaction_tag (
+  transaction_id INTEGER NOT NULL,
+  tag_id INTEGER NOT NULL,
+  PRIMARY KEY (transaction_id, tag_id),
+  FOREIGN KEY (transaction_id) REFERENCES money_transaction(id),
+  FOREIGN KEY (tag_id) REFERENCES tag(id)
+);
+
+CREATE INDEX transaction_tag_by_tag ON transaction_tag (tag_id);
@@ -0,0 +1,2 @@
+DROP INDEX transaction_split_by_parent;
+DROP TABLE transaction_split;
@@ -0,0 +1,13 @@
+-- Split lines of a transaction. Their amounts add up to the parent amount,
+-- and category totals count them instead of the parent.
+CREATE TABLE transaction_split (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  parent_transaction INTEGER NOT NULL,
+  split_category INTEGER NOT NULL,
+  amount BIGINT NOT NULL DEFAULT 0,
+  note VARCHAR NOT NULL DEFAULT '',
+  FOREIGN KEY (parent_transaction) REFERENCES money_transaction(id),
+  FOREIGN KEY (split_category) REFERENCES category(id)
+);
+
+CREATE INDEX transaction_split_by_parent ON transaction_split (parent_transaction);
@@ -0,0 +1,2 @@
+ALTER TABLE money_transaction DROP COLUMN payee_id;
+DROP TABLE payee;
@@ -0,0 +1,12 @@
+-- The default category and account are the ones last used with the payee
+-- and prefill the transaction form.
+CREATE TABLE payee (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  name VARCHAR NOT NULL UNIQUE,
+  default_category INTEGER,
+  default_account INTEGER,
+  FOREIGN KEY (default_category) REFERENCES category(id),
+  FOREIGN KEY (default_account) REFERENCES account(id)
+);
+
+ALTER TABLE money_transaction ADD COLUMN payee_id INTEGER REFERENCES payee(id);
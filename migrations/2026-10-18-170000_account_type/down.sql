@@ -0,0 +1 @@
+ALTER TABLE account DROP COLUMN account_type;
@@ -0,0 +1,3 @@
+-- `account_type` was declared in the schema but never created. Existing
+-- accounts become checking accounts.
+ALTER TABLE account ADD COLUMN account_type VARCHAR NOT NULL DEFAULT 'checking';
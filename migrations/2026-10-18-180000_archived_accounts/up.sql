@@ -0,0 +1,3 @@
+-- Archived accounts keep their transactions but are no longer offered when
+-- entering new ones.
+ALTER TABLE account ADD COLUMN is_archived BOOLEAN NOT NULL DEFAULT 0;
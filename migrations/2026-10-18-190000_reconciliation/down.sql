@@ -0,0 +1,2 @@
+DROP TABLE reconciliation;
+ALTER TABLE money_transaction DROP COLUMN cleared_status;
@@ -0,0 +1,11 @@
+-- Every transaction starts uncleared; ticking it off against a bank statement
+-- clears it, and finishing the reconciliation locks it as reconciled.
+ALTER TABLE money_transaction ADD COLUMN cleared_status VARCHAR NOT NULL DEFAULT 'uncleared';
+
+CREATE TABLE reconciliation (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  bank_account INTEGER NOT NULL,
+  statement_date DATE NOT NULL,
+  statement_balance BIGINT NOT NULL,
+  FOREIGN KEY (bank_account) REFERENCES account(id)
+);
@@ -0,0 +1,2 @@
+DROP TABLE goal_contribution;
+DROP TABLE goal;
@@ -0,0 +1,20 @@
+-- A goal either follows the balance of a linked account or adds up the
+-- contributions recorded for it. Amounts are in the reporting currency.
+CREATE TABLE goal (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  name VARCHAR NOT NULL,
+  target_amount BIGINT NOT NULL,
+  target_date DATE NOT NULL,
+  linked_account INTEGER,
+  FOREIGN KEY (linked_account) REFERENCES account(id)
+);
+
+CREATE TABLE goal_contribution (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  goal_id INTEGER NOT NULL,
+  contribution_date DATE NOT NULL,
+  amount BIGINT NOT NULL,
+  FOREIGN KEY (goal_id) REFERENCES goal(id)
+);
+
+CREATE INDEX goal_contribution_by_goal ON goal_contribution (goal_id);
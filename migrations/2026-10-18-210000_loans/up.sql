@@ -0,0 +1,33 @@
+-- The terms of a loan account. The loan account opens with the principal as
+-- the amount owed; every posted payment moves the principal part in from
+-- `payment_account` and books the interest part under `interest_category`.
+CREATE TABLE loan (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  loan_account INTEGER NOT NULL UNIQUE,
+  principal BIGINT NOT NULL,
+  annual_rate DOUBLE NOT NULL,
+  term INTEGER NOT NULL,
+  frequency VARCHAR NOT NULL,
+  first_payment DATE NOT NULL,
+  payment_account INTEGER NOT NULL,
+  interest_category INTEGER NOT NULL,
+  FOREIGN KEY (loan_account) REFERENCES account(id),
+  FOREIGN KEY (payment_account) REFERENCES account(id),
+  FOREIGN KEY (interest_category) REFERENCES category(id)
+);
+
+CREATE TABLE loan_payment (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  loan_id INTEGER NOT NULL,
+  payment_number INTEGER NOT NULL,
+  payment_date DATE NOT NULL,
+  principal BIGINT NOT NULL,
+  interest BIGINT NOT NULL,
+  transfer_transaction INTEGER NOT NULL,
+  interest_transaction INTEGER,
+  FOREIGN KEY (loan_id) REFERENCES loan(id),
+  FOREIGN KEY (transfer_transaction) REFERENCES money_transaction(id),
+  FOREIGN KEY (interest_transaction) REFERENCES money_transaction(id)
+);
+
+CREATE INDEX loan_payment_by_loan ON loan_payment (loan_id);
@@ -0,0 +1,4 @@
+DROP TABLE investment_trade;
+DROP TABLE security_price;
+DROP TABLE security;
+ALTER TABLE account DROP COLUMN cost_basis_method;
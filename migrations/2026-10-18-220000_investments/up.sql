@@ -0,0 +1,37 @@
+-- Investment accounts hold securities next to their cash. Each trade moves
+-- cash through `cash_transaction`; prices are in the currency of the account
+-- holding the security.
+ALTER TABLE account ADD COLUMN cost_basis_method VARCHAR NOT NULL DEFAULT 'fifo';
+
+CREATE TABLE security (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  symbol VARCHAR NOT NULL UNIQUE,
+  name VARCHAR NOT NULL
+);
+
+CREATE TABLE security_price (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  security_id INTEGER NOT NULL,
+  price_date DATE NOT NULL,
+  price DOUBLE NOT NULL,
+  UNIQUE (security_id, price_date),
+  FOREIGN KEY (security_id) REFERENCES security(id)
+);
+
+CREATE TABLE investment_trade (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  bank_account INTEGER NOT NULL,
+  security_id INTEGER NOT NULL,
+  trade_kind VARCHAR NOT NULL,
+  trade_date DATE NOT NULL,
+  quantity DOUBLE NOT NULL,
+  price DOUBLE NOT NULL,
+  fees BIGINT NOT NULL,
+  amount BIGINT NOT NULL,
+  cash_transaction INTEGER NOT NULL,
+  FOREIGN KEY (bank_account) REFERENCES account(id),
+  FOREIGN KEY (security_id) REFERENCES security(id),
+  FOREIGN KEY (cash_transaction) REFERENCES money_transaction(id)
+);
+
+CREATE INDEX investment_trade_by_account ON investment_trade (bank_account);
@@ -0,0 +1,2 @@
+DROP TABLE change_journal;
+DROP TABLE undo_log;
@@ -0,0 +1,15 @@
+-- Every change to the other tables is journaled by triggers, installed when
+-- the store opens, as the SQL statement that reverses it. A step groups the
+-- statements written by one user action; undoing a step runs them backwards
+-- and keeps the statements that redo it in their place.
+CREATE TABLE undo_log (
+  seq INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  sql_text TEXT NOT NULL
+);
+
+CREATE TABLE change_journal (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  first_seq INTEGER NOT NULL,
+  last_seq INTEGER NOT NULL,
+  is_undone BOOLEAN NOT NULL DEFAULT 0
+);
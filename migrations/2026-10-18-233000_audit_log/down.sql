@@ -0,0 +1,7 @@
+DROP TABLE audit_log;
+ALTER TABLE money_transaction DROP COLUMN updated_at;
+ALTER TABLE money_transaction DROP COLUMN created_at;
+ALTER TABLE category DROP COLUMN updated_at;
+ALTER TABLE category DROP COLUMN created_at;
+ALTER TABLE account DROP COLUMN updated_at;
+ALTER TABLE account DROP COLUMN created_at;
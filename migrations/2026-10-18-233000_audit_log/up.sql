@@ -0,0 +1,28 @@
+-- Existing rows are stamped with the time of the upgrade, transactions
+-- with their own date.
+ALTER TABLE account ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE account ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE category ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE category ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE money_transaction ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE money_transaction ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+
+UPDATE account SET created_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP;
+UPDATE category SET created_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP;
+UPDATE money_transaction SET created_at = transaction_date, updated_at = transaction_date;
+
+-- One row per created or deleted row, and per changed field of an updated
+-- one, with the values as text.
+CREATE TABLE audit_log (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  audited_table VARCHAR NOT NULL,
+  row_id INTEGER NOT NULL,
+  action VARCHAR NOT NULL,
+  field_name VARCHAR,
+  old_value TEXT,
+  new_value TEXT,
+  changed_by VARCHAR NOT NULL,
+  changed_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX audit_log_row ON audit_log (audited_table, row_id);
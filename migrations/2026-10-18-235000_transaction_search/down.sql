@@ -0,0 +1,14 @@
+DROP TRIGGER category_search_delete;
+DROP TRIGGER category_search_update;
+DROP TRIGGER category_search_insert;
+DROP TRIGGER payee_search_delete;
+DROP TRIGGER payee_search_update;
+DROP TRIGGER payee_search_insert;
+DROP TRIGGER transaction_split_search_delete;
+DROP TRIGGER transaction_split_search_update;
+DROP TRIGGER transaction_split_search_insert;
+DROP TRIGGER money_transaction_search_delete;
+DROP TRIGGER money_transaction_search_update;
+DROP TRIGGER money_transaction_search_insert;
+DROP TABLE transaction_search;
+DROP VIEW searchable_transaction;
@@ -0,0 +1,132 @@
+-- The words of a transaction that search finds it by: its note, its payee
+-- and the names of its category and split categories.
+CREATE VIEW searchable_transaction AS
+SELECT
+  t.id AS id,
+  t.description AS description,
+  COALESCE(p.name, '') AS payee,
+  COALESCE((
+    SELECT group_concat(c.name, ' ')
+    FROM category c
+    WHERE c.id = t.transaction_category
+      OR c.id IN (
+        SELECT s.split_category FROM transaction_split s WHERE s.parent_transaction = t.id
+      )
+  ), '') AS category
+FROM money_transaction t
+LEFT JOIN payee p ON p.id = t.payee_id;
+
+-- Rows are keyed by the transaction id. Indexing word prefixes of two and
+-- three characters keeps the short prefixes typed first fast.
+CREATE VIRTUAL TABLE transaction_search USING fts5(
+  description,
+  payee,
+  category,
+  tokenize = 'unicode61 remove_diacritics 2',
+  prefix = '2 3'
+);
+
+INSERT INTO transaction_search (rowid, description, payee, category)
+SELECT id, description, payee, category FROM searchable_transaction;
+
+-- The triggers index a transaction again whenever one of its words may have
+-- changed, undo and redo included. Payees and categories are indexed again
+-- when inserted too, as undoing a deletion can restore them after the
+-- transactions that use them.
+CREATE TRIGGER money_transaction_search_insert AFTER INSERT ON money_transaction BEGIN
+  INSERT INTO transaction_search (rowid, description, payee, category)
+  SELECT id, description, payee, category FROM searchable_transaction WHERE id = new.id;
+END;
+
+CREATE TRIGGER money_transaction_search_update
+AFTER UPDATE OF description, payee_id, transaction_category ON money_transaction BEGIN
+  DELETE FROM transaction_search WHERE rowid = old.id;
+  INSERT INTO transaction_search (rowid, description, payee, category)
+  SELECT id, description, payee, category FROM searchable_transaction WHERE id = new.id;
+END;
+
+CREATE TRIGGER money_transaction_search_delete AFTER DELETE ON money_transaction BEGIN
+  DELETE FROM transaction_search WHERE rowid = old.id;
+END;
+
+CREATE TRIGGER transaction_split_search_insert AFTER INSERT ON transaction_split BEGIN
+  DELETE FROM transaction_search WHERE rowid = new.parent_transaction;
+  INSERT INTO transaction_search (rowid, description, payee, category)
+  SELECT id, description, payee, category FROM searchable_transaction
+  WHERE id = new.parent_transaction;
+END;
+
+CREATE TRIGGER transaction_split_search_update
+AFTER UPDATE OF parent_transaction, split_category ON transaction_split BEGIN
+  DELETE FROM transaction_search WHERE rowid IN (old.parent_transaction, new.parent_transaction);
+  INSERT INTO transaction_search (rowid, description, payee, category)
+  SELECT id, description, payee, category FROM searchable_transaction
+  WHERE id IN (old.parent_transaction, new.parent_transaction);
+END;
+
+CREATE TRIGGER transaction_split_search_delete AFTER DELETE ON transaction_split BEGIN
+  DELETE FROM transaction_search WHERE rowid = old.parent_transaction;
+  INSERT INTO transaction_search (rowid, description, payee, category)
+  SELECT id, description, payee, category FROM searchable_transaction
+  WHERE id = old.parent_transaction;
+END;
+
+CREATE TRIGGER payee_search_insert AFTER INSERT ON payee BEGIN
+  DELETE FROM transaction_search
+  WHERE rowid IN (SELECT id FROM money_transaction WHERE payee_id = new.id);
+  INSERT INTO transaction_search (rowid, description, payee, category)
+  SELECT id, description, payee, category FROM searchable_transaction
+  WHERE id IN (SELECT id FROM money_transaction WHERE payee_id = new.id);
+END;
+
+CREATE TRIGGER payee_search_update AFTER UPDATE OF name ON payee BEGIN
+  DELETE FROM transaction_search
+  WHERE rowid IN (SELECT id FROM money_transaction WHERE payee_id = new.id);
+  INSERT INTO transaction_search (rowid, description, payee, category)
+  SELECT id, description, payee, category FROM searchable_transaction
+  WHERE id IN (SELECT id FROM money_transaction WHERE payee_id = new.id);
+END;
+
+CREATE TRIGGER payee_search_delete AFTER DELETE ON payee BEGIN
+  DELETE FROM transaction_search
+  WHERE rowid IN (SELECT id FROM money_transaction WHERE payee_id = old.id);
+  INSERT INTO transaction_search (rowid, description, payee, category)
+  SELECT id, description, payee, category FROM searchable_transaction
+  WHERE id IN (SELECT id FROM money_transaction WHERE payee_id = old.id);
+END;
+
+CREATE TRIGGER category_search_insert AFTER INSERT ON category BEGIN
+  DELETE FROM transaction_search WHERE rowid IN (
+    SELECT id FROM money_transaction WHERE transaction_category = new.id
+    UNION SELECT parent_transaction FROM transaction_split WHERE split_category = new.id
+  );
+  INSERT INTO transaction_search (rowid, description, payee, category)
+  SELECT id, description, payee, category FROM searchable_transaction WHERE id IN (
+    SELECT id FROM money_transaction WHERE transaction_category = new.id
+    UNION SELECT parent_transaction FROM transaction_split WHERE split_category = new.id
+  );
+END;
+
+CREATE TRIGGER category_search_update AFTER UPDATE OF name ON category BEGIN
+  DELETE FROM transaction_search WHERE rowid IN (
+    SELECT id FROM money_transaction WHERE transaction_category = new.id
+    UNION SELECT parent_transaction FROM transaction_split WHERE split_category = new.id
+  );
+  INSERT INTO transaction_search (rowid, description, payee, category)
+  SELECT id, description, payee, category FROM searchable_transaction WHERE id IN (
+    SELECT id FROM money_transaction WHERE transaction_category = new.id
+    UNION SELECT parent_transaction FROM transaction_split WHERE split_category = new.id
+  );
+END;
+
+CREATE TRIGGER category_search_delete AFTER DELETE ON category BEGIN
+  DELETE FROM transaction_search WHERE rowid IN (
+    SELECT id FROM money_transaction WHERE transaction_category = old.id
+    UNION SELECT parent_transaction FROM transaction_split WHERE split_category = old.id
+  );
+  INSERT INTO transaction_search (rowid, description, payee, category)
+  SELECT id, description, payee, category FROM searchable_transaction WHERE id IN (
+    SELECT id FROM money_transaction WHERE transaction_category = old.id
+    UNION SELECT parent_transaction FROM transaction_split WHERE split_category = old.id
+  );
+END;
@@ -0,0 +1,2 @@
+ALTER TABLE recurring_transaction DROP COLUMN occurrence_index;
+ALTER TABLE recurring_transaction DROP COLUMN start_date;
@@ -0,0 +1,7 @@
+-- Occurrences are counted from `start_date`; `occurrence_index` is the
+-- number of the one on `next_date`. Existing rules start over from their
+-- next occurrence, and those that have ended from the day of the upgrade.
+ALTER TABLE recurring_transaction ADD COLUMN start_date DATE NOT NULL DEFAULT '1970-01-01';
+ALTER TABLE recurring_transaction ADD COLUMN occurrence_index INTEGER NOT NULL DEFAULT 0;
+
+UPDATE recurring_transaction SET start_date = COALESCE(next_date, CURRENT_DATE);
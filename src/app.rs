@@ -4,9 +4,13 @@ use std::collections::HashMap;
 
 use crate::config::Config;
 use crate::core::nav::NavPage;
-use crate::{fl, pages};
+use crate::errors::DataStoreError;
+use crate::{data, fl, ledger, pages, STORE};
 use cosmic::app::{self, Core, Task};
-use cosmic::iced::{Alignment, Length};
+use cosmic::iced::keyboard::{Key, Modifiers};
+use cosmic::iced::{event, keyboard, Alignment, Event, Length, Subscription};
+use cosmic::widget::menu::action::MenuAction as _;
+use cosmic::widget::menu::key_bind::{KeyBind, Modifier};
 use cosmic::widget::{self, menu, nav_bar};
 use cosmic::{cosmic_theme, theme, Application, ApplicationExt, Element};
 
@@ -24,9 +28,27 @@ pub struct MoneyManager {
     key_binds: HashMap<menu::KeyBind, MenuAction>,
     /// A model that contains all of the pages assigned to the nav bar panel.
     nav: nav_bar::Model,
+    /// Whether the change journal has a step to undo or to redo.
+    can_undo: bool,
+    can_redo: bool,
+    /// The pages, built once the ledger is open: an encrypted ledger stays
+    /// locked until its passphrase is entered.
+    pages: Option<Pages>,
+    unlock: pages::unlock::Unlock,
+    /// Shown instead of the pages when the ledger failed to open at start.
+    recovery: Option<pages::recovery::Recovery>,
+    /// The errors the pages ran into.
+    toasts: widget::toaster::Toasts<Message>,
+}
 
+/// The pages assigned to the nav bar panel, and the welcome page.
+#[derive(Default)]
+pub struct Pages {
     pub accounts: pages::accounts::Accounts,
     pub categories: pages::categories::Categories,
+    pub recurring: pages::recurring::Recurring,
+    pub goals: pages::goals::Goals,
+    pub securities: pages::securities::Securities,
     pub settings: pages::settings::Settings,
     pub transactions: pages::transactions::Transactions,
     pub welcome: pages::welcome::Welcome,
@@ -36,26 +58,82 @@ pub struct MoneyManager {
 pub enum Message {
     LaunchUrl(String),
     ToggleContextPage(ContextPage),
+    ShowContextPage(ContextPage),
+    Key(Modifiers, Key),
+    Undo,
+    Redo,
+    /// Undo or redo ran, reporting whether it changed anything.
+    HistoryStepped(Result<bool, String>),
+    /// The change journal was checkpointed, reporting whether there is a
+    /// step to undo and one to redo.
+    HistoryUpdated(bool, bool),
+    LedgerOpened,
+    Unlocked,
+    Recovered,
+    /// A change queued by a page ran. A failure is shown as a toast.
+    ChangeFinished(Result<(), DataStoreError>),
+    ShowError(String),
+    CloseToast(widget::toaster::ToastId),
 
     Accounts(pages::accounts::AccountsMessage),
     Categories(pages::categories::CategoriesMessage),
     Transactions(pages::transactions::TransactionMessage),
+    Recurring(pages::recurring::RecurringMessage),
+    Goals(pages::goals::GoalsMessage),
+    Securities(pages::securities::SecuritiesMessage),
     Settings(pages::settings::SettingsMessage),
     Welcome(pages::welcome::WelcomeMessage),
+    Unlock(pages::unlock::UnlockMessage),
+    Recovery(pages::recovery::RecoveryMessage),
 
     GoToAccounts,
 }
 
+impl Message {
+    /// Whether the message reports that the store changed, so the changes
+    /// made since the last checkpoint form a step to undo. Undo and redo
+    /// and opening a ledger change what there is to undo and redo.
+    fn finishes_change(&self) -> bool {
+        use pages::accounts::AccountsMessage;
+        use pages::securities::SecuritiesMessage;
+        use pages::transactions::TransactionMessage;
+
+        matches!(
+            self,
+            Message::ChangeFinished(Ok(()))
+                | Message::HistoryStepped(Ok(true))
+                | Message::LedgerOpened
+                | Message::Unlocked
+                | Message::Recovered
+                | Message::Transactions(TransactionMessage::Saved(Ok(())))
+                | Message::Accounts(
+                    AccountsMessage::Reconciled(Ok(()))
+                        | AccountsMessage::LoanPaymentPosted(_, Ok(()))
+                        | AccountsMessage::TradeSubmitted(Ok(()))
+                )
+                | Message::Securities(SecuritiesMessage::PricesSaved(_))
+        )
+    }
+}
+
+/// Shows an error as a toast over the page that ran into it.
+pub fn error_toast(error: impl std::fmt::Display) -> cosmic::Task<Message> {
+    let message = error.to_string();
+    cosmic::Task::perform(async {}, move |_| Message::ShowError(message))
+}
+
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub enum ContextPage {
     #[default]
     About,
+    TransactionHistory,
 }
 
 impl ContextPage {
     fn title(&self) -> String {
         match self {
             Self::About => fl!("about"),
+            Self::TransactionHistory => fl!("transaction-history"),
         }
     }
 }
@@ -63,6 +141,8 @@ impl ContextPage {
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum MenuAction {
     About,
+    Undo,
+    Redo,
 }
 
 impl menu::action::MenuAction for MenuAction {
@@ -71,13 +151,16 @@ impl menu::action::MenuAction for MenuAction {
     fn message(&self) -> Self::Message {
         match self {
             MenuAction::About => Message::ToggleContextPage(ContextPage::About),
+            MenuAction::Undo => Message::Undo,
+            MenuAction::Redo => Message::Redo,
         }
     }
 }
 
 impl Application for MoneyManager {
     type Executor = cosmic::executor::Default;
-    type Flags = ();
+    /// Why the ledger could not be opened at start, if it could not.
+    type Flags = Option<DataStoreError>;
     type Message = Message;
     const APP_ID: &'static str = "com.francescogaglione.cosmicmoney";
 
@@ -93,7 +176,7 @@ impl Application for MoneyManager {
         Some(&self.nav)
     }
 
-    fn init(mut core: Core, _flags: Self::Flags) -> (Self, Task<Self::Message>) {
+    fn init(mut core: Core, startup_error: Self::Flags) -> (Self, Task<Self::Message>) {
         let mut nav = nav_bar::Model::default();
 
         for &nav_page in NavPage::all() {
@@ -117,28 +200,57 @@ impl Application for MoneyManager {
         let mut app = MoneyManager {
             core,
             context_page: ContextPage::default(),
-            key_binds: HashMap::new(),
+            key_binds: key_binds(),
             nav,
-            accounts: pages::accounts::Accounts::default(),
-            categories: pages::categories::Categories::default(),
-            settings: pages::settings::Settings::default(),
-            transactions: pages::transactions::Transactions::default(),
-            welcome: pages::welcome::Welcome::default(),
+            can_undo: false,
+            can_redo: false,
+            pages: (startup_error.is_none() && !ledger::is_encrypted(&ledger::current_ledger()))
+                .then(Pages::default),
+            unlock: pages::unlock::Unlock::default(),
+            recovery: startup_error.map(pages::recovery::Recovery::new),
+            toasts: widget::toaster::Toasts::new(Message::CloseToast),
         };
 
-        let command = app.update_title();
+        let mut commands = vec![app.update_history(), app.update_title()];
+        if app.pages.is_some() {
+            commands.push(Self::refresh_pages());
+        }
+        if app.recovery.is_some() {
+            commands.push(Task::perform(async {}, |_| {
+                cosmic::app::Message::App(Message::Recovery(
+                    pages::recovery::RecoveryMessage::Update,
+                ))
+            }));
+        }
 
-        (app, command)
+        (app, Task::batch(commands))
     }
 
     fn header_start(&self) -> Vec<Element<Self::Message>> {
-        let menu_bar = menu::bar(vec![menu::Tree::with_children(
-            menu::root(fl!("view")),
-            menu::items(
-                &self.key_binds,
-                vec![menu::Item::Button(fl!("about"), MenuAction::About)],
+        let undo = if self.can_undo {
+            menu::Item::Button(fl!("undo"), MenuAction::Undo)
+        } else {
+            menu::Item::ButtonDisabled(fl!("undo"), MenuAction::Undo)
+        };
+        let redo = if self.can_redo {
+            menu::Item::Button(fl!("redo"), MenuAction::Redo)
+        } else {
+            menu::Item::ButtonDisabled(fl!("redo"), MenuAction::Redo)
+        };
+
+        let menu_bar = menu::bar(vec![
+            menu::Tree::with_children(
+                menu::root(fl!("edit")),
+                menu::items(&self.key_binds, vec![undo, redo]),
+            ),
+            menu::Tree::with_children(
+                menu::root(fl!("view")),
+                menu::items(
+                    &self.key_binds,
+                    vec![menu::Item::Button(fl!("about"), MenuAction::About)],
+                ),
             ),
-        )]);
+        ]);
 
         vec![menu_bar.into()]
     }
@@ -149,16 +261,22 @@ impl Application for MoneyManager {
         let nav_page = self.nav.data::<NavPage>(entity).unwrap_or_default();
         let config = Config::load();
 
-        widget::column::with_children(vec![if !config.1.is_user_initialized {
-            self.welcome.view().map(Message::Welcome)
-        } else {
-            nav_page.view(self)
+        let content = widget::column::with_children(vec![match &self.pages {
+            None => match &self.recovery {
+                Some(recovery) => recovery.view().map(Message::Recovery),
+                None => self.unlock.view().map(Message::Unlock),
+            },
+            Some(pages) if !config.1.is_user_initialized => {
+                pages.welcome.view().map(Message::Welcome)
+            }
+            Some(pages) => nav_page.view(pages),
         }])
         .padding(spacing.space_xs)
         .width(Length::Fill)
         .height(Length::Fill)
-        .align_x(Alignment::Center)
-        .into()
+        .align_x(Alignment::Center);
+
+        widget::toaster(&self.toasts, content)
     }
 
     fn update(
@@ -166,6 +284,7 @@ impl Application for MoneyManager {
         message: Self::Message,
     ) -> cosmic::iced::Task<app::Message<Self::Message>> {
         let mut commands = vec![];
+        let finishes_change = message.finishes_change();
         match message {
             Message::LaunchUrl(url) => {
                 let _result = open::that_detached(url);
@@ -183,37 +302,117 @@ impl Application for MoneyManager {
                 // Set the title of the context drawer.
                 self.set_context_title(context_page.title());
             }
-            Message::Accounts(message) => {
-                commands.push(self.accounts.update(message).map(cosmic::app::Message::App))
+            Message::ShowContextPage(context_page) => {
+                self.context_page = context_page;
+                self.core.window.show_context = true;
+                self.set_context_title(context_page.title());
             }
-            Message::Settings(message) => {
-                commands.push(self.settings.update(message).map(cosmic::app::Message::App))
+            Message::Key(modifiers, key) => {
+                let action = self
+                    .key_binds
+                    .iter()
+                    .find(|(key_bind, _)| key_bind.matches(modifiers, &key))
+                    .map(|(_, action)| *action);
+                if let Some(action) = action {
+                    return self.update(action.message());
+                }
             }
-            Message::Categories(message) => commands.push(
-                self.categories
-                    .update(message)
-                    .map(cosmic::app::Message::App),
+            Message::Undo if self.can_undo => commands.push(
+                data::request(
+                    &STORE,
+                    |store| store.undo(),
+                    |stepped| Message::HistoryStepped(stepped.map_err(|e| e.to_string())),
+                )
+                .map(cosmic::app::Message::App),
             ),
-            Message::Transactions(message) => commands.push(
-                self.transactions
-                    .update(message)
-                    .map(cosmic::app::Message::App),
+            Message::Redo if self.can_redo => commands.push(
+                data::request(
+                    &STORE,
+                    |store| store.redo(),
+                    |stepped| Message::HistoryStepped(stepped.map_err(|e| e.to_string())),
+                )
+                .map(cosmic::app::Message::App),
             ),
+            Message::Undo | Message::Redo => {}
+            Message::HistoryStepped(result) => match result {
+                Ok(true) => commands.push(Self::refresh_pages()),
+                Ok(false) => {}
+                Err(e) => {
+                    log::error!("{}", e);
+                    commands.push(self.show_error(e));
+                }
+            },
+            Message::HistoryUpdated(can_undo, can_redo) => {
+                self.can_undo = can_undo;
+                self.can_redo = can_redo;
+            }
+            Message::LedgerOpened => commands.push(Self::refresh_pages()),
+            Message::Unlocked | Message::Recovered => {
+                self.recovery = None;
+                self.pages = Some(Pages::default());
+                commands.push(Self::refresh_pages());
+            }
+            Message::ChangeFinished(Ok(())) => {}
+            Message::ChangeFinished(Err(e)) => {
+                log::error!("{}", e);
+                commands.push(self.show_error(e.to_string()));
+            }
+            Message::ShowError(message) => commands.push(self.show_error(message)),
+            Message::CloseToast(id) => self.toasts.remove(id),
+            Message::Unlock(message) => {
+                commands.push(self.unlock.update(message).map(cosmic::app::Message::App))
+            }
+            Message::Recovery(message) => {
+                if let Some(recovery) = &mut self.recovery {
+                    commands.push(recovery.update(message).map(cosmic::app::Message::App))
+                }
+            }
+            Message::Accounts(message) => {
+                commands.push(self.update_pages(|pages| pages.accounts.update(message)))
+            }
+            Message::Settings(message) => {
+                commands.push(self.update_pages(|pages| pages.settings.update(message)))
+            }
+            Message::Categories(message) => {
+                commands.push(self.update_pages(|pages| pages.categories.update(message)))
+            }
+            Message::Transactions(message) => {
+                commands.push(self.update_pages(|pages| pages.transactions.update(message)))
+            }
+            Message::Recurring(message) => {
+                commands.push(self.update_pages(|pages| pages.recurring.update(message)))
+            }
+            Message::Goals(message) => {
+                commands.push(self.update_pages(|pages| pages.goals.update(message)))
+            }
+            Message::Securities(message) => {
+                commands.push(self.update_pages(|pages| pages.securities.update(message)))
+            }
             Message::Welcome(welcome_message) => {
-                commands.push(
-                    self.welcome
-                        .update(welcome_message)
-                        .map(cosmic::app::Message::App),
-                );
+                commands.push(self.update_pages(|pages| pages.welcome.update(welcome_message)));
             }
             Message::GoToAccounts => {
                 self.nav.activate_position(0);
                 self.core.nav_bar_set_toggled(true);
             }
         }
+        if finishes_change {
+            commands.push(self.update_history());
+        }
         Task::batch(commands)
     }
 
+    fn subscription(&self) -> Subscription<Self::Message> {
+        event::listen_with(|event, status, _| match event {
+            Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. })
+                if status == event::Status::Ignored =>
+            {
+                Some(Message::Key(modifiers, key))
+            }
+            _ => None,
+        })
+    }
+
     fn context_drawer(&self) -> Option<Element<Self::Message>> {
         if !self.core.window.show_context {
             return None;
@@ -221,6 +420,10 @@ impl Application for MoneyManager {
 
         Some(match self.context_page {
             ContextPage::About => self.about(),
+            ContextPage::TransactionHistory => match &self.pages {
+                Some(pages) => pages.transactions.history_view().map(Message::Transactions),
+                None => return None,
+            },
         })
     }
 
@@ -259,6 +462,70 @@ impl MoneyManager {
             .into()
     }
 
+    /// Records the changes of a finished change as one undoable step and
+    /// refreshes the state of the Undo and Redo menu items.
+    fn update_history(&self) -> Task<Message> {
+        if self.pages.is_none() {
+            return Task::none();
+        }
+        data::request(
+            &STORE,
+            |store| {
+                if let Err(e) = store.checkpoint() {
+                    log::error!("{}", e);
+                }
+                Ok((
+                    store.can_undo().unwrap_or(false),
+                    store.can_redo().unwrap_or(false),
+                ))
+            },
+            |history| {
+                let (can_undo, can_redo) = history.unwrap_or_else(|e| {
+                    log::error!("{}", e);
+                    (false, false)
+                });
+                cosmic::app::Message::App(Message::HistoryUpdated(can_undo, can_redo))
+            },
+        )
+    }
+
+    fn show_error(&mut self, message: String) -> Task<Message> {
+        self.toasts
+            .push(widget::toaster::Toast::new(message))
+            .map(cosmic::app::Message::App)
+    }
+
+    /// Hands a message to the pages, which only exist once the ledger is open.
+    fn update_pages(
+        &mut self,
+        update: impl FnOnce(&mut Pages) -> cosmic::Task<Message>,
+    ) -> Task<Message> {
+        match &mut self.pages {
+            Some(pages) => update(pages).map(cosmic::app::Message::App),
+            None => Task::none(),
+        }
+    }
+
+    /// Loads every page once the ledger is open, and reloads them after undo,
+    /// redo or opening another ledger changed the data under them.
+    fn refresh_pages() -> Task<Message> {
+        let messages = vec![
+            Message::Accounts(pages::accounts::AccountsMessage::Update),
+            Message::Categories(pages::categories::CategoriesMessage::Update),
+            Message::Transactions(pages::transactions::TransactionMessage::UpdatePage),
+            Message::Recurring(pages::recurring::RecurringMessage::UpdatePage),
+            Message::Goals(pages::goals::GoalsMessage::UpdatePage),
+            Message::Securities(pages::securities::SecuritiesMessage::UpdatePage),
+            Message::Settings(pages::settings::SettingsMessage::Update),
+            Message::Welcome(pages::welcome::WelcomeMessage::Update),
+        ];
+        Task::batch(
+            messages.into_iter().map(|message| {
+                Task::perform(async {}, move |_| cosmic::app::Message::App(message))
+            }),
+        )
+    }
+
     pub fn update_title(&mut self) -> Task<Message> {
         let mut window_title = fl!("app-title");
 
@@ -274,3 +541,22 @@ impl MoneyManager {
         }
     }
 }
+
+fn key_binds() -> HashMap<KeyBind, MenuAction> {
+    HashMap::from([
+        (
+            KeyBind {
+                modifiers: vec![Modifier::Ctrl],
+                key: Key::Character("z".into()),
+            },
+            MenuAction::Undo,
+        ),
+        (
+            KeyBind {
+                modifiers: vec![Modifier::Ctrl, Modifier::Shift],
+                key: Key::Character("Z".into()),
+            },
+            MenuAction::Redo,
+        ),
+    ])
+}
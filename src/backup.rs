@@ -0,0 +1,188 @@
+//! Backups are copies of a ledger file kept under `backups/<ledger>` in the
+//! data directory, named after the local time they were taken. An automatic
+//! backup is taken whenever a ledger is opened, then the older ones are
+//! thinned out to the newest of each day, week and month the config keeps.
+//! Backups taken before migrating a ledger are never thinned out.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Local, NaiveDateTime};
+
+use crate::{config::Config, errors::DataStoreError, ledger, store::Store, STORE};
+
+const BACKUP_EXTENSION: &str = "db";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+const MIGRATION_SUFFIX: &str = "-migration";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupKind {
+    Automatic,
+    Migration,
+}
+
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub taken_at: NaiveDateTime, // local time
+    pub kind: BackupKind,
+}
+
+pub fn backup_dir(ledger_name: &str) -> Result<PathBuf, DataStoreError> {
+    Ok(ledger::data_dir()?.join("backups").join(ledger_name))
+}
+
+/// Copies a ledger file as it is on disk, so no connection may be writing
+/// to it. New, empty ledgers have nothing to back up.
+pub fn snapshot(ledger_name: &str, kind: BackupKind) -> Result<Option<PathBuf>, DataStoreError> {
+    let source = ledger::ledger_path(ledger_name)?;
+    if std::fs::metadata(&source).map_or(true, |metadata| metadata.len() == 0) {
+        return Ok(None);
+    }
+
+    let dir = backup_dir(ledger_name)?;
+    std::fs::create_dir_all(&dir).map_err(file_error)?;
+    let suffix = match kind {
+        BackupKind::Automatic => "",
+        BackupKind::Migration => MIGRATION_SUFFIX,
+    };
+    let target = dir.join(format!(
+        "{}{}.{}",
+        Local::now().format(TIMESTAMP_FORMAT),
+        suffix,
+        BACKUP_EXTENSION
+    ));
+    std::fs::copy(&source, &target).map_err(file_error)?;
+    log::info!("Ledger backed up to {:?}", target);
+    Ok(Some(target))
+}
+
+/// Takes the automatic backup of a ledger about to be opened.
+pub fn back_up_on_open(ledger_name: &str) {
+    if let Err(e) = snapshot(ledger_name, BackupKind::Automatic).and_then(|_| rotate(ledger_name)) {
+        log::error!("Failed to back up {}: {}", ledger_name, e);
+    }
+}
+
+/// The backups of a ledger, newest first.
+pub fn list_backups(ledger_name: &str) -> Result<Vec<Backup>, DataStoreError> {
+    let entries = match std::fs::read_dir(backup_dir(ledger_name)?) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(file_error(e)),
+    };
+
+    let mut backups = entries
+        .filter_map(|entry| entry.ok().and_then(|entry| parse_backup(entry.path())))
+        .collect::<Vec<Backup>>();
+    backups.sort_by(|a, b| b.taken_at.cmp(&a.taken_at));
+    Ok(backups)
+}
+
+/// Deletes the automatic backups that are not the newest of one of the
+/// newest days, weeks or months kept by the config. The newest backup is
+/// always kept.
+pub fn rotate(ledger_name: &str) -> Result<(), DataStoreError> {
+    let config = Config::load().1;
+    let automatic = list_backups(ledger_name)?
+        .into_iter()
+        .filter(|backup| backup.kind == BackupKind::Automatic)
+        .collect::<Vec<Backup>>();
+
+    let periods: [(u32, fn(&NaiveDateTime) -> (i32, u32)); 3] = [
+        (config.backups_daily, |time| (time.year(), time.ordinal())),
+        (config.backups_weekly, |time| {
+            (time.iso_week().year(), time.iso_week().week())
+        }),
+        (config.backups_monthly, |time| (time.year(), time.month())),
+    ];
+    let mut keep = automatic
+        .first()
+        .map(|backup| &backup.path)
+        .into_iter()
+        .collect::<HashSet<&PathBuf>>();
+    for (count, period) in periods {
+        let mut kept_periods = vec![];
+        for backup in &automatic {
+            let key = period(&backup.taken_at);
+            if kept_periods.last() == Some(&key) {
+                continue;
+            }
+            if kept_periods.len() == count as usize {
+                break;
+            }
+            kept_periods.push(key);
+            keep.insert(&backup.path);
+        }
+    }
+
+    for backup in &automatic {
+        if !keep.contains(&backup.path) {
+            std::fs::remove_file(&backup.path).map_err(file_error)?;
+        }
+    }
+    Ok(())
+}
+
+/// Rewrites the backups of a ledger from `old_passphrase` to `passphrase`.
+/// Every backup is tried, and the last one that failed is reported.
+pub fn rekey_backups(
+    ledger_name: &str,
+    old_passphrase: Option<&str>,
+    passphrase: Option<&str>,
+) -> Result<(), DataStoreError> {
+    let mut result = Ok(());
+    for backup in list_backups(ledger_name)? {
+        if let Err(e) = Store::rekey(&backup.path, old_passphrase, passphrase) {
+            log::error!("Failed to re-encrypt the backup {:?}: {}", backup.path, e);
+            result = Err(e);
+        }
+    }
+    result
+}
+
+/// Replaces the current ledger with one of its backups, once the backup
+/// passed an integrity check. The ledger is backed up first, so restoring
+/// that backup undoes the restore.
+pub fn restore(backup: &Path) -> Result<(), DataStoreError> {
+    let name = ledger::current_ledger();
+    let path = ledger::ledger_path(&name)?;
+    let mut store = STORE.lock().unwrap();
+    let passphrase = store.passphrase().map(str::to_string);
+    Store::inspect(backup, passphrase.as_deref())?;
+    snapshot(&name, BackupKind::Automatic)?;
+
+    let restored = path.with_extension("restore");
+    std::fs::copy(backup, &restored).map_err(file_error)?;
+    std::fs::rename(&restored, &path).map_err(file_error)?;
+    let reporting_currency_id = Config::load().1.currency_id;
+    *store = Store::open(&path, passphrase.as_deref(), Some(reporting_currency_id))?;
+    ledger::post_due_recurring_transactions(&mut store);
+    Ok(())
+}
+
+/// Writes a copy of the current ledger to a path of the user's choosing.
+pub fn back_up_to(target: &Path) -> Result<(), DataStoreError> {
+    STORE.lock().unwrap().backup_to(target)
+}
+
+fn parse_backup(path: PathBuf) -> Option<Backup> {
+    if path.extension()? != BACKUP_EXTENSION {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    let (timestamp, kind) = match stem.strip_suffix(MIGRATION_SUFFIX) {
+        Some(timestamp) => (timestamp, BackupKind::Migration),
+        None => (stem, BackupKind::Automatic),
+    };
+    let taken_at = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+    Some(Backup {
+        path,
+        taken_at,
+        kind,
+    })
+}
+
+fn file_error(e: std::io::Error) -> DataStoreError {
+    DataStoreError::LedgerFileError(e.to_string())
+}
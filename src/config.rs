@@ -13,6 +13,13 @@ pub const CONFIG_VERSION: u64 = 1;
 pub struct Config {
     pub is_user_initialized: bool,
     pub currency_id: i32,
+    /// The ledger opened on start, see `crate::ledger`.
+    pub ledger: String,
+    /// How many of the newest days, weeks and months keep an automatic
+    /// backup, see `crate::backup`.
+    pub backups_daily: u32,
+    pub backups_weekly: u32,
+    pub backups_monthly: u32,
 }
 
 impl Default for Config {
@@ -20,6 +27,10 @@ impl Default for Config {
         Self {
             is_user_initialized: false,
             currency_id: 1,
+            ledger: crate::ledger::DEFAULT_LEDGER.to_string(),
+            backups_daily: 7,
+            backups_weekly: 4,
+            backups_monthly: 6,
         }
     }
 }
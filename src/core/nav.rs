@@ -8,6 +8,9 @@ pub enum NavPage {
     Accounts,
     Categories,
     Transactions,
+    Recurring,
+    Goals,
+    Securities,
     Settings,
     Welcome,
 }
@@ -24,6 +27,9 @@ impl NavPage {
             Self::Accounts => fl!("page_accounts"),
             Self::Categories => fl!("page_categories"),
             Self::Transactions => fl!("page_transactions"),
+            Self::Recurring => fl!("page_recurring"),
+            Self::Goals => fl!("page_goals"),
+            Self::Securities => fl!("page_securities"),
             Self::Settings => fl!("page_settings"),
             Self::Welcome => fl!("page-welcome"),
         }
@@ -34,18 +40,24 @@ impl NavPage {
             NavPage::Accounts => icon::from_name("contact-new-symbolic").into(),
             NavPage::Categories => icon::from_name("sidebar-places-symbolic").into(),
             NavPage::Transactions => icon::from_name("network-transmit-receive-symbolic").into(),
+            NavPage::Recurring => icon::from_name("view-refresh-symbolic").into(),
+            NavPage::Goals => icon::from_name("starred-symbolic").into(),
+            NavPage::Securities => icon::from_name("view-statistics-symbolic").into(),
             NavPage::Settings => icon::from_name("application-default-symbolic").into(),
             NavPage::Welcome => icon::from_name("application-default-symbolic").into(), //TODO here the icon is useless
         }
     }
 
-    pub fn view<'a>(&self, app: &'a app::MoneyManager) -> Element<'a, app::Message> {
+    pub fn view<'a>(&self, pages: &'a app::Pages) -> Element<'a, app::Message> {
         match self {
-            NavPage::Accounts => app.accounts.view().map(app::Message::Accounts),
-            NavPage::Categories => app.categories.view().map(app::Message::Categories),
-            NavPage::Transactions => app.transactions.view().map(app::Message::Transactions),
-            NavPage::Settings => app.settings.view().map(app::Message::Settings),
-            NavPage::Welcome => app.welcome.view().map(app::Message::Welcome),
+            NavPage::Accounts => pages.accounts.view().map(app::Message::Accounts),
+            NavPage::Categories => pages.categories.view().map(app::Message::Categories),
+            NavPage::Transactions => pages.transactions.view().map(app::Message::Transactions),
+            NavPage::Recurring => pages.recurring.view().map(app::Message::Recurring),
+            NavPage::Goals => pages.goals.view().map(app::Message::Goals),
+            NavPage::Securities => pages.securities.view().map(app::Message::Securities),
+            NavPage::Settings => pages.settings.view().map(app::Message::Settings),
+            NavPage::Welcome => pages.welcome.view().map(app::Message::Welcome),
         }
     }
 
@@ -54,6 +66,9 @@ impl NavPage {
             Self::Accounts,
             Self::Categories,
             Self::Transactions,
+            Self::Recurring,
+            Self::Goals,
+            Self::Securities,
             Self::Settings,
         ]
     }
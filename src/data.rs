@@ -0,0 +1,109 @@
+//! The data service keeps database work off the UI thread. Pages queue
+//! requests that an actor on the tokio runtime hands to the blocking pool
+//! one at a time, in the order they were queued, so a page reloading after
+//! a change always sees that change. The results come back through tokio
+//! channels as the output of a `Task`.
+
+use std::future::Future;
+use std::sync::Once;
+
+use cosmic::Task;
+use once_cell::sync::Lazy;
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use crate::{app, errors::DataStoreError, repository::Repository, store::SharedStore};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The queue of requests. The receiving end belongs to the actor once it
+/// runs; sending never blocks, so requests keep their order even before.
+struct Queue {
+    sender: mpsc::UnboundedSender<Job>,
+    receiver: Mutex<mpsc::UnboundedReceiver<Job>>,
+}
+
+static QUEUE: Lazy<Queue> = Lazy::new(|| {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    Queue {
+        sender,
+        receiver: Mutex::new(receiver),
+    }
+});
+
+static ACTOR: Once = Once::new();
+
+/// Runs the queued requests until the runtime shuts down. A request that
+/// panics only fails itself.
+async fn serve() {
+    let mut receiver = QUEUE.receiver.lock().await;
+    while let Some(job) = receiver.recv().await {
+        if let Err(e) = tokio::task::spawn_blocking(job).await {
+            log::error!("A data request failed: {}", e);
+        }
+    }
+}
+
+/// Queues work right away and resolves to its result. The actor starts
+/// with the first result awaited, as that happens on the runtime.
+pub fn run<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, DataStoreError> + Send + 'static,
+) -> impl Future<Output = Result<T, DataStoreError>> + Send + 'static {
+    let (sender, receiver) = oneshot::channel();
+    let job: Job = Box::new(move || {
+        let _ = sender.send(work());
+    });
+    let queued = QUEUE.sender.send(job).is_ok();
+    async move {
+        if !queued {
+            return Err(DataStoreError::ServiceStopped);
+        }
+        ACTOR.call_once(|| {
+            tokio::spawn(serve());
+        });
+        receiver
+            .await
+            .unwrap_or(Err(DataStoreError::ServiceStopped))
+    }
+}
+
+/// Queues work on a store and turns its result into a message.
+pub fn request<R, T, M>(
+    store: &SharedStore<R>,
+    work: impl FnOnce(&mut R) -> Result<T, DataStoreError> + Send + 'static,
+    into_message: impl FnOnce(Result<T, DataStoreError>) -> M + Send + 'static,
+) -> Task<M>
+where
+    R: Repository,
+    T: Send + 'static,
+    M: Send + 'static,
+{
+    let store = store.clone();
+    Task::perform(
+        run(move || match store.lock() {
+            Ok(mut store) => work(&mut store),
+            Err(_) => Err(DataStoreError::ServiceStopped),
+        }),
+        into_message,
+    )
+}
+
+/// Queues a change to a store that no page waits for. A failure is shown
+/// as a toast.
+pub fn execute<R: Repository, T: Send + 'static>(
+    store: &SharedStore<R>,
+    work: impl FnOnce(&mut R) -> Result<T, DataStoreError> + Send + 'static,
+) -> Task<app::Message> {
+    request(store, work, |result| {
+        app::Message::ChangeFinished(result.map(|_| ()))
+    })
+}
+
+/// Runs the work queued so far on the calling thread. Tests await no
+/// tasks, so the actor never starts and leaves the queue to them.
+#[cfg(test)]
+pub fn wait_idle() {
+    let mut receiver = QUEUE.receiver.blocking_lock();
+    while let Ok(job) = receiver.try_recv() {
+        job();
+    }
+}
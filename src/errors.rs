@@ -1,10 +1,14 @@
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum DataStoreError {
     #[error("Error happened on database connection")]
     Connection,
 
+    #[error("Error happened on database migration: {0}")]
+    MigrationError(String),
+
     #[error("Error happened on database query: {0}")]
     QueryError(String),
 
@@ -13,4 +17,101 @@ pub enum DataStoreError {
 
     #[error("Error happened on database update: {0}")]
     UpdateError(String),
+
+    #[error("Error happened on database delete: {0}")]
+    DeleteError(String),
+
+    #[error("The change conflicts with other data: {0}")]
+    ConstraintViolation(String),
+
+    #[error("The record does not exist anymore")]
+    NotFound,
+
+    #[error("The amount is too large")]
+    AmountOverflow,
+
+    #[error("No exchange rate from {0} to {1}")]
+    MissingExchangeRate(String, String),
+
+    #[error("Split lines must add up to the transaction amount")]
+    SplitMismatch,
+
+    #[error("A category cannot be nested inside itself or its subcategories")]
+    CategoryCycle,
+
+    #[error("Reconciled transactions cannot be changed")]
+    TransactionLocked,
+
+    #[error("The cleared balance does not match the statement balance")]
+    ReconciliationMismatch,
+
+    #[error("The loan is already paid off")]
+    LoanPaidOff,
+
+    #[error("Cannot sell more shares than the account holds")]
+    InsufficientQuantity,
+
+    #[error("Investment trades can only be deleted, not edited")]
+    TradeLocked,
+
+    #[error("Error happened on ledger file: {0}")]
+    LedgerFileError(String),
+
+    #[error("A ledger named {0} already exists")]
+    LedgerExists(String),
+
+    #[error("Ledger names cannot be empty or contain path separators")]
+    InvalidLedgerName,
+
+    #[error("The open ledger cannot be deleted")]
+    LedgerOpen,
+
+    #[error("The passphrase is wrong")]
+    WrongPassphrase,
+
+    #[error("The database failed its integrity check: {0}")]
+    IntegrityError(String),
+
+    #[error("The data directory is read-only: {0}")]
+    ReadOnly(String),
+
+    #[error("The data directory could not be found")]
+    NoDataDirectory,
+
+    #[error("The data service stopped before finishing the request")]
+    ServiceStopped,
+}
+
+impl DataStoreError {
+    pub fn query(e: DieselError) -> Self {
+        Self::classify(e, Self::QueryError)
+    }
+
+    pub fn insert(e: DieselError) -> Self {
+        Self::classify(e, Self::InsertError)
+    }
+
+    pub fn update(e: DieselError) -> Self {
+        Self::classify(e, Self::UpdateError)
+    }
+
+    pub fn delete(e: DieselError) -> Self {
+        Self::classify(e, Self::DeleteError)
+    }
+
+    /// Tells missing rows and violated constraints apart from the other
+    /// failures of a statement, which become `other`.
+    fn classify(e: DieselError, other: fn(String) -> Self) -> Self {
+        match e {
+            DieselError::NotFound => Self::NotFound,
+            DieselError::DatabaseError(
+                DatabaseErrorKind::UniqueViolation
+                | DatabaseErrorKind::ForeignKeyViolation
+                | DatabaseErrorKind::NotNullViolation
+                | DatabaseErrorKind::CheckViolation,
+                info,
+            ) => Self::ConstraintViolation(info.message().to_string()),
+            e => other(e.to_string()),
+        }
+    }
 }
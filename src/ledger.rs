@@ -0,0 +1,242 @@
+//! Ledgers are separate database files kept in the application data
+//! directory. A ledger is named after its file, without the extension.
+//! Ledgers protected by a passphrase are encrypted by SQLCipher, so their
+//! data is only ever decrypted in memory.
+
+use std::io::Read;
+use std::path::PathBuf;
+
+use chrono::Local;
+use directories::ProjectDirs;
+
+use crate::{
+    app,
+    backup::{self, BackupKind},
+    config::Config,
+    errors::DataStoreError,
+    store::Store,
+    STORE,
+};
+
+pub const DEFAULT_LEDGER: &str = "cosmic-money";
+const LEDGER_EXTENSION: &str = "db";
+/// Every plain SQLite file starts with this header.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// The directory ledgers are kept in, which cannot be found without a home
+/// directory.
+pub fn data_dir() -> Result<PathBuf, DataStoreError> {
+    let directories = ProjectDirs::from(app::QUALIFIER, app::ORG, app::APP)
+        .ok_or(DataStoreError::NoDataDirectory)?;
+
+    let data_dir = directories.data_dir();
+    // a directory that cannot be created fails the ledger opened in it
+    if let Err(e) = std::fs::create_dir_all(data_dir) {
+        log::error!("Error creating data directory: {:?}", e);
+    }
+
+    Ok(data_dir.to_path_buf())
+}
+
+/// Ledgers are written along with their journal and backups, so the data
+/// directory has to be writable even when the ledger already exists.
+pub fn check_writable() -> Result<(), DataStoreError> {
+    let dir = data_dir()?;
+    let probe = dir.join(".write-check");
+    std::fs::write(&probe, b"")
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::ReadOnlyFilesystem => {
+                DataStoreError::ReadOnly(dir.display().to_string())
+            }
+            _ => DataStoreError::LedgerFileError(e.to_string()),
+        })
+}
+
+pub fn ledger_path(name: &str) -> Result<PathBuf, DataStoreError> {
+    Ok(data_dir()?.join(format!("{}.{}", name, LEDGER_EXTENSION)))
+}
+
+/// The ledger the application works on, as remembered in the config.
+pub fn current_ledger() -> String {
+    Config::load().1.ledger
+}
+
+/// The ledgers found in the data directory, sorted by name.
+pub fn list_ledgers() -> Result<Vec<String>, DataStoreError> {
+    let entries = std::fs::read_dir(data_dir()?)
+        .map_err(|e| DataStoreError::LedgerFileError(e.to_string()))?;
+
+    let mut ledgers = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == LEDGER_EXTENSION))
+        .filter_map(|path| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+        })
+        .collect::<Vec<String>>();
+    ledgers.sort();
+    Ok(ledgers)
+}
+
+/// Creates an empty ledger with its migrations applied.
+pub fn create_ledger(name: &str) -> Result<(), DataStoreError> {
+    let name = valid_name(name)?;
+    let path = ledger_path(name)?;
+    if path.exists() {
+        return Err(DataStoreError::LedgerExists(name.to_string()));
+    }
+
+    Store::open(&path, None, None)?;
+    Ok(())
+}
+
+/// Whether a ledger is encrypted and needs its passphrase to be opened.
+/// A file too short to have a header is a new, empty ledger, and a ledger
+/// that cannot be found reports why once it is opened. A damaged plain
+/// ledger is not mistaken for an encrypted one, so it reaches the recovery
+/// screen instead of asking for a passphrase.
+pub fn is_encrypted(name: &str) -> bool {
+    let mut header = [0; SQLITE_HEADER.len()];
+    ledger_path(name).is_ok_and(|path| {
+        std::fs::File::open(&path)
+            .and_then(|mut file| file.read_exact(&mut header))
+            .is_ok_and(|_| &header != SQLITE_HEADER)
+            && Store::is_encrypted(&path)
+    })
+}
+
+/// Opens the current ledger, encrypted with `passphrase`, at start.
+pub fn unlock(passphrase: &str) -> Result<(), DataStoreError> {
+    let store = open_store(&current_ledger(), Some(passphrase))?;
+    activate(store);
+    Ok(())
+}
+
+/// Encrypts the current ledger with a key derived from `passphrase`, or
+/// decrypts it when `None`. Its backups follow, as they are restored with
+/// the passphrase of the ledger.
+pub fn set_passphrase(passphrase: Option<&str>) -> Result<(), DataStoreError> {
+    let name = current_ledger();
+    let path = ledger_path(&name)?;
+    let mut store = STORE.lock().unwrap();
+    let old_passphrase = store.passphrase().map(str::to_string);
+    store.set_passphrase(&path, passphrase)?;
+    backup::rekey_backups(&name, old_passphrase.as_deref(), passphrase)
+}
+
+/// Switches the application to another ledger and remembers it for the
+/// next start. Encrypted ledgers need their `passphrase`.
+pub fn open_ledger(name: &str, passphrase: Option<&str>) -> Result<(), DataStoreError> {
+    let path = ledger_path(name)?;
+    if !path.exists() {
+        return Err(DataStoreError::LedgerFileError(format!(
+            "{} does not exist",
+            path.display()
+        )));
+    }
+
+    let store = open_store(name, passphrase)?;
+    activate(store);
+    remember_ledger(name);
+    Ok(())
+}
+
+/// Renames a ledger file and its backups. The open ledger is reopened under
+/// its new name.
+pub fn rename_ledger(name: &str, new_name: &str) -> Result<(), DataStoreError> {
+    let new_name = valid_name(new_name)?;
+    let new_path = ledger_path(new_name)?;
+    if new_path.exists() {
+        return Err(DataStoreError::LedgerExists(new_name.to_string()));
+    }
+
+    std::fs::rename(ledger_path(name)?, &new_path)
+        .map_err(|e| DataStoreError::LedgerFileError(e.to_string()))?;
+    let backups = backup::backup_dir(name)?;
+    if backups.exists() {
+        if let Err(e) = std::fs::rename(backups, backup::backup_dir(new_name)?) {
+            log::error!("Failed to move the backups of {}: {}", name, e);
+        }
+    }
+
+    if name == current_ledger() {
+        let passphrase = STORE.lock().unwrap().passphrase().map(str::to_string);
+        open_ledger(new_name, passphrase.as_deref())?;
+    }
+    Ok(())
+}
+
+/// Deletes a ledger file and its backups for good. The open ledger cannot
+/// be deleted.
+pub fn delete_ledger(name: &str) -> Result<(), DataStoreError> {
+    if name == current_ledger() {
+        return Err(DataStoreError::LedgerOpen);
+    }
+
+    std::fs::remove_file(ledger_path(name)?)
+        .map_err(|e| DataStoreError::LedgerFileError(e.to_string()))?;
+    let backups = backup::backup_dir(name)?;
+    if backups.exists() {
+        std::fs::remove_dir_all(backups)
+            .map_err(|e| DataStoreError::LedgerFileError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Checks, backs up and opens a ledger, migrating it when it is behind. A
+/// copy of the ledger as it was before the migrations is kept for good. A
+/// damaged ledger is left alone, so its backups are not rotated out by
+/// copies of it.
+pub fn open_store(name: &str, passphrase: Option<&str>) -> Result<Store, DataStoreError> {
+    let path = ledger_path(name)?;
+    Store::check(&path, passphrase)?;
+    backup::back_up_on_open(name);
+
+    if Store::has_pending_migrations(&path, passphrase)? {
+        if let Err(e) = backup::snapshot(name, BackupKind::Migration) {
+            log::error!("Failed to back up the ledger before migrating it: {}", e);
+        }
+    }
+    Store::open(&path, passphrase, Some(Config::load().1.currency_id))
+}
+
+/// Makes `store` the one the application works on.
+pub fn activate(store: Store) {
+    let mut current = STORE.lock().unwrap();
+    *current = store;
+    post_due_recurring_transactions(&mut current);
+}
+
+/// Posts the recurring transactions that came due while the ledger was
+/// closed, as one step of the undo history.
+pub fn post_due_recurring_transactions(store: &mut Store) {
+    log::info!("Posting due recurring transactions...");
+    let today = Local::now().date_naive();
+    match store.post_due_recurring_transactions(today) {
+        Ok(posted) => log::info!("Recurring transactions posted: {}", posted),
+        Err(e) => log::error!("Failed to post recurring transactions: {}", e),
+    }
+    if let Err(e) = store.checkpoint() {
+        log::error!("Failed to record the posted transactions: {}", e);
+    }
+}
+
+fn remember_ledger(name: &str) {
+    let (handler, mut config) = Config::load();
+    if let Some(handler) = handler {
+        if let Err(e) = config.set_ledger(&handler, name.to_string()) {
+            log::error!("failed to save the ledger: {}", e);
+        }
+    }
+}
+
+/// Ledger names become file names, so they cannot be empty, hidden or
+/// point to another directory.
+fn valid_name(name: &str) -> Result<&str, DataStoreError> {
+    let name = name.trim();
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(DataStoreError::InvalidLedgerName);
+    }
+    Ok(name)
+}
@@ -1,80 +1,78 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::{path::PathBuf, sync::Mutex};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
 use app::MoneyManager;
-use diesel::{Connection, SqliteConnection};
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use directories::ProjectDirs;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations};
 use dotenvy::dotenv;
+use errors::DataStoreError;
 use once_cell::sync::Lazy;
-use store::Store;
+use store::{SharedStore, Store};
 
 mod app;
+mod backup;
 mod config;
 mod core;
+mod data;
 mod errors;
+mod ledger;
 mod models;
 mod pages;
+mod repository;
 mod schema;
 mod store;
 mod widget;
 
-static STORE: Lazy<Mutex<Store>> = Lazy::new(|| Mutex::new(Store::default()));
-static DATABASE_URL: &str = "cosmic-money.db";
+static STORE: Lazy<SharedStore> = Lazy::new(|| Arc::new(Mutex::new(Store::default())));
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
 
-pub fn run_migration(connection: &mut SqliteConnection) {
-    connection.run_pending_migrations(MIGRATIONS).unwrap();
-}
-
 use std::fs::File;
 
-pub fn get_database_url() -> PathBuf {
-    let directories = ProjectDirs::from(app::QUALIFIER, app::ORG, app::APP)
-        .expect("Failed to get app data directory");
-
-    let data_dir = directories.data_dir();
-    let db_path = data_dir.join(DATABASE_URL);
-
-    std::fs::create_dir_all(data_dir)
-        .unwrap_or_else(|e| panic!("Error creating data directory: {:?}", e));
+pub fn get_database_url() -> Result<PathBuf, DataStoreError> {
+    ledger::check_writable()?;
+    let db_path = ledger::ledger_path(&ledger::current_ledger())?;
 
     if !db_path.exists() {
-        File::create(&db_path).unwrap_or_else(|e| panic!("Error creating database file: {:?}", e));
+        File::create(&db_path).map_err(|e| DataStoreError::LedgerFileError(e.to_string()))?;
         log::info!("Database file created: {:?}", db_path);
     } else {
         log::info!("Database file already exists: {:?}", db_path);
     }
 
     log::info!("Returning database path: {:?}", db_path);
-    db_path
+    Ok(db_path)
+}
+
+/// Checks, backs up and migrates the current ledger, then opens it in
+/// `STORE`.
+pub fn open_database() -> Result<(), DataStoreError> {
+    get_database_url()?;
+    ledger::activate(ledger::open_store(&ledger::current_ledger(), None)?);
+    Ok(())
 }
 
 /// The `cosmic::app::run()` function is the starting point of your application.
 /// It takes two arguments:
 /// - `settings` is a structure that contains everything relevant with your app's configuration, such as antialiasing, themes, icons, etc...
-/// - `()` is the flags that your app needs to use before it starts.
-///  If your app does not need any flags, you can pass in `()`.
+/// - `startup_error` is the flags that your app needs to use before it starts:
+///  why the ledger could not be opened, if it could not.
 fn main() -> cosmic::iced::Result {
     dotenv().ok();
     env_logger::init();
 
-    let database_url_path = get_database_url();
-    let database_url = if let Some(url) = database_url_path.to_str() {
-        log::info!("url: {}", url);
-        url
+    // an encrypted ledger is opened once its passphrase is entered
+    let startup_error = if ledger::is_encrypted(&ledger::current_ledger()) {
+        log::info!("The ledger is encrypted, waiting for its passphrase");
+        None
     } else {
-        panic!("unable to get local database url");
+        open_database()
+            .inspect_err(|e| log::error!("Failed to open the ledger: {}", e))
+            .err()
     };
 
-    log::info!("Running migration...");
-    let mut connection = SqliteConnection::establish(database_url)
-        .unwrap_or_else(|e| panic!("Error connecting to {:?} with error: {:?}", database_url, e));
-    run_migration(&mut connection);
-    let applied = connection.applied_migrations();
-    log::info!("Migration completed, applied: {:?}", applied);
-
     let settings = cosmic::app::Settings::default();
-    cosmic::app::run::<MoneyManager>(settings, ())
+    cosmic::app::run::<MoneyManager>(settings, startup_error)
 }
@@ -1,16 +1,365 @@
+use crate::errors::DataStoreError;
 use crate::schema::account;
+use crate::schema::audit_log;
+use crate::schema::budget;
 use crate::schema::category;
+use crate::schema::change_journal;
+use crate::schema::exchange_rate;
+use crate::schema::goal;
 use crate::schema::money_transaction;
+use crate::schema::payee;
+use crate::schema::recurring_transaction;
+use crate::schema::security;
+use crate::schema::tag;
+use chrono::{Datelike, Days, Months, NaiveDate};
+use diesel::backend::Backend;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
 use diesel::prelude::*;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::{BigInt, Text};
+use diesel::sqlite::Sqlite;
+use diesel::AsExpression;
+use std::fmt;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
 
-#[derive(Queryable, Selectable, Clone)]
+/// An exact amount of money stored as integer minor units (e.g. cents).
+///
+/// `scale` is the number of decimal digits used by the currency, so
+/// `Money::new(1250, 2)` represents `12.50`. Only the minor units are
+/// written to the database; the scale comes from the currency.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, AsExpression)]
+#[diesel(sql_type = BigInt)]
+pub struct Money {
+    minor_units: i64,
+    scale: u32,
+}
+
+impl Money {
+    pub const DEFAULT_SCALE: u32 = 2;
+
+    pub fn new(minor_units: i64, scale: u32) -> Self {
+        Self { minor_units, scale }
+    }
+
+    pub fn zero(scale: u32) -> Self {
+        Self::new(0, scale)
+    }
+
+    pub fn minor_units(&self) -> i64 {
+        self.minor_units
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.minor_units < 0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.minor_units == 0
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::new(self.minor_units.abs(), self.scale)
+    }
+
+    /// Parses a decimal string such as `"12"`, `"-3.5"` or `"1234,56"`.
+    ///
+    /// Returns `None` if the input is not a number or has more decimal
+    /// digits than `scale` allows.
+    pub fn parse(input: &str, scale: u32) -> Option<Self> {
+        let input = input.trim();
+        let (negative, digits) = match input.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, input.strip_prefix('+').unwrap_or(input)),
+        };
+        let (int_part, frac_part) = match digits.find(['.', ',']) {
+            Some(pos) => (&digits[..pos], &digits[pos + 1..]),
+            None => (digits, ""),
+        };
+
+        if int_part.is_empty() && frac_part.is_empty() {
+            return None;
+        }
+        if !int_part.chars().all(|c| c.is_ascii_digit())
+            || !frac_part.chars().all(|c| c.is_ascii_digit())
+            || frac_part.len() > scale as usize
+        {
+            return None;
+        }
+
+        let factor = 10i64.checked_pow(scale)?;
+        let int_value = if int_part.is_empty() {
+            0
+        } else {
+            int_part.parse::<i64>().ok()?
+        };
+        let frac_value = if frac_part.is_empty() {
+            0
+        } else {
+            frac_part.parse::<i64>().ok()? * 10i64.pow(scale - frac_part.len() as u32)
+        };
+
+        let minor_units = int_value.checked_mul(factor)?.checked_add(frac_value)?;
+        Some(Self::new(
+            if negative { -minor_units } else { minor_units },
+            scale,
+        ))
+    }
+
+    /// Parses an amount that may be left out, where an empty field stands
+    /// for zero.
+    pub fn parse_or_zero(input: &str, scale: u32) -> Option<Self> {
+        if input.trim().is_empty() {
+            Some(Self::zero(scale))
+        } else {
+            Self::parse(input, scale)
+        }
+    }
+
+    /// Converts the amount with an exchange `rate` into a currency with the
+    /// given `scale`, rounding half away from zero.
+    pub fn convert(&self, rate: f64, scale: u32) -> Self {
+        let shift = 10f64.powi(scale as i32 - self.scale as i32);
+        Self::new(
+            (self.minor_units as f64 * rate * shift).round() as i64,
+            scale,
+        )
+    }
+
+    /// Converts the amount to another scale, rounding half away from zero
+    /// when digits are dropped.
+    pub fn rescale(&self, scale: u32) -> Result<Self, DataStoreError> {
+        if scale >= self.scale {
+            let minor_units = 10i64
+                .checked_pow(scale - self.scale)
+                .and_then(|factor| self.minor_units.checked_mul(factor))
+                .ok_or(DataStoreError::AmountOverflow)?;
+            Ok(Self::new(minor_units, scale))
+        } else {
+            let Some(factor) = 10i64.checked_pow(self.scale - scale) else {
+                // the amount is less than one unit of the smaller scale
+                return Ok(Self::zero(scale));
+            };
+            let half = factor / 2;
+            let quotient = self.minor_units / factor;
+            let remainder = self.minor_units % factor;
+            let rounded = if remainder >= half {
+                quotient + 1
+            } else if remainder <= -half {
+                quotient - 1
+            } else {
+                quotient
+            };
+            Ok(Self::new(rounded, scale))
+        }
+    }
+
+    /// Adds two amounts at the larger of their scales.
+    pub fn checked_add(self, rhs: Money) -> Result<Money, DataStoreError> {
+        self.combine(rhs, i64::checked_add)
+    }
+
+    /// Subtracts two amounts at the larger of their scales.
+    pub fn checked_sub(self, rhs: Money) -> Result<Money, DataStoreError> {
+        self.combine(rhs, i64::checked_sub)
+    }
+
+    fn combine(self, rhs: Money, op: fn(i64, i64) -> Option<i64>) -> Result<Money, DataStoreError> {
+        let scale = self.scale.max(rhs.scale);
+        op(
+            self.rescale(scale)?.minor_units,
+            rhs.rescale(scale)?.minor_units,
+        )
+        .map(|minor_units| Money::new(minor_units, scale))
+        .ok_or(DataStoreError::AmountOverflow)
+    }
+
+    /// Like [`Money::combine`], clamping the result to the bounds of the
+    /// minor units.
+    fn saturating_combine(self, rhs: Money, op: fn(i128, i128) -> i128) -> Money {
+        let scale = self.scale.max(rhs.scale);
+        let widen = |money: Money| {
+            (money.minor_units as i128).saturating_mul(10i128.saturating_pow(scale - money.scale))
+        };
+        let minor_units = op(widen(self), widen(rhs)).clamp(i64::MIN as i128, i64::MAX as i128);
+        Money::new(minor_units as i64, scale)
+    }
+}
+
+/// An operator that overflowed panics in debug builds, and release builds go
+/// on with the `clamped` result.
+fn overflowed(clamped: Money) -> Money {
+    if cfg!(debug_assertions) {
+        panic!("amount overflow");
+    }
+    clamped
+}
+
+impl ToSql<BigInt, Sqlite> for Money {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.minor_units);
+        Ok(IsNull::No)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let value = self.minor_units.unsigned_abs();
+        if self.scale == 0 {
+            return write!(f, "{}{}", sign, value);
+        }
+        let factor = 10u64.pow(self.scale);
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            sign,
+            value / factor,
+            value % factor,
+            width = self.scale as usize
+        )
+    }
+}
+
+/// The operators saturate at the bounds of the minor units instead of
+/// overflowing, for the amounts worked out to be shown. Sums read from the
+/// ledger go through [`Money::checked_add`] and [`Money::checked_sub`].
+// The operators are for amounts that cannot grow with the data, such as the
+// parts of one payment. Like the integer operators they panic on overflow in
+// debug builds; sums over the ledger use `checked_add` and `checked_sub`.
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, rhs: Money) -> Money {
+        self.checked_add(rhs)
+            .unwrap_or_else(|_| overflowed(self.saturating_combine(rhs, i128::saturating_add)))
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, rhs: Money) -> Money {
+        self.checked_sub(rhs)
+            .unwrap_or_else(|_| overflowed(self.saturating_combine(rhs, i128::saturating_sub)))
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        match self.minor_units.checked_neg() {
+            Some(minor_units) => Money::new(minor_units, self.scale),
+            None => overflowed(Money::new(i64::MAX, self.scale)),
+        }
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, rhs: Money) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, rhs: Money) {
+        *self = *self - rhs;
+    }
+}
+
+/// What an account holds. Credit cards and loans are liabilities: their
+/// initial balance is the amount owed, and they count negatively in net worth.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum AccountType {
+    #[default]
+    Checking,
+    Savings,
+    CreditCard,
+    Cash,
+    Investment,
+    Loan,
+}
+
+impl AccountType {
+    pub const ALL: [AccountType; 6] = [
+        AccountType::Checking,
+        AccountType::Savings,
+        AccountType::CreditCard,
+        AccountType::Cash,
+        AccountType::Investment,
+        AccountType::Loan,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountType::Checking => "checking",
+            AccountType::Savings => "savings",
+            AccountType::CreditCard => "credit_card",
+            AccountType::Cash => "cash",
+            AccountType::Investment => "investment",
+            AccountType::Loan => "loan",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == value)
+    }
+
+    pub fn is_liability(&self) -> bool {
+        matches!(self, AccountType::CreditCard | AccountType::Loan)
+    }
+}
+
+impl ToSql<Text, Sqlite> for AccountType {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for AccountType {
+    fn from_sql(value: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        AccountType::from_str(&value)
+            .ok_or_else(|| format!("Unknown account type: {}", value).into())
+    }
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
 #[diesel(table_name = crate::schema::account)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct Account {
     pub id: i32,
     pub name: String,
-    pub initial_balance: f32,
+    pub account_type: AccountType,
+    pub initial_balance: i64, // Minor units, see `Money`
     pub account_description: String,
+    pub currency_id: Option<i32>, // `None` uses the reporting currency
+    pub is_archived: bool,
+    pub cost_basis_method: CostBasisMethod, // Only used by investment accounts
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl Account {
+    pub fn initial_balance(&self, scale: u32) -> Money {
+        Money::new(self.initial_balance, scale)
+    }
+}
+
+/// Accounts that can be picked for new entries: the open ones, plus the
+/// archived ones listed in `keep` (e.g. the account of an edited transaction).
+pub fn open_accounts(accounts: &[Account], keep: &[i32]) -> Vec<Account> {
+    accounts
+        .iter()
+        .filter(|a| !a.is_archived || keep.contains(&a.id))
+        .cloned()
+        .collect()
 }
 
 impl AsRef<str> for Account {
@@ -23,8 +372,10 @@ impl AsRef<str> for Account {
 #[diesel(table_name = account)]
 pub struct NewAccount {
     pub name: String,
-    pub initial_balance: f32,
+    pub account_type: AccountType,
+    pub initial_balance: Money,
     pub account_description: String,
+    pub currency_id: Option<i32>,
 }
 
 #[derive(Insertable)]
@@ -32,8 +383,10 @@ pub struct NewAccount {
 pub struct UpdateAccount {
     pub id: i32,
     pub name: String,
-    pub initial_balance: f32,
+    pub account_type: AccountType,
+    pub initial_balance: Money,
     pub account_description: String,
+    pub currency_id: Option<i32>,
 }
 
 #[derive(Queryable, Selectable, Debug, Clone)]
@@ -44,6 +397,91 @@ pub struct Category {
     pub name: String,
     pub category_description: String,
     pub is_income: bool,
+    pub parent_category: Option<i32>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Orders categories depth first so that each one follows its parent,
+/// paired with its depth. Categories whose parent is missing are roots.
+pub fn category_tree(categories: &[Category]) -> Vec<(&Category, usize)> {
+    fn visit<'a>(
+        categories: &'a [Category],
+        parent: &'a Category,
+        depth: usize,
+        tree: &mut Vec<(&'a Category, usize)>,
+    ) {
+        // a cycle in stored data must not loop forever
+        if tree.iter().any(|(c, _)| c.id == parent.id) {
+            return;
+        }
+        tree.push((parent, depth));
+        for child in categories
+            .iter()
+            .filter(|c| c.parent_category == Some(parent.id))
+        {
+            visit(categories, child, depth + 1, tree);
+        }
+    }
+
+    let mut tree = vec![];
+    for root in categories.iter().filter(|c| {
+        c.parent_category
+            .map_or(true, |parent| !categories.iter().any(|p| p.id == parent))
+    }) {
+        visit(categories, root, 0, &mut tree);
+    }
+    tree
+}
+
+/// The category name preceded by its ancestors, e.g. "Food › Restaurants".
+pub fn category_path(categories: &[Category], category: &Category) -> String {
+    let mut path = vec![category.name.as_str()];
+    let mut parent = category.parent_category;
+    while let Some(parent_category) = parent
+        .and_then(|parent| categories.iter().find(|c| c.id == parent))
+        .filter(|c| path.len() < categories.len() && c.id != category.id)
+    {
+        path.push(&parent_category.name);
+        parent = parent_category.parent_category;
+    }
+    path.reverse();
+    path.join(" › ")
+}
+
+/// Reorders categories so that subcategories directly follow their parent.
+pub fn categories_by_parent(categories: Vec<Category>) -> Vec<Category> {
+    category_tree(&categories)
+        .into_iter()
+        .map(|(category, _)| category.clone())
+        .collect()
+}
+
+/// Dropdown labels showing each category under its parent.
+pub fn category_labels(categories: &[Category]) -> Vec<String> {
+    categories
+        .iter()
+        .map(|category| category_path(categories, category))
+        .collect()
+}
+
+/// Ids of the category and of all the categories nested below it.
+pub fn category_with_descendants(categories: &[Category], category_id: i32) -> Vec<i32> {
+    let mut ids = vec![category_id];
+    let mut index = 0;
+    while index < ids.len() {
+        let parent = ids[index];
+        for child in categories
+            .iter()
+            .filter(|c| c.parent_category == Some(parent))
+        {
+            if !ids.contains(&child.id) {
+                ids.push(child.id);
+            }
+        }
+        index += 1;
+    }
+    ids
 }
 
 impl AsRef<str> for Category {
@@ -58,6 +496,7 @@ pub struct NewCategory {
     pub name: String,
     pub is_income: bool,
     pub category_description: String,
+    pub parent_category: Option<i32>,
 }
 
 #[derive(Insertable)]
@@ -67,19 +506,95 @@ pub struct UpdateCategory<'a> {
     pub name: &'a str,
     pub is_income: bool,
     pub category_description: String,
+    pub parent_category: Option<i32>,
 }
 
-#[derive(Queryable, Selectable, Debug)]
+#[derive(Queryable, Selectable, Debug, Clone)]
 #[diesel(table_name = crate::schema::money_transaction)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct MoneyTransaction {
     pub id: i32,
     pub bank_account: i32, // Foreign key referencing the 'account' table
-    pub transaction_category: i32, // Foreign key referencing the 'category' table
+    pub transaction_category: Option<i32>, // Foreign key referencing the 'category' table, `None` for transfers
     pub description: String,
-    pub amount: f32,
+    pub amount: i64, // Minor units, see `Money`
     pub transaction_date: chrono::NaiveDateTime,
     pub is_expense: bool,
+    pub transfer_peer: Option<i32>, // The other side of a transfer
+    pub payee_id: Option<i32>,
+    pub cleared_status: ClearedStatus,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::reconciliation)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Reconciliation {
+    pub id: i32,
+    pub bank_account: i32,
+    pub statement_date: NaiveDate,
+    pub statement_balance: i64, // Minor units, see `Money`
+}
+
+/// How far a transaction has been checked against the bank statements.
+/// Reconciled transactions are locked.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum ClearedStatus {
+    #[default]
+    Uncleared,
+    Cleared,
+    Reconciled,
+}
+
+impl ClearedStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClearedStatus::Uncleared => "uncleared",
+            ClearedStatus::Cleared => "cleared",
+            ClearedStatus::Reconciled => "reconciled",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        [
+            ClearedStatus::Uncleared,
+            ClearedStatus::Cleared,
+            ClearedStatus::Reconciled,
+        ]
+        .into_iter()
+        .find(|s| s.as_str() == value)
+    }
+}
+
+impl ToSql<Text, Sqlite> for ClearedStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for ClearedStatus {
+    fn from_sql(value: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        ClearedStatus::from_str(&value)
+            .ok_or_else(|| format!("Unknown cleared status: {}", value).into())
+    }
+}
+
+impl MoneyTransaction {
+    pub fn amount(&self, scale: u32) -> Money {
+        Money::new(self.amount, scale)
+    }
+
+    pub fn is_transfer(&self) -> bool {
+        self.transfer_peer.is_some()
+    }
+
+    pub fn is_reconciled(&self) -> bool {
+        self.cleared_status == ClearedStatus::Reconciled
+    }
 }
 
 #[derive(Insertable)]
@@ -88,18 +603,68 @@ pub struct NewMoneyTransaction {
     pub bank_account: i32,
     pub transaction_category: i32,
     pub description: String,
-    pub amount: f32,
+    pub amount: Money,
+    pub transaction_date: chrono::NaiveDateTime,
+    pub is_expense: bool,
+    pub payee_id: Option<i32>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = money_transaction)]
+pub struct UpdateMoneyTransaction {
+    pub id: i32,
+    pub bank_account: i32,
+    pub transaction_category: i32,
+    pub description: String,
+    pub amount: Money,
     pub transaction_date: chrono::NaiveDateTime,
     pub is_expense: bool,
+    pub payee_id: Option<i32>,
+}
+
+/// A movement of money between two accounts, stored as an expense on
+/// `from_account` and an income on `to_account` linked by `transfer_peer`.
+#[derive(Debug, Clone)]
+pub struct NewTransfer {
+    pub from_account: i32,
+    pub to_account: i32,
+    pub description: String,
+    pub amount: Money,    // In the currency of `from_account`
+    pub to_amount: Money, // In the currency of `to_account`
+    pub transaction_date: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateTransfer {
+    pub id: i32, // Either side of the transfer
+    pub from_account: i32,
+    pub to_account: i32,
+    pub description: String,
+    pub amount: Money,
+    pub to_amount: Money,
+    pub transaction_date: chrono::NaiveDateTime,
 }
 
-#[derive(Queryable, Selectable, Debug)]
+/// A transaction as entered in the form, saved in one go together with the
+/// payee, tags and split lines of a regular transaction.
+pub enum TransactionEntry {
+    Transfer(NewTransfer),
+    Regular {
+        transaction: NewMoneyTransaction, // `payee_id` is set from `payee`
+        payee: Option<String>,
+        tag_names: Vec<String>,
+        splits: Vec<NewTransactionSplit>,
+    },
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
 #[diesel(table_name = crate::schema::currency)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct Currency {
     pub id: i32,
     pub label: String,
     pub symbol: String,
+    pub scale: i32,
 }
 
 impl AsRef<str> for Currency {
@@ -107,3 +672,982 @@ impl AsRef<str> for Currency {
         &self.label
     }
 }
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::exchange_rate)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ExchangeRate {
+    pub id: i32,
+    pub from_currency: i32,
+    pub to_currency: i32,
+    pub rate: f64, // Units of `to_currency` for one unit of `from_currency`
+    pub rate_date: chrono::NaiveDate,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = exchange_rate)]
+pub struct NewExchangeRate {
+    pub from_currency: i32,
+    pub to_currency: i32,
+    pub rate: f64,
+    pub rate_date: chrono::NaiveDate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    pub const ALL: [Frequency; 4] = [
+        Frequency::Daily,
+        Frequency::Weekly,
+        Frequency::Monthly,
+        Frequency::Yearly,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Frequency::Daily => "daily",
+            Frequency::Weekly => "weekly",
+            Frequency::Monthly => "monthly",
+            Frequency::Yearly => "yearly",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        Frequency::ALL
+            .into_iter()
+            .find(|frequency| frequency.as_str() == value)
+    }
+}
+
+/// A rule that posts the same income or expense on a schedule.
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::recurring_transaction)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RecurringTransaction {
+    pub id: i32,
+    pub bank_account: i32,
+    pub transaction_category: i32,
+    pub description: String,
+    pub is_expense: bool,
+    pub amount: i64,       // Minor units, see `Money`
+    pub frequency: String, // See `Frequency::as_str`
+    pub frequency_interval: i32,
+    pub day_of_month: Option<i32>,    // Only used by monthly rules
+    pub next_date: Option<NaiveDate>, // `None` once the rule has ended
+    pub end_date: Option<NaiveDate>,
+    pub remaining_occurrences: Option<i32>,
+    pub start_date: NaiveDate,
+    pub occurrence_index: i32, // Which occurrence `next_date` is
+}
+
+impl RecurringTransaction {
+    pub fn amount(&self, scale: u32) -> Money {
+        Money::new(self.amount, scale)
+    }
+
+    pub fn frequency(&self) -> Frequency {
+        Frequency::from_str(&self.frequency).unwrap_or(Frequency::Monthly)
+    }
+
+    /// The occurrence numbered `index` counting from `start_date`, ignoring
+    /// the end date and the occurrence count. Working each one out from the
+    /// start keeps a day clamped to a short month from carrying over.
+    pub fn occurrence(&self, index: i32) -> Option<NaiveDate> {
+        let steps = (self.frequency_interval.max(1) as u32).checked_mul(index.max(0) as u32)?;
+        let first_of_month = self.start_date.with_day(1)?;
+        match self.frequency() {
+            Frequency::Daily => self.start_date.checked_add_days(Days::new(steps as u64)),
+            Frequency::Weekly => self
+                .start_date
+                .checked_add_days(Days::new(7 * steps as u64)),
+            Frequency::Monthly => {
+                let month = first_of_month.checked_add_months(Months::new(steps))?;
+                let day = self
+                    .day_of_month
+                    .map(|day| day as u32)
+                    .unwrap_or(self.start_date.day());
+                Some(clamp_day(month, day))
+            }
+            Frequency::Yearly => {
+                let month =
+                    first_of_month.checked_add_months(Months::new(steps.checked_mul(12)?))?;
+                Some(clamp_day(month, self.start_date.day()))
+            }
+        }
+    }
+
+    /// The rule once the occurrence on `next_date` is posted or skipped.
+    pub fn advanced(&self) -> RecurringTransaction {
+        let occurrence_index = self.occurrence_index + 1;
+        let remaining_occurrences = self.remaining_occurrences.map(|count| count - 1);
+        let next_date = if remaining_occurrences.is_some_and(|count| count <= 0) {
+            None
+        } else {
+            self.next_date
+                .and_then(|_| self.occurrence(occurrence_index))
+                .filter(|date| self.end_date.map_or(true, |end| *date <= end))
+        };
+        RecurringTransaction {
+            next_date,
+            occurrence_index,
+            remaining_occurrences,
+            ..self.clone()
+        }
+    }
+}
+
+/// The `day` of the month of `month`, or its last day when it is shorter.
+fn clamp_day(month: NaiveDate, day: u32) -> NaiveDate {
+    (1..=day.clamp(1, 31))
+        .rev()
+        .find_map(|day| month.with_day(day))
+        .unwrap_or(month)
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = recurring_transaction)]
+pub struct NewRecurringTransaction {
+    pub bank_account: i32,
+    pub transaction_category: i32,
+    pub description: String,
+    pub is_expense: bool,
+    pub amount: Money,
+    pub frequency: String,
+    pub frequency_interval: i32,
+    pub day_of_month: Option<i32>,
+    pub start_date: NaiveDate, // The first occurrence
+    pub end_date: Option<NaiveDate>,
+    pub remaining_occurrences: Option<i32>,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::budget)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Budget {
+    pub id: i32,
+    pub budget_category: i32,
+    pub budget_month: NaiveDate, // First day of the month
+    pub amount: i64,             // Minor units of the reporting currency
+    pub rollover: bool,
+}
+
+impl Budget {
+    pub fn amount(&self, scale: u32) -> Money {
+        Money::new(self.amount, scale)
+    }
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = budget)]
+pub struct NewBudget {
+    pub budget_category: i32,
+    pub budget_month: NaiveDate,
+    pub amount: Money,
+    pub rollover: bool,
+}
+
+/// How a category stands against its budget in a month.
+#[derive(Debug, Clone, Copy)]
+pub struct BudgetSummary {
+    pub budgeted: Money,
+    pub rolled_over: Money, // Left unspent in the previous month
+    pub spent: Money,
+    pub rollover: bool,
+}
+
+impl BudgetSummary {
+    pub fn available(&self) -> Money {
+        self.budgeted + self.rolled_over
+    }
+
+    pub fn remaining(&self) -> Money {
+        self.available() - self.spent
+    }
+
+    pub fn is_overspent(&self) -> bool {
+        self.remaining().is_negative()
+    }
+
+    /// Spent share of the available amount, capped at 1.
+    pub fn progress(&self) -> f32 {
+        let available = self.available().minor_units();
+        if available <= 0 {
+            return if self.spent.is_zero() { 0. } else { 1. };
+        }
+        // spending too large for the scale is well over the available amount
+        self.spent
+            .rescale(self.available().scale())
+            .map_or(1., |spent| {
+                (spent.minor_units() as f32 / available as f32).clamp(0., 1.)
+            })
+    }
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::tag)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+}
+
+impl AsRef<str> for Tag {
+    fn as_ref(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = tag)]
+pub struct NewTag {
+    pub name: String,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::transaction_tag)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct TransactionTag {
+    pub transaction_id: i32,
+    pub tag_id: i32,
+}
+
+/// A share of a transaction assigned to its own category.
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::transaction_split)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct TransactionSplit {
+    pub id: i32,
+    pub parent_transaction: i32,
+    pub split_category: i32,
+    pub amount: i64, // Minor units, in the currency of the parent's account
+    pub note: String,
+}
+
+impl TransactionSplit {
+    pub fn amount(&self, scale: u32) -> Money {
+        Money::new(self.amount, scale)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewTransactionSplit {
+    pub split_category: i32,
+    pub amount: Money,
+    pub note: String,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::payee)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Payee {
+    pub id: i32,
+    pub name: String,
+    pub default_category: Option<i32>, // Last category used with the payee
+    pub default_account: Option<i32>,  // Last account used with the payee
+}
+
+impl AsRef<str> for Payee {
+    fn as_ref(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = payee)]
+pub struct NewPayee {
+    pub name: String,
+    pub default_category: Option<i32>,
+    pub default_account: Option<i32>,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::goal)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Goal {
+    pub id: i32,
+    pub name: String,
+    pub target_amount: i64, // Minor units of the reporting currency
+    pub target_date: NaiveDate,
+    pub linked_account: Option<i32>, // `None` tracks manual contributions
+}
+
+impl Goal {
+    pub fn target_amount(&self, scale: u32) -> Money {
+        Money::new(self.target_amount, scale)
+    }
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = goal)]
+pub struct NewGoal {
+    pub name: String,
+    pub target_amount: Money,
+    pub target_date: NaiveDate,
+    pub linked_account: Option<i32>,
+}
+
+/// Where a goal stands, in the reporting currency.
+#[derive(Debug, Clone, Copy)]
+pub struct GoalProgress {
+    pub target: Money,
+    pub saved: Money,
+    pub monthly_contribution: Money, // Average over the recent history
+}
+
+impl GoalProgress {
+    pub fn remaining(&self) -> Money {
+        let remaining = self.target - self.saved;
+        if remaining.is_negative() {
+            Money::zero(self.target.scale())
+        } else {
+            remaining
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Saved share of the target, capped at 1.
+    pub fn progress(&self) -> f32 {
+        let target = self.target.minor_units();
+        if target <= 0 {
+            return 1.;
+        }
+        (self.saved.minor_units() as f32 / target as f32).clamp(0., 1.)
+    }
+
+    /// What has to be put aside each month from `today` to reach the target
+    /// by `target_date`. A target date in the past asks for everything now.
+    pub fn needed_per_month(&self, today: &NaiveDate, target_date: &NaiveDate) -> Money {
+        let months = (target_date.year() - today.year()) * 12 + target_date.month() as i32
+            - today.month() as i32;
+        let months = months.max(1) as i64;
+        let remaining = self.remaining().minor_units();
+        Money::new((remaining + months - 1) / months, self.target.scale())
+    }
+
+    /// When the target is reached if the average monthly contribution keeps
+    /// up, or `None` when nothing is being put aside.
+    pub fn projected_completion(&self, today: &NaiveDate) -> Option<NaiveDate> {
+        if self.is_complete() {
+            return Some(*today);
+        }
+        let monthly = self.monthly_contribution.minor_units();
+        if monthly <= 0 {
+            return None;
+        }
+        let remaining = self.remaining().minor_units();
+        let months = ((remaining + monthly - 1) / monthly).min(u32::MAX as i64) as u32;
+        today.checked_add_months(Months::new(months))
+    }
+}
+
+/// The repayment terms of a loan account.
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::loan)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Loan {
+    pub id: i32,
+    pub loan_account: i32,
+    pub principal: i64,    // Minor units of the loan account currency
+    pub annual_rate: f64,  // Percent, e.g. 4.5
+    pub term: i32,         // Number of payments
+    pub frequency: String, // See `Frequency::as_str`
+    pub first_payment: NaiveDate,
+    pub payment_account: i32,
+    pub interest_category: i32,
+}
+
+impl Loan {
+    pub fn principal(&self, scale: u32) -> Money {
+        Money::new(self.principal, scale)
+    }
+
+    pub fn frequency(&self) -> Frequency {
+        Frequency::from_str(&self.frequency).unwrap_or(Frequency::Monthly)
+    }
+
+    /// The interest rate applied at each payment.
+    pub fn periodic_rate(&self) -> f64 {
+        let periods_per_year = match self.frequency() {
+            Frequency::Daily => 365.,
+            Frequency::Weekly => 52.,
+            Frequency::Monthly => 12.,
+            Frequency::Yearly => 1.,
+        };
+        self.annual_rate / 100. / periods_per_year
+    }
+
+    /// The level payment that repays the principal over the term.
+    pub fn payment(&self, scale: u32) -> Money {
+        let principal = self.principal as f64;
+        let term = self.term.max(1);
+        let rate = self.periodic_rate();
+        let payment = if rate == 0. {
+            principal / term as f64
+        } else {
+            principal * rate / (1. - (1. + rate).powi(-term))
+        };
+        Money::new(payment.round() as i64, scale)
+    }
+
+    /// The due date of payment `number`, counting from 1.
+    pub fn payment_date(&self, number: i32) -> Option<NaiveDate> {
+        let periods = (number - 1).max(0) as u32;
+        match self.frequency() {
+            Frequency::Daily => self
+                .first_payment
+                .checked_add_days(Days::new(periods as u64)),
+            Frequency::Weekly => self
+                .first_payment
+                .checked_add_days(Days::new(7 * periods as u64)),
+            Frequency::Monthly => self.first_payment.checked_add_months(Months::new(periods)),
+            Frequency::Yearly => self
+                .first_payment
+                .checked_add_months(Months::new(12 * periods)),
+        }
+    }
+
+    /// Splits payment `number` into principal and interest while `owed` is
+    /// still due. The last payment of the term clears whatever is left.
+    pub fn installment(&self, number: i32, owed: Money) -> Installment {
+        let scale = owed.scale();
+        let interest = Money::new(
+            (owed.minor_units() as f64 * self.periodic_rate()).round() as i64,
+            scale,
+        );
+        let mut principal = self.payment(scale) - interest;
+        if number >= self.term || principal.minor_units() > owed.minor_units() {
+            principal = owed;
+        }
+        if principal.is_negative() {
+            principal = Money::zero(scale);
+        }
+        Installment {
+            number,
+            payment_date: self.payment_date(number).unwrap_or(self.first_payment),
+            principal,
+            interest,
+            remaining: owed - principal,
+        }
+    }
+
+    /// The full amortization schedule of the original terms.
+    pub fn schedule(&self, scale: u32) -> Vec<Installment> {
+        let mut owed = self.principal(scale);
+        let mut schedule = vec![];
+        for number in 1..=self.term.max(1) {
+            let installment = self.installment(number, owed);
+            owed = installment.remaining;
+            schedule.push(installment);
+            if owed.is_zero() {
+                break;
+            }
+        }
+        schedule
+    }
+}
+
+/// The terms entered when opening a loan account; the principal is the
+/// amount owed of the new account.
+#[derive(Debug, Clone)]
+pub struct NewLoan {
+    pub annual_rate: f64,
+    pub term: i32,
+    pub frequency: String,
+    pub first_payment: NaiveDate,
+    pub payment_account: i32,
+    pub interest_category: i32,
+}
+
+/// One line of an amortization schedule.
+#[derive(Debug, Clone, Copy)]
+pub struct Installment {
+    pub number: i32,
+    pub payment_date: NaiveDate,
+    pub principal: Money,
+    pub interest: Money,
+    pub remaining: Money, // Owed once the payment is made
+}
+
+impl Installment {
+    pub fn payment(&self) -> Money {
+        self.principal + self.interest
+    }
+}
+
+/// A posted loan payment: the principal moved into the loan account by a
+/// transfer and the interest booked as an expense.
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::loan_payment)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct LoanPayment {
+    pub id: i32,
+    pub loan_id: i32,
+    pub payment_number: i32,
+    pub payment_date: NaiveDate,
+    pub principal: i64, // Minor units of the loan account currency
+    pub interest: i64,
+    pub transfer_transaction: i32, // The side of the transfer on the loan account
+    pub interest_transaction: Option<i32>, // `None` when no interest was due
+}
+
+impl LoanPayment {
+    pub fn principal(&self, scale: u32) -> Money {
+        Money::new(self.principal, scale)
+    }
+
+    pub fn interest(&self, scale: u32) -> Money {
+        Money::new(self.interest, scale)
+    }
+}
+
+/// How the cost of sold shares is taken from the lots an account holds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum CostBasisMethod {
+    #[default]
+    Fifo,
+    AverageCost,
+}
+
+impl CostBasisMethod {
+    pub const ALL: [CostBasisMethod; 2] = [CostBasisMethod::Fifo, CostBasisMethod::AverageCost];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CostBasisMethod::Fifo => "fifo",
+            CostBasisMethod::AverageCost => "average_cost",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.as_str() == value)
+    }
+}
+
+impl ToSql<Text, Sqlite> for CostBasisMethod {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for CostBasisMethod {
+    fn from_sql(value: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        CostBasisMethod::from_str(&value)
+            .ok_or_else(|| format!("Unknown cost basis method: {}", value).into())
+    }
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::security)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Security {
+    pub id: i32,
+    pub symbol: String,
+    pub name: String,
+}
+
+impl AsRef<str> for Security {
+    fn as_ref(&self) -> &str {
+        &self.symbol
+    }
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = security)]
+pub struct NewSecurity {
+    pub symbol: String,
+    pub name: String,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::security_price)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct SecurityPrice {
+    pub id: i32,
+    pub security_id: i32,
+    pub price_date: NaiveDate,
+    pub price: f64, // Per share, in the currency of the holding account
+}
+
+/// Reads price history as `date,price` lines, e.g. `2026-10-16,123.45`. A
+/// header line and blank lines are skipped. Fails with the number of the
+/// first line that cannot be read.
+pub fn parse_price_csv(input: &str) -> Result<Vec<(NaiveDate, f64)>, usize> {
+    let mut prices = vec![];
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line
+            .split([',', ';'])
+            .map(|field| field.trim().trim_matches('"'));
+        let date = fields
+            .next()
+            .and_then(|field| NaiveDate::parse_from_str(field, "%Y-%m-%d").ok());
+        let price = fields
+            .next()
+            .and_then(|field| field.parse::<f64>().ok())
+            .filter(|price| *price >= 0.);
+        match (date, price) {
+            (Some(date), Some(price)) => prices.push((date, price)),
+            _ if index == 0 => continue, // header
+            _ => return Err(index + 1),
+        }
+    }
+    Ok(prices)
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum TradeKind {
+    #[default]
+    Buy,
+    Sell,
+    Dividend,
+}
+
+impl TradeKind {
+    pub const ALL: [TradeKind; 3] = [TradeKind::Buy, TradeKind::Sell, TradeKind::Dividend];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TradeKind::Buy => "buy",
+            TradeKind::Sell => "sell",
+            TradeKind::Dividend => "dividend",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == value)
+    }
+}
+
+impl ToSql<Text, Sqlite> for TradeKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for TradeKind {
+    fn from_sql(value: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        TradeKind::from_str(&value).ok_or_else(|| format!("Unknown trade kind: {}", value).into())
+    }
+}
+
+/// A buy, sell or dividend on an investment account. Its cash side is the
+/// `cash_transaction` booked on the same account.
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::investment_trade)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct InvestmentTrade {
+    pub id: i32,
+    pub bank_account: i32,
+    pub security_id: i32,
+    pub trade_kind: TradeKind,
+    pub trade_date: NaiveDate,
+    pub quantity: f64, // Zero for dividends
+    pub price: f64,    // Per share, zero for dividends
+    pub fees: i64,     // Minor units of the account currency
+    pub amount: i64,   // The cash paid or received, fees included
+    pub cash_transaction: i32,
+}
+
+impl InvestmentTrade {
+    pub fn amount(&self, scale: u32) -> Money {
+        Money::new(self.amount, scale)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewTrade {
+    pub bank_account: i32,
+    pub security_id: i32,
+    pub trade_kind: TradeKind,
+    pub trade_date: NaiveDate,
+    pub quantity: f64,
+    pub price: f64,
+    pub fees: Money,
+    pub dividend: Money,                // Only used by dividends
+    pub dividend_category: Option<i32>, // The income category of a dividend
+}
+
+impl NewTrade {
+    /// The cash paid for a buy or received from a sell or a dividend.
+    pub fn amount(&self) -> Money {
+        let scale = self.fees.scale();
+        let gross = Money::new(
+            (self.quantity * self.price * 10f64.powi(scale as i32)).round() as i64,
+            scale,
+        );
+        match self.trade_kind {
+            TradeKind::Buy => gross + self.fees,
+            TradeKind::Sell => gross - self.fees,
+            TradeKind::Dividend => self.dividend,
+        }
+    }
+}
+
+/// Shares bought together and not sold yet, with what they cost.
+#[derive(Debug, Clone, Copy)]
+pub struct Lot {
+    pub trade_date: NaiveDate,
+    pub quantity: f64,
+    pub cost: Money,
+}
+
+/// What an account holds of one security.
+#[derive(Debug, Clone)]
+pub struct Holding {
+    pub security_id: i32,
+    pub lots: Vec<Lot>,
+    pub realized_gain: Money,
+    pub dividends: Money,
+}
+
+/// Quantities below this are rounding left-overs of fractional shares.
+const QUANTITY_EPSILON: f64 = 1e-9;
+
+impl Holding {
+    pub fn quantity(&self) -> f64 {
+        self.lots.iter().map(|lot| lot.quantity).sum()
+    }
+
+    pub fn cost_basis(&self) -> Money {
+        self.lots
+            .iter()
+            .fold(Money::zero(self.realized_gain.scale()), |total, lot| {
+                total + lot.cost
+            })
+    }
+
+    pub fn market_value(&self, price: f64) -> Money {
+        let scale = self.realized_gain.scale();
+        Money::new(
+            (self.quantity() * price * 10f64.powi(scale as i32)).round() as i64,
+            scale,
+        )
+    }
+
+    pub fn unrealized_gain(&self, price: f64) -> Money {
+        self.market_value(price) - self.cost_basis()
+    }
+
+    /// Takes `quantity` shares out of the lots, oldest first, and returns
+    /// their cost. Selling more than is held only takes what is there.
+    fn sell(&mut self, quantity: f64) -> Money {
+        let mut cost = Money::zero(self.realized_gain.scale());
+        let mut remaining = quantity;
+        while remaining > QUANTITY_EPSILON {
+            let Some(lot) = self.lots.first_mut() else {
+                break;
+            };
+            let taken = remaining.min(lot.quantity);
+            let taken_cost = Money::new(
+                (lot.cost.minor_units() as f64 * taken / lot.quantity).round() as i64,
+                lot.cost.scale(),
+            );
+            lot.quantity -= taken;
+            lot.cost -= taken_cost;
+            cost += taken_cost;
+            remaining -= taken;
+            if lot.quantity <= QUANTITY_EPSILON {
+                self.lots.remove(0);
+            }
+        }
+        cost
+    }
+}
+
+/// Replays the trades of an account, in date order, into its holdings. With
+/// the average cost method every buy is pooled into a single lot.
+pub fn holdings(trades: &[InvestmentTrade], method: CostBasisMethod, scale: u32) -> Vec<Holding> {
+    let mut ordered: Vec<&InvestmentTrade> = trades.iter().collect();
+    ordered.sort_by_key(|t| (t.trade_date, t.id));
+
+    let mut holdings: Vec<Holding> = vec![];
+    for trade in ordered {
+        let index = match holdings
+            .iter()
+            .position(|h| h.security_id == trade.security_id)
+        {
+            Some(index) => index,
+            None => {
+                holdings.push(Holding {
+                    security_id: trade.security_id,
+                    lots: vec![],
+                    realized_gain: Money::zero(scale),
+                    dividends: Money::zero(scale),
+                });
+                holdings.len() - 1
+            }
+        };
+        let holding = &mut holdings[index];
+        match trade.trade_kind {
+            TradeKind::Buy => {
+                let lot = Lot {
+                    trade_date: trade.trade_date,
+                    quantity: trade.quantity,
+                    cost: trade.amount(scale),
+                };
+                match holding.lots.first_mut() {
+                    Some(pooled) if method == CostBasisMethod::AverageCost => {
+                        pooled.quantity += lot.quantity;
+                        pooled.cost += lot.cost;
+                    }
+                    _ => holding.lots.push(lot),
+                }
+            }
+            TradeKind::Sell => {
+                let cost = holding.sell(trade.quantity);
+                holding.realized_gain += trade.amount(scale) - cost;
+            }
+            TradeKind::Dividend => holding.dividends += trade.amount(scale),
+        }
+    }
+    holdings
+}
+
+/// The holdings of an investment account valued at their latest price.
+#[derive(Debug, Clone)]
+pub struct InvestmentSummary {
+    pub holdings: Vec<(Holding, Option<f64>)>, // With the latest known price
+    pub market_value: Money,
+    pub cost_basis: Money,
+    pub unrealized_gain: Money,
+    pub realized_gain: Money,
+    pub dividends: Money,
+}
+
+/// One undoable user action: the range of `undo_log` statements that
+/// reverse it, or that redo it once it has been undone.
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = change_journal)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ChangeStep {
+    pub id: i32,
+    pub first_seq: i32,
+    pub last_seq: i32,
+    pub is_undone: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        [
+            AuditAction::Create,
+            AuditAction::Update,
+            AuditAction::Delete,
+        ]
+        .into_iter()
+        .find(|a| a.as_str() == value)
+    }
+}
+
+impl ToSql<Text, Sqlite> for AuditAction {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for AuditAction {
+    fn from_sql(value: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        AuditAction::from_str(&value)
+            .ok_or_else(|| format!("Unknown audit action: {}", value).into())
+    }
+}
+
+/// A change to an account, category or transaction. Updates are recorded
+/// one field at a time, values are stored as they are in the database:
+/// amounts in minor units and references as ids.
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = audit_log)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct AuditEntry {
+    pub id: i32,
+    pub audited_table: String,
+    pub row_id: i32,
+    pub action: AuditAction,
+    pub field_name: Option<String>, // `None` for creations and deletions
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub changed_by: String,
+    pub changed_at: chrono::NaiveDateTime, // UTC
+}
+
+/// How many rows a ledger or one of its backups holds.
+#[derive(Debug, Clone, Copy)]
+pub struct LedgerSummary {
+    pub accounts: i64,
+    pub categories: i64,
+    pub transactions: i64,
+}
+
+/// Marks the start of a matched word in the fields of a [`TransactionMatch`].
+pub const MATCH_START: char = '\u{2}';
+/// Marks the end of a matched word in the fields of a [`TransactionMatch`].
+pub const MATCH_END: char = '\u{3}';
+
+/// A transaction found by a search, with the matched words of its note,
+/// payee and category names marked by [`MATCH_START`] and [`MATCH_END`].
+#[derive(QueryableByName, Debug, Clone)]
+pub struct TransactionMatch {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub transaction_id: i32,
+    #[diesel(sql_type = Text)]
+    pub description: String,
+    #[diesel(sql_type = Text)]
+    pub payee: String,
+    #[diesel(sql_type = Text)]
+    pub category: String,
+}
+
+/// Splits a field of a [`TransactionMatch`] into its pieces, telling
+/// whether each is a matched word.
+pub fn highlighted_parts(text: &str) -> Vec<(&str, bool)> {
+    let mut parts = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find(MATCH_START) {
+        if start > 0 {
+            parts.push((&rest[..start], false));
+        }
+        rest = &rest[start + MATCH_START.len_utf8()..];
+        let end = rest.find(MATCH_END).unwrap_or(rest.len());
+        parts.push((&rest[..end], true));
+        rest = rest.get(end + MATCH_END.len_utf8()..).unwrap_or("");
+    }
+    if !rest.is_empty() {
+        parts.push((rest, false));
+    }
+    parts
+}
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
 use cosmic::{
     iced::{self, Length, Padding},
     widget::{self, column, settings, Space},
@@ -7,16 +10,30 @@ use cosmic::{
 use crate::{
     app,
     config::Config,
+    data,
+    errors::DataStoreError,
     fl,
-    models::{Account, NewAccount, UpdateAccount},
+    models::{
+        categories_by_parent, category_labels, open_accounts, Account, AccountType, Category,
+        ClearedStatus, CostBasisMethod, Currency, Frequency, Installment, InvestmentSummary, Loan,
+        LoanPayment, Money, MoneyTransaction, NewAccount, NewLoan, NewTrade, Reconciliation,
+        Security, TradeKind, UpdateAccount,
+    },
+    repository::Repository,
+    store::{SharedStore, Store},
+    widget::date_picker::date_picker,
     STORE,
 };
 
-use super::transactions::TransactionMessage;
+use super::recurring::frequency_label;
+use super::transactions::{cleared_status_label, TransactionMessage};
+
+const MISMATCH_COLOR: cosmic::iced::Color = cosmic::iced::Color::from_rgb(0.85, 0.2, 0.2);
 
 #[derive(Debug, Clone)]
 pub enum AccountsMessage {
     Update,
+    Loaded(Result<AccountsData, DataStoreError>),
     AddAccountView,
     CancelNewBankAccount,
     SubmitNewBankAccount,
@@ -29,51 +46,356 @@ pub enum AccountsMessage {
     EditAccountSubmit,
     NewBankAccountNameChanged(String),
     NewBankAccountInitialValueChanged(String),
+    NewBankAccountCurrencyChanged(usize),
+    NewBankAccountTypeChanged(usize),
+    EditAccountCurrency(usize),
+    EditAccountType(usize),
+    ToggleArchiveAccount(i32),
+    StartReconcile(i32),
+    ReconciliationLoaded(Result<ReconciliationData, DataStoreError>),
+    StatementDateChanged(i64),
+    StatementBalanceChanged(String),
+    ToggleCleared(i32, bool),
+    FinishReconcile,
+    Reconciled(Result<(), String>),
+    CancelReconcile,
+    NewLoanRateChanged(String),
+    NewLoanTermChanged(String),
+    NewLoanFrequencyChanged(usize),
+    NewLoanFirstPaymentChanged(i64),
+    NewLoanPaymentAccountChanged(usize),
+    NewLoanInterestCategoryChanged(usize),
+    PostLoanPayment(i32),
+    LoanPaymentPosted(i32, Result<(), String>),
+    ToggleLoanSchedule(i32),
+    ScheduleLoaded(i32, Result<Vec<LoanPayment>, DataStoreError>),
+    CostBasisMethodChanged(i32, usize),
+    StartTrade(i32),
+    CancelTrade,
+    TradeKindChanged(usize),
+    TradeSecurityChanged(usize),
+    TradeDateChanged(i64),
+    TradeQuantityChanged(String),
+    TradePriceChanged(String),
+    TradeFeesChanged(String),
+    TradeDividendChanged(String),
+    TradeCategoryChanged(usize),
+    SubmitTrade,
+    TradeSubmitted(Result<(), String>),
 }
 
 pub struct Accounts {
-    currency_symbol: String,
+    store: SharedStore,
+    loading: bool,
+    reporting_currency_id: i32,
+    currencies: Vec<Currency>,
     accounts: Vec<Account>,
+    net_worth: String,
+    balances: HashMap<i32, Money>,
+    type_totals: Vec<(AccountType, Money)>,
+    next_installments: HashMap<i32, Installment>,
+    investment_summaries: HashMap<i32, InvestmentSummary>,
     add_account_view_visible: bool,
     form_new_account_name_value: String,
     form_new_account_initial_value: String,
-    new_account_initial_value: f32,
+    form_new_account_currency: Option<usize>,
     new_account_description: String,
     edit_account_name: String,
     edit_account_balance: String,
     edit_account_description: String,
+    edit_account_currency: Option<usize>,
     editing_account: Option<i32>,
+    account_type_options: Vec<String>,
+    form_new_account_type: Option<usize>,
+    edit_account_type: Option<usize>,
+    reconciling_account: Option<i32>,
+    statement_date: i64,
+    statement_balance: String,
+    reconcile_transactions: Vec<MoneyTransaction>,
+    last_reconciliation: Option<Reconciliation>,
+    cleared_balance: Money,
+    reconcile_error: Option<String>,
+    loans: Vec<Loan>,
+    frequency_labels: Vec<String>,
+    payment_accounts: Vec<Account>,
+    interest_categories: Vec<Category>,
+    interest_category_labels: Vec<String>,
+    form_loan_rate: String,
+    form_loan_term: String,
+    form_loan_frequency: Option<usize>,
+    form_loan_first_payment: i64,
+    form_loan_payment_account: Option<usize>,
+    form_loan_interest_category: Option<usize>,
+    schedule_loan: Option<i32>,
+    schedule_payments: Vec<LoanPayment>,
+    loan_error: Option<(i32, String)>, // The loan whose payment failed
+    new_loan_error: Option<String>,
+    securities: Vec<Security>,
+    trade_kind_labels: Vec<String>,
+    cost_basis_labels: Vec<String>,
+    income_categories: Vec<Category>,
+    income_category_labels: Vec<String>,
+    trading_account: Option<i32>,
+    form_trade_kind: Option<usize>,
+    form_trade_security: Option<usize>,
+    form_trade_date: i64,
+    form_trade_quantity: String,
+    form_trade_price: String,
+    form_trade_fees: String,
+    form_trade_dividend: String,
+    form_trade_category: Option<usize>,
+    trade_error: Option<String>,
+}
+
+pub fn account_type_label(account_type: AccountType) -> String {
+    match account_type {
+        AccountType::Checking => fl!("account-type-checking"),
+        AccountType::Savings => fl!("account-type-savings"),
+        AccountType::CreditCard => fl!("account-type-credit-card"),
+        AccountType::Cash => fl!("account-type-cash"),
+        AccountType::Investment => fl!("account-type-investment"),
+        AccountType::Loan => fl!("account-type-loan"),
+    }
 }
 
 impl Default for Accounts {
     fn default() -> Self {
-        let mut store = STORE.lock().unwrap();
+        Self::new(STORE.clone())
+    }
+}
+
+pub fn trade_kind_label(kind: TradeKind) -> String {
+    match kind {
+        TradeKind::Buy => fl!("trade-buy"),
+        TradeKind::Sell => fl!("trade-sell"),
+        TradeKind::Dividend => fl!("trade-dividend"),
+    }
+}
+
+fn cost_basis_label(method: CostBasisMethod) -> String {
+    match method {
+        CostBasisMethod::Fifo => fl!("cost-basis-fifo"),
+        CostBasisMethod::AverageCost => fl!("cost-basis-average"),
+    }
+}
+
+/// Loan payments come out of the accounts that are not liabilities.
+fn payment_accounts(accounts: &[Account]) -> Vec<Account> {
+    open_accounts(accounts, &[])
+        .into_iter()
+        .filter(|a| !a.account_type.is_liability())
+        .collect()
+}
+
+fn categories_of_kind(categories: &[Category], is_income: bool) -> Vec<Category> {
+    categories_by_parent(
+        categories
+            .iter()
+            .filter(|c| c.is_income == is_income)
+            .cloned()
+            .collect(),
+    )
+}
+
+/// What the accounts page shows, read by the data service.
+#[derive(Debug, Clone)]
+pub struct AccountsData {
+    reporting_currency_id: i32,
+    currencies: Vec<Currency>,
+    accounts: Vec<Account>,
+    net_worth: String,
+    balances: HashMap<i32, Money>,
+    type_totals: Vec<(AccountType, Money)>,
+    loans: Vec<Loan>,
+    next_installments: HashMap<i32, Installment>,
+    investment_summaries: HashMap<i32, InvestmentSummary>,
+    categories: Vec<Category>,
+    securities: Vec<Security>,
+}
+
+impl AccountsData {
+    fn load(store: &mut Store) -> Result<Self, DataStoreError> {
         let config = Config::load();
+        let reporting_currency_id = config.1.currency_id;
+        let accounts = store.get_accounts()?;
+        let currencies = store.get_currencies()?;
+        let net_worth = format!(
+            "{} {}",
+            store.get_net_worth(reporting_currency_id)?,
+            currencies
+                .iter()
+                .find(|c| c.id == reporting_currency_id)
+                .map(|c| c.symbol.clone())
+                .unwrap_or_default()
+        );
+        let balances = accounts
+            .iter()
+            .map(|account| {
+                store
+                    .get_account_balance(account.id, reporting_currency_id)
+                    .map(|balance| (account.id, balance))
+            })
+            .collect::<Result<HashMap<i32, Money>, DataStoreError>>()?;
+        let loans = store.get_loans()?;
+        let mut next_installments = HashMap::new();
+        for loan in &loans {
+            if let Some(installment) =
+                store.get_next_loan_installment(loan, reporting_currency_id)?
+            {
+                next_installments.insert(loan.id, installment);
+            }
+        }
+        let today = Utc::now().date_naive();
+        let investment_summaries = accounts
+            .iter()
+            .filter(|account| account.account_type == AccountType::Investment)
+            .map(|account| {
+                store
+                    .get_investment_summary(account.id, &today, reporting_currency_id)
+                    .map(|summary| (account.id, summary))
+            })
+            .collect::<Result<HashMap<i32, InvestmentSummary>, DataStoreError>>()?;
+        Ok(Self {
+            reporting_currency_id,
+            net_worth,
+            balances,
+            type_totals: store.get_account_type_totals(reporting_currency_id)?,
+            loans,
+            next_installments,
+            investment_summaries,
+            categories: store.get_categories()?,
+            securities: store.get_securities()?,
+            accounts,
+            currencies,
+        })
+    }
+}
 
-        let accounts = store.get_accounts();
-        let currency_symbol = store.get_currency_symbol_by_id(config.1.currency_id);
+/// The account being reconciled as of a statement date, read by the data
+/// service.
+#[derive(Debug, Clone)]
+pub struct ReconciliationData {
+    account_id: i32,
+    statement_date: i64,
+    transactions: Vec<MoneyTransaction>,
+    last_reconciliation: Option<Reconciliation>,
+    cleared_balance: Money,
+}
+
+impl ReconciliationData {
+    fn load(
+        store: &mut Store,
+        account_id: i32,
+        statement_date: i64,
+        reporting_currency_id: i32,
+    ) -> Result<Self, DataStoreError> {
+        let mut transactions = store
+            .get_money_transactions()?
+            .into_iter()
+            .filter(|t| t.bank_account == account_id && !t.is_reconciled())
+            .collect::<Vec<MoneyTransaction>>();
+        transactions.sort_by_key(|t| t.transaction_date);
+        Ok(Self {
+            account_id,
+            statement_date,
+            transactions,
+            last_reconciliation: store.get_last_reconciliation(account_id)?,
+            cleared_balance: store.get_cleared_balance(
+                account_id,
+                &date_of(statement_date),
+                reporting_currency_id,
+            )?,
+        })
+    }
+}
+
+fn date_of(timestamp: i64) -> chrono::NaiveDate {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .date_naive()
+}
 
+impl Accounts {
+    /// The page starts out empty until `AccountsMessage::Update` loads it.
+    pub fn new(store: SharedStore) -> Self {
         Self {
-            currency_symbol: currency_symbol.unwrap_or_else(|_| "USD".to_string()),
-            accounts: if let Ok(accounts) = accounts {
-                accounts
-            } else {
-                Vec::new()
-            },
+            store,
+            loading: true,
+            reporting_currency_id: Config::load().1.currency_id,
+            currencies: vec![],
+            accounts: vec![],
+            net_worth: "".to_string(),
+            balances: HashMap::new(),
+            type_totals: vec![],
+            next_installments: HashMap::new(),
+            investment_summaries: HashMap::new(),
             add_account_view_visible: false,
             form_new_account_name_value: fl!("bank-account"),
             form_new_account_initial_value: "".to_string(),
+            form_new_account_currency: None,
             new_account_description: "".to_string(),
-            new_account_initial_value: 0.,
             editing_account: None,
             edit_account_name: "".to_string(),
             edit_account_balance: "".to_string(),
             edit_account_description: "".to_string(),
+            edit_account_currency: None,
+            account_type_options: AccountType::ALL
+                .iter()
+                .map(|t| account_type_label(*t))
+                .collect(),
+            form_new_account_type: Some(0),
+            edit_account_type: Some(0),
+            reconciling_account: None,
+            statement_date: Utc::now().timestamp(),
+            statement_balance: "".to_string(),
+            reconcile_transactions: vec![],
+            last_reconciliation: None,
+            cleared_balance: Money::zero(Money::DEFAULT_SCALE),
+            reconcile_error: None,
+            loans: vec![],
+            frequency_labels: Frequency::ALL
+                .iter()
+                .map(|frequency| frequency_label(*frequency))
+                .collect(),
+            payment_accounts: vec![],
+            interest_category_labels: vec![],
+            interest_categories: vec![],
+            form_loan_rate: "".to_string(),
+            form_loan_term: "".to_string(),
+            form_loan_frequency: Frequency::ALL
+                .iter()
+                .position(|frequency| *frequency == Frequency::Monthly),
+            form_loan_first_payment: Utc::now().timestamp(),
+            form_loan_payment_account: None,
+            form_loan_interest_category: None,
+            schedule_loan: None,
+            schedule_payments: vec![],
+            loan_error: None,
+            new_loan_error: None,
+            securities: vec![],
+            trade_kind_labels: TradeKind::ALL
+                .iter()
+                .map(|k| trade_kind_label(*k))
+                .collect(),
+            cost_basis_labels: CostBasisMethod::ALL
+                .iter()
+                .map(|m| cost_basis_label(*m))
+                .collect(),
+            income_category_labels: vec![],
+            income_categories: vec![],
+            trading_account: None,
+            form_trade_kind: Some(0),
+            form_trade_security: None,
+            form_trade_date: Utc::now().timestamp(),
+            form_trade_quantity: "".to_string(),
+            form_trade_price: "".to_string(),
+            form_trade_fees: "".to_string(),
+            form_trade_dividend: "".to_string(),
+            form_trade_category: None,
+            trade_error: None,
         }
     }
-}
 
-impl Accounts {
     pub fn view<'a>(&'a self) -> Element<'a, AccountsMessage> {
         let mut col = column::<AccountsMessage>().push(widget::text::title1(fl!("page_accounts")));
 
@@ -86,112 +408,212 @@ impl Accounts {
             .align_x(iced::alignment::Horizontal::Right),
         );
 
+        col = col.push(widget::text::title4(format!(
+            "{}: {}",
+            fl!("net-worth"),
+            self.net_worth
+        )));
+
         if self.add_account_view_visible {
             col = col.push(self.add_account_view())
         }
 
         if self.accounts.len() > 0 {
-            for account in &self.accounts {
-                let edit_button = widget::button::icon(widget::icon::from_name("edit-symbolic"))
-                    .on_press(AccountsMessage::EditAccount(account.id));
-                let mut main_col = widget::column().push(
-                    widget::row()
-                        .push(
-                            widget::column()
-                                .push(widget::text::text(format!(
-                                    "{}: {} {}",
-                                    "Balance",
-                                    self.read_account_balance(account.id),
-                                    self.currency_symbol
-                                )))
-                                .width(Length::Fill),
-                        )
-                        .push(match self.editing_account {
-                            Some(id) => {
-                                if id == account.id {
-                                    widget::button::icon(widget::icon::from_name(
-                                        "window-close-symbolic",
-                                    ))
-                                    .on_press(AccountsMessage::CloseEditAccount)
+            let type_totals = &self.type_totals;
+            for account_type in AccountType::ALL
+                .into_iter()
+                .filter(|t| self.accounts.iter().any(|a| a.account_type == *t))
+            {
+                let total = type_totals
+                    .iter()
+                    .find(|(t, _)| *t == account_type)
+                    .map(|(_, total)| total.to_string())
+                    .unwrap_or_default();
+                col = col
+                    .push(Space::with_height(10))
+                    .push(widget::text::title3(format!(
+                        "{}: {} {}",
+                        account_type_label(account_type),
+                        total,
+                        self.currency_of(None)
+                            .map(|c| c.symbol.clone())
+                            .unwrap_or_default()
+                    )))
+                    .push(Space::with_height(10));
+                for account in self
+                    .accounts
+                    .iter()
+                    .filter(|a| a.account_type == account_type)
+                {
+                    let edit_button =
+                        widget::button::icon(widget::icon::from_name("edit-symbolic"))
+                            .on_press(AccountsMessage::EditAccount(account.id));
+                    let mut main_col = widget::column().push(
+                        widget::row()
+                            .push(
+                                widget::column()
+                                    .push(widget::text::text(format!(
+                                        "{}: {} {}",
+                                        "Balance",
+                                        self.account_balance(account.id),
+                                        self.currency_of(account.currency_id)
+                                            .map(|c| c.symbol.clone())
+                                            .unwrap_or_default()
+                                    )))
+                                    .width(Length::Fill),
+                            )
+                            .push(
+                                widget::button::text(fl!("reconcile"))
+                                    .on_press(AccountsMessage::StartReconcile(account.id)),
+                            )
+                            .push(Space::with_width(10))
+                            .push(
+                                widget::button::text(if account.is_archived {
+                                    fl!("unarchive")
                                 } else {
-                                    edit_button
+                                    fl!("archive")
+                                })
+                                .on_press(AccountsMessage::ToggleArchiveAccount(account.id)),
+                            )
+                            .push(Space::with_width(10))
+                            .push(match self.editing_account {
+                                Some(id) => {
+                                    if id == account.id {
+                                        widget::button::icon(widget::icon::from_name(
+                                            "window-close-symbolic",
+                                        ))
+                                        .on_press(AccountsMessage::CloseEditAccount)
+                                    } else {
+                                        edit_button
+                                    }
                                 }
-                            }
-                            None => edit_button,
-                        })
-                        .width(Length::Fill),
-                );
-                if let Some(account_id) = self.editing_account {
-                    if account_id == account.id {
-                        main_col = main_col.push(widget::divider::horizontal::default());
-                        main_col = main_col.push(Space::with_height(10));
-                        main_col = main_col.push(
-                            widget::row()
-                                .push(
-                                    widget::column()
-                                        .push(widget::text::title4(fl!("account-name")))
-                                        .push(
-                                            widget::text_input(
-                                                fl!("account-name"),
-                                                &self.edit_account_name,
+                                None => edit_button,
+                            })
+                            .width(Length::Fill),
+                    );
+                    if let Some(loan) = self.loans.iter().find(|l| l.loan_account == account.id) {
+                        main_col = main_col
+                            .push(Space::with_height(10))
+                            .push(self.loan_view(account, loan));
+                    }
+                    if account.account_type == AccountType::Investment {
+                        main_col = main_col
+                            .push(Space::with_height(10))
+                            .push(self.investment_view(account));
+                    }
+                    if self.reconciling_account == Some(account.id) {
+                        main_col = main_col
+                            .push(widget::divider::horizontal::default())
+                            .push(Space::with_height(10))
+                            .push(self.reconcile_view(account));
+                    }
+                    if let Some(account_id) = self.editing_account {
+                        if account_id == account.id {
+                            main_col = main_col.push(widget::divider::horizontal::default());
+                            main_col = main_col.push(Space::with_height(10));
+                            main_col = main_col.push(
+                                widget::row()
+                                    .push(
+                                        widget::column()
+                                            .push(widget::text::title4(fl!("account-name")))
+                                            .push(
+                                                widget::text_input(
+                                                    fl!("account-name"),
+                                                    &self.edit_account_name,
+                                                )
+                                                .on_input(AccountsMessage::EditAccountName),
                                             )
-                                            .on_input(AccountsMessage::EditAccountName),
-                                        )
-                                        .width(Length::Fill),
-                                )
-                                .push(Space::with_width(10))
-                                .push(
-                                    widget::column()
-                                        .push(widget::text::title4(fl!("balance")))
-                                        .push(
-                                            widget::text_input(
-                                                fl!("balance"),
-                                                &self.edit_account_balance,
+                                            .width(Length::Fill),
+                                    )
+                                    .push(Space::with_width(10))
+                                    .push(
+                                        widget::column()
+                                            .push(widget::text::title4(
+                                                self.balance_label(self.edit_account_type),
+                                            ))
+                                            .push(
+                                                widget::text_input(
+                                                    fl!("balance"),
+                                                    &self.edit_account_balance,
+                                                )
+                                                .on_input(AccountsMessage::EditAccountBalance),
                                             )
-                                            .on_input(AccountsMessage::EditAccountBalance),
+                                            .width(Length::Fill),
+                                    ),
+                            );
+                            main_col = main_col.push(Space::with_height(10));
+                            main_col = main_col.push(
+                                widget::row()
+                                    .push(
+                                        widget::column()
+                                            .push(widget::text::text(fl!("account-type")))
+                                            .push(widget::dropdown(
+                                                &self.account_type_options,
+                                                self.edit_account_type,
+                                                AccountsMessage::EditAccountType,
+                                            ))
+                                            .width(Length::Fill),
+                                    )
+                                    .push(Space::with_width(10))
+                                    .push(
+                                        widget::column()
+                                            .push(widget::text::text(fl!("currency")))
+                                            .push(widget::dropdown(
+                                                &self.currencies,
+                                                self.edit_account_currency,
+                                                AccountsMessage::EditAccountCurrency,
+                                            ))
+                                            .width(Length::Fill),
+                                    ),
+                            );
+                            main_col = main_col.push(Space::with_height(10));
+                            main_col = main_col.push(
+                                widget::column()
+                                    .push(widget::text::text(fl!("description")))
+                                    .push(
+                                        widget::text_input(
+                                            fl!("description"),
+                                            &self.edit_account_description,
                                         )
-                                        .width(Length::Fill),
-                                ),
-                        );
-                        main_col = main_col.push(Space::with_height(10));
-                        main_col = main_col.push(
-                            widget::column()
-                                .push(widget::text::text(fl!("description")))
-                                .push(
-                                    widget::text_input(
-                                        fl!("description"),
-                                        &self.edit_account_description,
+                                        .on_input(AccountsMessage::EditAccountDescription),
+                                    ),
+                            );
+                            main_col = main_col.push(Space::with_height(10));
+                            main_col = main_col.push(
+                                widget::row()
+                                    .push(
+                                        widget::button::text(fl!("save"))
+                                            .on_press(AccountsMessage::EditAccountSubmit)
+                                            .class(widget::button::ButtonClass::Suggested),
                                     )
-                                    .on_input(AccountsMessage::EditAccountDescription),
-                                ),
-                        );
-                        main_col = main_col.push(Space::with_height(10));
-                        main_col = main_col.push(
-                            widget::row()
-                                .push(
-                                    widget::button::text(fl!("save"))
-                                        .on_press(AccountsMessage::EditAccountSubmit)
-                                        .class(widget::button::ButtonClass::Suggested),
-                                )
-                                .push(Space::with_width(10))
-                                .push(
-                                    widget::button::text(fl!("cancel"))
-                                        .on_press(AccountsMessage::CloseEditAccount)
-                                        .class(widget::button::ButtonClass::Destructive),
-                                ),
-                        )
+                                    .push(Space::with_width(10))
+                                    .push(
+                                        widget::button::text(fl!("cancel"))
+                                            .on_press(AccountsMessage::CloseEditAccount)
+                                            .class(widget::button::ButtonClass::Destructive),
+                                    ),
+                            )
+                        }
                     }
+                    col = col
+                        .push(
+                            settings::section()
+                                .title(if account.is_archived {
+                                    format!("{} ({})", account.name, fl!("archived"))
+                                } else {
+                                    account.name.to_string()
+                                })
+                                .add(main_col),
+                        )
+                        .push(Space::with_height(20));
                 }
-                col = col
-                    .push(
-                        settings::section()
-                            .title(account.name.to_string())
-                            .add(main_col),
-                    )
-                    .push(Space::with_height(20));
             }
         } else {
-            col = col.push(widget::text::text(fl!("no-elements")));
+            col = col.push(widget::text::text(if self.loading {
+                fl!("loading")
+            } else {
+                fl!("no-elements")
+            }));
         }
 
         widget::scrollable(
@@ -202,6 +624,580 @@ impl Accounts {
         .into()
     }
 
+    /// Ticks off the transactions found on a bank statement until the cleared
+    /// balance matches the statement balance.
+    fn reconcile_view<'a>(&'a self, account: &Account) -> Element<'a, AccountsMessage> {
+        let scale = self.account_scale(account);
+        let symbol = self
+            .currency_of(account.currency_id)
+            .map(|c| c.symbol.clone())
+            .unwrap_or_default();
+        let statement_date = self.statement_date();
+
+        let mut element = widget::column()
+            .push(widget::text::title4(fl!("reconcile")))
+            .push_maybe(self.last_reconciliation.as_ref().map(|r| {
+                widget::text::text(format!(
+                    "{}: {} ({} {})",
+                    fl!("last-reconciled"),
+                    r.statement_date.format("%d-%m-%Y"),
+                    self.displayed_balance(account, Money::new(r.statement_balance, scale)),
+                    symbol
+                ))
+            }))
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("statement-date")))
+                            .push(date_picker(self.statement_date, |date| {
+                                AccountsMessage::StatementDateChanged(date)
+                            }))
+                            .width(Length::Fill),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(if account.account_type.is_liability() {
+                                fl!("statement-amount-owed")
+                            } else {
+                                fl!("statement-balance")
+                            }))
+                            .push(
+                                widget::text_input("0", &self.statement_balance)
+                                    .on_input(AccountsMessage::StatementBalanceChanged),
+                            )
+                            .width(Length::Fill),
+                    ),
+            )
+            .push(Space::with_height(10));
+
+        for t in self
+            .reconcile_transactions
+            .iter()
+            .filter(|t| t.transaction_date.date() <= statement_date)
+        {
+            let signed_amount = if t.is_expense {
+                -t.amount(scale)
+            } else {
+                t.amount(scale)
+            };
+            element = element.push(
+                widget::row()
+                    .push(
+                        widget::checkbox(
+                            format!(
+                                "{}  {}",
+                                t.transaction_date.format("%d-%m-%Y"),
+                                t.description
+                            ),
+                            t.cleared_status == ClearedStatus::Cleared,
+                        )
+                        .on_toggle(move |checked| AccountsMessage::ToggleCleared(t.id, checked))
+                        .width(Length::Fill),
+                    )
+                    .push(widget::text::text(format!("{} {}", signed_amount, symbol))),
+            );
+        }
+
+        let cleared_balance = self.cleared_balance;
+        let difference = self
+            .statement_balance_value(account)
+            .map(|statement| statement - cleared_balance);
+
+        element = element
+            .push(Space::with_height(10))
+            .push(widget::text::text(format!(
+                "{}: {} {}",
+                fl!("cleared-balance"),
+                self.displayed_balance(account, cleared_balance),
+                symbol
+            )))
+            .push_maybe(difference.map(|difference| {
+                let text =
+                    widget::text::text(format!("{}: {} {}", fl!("difference"), difference, symbol));
+                if difference.is_zero() {
+                    text
+                } else {
+                    text.class(cosmic::theme::Text::Color(MISMATCH_COLOR))
+                }
+            }))
+            .push_maybe(
+                self.reconcile_error
+                    .as_ref()
+                    .map(|e| widget::text::text(e.clone())),
+            )
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::button::text(fl!("finish-reconciliation"))
+                            .on_press_maybe(
+                                difference
+                                    .filter(|difference| difference.is_zero())
+                                    .map(|_| AccountsMessage::FinishReconcile),
+                            )
+                            .class(widget::button::ButtonClass::Suggested),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::button::text(fl!("cancel"))
+                            .on_press(AccountsMessage::CancelReconcile)
+                            .class(widget::button::ButtonClass::Destructive),
+                    ),
+            )
+            .push(Space::with_height(10));
+
+        element.into()
+    }
+
+    /// The terms of a loan, its next payment and, on demand, the full
+    /// amortization schedule.
+    fn loan_view<'a>(&'a self, account: &Account, loan: &Loan) -> Element<'a, AccountsMessage> {
+        let scale = self.account_scale(account);
+        let symbol = self
+            .currency_of(account.currency_id)
+            .map(|c| c.symbol.clone())
+            .unwrap_or_default();
+        let owed = self.displayed_balance(account, self.account_balance(account.id));
+        let next = self.next_installments.get(&loan.id);
+
+        let mut element = widget::column()
+            .push(widget::text::text(format!(
+                "{}: {} {}",
+                fl!("remaining-balance"),
+                owed,
+                symbol
+            )))
+            .push(widget::text::text(fl!(
+                "loan-terms",
+                rate = loan.annual_rate.to_string(),
+                payments = loan.term,
+                frequency = frequency_label(loan.frequency())
+            )))
+            .push(widget::text::text(match next {
+                Some(installment) => format!(
+                    "{} ({}/{}): {}  {} {} ({} {}, {} {})",
+                    fl!("next-payment"),
+                    installment.number,
+                    loan.term,
+                    installment.payment_date.format("%d-%m-%Y"),
+                    installment.payment(),
+                    symbol,
+                    installment.principal,
+                    fl!("principal"),
+                    installment.interest,
+                    fl!("interest")
+                ),
+                None => fl!("loan-paid-off"),
+            }))
+            .push_maybe(
+                self.loan_error
+                    .as_ref()
+                    .filter(|(loan_id, _)| *loan_id == loan.id)
+                    .map(|(_, e)| widget::text::text(e.clone())),
+            )
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::button::text(fl!("post-payment"))
+                            .on_press_maybe(next.map(|_| AccountsMessage::PostLoanPayment(loan.id)))
+                            .class(widget::button::ButtonClass::Suggested),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::button::text(if self.schedule_loan == Some(loan.id) {
+                            fl!("hide-schedule")
+                        } else {
+                            fl!("show-schedule")
+                        })
+                        .on_press(AccountsMessage::ToggleLoanSchedule(loan.id)),
+                    ),
+            );
+
+        if self.schedule_loan == Some(loan.id) {
+            element = element
+                .push(Space::with_height(10))
+                .push(self.schedule_view(loan, scale));
+        }
+
+        element.into()
+    }
+
+    /// The planned payments of a loan; posted ones show how they were
+    /// actually split.
+    fn schedule_view<'a>(&'a self, loan: &Loan, scale: u32) -> Element<'a, AccountsMessage> {
+        let mut element = widget::column().push(schedule_row([
+            "#".to_string(),
+            fl!("date"),
+            fl!("payment"),
+            fl!("principal"),
+            fl!("interest"),
+            fl!("remaining-balance"),
+            "".to_string(),
+        ]));
+
+        for installment in loan.schedule(scale) {
+            let posted = self
+                .schedule_payments
+                .iter()
+                .find(|p| p.payment_number == installment.number);
+            let (date, principal, interest) = match posted {
+                Some(p) => (p.payment_date, p.principal(scale), p.interest(scale)),
+                None => (
+                    installment.payment_date,
+                    installment.principal,
+                    installment.interest,
+                ),
+            };
+            element = element.push(schedule_row([
+                installment.number.to_string(),
+                date.format("%d-%m-%Y").to_string(),
+                (principal + interest).to_string(),
+                principal.to_string(),
+                interest.to_string(),
+                installment.remaining.to_string(),
+                if posted.is_some() {
+                    fl!("paid")
+                } else {
+                    "".to_string()
+                },
+            ]));
+        }
+
+        element.into()
+    }
+
+    /// The holdings of an investment account at their latest price, and the
+    /// form to book a trade.
+    fn investment_view<'a>(&'a self, account: &Account) -> Element<'a, AccountsMessage> {
+        let symbol = self
+            .currency_of(account.currency_id)
+            .map(|c| c.symbol.clone())
+            .unwrap_or_default();
+        let account_id = account.id;
+
+        let mut element = widget::column();
+        if let Some(summary) = self.investment_summaries.get(&account.id) {
+            element = element
+                .push(
+                    widget::row()
+                        .push(
+                            widget::text::text(format!(
+                                "{}: {} {}",
+                                fl!("market-value"),
+                                summary.market_value,
+                                symbol
+                            ))
+                            .width(Length::Fill),
+                        )
+                        .push(
+                            widget::text::text(format!(
+                                "{}: {} {}",
+                                fl!("cost-basis"),
+                                summary.cost_basis,
+                                symbol
+                            ))
+                            .width(Length::Fill),
+                        ),
+                )
+                .push(
+                    widget::row()
+                        .push(
+                            widget::text::text(format!(
+                                "{}: {} {}",
+                                fl!("unrealized-gain"),
+                                summary.unrealized_gain,
+                                symbol
+                            ))
+                            .width(Length::Fill),
+                        )
+                        .push(
+                            widget::text::text(format!(
+                                "{}: {} {}",
+                                fl!("realized-gain"),
+                                summary.realized_gain,
+                                symbol
+                            ))
+                            .width(Length::Fill),
+                        )
+                        .push(
+                            widget::text::text(format!(
+                                "{}: {} {}",
+                                fl!("dividends"),
+                                summary.dividends,
+                                symbol
+                            ))
+                            .width(Length::Fill),
+                        ),
+                )
+                .push(Space::with_height(10))
+                .push(self.holdings_view(summary));
+        }
+
+        element = element.push(Space::with_height(10)).push(
+            widget::row()
+                .push(widget::text::text(fl!("cost-basis-method")))
+                .push(Space::with_width(10))
+                .push(widget::dropdown(
+                    &self.cost_basis_labels,
+                    CostBasisMethod::ALL
+                        .iter()
+                        .position(|m| *m == account.cost_basis_method),
+                    move |index| AccountsMessage::CostBasisMethodChanged(account_id, index),
+                ))
+                .push(Space::with_width(10))
+                .push(
+                    widget::button::text(fl!("add-trade"))
+                        .on_press(AccountsMessage::StartTrade(account.id)),
+                )
+                .align_y(iced::Alignment::Center),
+        );
+
+        if self.trading_account == Some(account.id) {
+            element = element
+                .push(Space::with_height(10))
+                .push(self.trade_view(account));
+        }
+
+        element.into()
+    }
+
+    fn holdings_view<'a>(&'a self, summary: &InvestmentSummary) -> Element<'a, AccountsMessage> {
+        let mut element = widget::column().push(holdings_row([
+            fl!("symbol"),
+            fl!("quantity"),
+            fl!("price"),
+            fl!("market-value"),
+            fl!("unrealized-gain"),
+        ]));
+
+        for (holding, price) in summary
+            .holdings
+            .iter()
+            .filter(|(holding, _)| holding.quantity() > 0.)
+        {
+            element = element.push(holdings_row([
+                self.securities
+                    .iter()
+                    .find(|s| s.id == holding.security_id)
+                    .map(|s| s.symbol.clone())
+                    .unwrap_or_else(|| fl!("not-found")),
+                holding.quantity().to_string(),
+                price.map(|price| price.to_string()).unwrap_or_default(),
+                holding.market_value(price.unwrap_or(0.)).to_string(),
+                holding.unrealized_gain(price.unwrap_or(0.)).to_string(),
+            ]));
+        }
+
+        element.into()
+    }
+
+    fn trade_view<'a>(&'a self, account: &Account) -> Element<'a, AccountsMessage> {
+        let is_dividend = self.trade_kind() == TradeKind::Dividend;
+
+        let mut element = widget::column()
+            .push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("trade-kind")))
+                            .push(widget::dropdown(
+                                &self.trade_kind_labels,
+                                self.form_trade_kind,
+                                AccountsMessage::TradeKindChanged,
+                            ))
+                            .width(Length::Fill),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("security")))
+                            .push(widget::dropdown(
+                                &self.securities,
+                                self.form_trade_security,
+                                AccountsMessage::TradeSecurityChanged,
+                            ))
+                            .width(Length::Fill),
+                    ),
+            )
+            .push(Space::with_height(10));
+
+        element = if is_dividend {
+            element.push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("amount")))
+                            .push(
+                                widget::text_input("0", &self.form_trade_dividend)
+                                    .on_input(AccountsMessage::TradeDividendChanged),
+                            )
+                            .width(Length::Fill),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("category")))
+                            .push(widget::dropdown(
+                                &self.income_category_labels,
+                                self.form_trade_category,
+                                AccountsMessage::TradeCategoryChanged,
+                            ))
+                            .width(Length::Fill),
+                    ),
+            )
+        } else {
+            element.push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("quantity")))
+                            .push(
+                                widget::text_input("0", &self.form_trade_quantity)
+                                    .on_input(AccountsMessage::TradeQuantityChanged),
+                            )
+                            .width(Length::Fill),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("price")))
+                            .push(
+                                widget::text_input("0", &self.form_trade_price)
+                                    .on_input(AccountsMessage::TradePriceChanged),
+                            )
+                            .width(Length::Fill),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("fees")))
+                            .push(
+                                widget::text_input("0", &self.form_trade_fees)
+                                    .on_input(AccountsMessage::TradeFeesChanged),
+                            )
+                            .width(Length::Fill),
+                    ),
+            )
+        };
+
+        let new_trade = self.new_trade(account);
+        element
+            .push(Space::with_height(10))
+            .push(widget::text::text(fl!("date")))
+            .push(date_picker(self.form_trade_date, |date| {
+                AccountsMessage::TradeDateChanged(date)
+            }))
+            .push_maybe(new_trade.as_ref().map(|trade| {
+                widget::text::text(format!(
+                    "{}: {} {}",
+                    fl!("amount"),
+                    trade.amount(),
+                    self.currency_of(account.currency_id)
+                        .map(|c| c.symbol.clone())
+                        .unwrap_or_default()
+                ))
+            }))
+            .push_maybe(
+                self.trade_error
+                    .as_ref()
+                    .map(|e| widget::text::text(e.clone())),
+            )
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::button::text(fl!("add-trade"))
+                            .on_press_maybe(new_trade.map(|_| AccountsMessage::SubmitTrade))
+                            .class(widget::button::ButtonClass::Suggested),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::button::text(fl!("cancel"))
+                            .on_press(AccountsMessage::CancelTrade)
+                            .class(widget::button::ButtonClass::Destructive),
+                    ),
+            )
+            .into()
+    }
+
+    fn new_loan_view<'a>(&'a self) -> Element<'a, AccountsMessage> {
+        widget::column()
+            .push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("interest-rate")))
+                            .push(
+                                widget::text_input("0", &self.form_loan_rate)
+                                    .on_input(AccountsMessage::NewLoanRateChanged),
+                            )
+                            .width(Length::Fill),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("number-of-payments")))
+                            .push(
+                                widget::text_input("12", &self.form_loan_term)
+                                    .on_input(AccountsMessage::NewLoanTermChanged),
+                            )
+                            .width(Length::Fill),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("frequency")))
+                            .push(widget::dropdown(
+                                &self.frequency_labels,
+                                self.form_loan_frequency,
+                                AccountsMessage::NewLoanFrequencyChanged,
+                            ))
+                            .width(Length::Fill),
+                    ),
+            )
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("payment-account")))
+                            .push(widget::dropdown(
+                                &self.payment_accounts,
+                                self.form_loan_payment_account,
+                                AccountsMessage::NewLoanPaymentAccountChanged,
+                            ))
+                            .width(Length::Fill),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("interest-category")))
+                            .push(widget::dropdown(
+                                &self.interest_category_labels,
+                                self.form_loan_interest_category,
+                                AccountsMessage::NewLoanInterestCategoryChanged,
+                            ))
+                            .width(Length::Fill),
+                    ),
+            )
+            .push(Space::with_height(10))
+            .push(widget::text::text(fl!("first-payment")))
+            .push(date_picker(self.form_loan_first_payment, |date| {
+                AccountsMessage::NewLoanFirstPaymentChanged(date)
+            }))
+            .push_maybe(
+                self.new_loan_error
+                    .as_ref()
+                    .map(|e| widget::text::text(e.clone())),
+            )
+            .push(Space::with_height(10))
+            .into()
+    }
+
     fn add_account_view<'a>(&'a self) -> Element<'a, AccountsMessage> {
         let mut element = widget::column();
 
@@ -228,7 +1224,23 @@ impl Accounts {
                             .push(Space::with_width(10))
                             .push(
                                 widget::column()
-                                    .push(widget::text::text(fl!("initial-value")))
+                                    .push(widget::text::text(fl!("account-type")))
+                                    .push(widget::dropdown(
+                                        &self.account_type_options,
+                                        self.form_new_account_type,
+                                        AccountsMessage::NewBankAccountTypeChanged,
+                                    ))
+                                    .width(Length::Fill),
+                            ),
+                    )
+                    .push(Space::with_height(10))
+                    .push(
+                        widget::row()
+                            .push(
+                                widget::column()
+                                    .push(widget::text::text(
+                                        self.balance_label(self.form_new_account_type),
+                                    ))
                                     .push(
                                         cosmic::widget::text_input(
                                             "0",
@@ -239,9 +1251,24 @@ impl Accounts {
                                         ),
                                     )
                                     .width(Length::Fill),
+                            )
+                            .push(Space::with_width(10))
+                            .push(
+                                widget::column()
+                                    .push(widget::text::text(fl!("currency")))
+                                    .push(widget::dropdown(
+                                        &self.currencies,
+                                        self.form_new_account_currency,
+                                        AccountsMessage::NewBankAccountCurrencyChanged,
+                                    ))
+                                    .width(Length::Fill),
                             ),
                     )
                     .push(Space::with_height(10))
+                    .push_maybe(
+                        (selected_account_type(self.form_new_account_type) == AccountType::Loan)
+                            .then(|| self.new_loan_view()),
+                    )
                     .push(
                         widget::column()
                             .width(Length::Fill)
@@ -289,16 +1316,37 @@ impl Accounts {
         match message {
             AccountsMessage::Update => {
                 log::info!("updating accounts");
-                let mut store = STORE.lock().unwrap();
-                let config = Config::load();
-                let accounts = store.get_accounts();
-                let currency_symbol = store.get_currency_symbol_by_id(config.1.currency_id);
-                if let Ok(accounts) = accounts {
-                    self.accounts = accounts;
-                    self.currency_symbol = currency_symbol.unwrap_or_else(|_| "USD".to_string());
-                }
+                self.loading = true;
+                commands.push(data::request(&self.store, AccountsData::load, |data| {
+                    app::Message::Accounts(AccountsMessage::Loaded(data))
+                }));
+                commands.push(self.load_schedule());
+            }
+            AccountsMessage::Loaded(Err(e)) => {
+                self.loading = false;
+                commands.push(app::error_toast(e));
+            }
+            AccountsMessage::Loaded(Ok(data)) => {
+                self.loading = false;
+                self.reporting_currency_id = data.reporting_currency_id;
+                self.currencies = data.currencies;
+                self.accounts = data.accounts;
+                self.net_worth = data.net_worth;
+                self.balances = data.balances;
+                self.type_totals = data.type_totals;
+                self.loans = data.loans;
+                self.next_installments = data.next_installments;
+                self.investment_summaries = data.investment_summaries;
+                self.payment_accounts = payment_accounts(&self.accounts);
+                self.interest_categories = categories_of_kind(&data.categories, false);
+                self.interest_category_labels = category_labels(&self.interest_categories);
+                self.income_categories = categories_of_kind(&data.categories, true);
+                self.income_category_labels = category_labels(&self.income_categories);
+                self.securities = data.securities;
             }
             AccountsMessage::AddAccountView => {
+                self.form_new_account_currency = self.currency_index(None);
+                self.new_loan_error = None;
                 self.add_account_view_visible = true;
             }
             AccountsMessage::NewBankAccountNameChanged(value) => {
@@ -307,28 +1355,48 @@ impl Accounts {
             AccountsMessage::CancelNewBankAccount => self.add_account_view_visible = false,
             AccountsMessage::NewBankAccountInitialValueChanged(value) => {
                 log::info!("value: {:?}", value);
-                if value == "" {
-                    self.form_new_account_initial_value = "".to_string();
-                    self.new_account_initial_value = 0.;
-                }
-                match value.parse::<f32>() {
-                    Ok(float_value) => {
-                        self.form_new_account_initial_value = value;
-                        self.new_account_initial_value = float_value;
-                    }
-                    Err(_) => {
-                        log::error!("error parsing the initial value")
-                    }
+                let scale = self.selected_scale(self.form_new_account_currency);
+                if value == "" || Money::parse(&value, scale).is_some() {
+                    self.form_new_account_initial_value = value;
+                } else {
+                    log::error!("error parsing the initial value")
                 }
             }
+            AccountsMessage::NewBankAccountCurrencyChanged(index) => {
+                self.form_new_account_currency = Some(index);
+            }
+            AccountsMessage::NewBankAccountTypeChanged(index) => {
+                self.form_new_account_type = Some(index);
+            }
             AccountsMessage::SubmitNewBankAccount => {
+                let is_loan =
+                    selected_account_type(self.form_new_account_type) == AccountType::Loan;
+                let new_loan = self.new_loan();
+                if is_loan && new_loan.is_none() {
+                    self.new_loan_error = Some(fl!("loan-terms-missing"));
+                    return Task::none();
+                }
+                let scale = self.selected_scale(self.form_new_account_currency);
+                let Some(initial_balance) =
+                    Money::parse_or_zero(&self.form_new_account_initial_value, scale)
+                else {
+                    return app::error_toast(fl!("invalid-amount"));
+                };
                 let new_account = NewAccount {
                     name: self.form_new_account_name_value.clone(),
-                    initial_balance: self.new_account_initial_value,
+                    account_type: selected_account_type(self.form_new_account_type),
+                    initial_balance,
                     account_description: self.new_account_description.clone(),
+                    currency_id: self
+                        .form_new_account_currency
+                        .and_then(|index| self.currencies.get(index))
+                        .map(|c| c.id),
                 };
-                let mut store = STORE.lock().unwrap();
-                store.create_account(&new_account);
+                let new_loan = new_loan.filter(|_| is_loan);
+                commands.push(data::execute(&self.store, move |store| match new_loan {
+                    Some(new_loan) => store.create_loan(&new_account, &new_loan),
+                    None => store.create_account(&new_account),
+                }));
                 commands.push(Task::perform(async {}, |_| {
                     app::Message::Accounts(AccountsMessage::Update)
                 }));
@@ -338,8 +1406,13 @@ impl Accounts {
                 self.editing_account = Some(id);
                 let account = self.accounts.clone().into_iter().find(|a| a.id == id);
                 if let Some(account) = account {
+                    self.edit_account_currency = self.currency_index(account.currency_id);
+                    self.edit_account_type = AccountType::ALL
+                        .iter()
+                        .position(|t| *t == account.account_type);
+                    let scale = self.selected_scale(self.edit_account_currency);
                     self.edit_account_name = account.name;
-                    self.edit_account_balance = account.initial_balance.to_string();
+                    self.edit_account_balance = account.initial_balance(scale).to_string();
                     self.edit_account_description = account.account_description;
                 }
             }
@@ -351,32 +1424,321 @@ impl Accounts {
                 self.edit_account_name = new_name;
             }
             AccountsMessage::EditAccountBalance(new_balance) => {
-                if new_balance.parse::<f32>().is_ok() || new_balance == "" {
+                let scale = self.selected_scale(self.edit_account_currency);
+                if Money::parse(&new_balance, scale).is_some() || new_balance == "" {
                     self.edit_account_balance = new_balance;
                 }
             }
+            AccountsMessage::EditAccountCurrency(index) => {
+                self.edit_account_currency = Some(index);
+            }
+            AccountsMessage::EditAccountType(index) => {
+                self.edit_account_type = Some(index);
+            }
+            AccountsMessage::StartReconcile(account_id) => {
+                self.reconciling_account = Some(account_id);
+                self.statement_date = Utc::now().timestamp();
+                self.statement_balance = "".to_string();
+                self.reconcile_error = None;
+                self.reconcile_transactions = vec![];
+                self.last_reconciliation = None;
+                commands.push(self.load_reconciliation());
+            }
+            AccountsMessage::ReconciliationLoaded(Err(e)) => commands.push(app::error_toast(e)),
+            AccountsMessage::ReconciliationLoaded(Ok(data)) => {
+                // Ignore a reconciliation the user has moved away from.
+                if self.reconciling_account == Some(data.account_id)
+                    && self.statement_date == data.statement_date
+                {
+                    self.reconcile_transactions = data.transactions;
+                    self.last_reconciliation = data.last_reconciliation;
+                    self.cleared_balance = data.cleared_balance;
+                }
+            }
+            AccountsMessage::StatementDateChanged(date) => {
+                self.statement_date = date;
+                commands.push(self.load_reconciliation());
+            }
+            AccountsMessage::StatementBalanceChanged(value) => {
+                let scale = self.reconciling_scale();
+                if value.is_empty() || value == "-" || Money::parse(&value, scale).is_some() {
+                    self.statement_balance = value;
+                }
+            }
+            AccountsMessage::ToggleCleared(transaction_id, checked) => {
+                let status = if checked {
+                    ClearedStatus::Cleared
+                } else {
+                    ClearedStatus::Uncleared
+                };
+                commands.push(data::execute(&self.store, move |store| {
+                    store.set_cleared_status(transaction_id, status)
+                }));
+                commands.push(self.load_reconciliation());
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Transactions(TransactionMessage::UpdatePage)
+                }));
+            }
+            AccountsMessage::FinishReconcile => {
+                let account = self
+                    .reconciling_account
+                    .and_then(|id| self.accounts.iter().find(|a| a.id == id));
+                if let Some((account, balance)) =
+                    account.and_then(|a| Some((a, self.statement_balance_value(a)?)))
+                {
+                    let account_id = account.id;
+                    let date = self.statement_date();
+                    let reporting_currency_id = self.reporting_currency_id;
+                    commands.push(data::request(
+                        &self.store,
+                        move |store| {
+                            store.finish_reconciliation(
+                                account_id,
+                                &date,
+                                balance,
+                                reporting_currency_id,
+                            )
+                        },
+                        |result| {
+                            app::Message::Accounts(AccountsMessage::Reconciled(
+                                result.map_err(|e| e.to_string()),
+                            ))
+                        },
+                    ));
+                }
+            }
+            AccountsMessage::Reconciled(result) => {
+                match result {
+                    Ok(()) => {
+                        self.reconciling_account = None;
+                        self.reconcile_error = None;
+                    }
+                    Err(e) => self.reconcile_error = Some(e),
+                }
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Transactions(TransactionMessage::UpdatePage)
+                }));
+            }
+            AccountsMessage::CancelReconcile => {
+                self.reconciling_account = None;
+            }
+            AccountsMessage::NewLoanRateChanged(value) => {
+                if value.is_empty() || value.parse::<f64>().is_ok_and(|rate| rate >= 0.) {
+                    self.form_loan_rate = value;
+                }
+            }
+            AccountsMessage::NewLoanTermChanged(value) => {
+                if value.is_empty() || value.parse::<u16>().is_ok() {
+                    self.form_loan_term = value;
+                }
+            }
+            AccountsMessage::NewLoanFrequencyChanged(index) => {
+                self.form_loan_frequency = Some(index);
+            }
+            AccountsMessage::NewLoanFirstPaymentChanged(date) => {
+                self.form_loan_first_payment = date;
+            }
+            AccountsMessage::NewLoanPaymentAccountChanged(index) => {
+                self.form_loan_payment_account = Some(index);
+            }
+            AccountsMessage::NewLoanInterestCategoryChanged(index) => {
+                self.form_loan_interest_category = Some(index);
+            }
+            AccountsMessage::PostLoanPayment(loan_id) => {
+                if let Some(loan) = self.loans.iter().find(|l| l.id == loan_id).cloned() {
+                    let reporting_currency_id = self.reporting_currency_id;
+                    commands.push(data::request(
+                        &self.store,
+                        move |store| store.post_loan_payment(&loan, reporting_currency_id),
+                        move |result| {
+                            app::Message::Accounts(AccountsMessage::LoanPaymentPosted(
+                                loan_id,
+                                result.map_err(|e| e.to_string()),
+                            ))
+                        },
+                    ));
+                }
+            }
+            AccountsMessage::LoanPaymentPosted(loan_id, result) => {
+                self.loan_error = result.err().map(|e| (loan_id, e));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Accounts(AccountsMessage::Update)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Transactions(TransactionMessage::UpdatePage)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Categories(super::categories::CategoriesMessage::Update)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Goals(super::goals::GoalsMessage::UpdatePage)
+                }));
+            }
+            AccountsMessage::CostBasisMethodChanged(account_id, index) => {
+                if let Some(method) = CostBasisMethod::ALL.get(index).copied() {
+                    commands.push(data::execute(&self.store, move |store| {
+                        store.set_cost_basis_method(account_id, method)
+                    }));
+                }
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Accounts(AccountsMessage::Update)
+                }));
+            }
+            AccountsMessage::StartTrade(account_id) => {
+                self.trading_account = Some(account_id);
+                self.form_trade_kind = Some(0);
+                self.form_trade_date = Utc::now().timestamp();
+                self.form_trade_quantity = "".to_string();
+                self.form_trade_price = "".to_string();
+                self.form_trade_fees = "".to_string();
+                self.form_trade_dividend = "".to_string();
+                self.trade_error = None;
+            }
+            AccountsMessage::CancelTrade => {
+                self.trading_account = None;
+            }
+            AccountsMessage::TradeKindChanged(index) => {
+                self.form_trade_kind = Some(index);
+            }
+            AccountsMessage::TradeSecurityChanged(index) => {
+                self.form_trade_security = Some(index);
+            }
+            AccountsMessage::TradeDateChanged(date) => {
+                self.form_trade_date = date;
+            }
+            AccountsMessage::TradeQuantityChanged(value) => {
+                if value.is_empty() || value.parse::<f64>().is_ok_and(|q| q >= 0.) {
+                    self.form_trade_quantity = value;
+                }
+            }
+            AccountsMessage::TradePriceChanged(value) => {
+                if value.is_empty() || value.parse::<f64>().is_ok_and(|p| p >= 0.) {
+                    self.form_trade_price = value;
+                }
+            }
+            AccountsMessage::TradeFeesChanged(value) => {
+                if value.is_empty() || Money::parse(&value, self.trading_scale()).is_some() {
+                    self.form_trade_fees = value;
+                }
+            }
+            AccountsMessage::TradeDividendChanged(value) => {
+                if value.is_empty() || Money::parse(&value, self.trading_scale()).is_some() {
+                    self.form_trade_dividend = value;
+                }
+            }
+            AccountsMessage::TradeCategoryChanged(index) => {
+                self.form_trade_category = Some(index);
+            }
+            AccountsMessage::SubmitTrade => {
+                let new_trade = self
+                    .trading_account
+                    .and_then(|id| self.accounts.iter().find(|a| a.id == id))
+                    .and_then(|account| self.new_trade(account));
+                if let Some(new_trade) = new_trade {
+                    let reporting_currency_id = self.reporting_currency_id;
+                    commands.push(data::request(
+                        &self.store,
+                        move |store| store.create_trade(&new_trade, reporting_currency_id),
+                        |result| {
+                            app::Message::Accounts(AccountsMessage::TradeSubmitted(
+                                result.map_err(|e| e.to_string()),
+                            ))
+                        },
+                    ));
+                }
+            }
+            AccountsMessage::TradeSubmitted(result) => {
+                match result {
+                    Ok(()) => {
+                        self.trading_account = None;
+                        self.trade_error = None;
+                    }
+                    Err(e) => self.trade_error = Some(e),
+                }
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Accounts(AccountsMessage::Update)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Transactions(TransactionMessage::UpdatePage)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Categories(super::categories::CategoriesMessage::Update)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Goals(super::goals::GoalsMessage::UpdatePage)
+                }));
+            }
+            AccountsMessage::ToggleLoanSchedule(loan_id) => {
+                self.schedule_loan = if self.schedule_loan == Some(loan_id) {
+                    None
+                } else {
+                    Some(loan_id)
+                };
+                self.schedule_payments = vec![];
+                commands.push(self.load_schedule());
+            }
+            AccountsMessage::ScheduleLoaded(_, Err(e)) => commands.push(app::error_toast(e)),
+            AccountsMessage::ScheduleLoaded(loan_id, Ok(payments)) => {
+                if self.schedule_loan == Some(loan_id) {
+                    self.schedule_payments = payments;
+                }
+            }
+            AccountsMessage::ToggleArchiveAccount(account_id) => {
+                if let Some(account) = self.accounts.iter().find(|a| a.id == account_id) {
+                    let archived = !account.is_archived;
+                    commands.push(data::execute(&self.store, move |store| {
+                        store.set_account_archived(account_id, archived)
+                    }));
+                }
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Accounts(AccountsMessage::Update)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Transactions(TransactionMessage::UpdatePage)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Recurring(super::recurring::RecurringMessage::UpdatePage)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Goals(super::goals::GoalsMessage::UpdatePage)
+                }));
+            }
             AccountsMessage::EditAccountDescription(new_description) => {
                 self.edit_account_description = new_description;
             }
             AccountsMessage::EditAccountSubmit => {
                 let id = self.editing_account.unwrap();
-                let new_balance = self.edit_account_balance.parse::<f32>().unwrap();
-                let initial_balance = self.read_account_balance(id);
-                let difference: f32 = new_balance - initial_balance;
+                let scale = self.selected_scale(self.edit_account_currency);
+                let Some(new_balance) = Money::parse_or_zero(&self.edit_account_balance, scale)
+                else {
+                    return app::error_toast(fl!("invalid-amount"));
+                };
                 let update_account = UpdateAccount {
                     id,
                     name: self.edit_account_name.clone(),
-                    initial_balance: initial_balance + difference,
+                    account_type: selected_account_type(self.edit_account_type),
+                    initial_balance: new_balance,
                     account_description: self.edit_account_description.clone(),
+                    currency_id: self
+                        .edit_account_currency
+                        .and_then(|index| self.currencies.get(index))
+                        .map(|c| c.id),
                 };
-                let mut store = STORE.lock().unwrap();
-                let _ = store.update_account(&update_account);
+                commands.push(data::execute(&self.store, move |store| {
+                    store.update_account(&update_account)
+                }));
                 commands.push(Task::perform(async {}, |_| {
                     app::Message::Accounts(AccountsMessage::Update)
                 }));
                 commands.push(Task::perform(async {}, |_| {
                     app::Message::Transactions(TransactionMessage::UpdatePage)
                 }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Recurring(super::recurring::RecurringMessage::UpdatePage)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Goals(super::goals::GoalsMessage::UpdatePage)
+                }));
                 commands.push(Task::perform(async {}, |_| {
                     app::Message::Accounts(AccountsMessage::CloseEditAccount)
                 }));
@@ -385,12 +1747,278 @@ impl Accounts {
         Task::batch(commands)
     }
 
-    fn read_account_balance(&self, account_id: i32) -> f32 {
-        let mut store = STORE.lock().unwrap();
-        if let Ok(balance) = store.get_account_balance(account_id) {
+    /// Reloads the transactions still open for the account being reconciled.
+    fn load_reconciliation(&self) -> Task<app::Message> {
+        let Some(account_id) = self.reconciling_account else {
+            return Task::none();
+        };
+        let statement_date = self.statement_date;
+        let reporting_currency_id = self.reporting_currency_id;
+        data::request(
+            &self.store,
+            move |store| {
+                ReconciliationData::load(store, account_id, statement_date, reporting_currency_id)
+            },
+            |data| app::Message::Accounts(AccountsMessage::ReconciliationLoaded(data)),
+        )
+    }
+
+    /// Reloads the posted payments of the loan whose schedule is shown.
+    fn load_schedule(&self) -> Task<app::Message> {
+        let Some(loan_id) = self.schedule_loan else {
+            return Task::none();
+        };
+        data::request(
+            &self.store,
+            move |store| store.get_loan_payments(loan_id),
+            move |payments| {
+                app::Message::Accounts(AccountsMessage::ScheduleLoaded(loan_id, payments))
+            },
+        )
+    }
+
+    /// The loan terms entered in the new account form, if they are complete.
+    fn new_loan(&self) -> Option<NewLoan> {
+        let term = self
+            .form_loan_term
+            .parse::<i32>()
+            .ok()
+            .filter(|term| *term > 0)?;
+        Some(NewLoan {
+            annual_rate: self.form_loan_rate.parse().unwrap_or(0.),
+            term,
+            frequency: self
+                .form_loan_frequency
+                .and_then(|index| Frequency::ALL.get(index))
+                .unwrap_or(&Frequency::Monthly)
+                .as_str()
+                .to_string(),
+            first_payment: DateTime::from_timestamp(self.form_loan_first_payment, 0)
+                .unwrap_or_default()
+                .date_naive(),
+            payment_account: self
+                .form_loan_payment_account
+                .and_then(|index| self.payment_accounts.get(index))?
+                .id,
+            interest_category: self
+                .form_loan_interest_category
+                .and_then(|index| self.interest_categories.get(index))?
+                .id,
+        })
+    }
+
+    fn trade_kind(&self) -> TradeKind {
+        self.form_trade_kind
+            .and_then(|index| TradeKind::ALL.get(index).copied())
+            .unwrap_or_default()
+    }
+
+    fn trading_scale(&self) -> u32 {
+        self.trading_account
+            .and_then(|id| self.accounts.iter().find(|a| a.id == id))
+            .map(|a| self.account_scale(a))
+            .unwrap_or(Money::DEFAULT_SCALE)
+    }
+
+    /// The trade entered in the form, if it is complete.
+    fn new_trade(&self, account: &Account) -> Option<NewTrade> {
+        let scale = self.account_scale(account);
+        let trade_kind = self.trade_kind();
+        let is_dividend = trade_kind == TradeKind::Dividend;
+        let (quantity, price) = if is_dividend {
+            (0., 0.)
+        } else {
+            (
+                self.form_trade_quantity
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|q| *q > 0.)?,
+                self.form_trade_price.parse::<f64>().ok()?,
+            )
+        };
+        Some(NewTrade {
+            bank_account: account.id,
+            security_id: self
+                .form_trade_security
+                .and_then(|index| self.securities.get(index))?
+                .id,
+            trade_kind,
+            trade_date: DateTime::from_timestamp(self.form_trade_date, 0)
+                .unwrap_or_default()
+                .date_naive(),
+            quantity,
+            price,
+            fees: if is_dividend {
+                Money::zero(scale)
+            } else {
+                Money::parse_or_zero(&self.form_trade_fees, scale)?
+            },
+            dividend: if is_dividend {
+                Money::parse(&self.form_trade_dividend, scale).filter(|d| !d.is_zero())?
+            } else {
+                Money::zero(scale)
+            },
+            dividend_category: if is_dividend {
+                Some(
+                    self.form_trade_category
+                        .and_then(|index| self.income_categories.get(index))?
+                        .id,
+                )
+            } else {
+                None
+            },
+        })
+    }
+
+    fn statement_date(&self) -> chrono::NaiveDate {
+        date_of(self.statement_date)
+    }
+
+    /// The statement balance as an account balance: liabilities are entered
+    /// as the amount owed.
+    fn statement_balance_value(&self, account: &Account) -> Option<Money> {
+        let balance = Money::parse(&self.statement_balance, self.account_scale(account))?;
+        Some(if account.account_type.is_liability() {
+            -balance
+        } else {
             balance
+        })
+    }
+
+    /// Shows a balance the way it is entered: the amount owed for liabilities.
+    fn displayed_balance(&self, account: &Account, balance: Money) -> Money {
+        if account.account_type.is_liability() {
+            -balance
+        } else {
+            balance
+        }
+    }
+
+    fn account_scale(&self, account: &Account) -> u32 {
+        self.currency_of(account.currency_id)
+            .map(|c| c.scale as u32)
+            .unwrap_or(Money::DEFAULT_SCALE)
+    }
+
+    fn reconciling_scale(&self) -> u32 {
+        self.reconciling_account
+            .and_then(|id| self.accounts.iter().find(|a| a.id == id))
+            .map(|a| self.account_scale(a))
+            .unwrap_or(Money::DEFAULT_SCALE)
+    }
+
+    fn account_balance(&self, account_id: i32) -> Money {
+        self.balances
+            .get(&account_id)
+            .copied()
+            .unwrap_or(Money::zero(Money::DEFAULT_SCALE))
+    }
+
+    /// Liabilities are opened with the amount owed rather than a balance.
+    fn balance_label(&self, selected_type: Option<usize>) -> String {
+        if selected_account_type(selected_type).is_liability() {
+            fl!("amount-owed")
         } else {
-            0.
+            fl!("initial-value")
         }
     }
+
+    /// Resolves an account currency, `None` meaning the reporting currency.
+    fn currency_of(&self, currency_id: Option<i32>) -> Option<&Currency> {
+        let currency_id = currency_id.unwrap_or(self.reporting_currency_id);
+        self.currencies.iter().find(|c| c.id == currency_id)
+    }
+
+    fn currency_index(&self, currency_id: Option<i32>) -> Option<usize> {
+        let currency_id = currency_id.unwrap_or(self.reporting_currency_id);
+        self.currencies.iter().position(|c| c.id == currency_id)
+    }
+
+    fn selected_scale(&self, selected: Option<usize>) -> u32 {
+        selected
+            .and_then(|index| self.currencies.get(index))
+            .map(|c| c.scale as u32)
+            .unwrap_or(Money::DEFAULT_SCALE)
+    }
+}
+
+fn selected_account_type(selected: Option<usize>) -> AccountType {
+    selected
+        .and_then(|index| AccountType::ALL.get(index).copied())
+        .unwrap_or_default()
+}
+
+fn holdings_row<'a>(cells: [String; 5]) -> Element<'a, AccountsMessage> {
+    cells
+        .into_iter()
+        .fold(widget::row(), |row, cell| {
+            row.push(widget::text::text(cell).width(Length::Fill))
+        })
+        .into()
+}
+
+fn schedule_row<'a>(cells: [String; 7]) -> Element<'a, AccountsMessage> {
+    cells
+        .into_iter()
+        .fold(widget::row(), |row, cell| {
+            row.push(widget::text::text(cell).width(Length::Fill))
+        })
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::store::Store;
+
+    fn accounts_page() -> (Accounts, SharedStore) {
+        let store = Arc::new(Mutex::new(Store::in_memory().unwrap()));
+        (Accounts::new(store.clone()), store)
+    }
+
+    #[test]
+    fn submitted_accounts_are_stored() {
+        let (mut accounts, store) = accounts_page();
+        let _ = accounts.update(AccountsMessage::AddAccountView);
+        let _ = accounts.update(AccountsMessage::NewBankAccountNameChanged(
+            "Bank".to_string(),
+        ));
+        let _ = accounts.update(AccountsMessage::NewBankAccountInitialValueChanged(
+            "100".to_string(),
+        ));
+        let _ = accounts.update(AccountsMessage::SubmitNewBankAccount);
+        assert!(!accounts.add_account_view_visible);
+        data::wait_idle();
+
+        let stored = store.lock().unwrap().get_accounts().unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].name, "Bank");
+
+        let loaded = AccountsData::load(&mut *store.lock().unwrap());
+        let _ = accounts.update(AccountsMessage::Loaded(loaded));
+        assert_eq!(accounts.accounts.len(), 1);
+        assert!(!accounts.loading);
+    }
+
+    #[test]
+    fn loans_are_not_created_without_their_terms() {
+        let (mut accounts, store) = accounts_page();
+        let loan = AccountType::ALL
+            .iter()
+            .position(|t| *t == AccountType::Loan)
+            .unwrap();
+        let _ = accounts.update(AccountsMessage::AddAccountView);
+        let _ = accounts.update(AccountsMessage::NewBankAccountNameChanged(
+            "Mortgage".to_string(),
+        ));
+        let _ = accounts.update(AccountsMessage::NewBankAccountTypeChanged(loan));
+        let _ = accounts.update(AccountsMessage::SubmitNewBankAccount);
+
+        assert!(accounts.new_loan_error.is_some());
+        assert!(accounts.add_account_view_visible);
+        data::wait_idle();
+        assert!(store.lock().unwrap().get_accounts().unwrap().is_empty());
+    }
 }
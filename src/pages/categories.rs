@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use chrono::{Datelike, Duration, Local, NaiveDate};
 use cosmic::{
     iced::{Alignment, Length, Padding},
@@ -8,16 +10,26 @@ use cosmic::{
 use crate::{
     app::{self, Message},
     config::Config,
+    data,
+    errors::DataStoreError,
     fl,
-    models::{Category, NewCategory, UpdateCategory},
+    models::{
+        categories_by_parent, category_path, category_tree, category_with_descendants, Budget,
+        BudgetSummary, Category, Money, NewBudget, NewCategory, Payee, UpdateCategory,
+    },
+    repository::Repository,
+    store::{SharedStore, Store},
     STORE,
 };
 
 use super::transactions::TransactionMessage;
 
+const OVERSPENT_COLOR: cosmic::iced::Color = cosmic::iced::Color::from_rgb(0.85, 0.2, 0.2);
+
 #[derive(Debug, Clone)]
 pub enum CategoriesMessage {
     Update,
+    Loaded(Result<CategoriesData, DataStoreError>),
     AddCategory,
     NewCategoryNameChanged(String),
     NewCategoryDescriptionChanged(String),
@@ -29,14 +41,33 @@ pub enum CategoriesMessage {
     EditCategorySubmitted,
     EditCategoryTypeChanged(usize),
     NewCategoryTypeChanged(usize),
+    NewCategoryParentChanged(usize),
+    EditCategoryParentChanged(usize),
     PreviousMonth,
     NextMonth,
     EditCategory(i32),
+    EditBudget(i32),
+    BudgetLoaded(i32, Result<Option<Budget>, DataStoreError>),
+    BudgetAmountChanged(String),
+    BudgetRolloverChanged(usize),
+    BudgetSubmitted,
+    BudgetCancel,
+    DeleteCategory(i32),
+    MergeIntoChanged(usize),
+    ConfirmDeleteCategory,
+    CancelDeleteCategory,
 }
 
 pub struct Categories {
+    store: SharedStore,
+    loading: bool,
     currency_symbol: String,
+    currency_scale: u32,
     categories: Vec<Category>,
+    spent: HashMap<i32, Money>,
+    rolled_up_spent: HashMap<i32, Money>,
+    budget_summaries: HashMap<i32, BudgetSummary>,
+    payee_spent: HashMap<i32, Money>,
     add_category_view_active: bool,
     form_new_category_name: String,
     form_new_category_description: String,
@@ -48,22 +79,138 @@ pub struct Categories {
     selected_category_type: Option<usize>,
     edit_category_type: Option<usize>,
     edit_category_id: Option<i32>,
+    rollover_options: Vec<String>,
+    edit_budget_category_id: Option<i32>,
+    form_budget_amount: String,
+    form_budget_rollover: Option<usize>,
+    payees: Vec<Payee>,
+    new_category_parent_options: Vec<CategoryOption>,
+    new_category_parent: Option<usize>,
+    edit_category_parent_options: Vec<CategoryOption>,
+    edit_category_parent: Option<usize>,
+    delete_category_id: Option<i32>,
+    merge_options: Vec<CategoryOption>,
+    merge_into: Option<usize>,
+}
+
+/// An entry of a category dropdown, `None` meaning no category (a top level one
+/// when picking a parent).
+struct CategoryOption {
+    id: Option<i32>,
+    label: String,
+}
+
+impl AsRef<str> for CategoryOption {
+    fn as_ref(&self) -> &str {
+        &self.label
+    }
+}
+
+/// What the categories page shows for a month, read by the data service.
+#[derive(Debug, Clone)]
+pub struct CategoriesData {
+    month_start: NaiveDate,
+    currency_symbol: String,
+    currency_scale: u32,
+    categories: Vec<Category>,
+    payees: Vec<Payee>,
+    spent: HashMap<i32, Money>,
+    rolled_up_spent: HashMap<i32, Money>,
+    budget_summaries: HashMap<i32, BudgetSummary>,
+    payee_spent: HashMap<i32, Money>,
+}
+
+impl CategoriesData {
+    fn load(
+        store: &mut Store,
+        month_start: NaiveDate,
+        month_end: NaiveDate,
+    ) -> Result<Self, DataStoreError> {
+        let reporting_currency_id = Config::load().1.currency_id;
+        let (currency_symbol, currency_scale) =
+            match store.get_currency_by_id(reporting_currency_id) {
+                Ok(currency) => (currency.symbol, currency.scale as u32),
+                Err(_) => ("USD".to_string(), Money::DEFAULT_SCALE),
+            };
+        let categories = categories_by_parent(store.get_categories()?);
+        let payees = store.get_payees()?;
+
+        let mut spent = HashMap::new();
+        let mut rolled_up_spent = HashMap::new();
+        let mut budget_summaries = HashMap::new();
+        for c in &categories {
+            spent.insert(
+                c.id,
+                store.calculate_expense_by_category(
+                    c.id,
+                    &month_start,
+                    &month_end,
+                    reporting_currency_id,
+                )?,
+            );
+            rolled_up_spent.insert(
+                c.id,
+                store.calculate_rolled_up_expense_by_category(
+                    c.id,
+                    &month_start,
+                    &month_end,
+                    reporting_currency_id,
+                )?,
+            );
+            if let Some(summary) =
+                store.get_budget_summary(c.id, &month_start, reporting_currency_id)?
+            {
+                budget_summaries.insert(c.id, summary);
+            }
+        }
+        let payee_spent = payees
+            .iter()
+            .map(|p| {
+                store
+                    .calculate_expense_by_payee(
+                        p.id,
+                        &month_start,
+                        &month_end,
+                        reporting_currency_id,
+                    )
+                    .map(|value| (p.id, value))
+            })
+            .collect::<Result<HashMap<i32, Money>, DataStoreError>>()?;
+
+        Ok(Self {
+            month_start,
+            currency_symbol,
+            currency_scale,
+            categories,
+            payees,
+            spent,
+            rolled_up_spent,
+            budget_summaries,
+            payee_spent,
+        })
+    }
 }
 
 impl Default for Categories {
     fn default() -> Self {
-        let mut store = STORE.lock().unwrap();
-        let config = Config::load();
-        let categories = store.get_categories();
+        Self::new(STORE.clone())
+    }
+}
+
+impl Categories {
+    /// The page starts out empty until `CategoriesMessage::Update` loads it.
+    pub fn new(store: SharedStore) -> Self {
         let now = Local::now();
-        let currency_symbol = store.get_currency_symbol_by_id(config.1.currency_id);
         Self {
-            currency_symbol: currency_symbol.unwrap_or_else(|_| "USD".to_string()),
-            categories: if let Ok(cat) = categories {
-                cat
-            } else {
-                vec![]
-            },
+            store,
+            loading: true,
+            currency_symbol: "USD".to_string(),
+            currency_scale: Money::DEFAULT_SCALE,
+            categories: vec![],
+            spent: HashMap::new(),
+            rolled_up_spent: HashMap::new(),
+            budget_summaries: HashMap::new(),
+            payee_spent: HashMap::new(),
             add_category_view_active: false,
             form_new_category_name: "".to_string(),
             form_new_category_description: "".to_string(),
@@ -75,11 +222,21 @@ impl Default for Categories {
             edit_category_form_name: "".to_string(),
             edit_category_form_description: "".to_string(),
             edit_category_type: Some(0),
+            rollover_options: vec![fl!("no-rollover"), fl!("rollover")],
+            edit_budget_category_id: None,
+            form_budget_amount: "".to_string(),
+            form_budget_rollover: Some(0),
+            payees: vec![],
+            new_category_parent_options: vec![],
+            new_category_parent: Some(0),
+            edit_category_parent_options: vec![],
+            edit_category_parent: Some(0),
+            delete_category_id: None,
+            merge_options: vec![],
+            merge_into: Some(0),
         }
     }
-}
 
-impl Categories {
     pub fn add_category_view<'a>(&'a self) -> Element<'a, CategoriesMessage> {
         let mut element = widget::column();
         element = element.push(Space::with_height(10));
@@ -125,6 +282,13 @@ impl Categories {
                         CategoriesMessage::NewCategoryTypeChanged,
                     ))
                     .push(Space::with_height(10))
+                    .push(widget::text::text(fl!("parent-category")))
+                    .push(widget::dropdown(
+                        &self.new_category_parent_options,
+                        self.new_category_parent,
+                        CategoriesMessage::NewCategoryParentChanged,
+                    ))
+                    .push(Space::with_height(10))
                     .push(
                         widget::row()
                             .push(
@@ -155,7 +319,7 @@ impl Categories {
 
     pub fn category_card<'a>(&'a self, c: &Category) -> Element<'a, CategoriesMessage> {
         let mut main_col = widget::column();
-        let info_col = widget::column()
+        let mut info_col = widget::column()
             .push(widget::text::title4(c.name.clone()))
             .push(widget::text::text(c.category_description.clone()))
             .push(widget::text::text(format!(
@@ -165,19 +329,71 @@ impl Categories {
                 self.currency_symbol
             )))
             .width(Length::Fill);
+        if self
+            .categories
+            .iter()
+            .any(|child| child.parent_category == Some(c.id))
+        {
+            info_col = info_col.push(widget::text::text(format!(
+                "{}: {} {}",
+                fl!("total-with-subcategories"),
+                self.calculate_rolled_up_by_category_id(c.id),
+                self.currency_symbol
+            )));
+        }
 
-        let row = widget::row().push(info_col).push(
-            widget::column()
+        let mut actions = widget::row();
+        if !c.is_income {
+            actions = actions
                 .push(
-                    widget::button::icon(widget::icon::from_name("edit-symbolic"))
-                        .on_press(CategoriesMessage::EditCategory(c.id)),
+                    widget::button::text(fl!("set-budget"))
+                        .on_press(CategoriesMessage::EditBudget(c.id)),
                 )
+                .push(Space::with_width(10));
+        }
+        actions = actions
+            .push(
+                widget::button::icon(widget::icon::from_name("edit-symbolic"))
+                    .on_press(CategoriesMessage::EditCategory(c.id)),
+            )
+            .push(
+                widget::button::icon(widget::icon::from_name("edit-delete-symbolic"))
+                    .on_press(CategoriesMessage::DeleteCategory(c.id)),
+            );
+
+        let row = widget::row().push(info_col).push(
+            widget::column()
+                .push(actions)
                 .align_x(Alignment::End)
                 .width(Length::Fill),
         );
 
         main_col = main_col.push(row);
 
+        if !c.is_income {
+            if let Some(summary) = self.budget_summary(c.id) {
+                main_col = main_col
+                    .push(Space::with_height(10))
+                    .push(self.budget_view(&summary));
+            }
+        }
+
+        if self.delete_category_id == Some(c.id) {
+            main_col = main_col
+                .push(Space::with_height(10))
+                .push(widget::divider::horizontal::default())
+                .push(Space::with_height(10))
+                .push(self.delete_category_view());
+        }
+
+        if self.edit_budget_category_id == Some(c.id) {
+            main_col = main_col
+                .push(Space::with_height(10))
+                .push(widget::divider::horizontal::default())
+                .push(Space::with_height(10))
+                .push(self.edit_budget_view());
+        }
+
         if self.edit_category_id == Some(c.id) {
             main_col = main_col.push(Space::with_height(10));
             main_col = main_col.push(widget::divider::horizontal::default());
@@ -206,6 +422,13 @@ impl Categories {
                             CategoriesMessage::EditCategoryTypeChanged,
                         ))
                         .push(Space::with_height(10))
+                        .push(widget::text::text(fl!("parent-category")))
+                        .push(widget::dropdown(
+                            &self.edit_category_parent_options,
+                            self.edit_category_parent,
+                            CategoriesMessage::EditCategoryParentChanged,
+                        ))
+                        .push(Space::with_height(10))
                         .push(
                             widget::row()
                                 .push(
@@ -231,6 +454,122 @@ impl Categories {
         element.into()
     }
 
+    fn budget_view<'a>(&self, summary: &BudgetSummary) -> Element<'a, CategoriesMessage> {
+        let mut budgeted = format!(
+            "{}: {} {}",
+            fl!("budgeted"),
+            summary.budgeted,
+            self.currency_symbol
+        );
+        if !summary.rolled_over.is_zero() {
+            budgeted = format!(
+                "{} (+{} {})",
+                budgeted,
+                summary.rolled_over,
+                fl!("rolled-over")
+            );
+        }
+
+        let remaining = if summary.is_overspent() {
+            widget::text::text(format!(
+                "{}: {} {}",
+                fl!("overspent"),
+                summary.remaining().abs(),
+                self.currency_symbol
+            ))
+            .class(cosmic::theme::Text::Color(OVERSPENT_COLOR))
+        } else {
+            widget::text::text(format!(
+                "{}: {} {}",
+                fl!("remaining"),
+                summary.remaining(),
+                self.currency_symbol
+            ))
+        };
+
+        widget::column()
+            .push(
+                widget::row()
+                    .push(widget::text::text(budgeted).width(Length::Fill))
+                    .push(
+                        widget::text::text(format!(
+                            "{}: {} {}",
+                            fl!("spent"),
+                            summary.spent,
+                            self.currency_symbol
+                        ))
+                        .width(Length::Fill),
+                    )
+                    .push(remaining.width(Length::Fill)),
+            )
+            .push(Space::with_height(5))
+            .push(widget::progress_bar(0.0..=1.0, summary.progress()).height(6))
+            .into()
+    }
+
+    fn delete_category_view<'a>(&'a self) -> Element<'a, CategoriesMessage> {
+        let mut element = widget::column();
+        let mut buttons = widget::row();
+        if self.merge_options.is_empty() {
+            element = element.push(widget::text::text(fl!("no-merge-category")));
+        } else {
+            element = element
+                .push(widget::text::text(fl!("merge-into")))
+                .push(widget::dropdown(
+                    &self.merge_options,
+                    self.merge_into,
+                    CategoriesMessage::MergeIntoChanged,
+                ));
+            buttons = buttons
+                .push(
+                    widget::button::text(fl!("delete"))
+                        .on_press(CategoriesMessage::ConfirmDeleteCategory)
+                        .class(widget::button::ButtonClass::Destructive),
+                )
+                .push(Space::with_width(10));
+        }
+        element
+            .push(Space::with_height(10))
+            .push(
+                buttons.push(
+                    widget::button::text(fl!("cancel"))
+                        .on_press(CategoriesMessage::CancelDeleteCategory),
+                ),
+            )
+            .into()
+    }
+
+    fn edit_budget_view<'a>(&'a self) -> Element<'a, CategoriesMessage> {
+        widget::column()
+            .push(widget::text::text(fl!("monthly-budget")))
+            .push(
+                widget::text_input(fl!("amount"), &self.form_budget_amount)
+                    .on_input(CategoriesMessage::BudgetAmountChanged),
+            )
+            .push(Space::with_height(10))
+            .push(widget::dropdown(
+                &self.rollover_options,
+                self.form_budget_rollover,
+                CategoriesMessage::BudgetRolloverChanged,
+            ))
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::button::text(fl!("save"))
+                            .on_press(CategoriesMessage::BudgetSubmitted)
+                            .class(widget::button::ButtonClass::Suggested),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::button::text(fl!("cancel"))
+                            .on_press(CategoriesMessage::BudgetCancel)
+                            .class(widget::button::ButtonClass::Destructive),
+                    ),
+            )
+            .into()
+    }
+
     pub fn view<'a>(&'a self) -> Element<'a, CategoriesMessage> {
         let mut element = widget::column()
             .padding(Padding::new(10.))
@@ -309,17 +648,20 @@ impl Categories {
 
         element = element.push(Space::with_height(10));
 
+        element = element.push_maybe(self.loading.then(|| widget::text::text(fl!("loading"))));
+
         element = element.push(widget::text::title4(fl!("income-categories")));
 
-        for c in &self
-            .categories
-            .clone()
+        for (c, depth) in category_tree(&self.categories)
             .into_iter()
-            .filter(|c| c.is_income)
-            .collect::<Vec<Category>>()
+            .filter(|(c, _)| c.is_income)
         {
             element = element
-                .push(self.category_card(c))
+                .push(
+                    widget::row()
+                        .push(Space::with_width(Length::Fixed(depth as f32 * 20.)))
+                        .push(self.category_card(c)),
+                )
                 .push(Space::with_height(10));
         }
 
@@ -327,18 +669,49 @@ impl Categories {
 
         element = element.push(widget::text::title4(fl!("expense-categories")));
 
-        for c in &self
-            .categories
-            .clone()
+        for (c, depth) in category_tree(&self.categories)
             .into_iter()
-            .filter(|c| !c.is_income)
-            .collect::<Vec<Category>>()
+            .filter(|(c, _)| !c.is_income)
         {
             element = element
-                .push(self.category_card(c))
+                .push(
+                    widget::row()
+                        .push(Space::with_width(Length::Fixed(depth as f32 * 20.)))
+                        .push(self.category_card(c)),
+                )
                 .push(Space::with_height(10));
         }
 
+        let payee_totals: Vec<(&Payee, Money)> = self
+            .payees
+            .iter()
+            .map(|p| (p, self.calculate_by_payee_id(p.id)))
+            .filter(|(_, spent)| !spent.is_zero())
+            .collect();
+        if !payee_totals.is_empty() {
+            element = element
+                .push(Space::with_height(10))
+                .push(widget::text::title4(fl!("payees")));
+            for (p, spent) in payee_totals {
+                element = element
+                    .push(
+                        widget::container(
+                            widget::row()
+                                .push(widget::text::text(p.name.clone()).width(Length::Fill))
+                                .push(widget::text::text(format!(
+                                    "{}: {} {}",
+                                    fl!("spent"),
+                                    spent,
+                                    self.currency_symbol
+                                ))),
+                        )
+                        .padding(10)
+                        .class(cosmic::theme::Container::Card),
+                    )
+                    .push(Space::with_height(10));
+            }
+        }
+
         widget::scrollable(element).into()
     }
 
@@ -347,17 +720,32 @@ impl Categories {
         match message {
             CategoriesMessage::Update => {
                 log::info!("updating category page");
-                let mut store = STORE.lock().unwrap();
-                let config = Config::load();
-                let currency_symbol = store.get_currency_symbol_by_id(config.1.currency_id);
-                let categories = store.get_categories();
-                if let Ok(categories) = categories {
-                    self.categories = categories;
-                    self.currency_symbol = currency_symbol.unwrap_or_else(|_| "USD".to_string());
+                commands.push(self.load());
+            }
+            CategoriesMessage::Loaded(Err(e)) => {
+                self.loading = false;
+                commands.push(app::error_toast(e));
+            }
+            CategoriesMessage::Loaded(Ok(data)) => {
+                // a month the user already moved away from
+                if data.month_start != self.get_month_start_and_end().0 {
+                    return Task::none();
                 }
+                self.loading = false;
+                self.currency_symbol = data.currency_symbol;
+                self.currency_scale = data.currency_scale;
+                self.categories = data.categories;
+                self.payees = data.payees;
+                self.spent = data.spent;
+                self.rolled_up_spent = data.rolled_up_spent;
+                self.budget_summaries = data.budget_summaries;
+                self.payee_spent = data.payee_spent;
             }
             CategoriesMessage::AddCategory => {
                 self.add_category_view_active = true;
+                self.new_category_parent_options =
+                    self.parent_options(self.selected_category_type == Some(0), None);
+                self.new_category_parent = Some(0);
             }
             CategoriesMessage::NewCategoryNameChanged(value) => {
                 self.form_new_category_name = value;
@@ -370,9 +758,14 @@ impl Categories {
                     name: self.form_new_category_name.clone(),
                     is_income: self.selected_category_type == Some(0),
                     category_description: self.form_new_category_description.clone(),
+                    parent_category: self
+                        .new_category_parent
+                        .and_then(|index| self.new_category_parent_options.get(index))
+                        .and_then(|option| option.id),
                 };
-                let mut store = STORE.lock().unwrap();
-                let _ = store.create_category(&new_category);
+                commands.push(data::execute(&self.store, move |store| {
+                    store.create_category(&new_category)
+                }));
                 self.add_category_view_active = false;
                 self.form_new_category_name = "".to_string();
                 commands.push(Task::perform(async {}, |_| {
@@ -381,6 +774,12 @@ impl Categories {
                 commands.push(Task::perform(async {}, |_| {
                     app::Message::Transactions(TransactionMessage::UpdatePage)
                 }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Recurring(super::recurring::RecurringMessage::UpdatePage)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Accounts(super::accounts::AccountsMessage::Update)
+                }));
             }
             CategoriesMessage::EditCategoryName(value) => {
                 self.edit_category_form_name = value;
@@ -389,26 +788,41 @@ impl Categories {
                 self.edit_category_form_description = value;
             }
             CategoriesMessage::EditCategoryTypeChanged(value) => {
-                self.edit_category_type = Some(value)
+                self.edit_category_type = Some(value);
+                self.edit_category_parent_options =
+                    self.parent_options(value == 0, self.edit_category_id);
+                self.edit_category_parent = Some(0);
+            }
+            CategoriesMessage::EditCategoryParentChanged(value) => {
+                self.edit_category_parent = Some(value);
             }
             CategoriesMessage::PreviousMonth => {
+                self.edit_budget_category_id = None;
                 if self.view_month == 1 {
                     self.view_month = 12;
                     self.view_year -= 1;
                 } else {
                     self.view_month -= 1;
                 }
+                commands.push(self.load());
             }
             CategoriesMessage::NextMonth => {
+                self.edit_budget_category_id = None;
                 if self.view_month == 12 {
                     self.view_month = 1;
                     self.view_year += 1;
                 } else {
                     self.view_month += 1;
                 }
+                commands.push(self.load());
             }
             CategoriesMessage::NewCategoryTypeChanged(value) => {
                 self.selected_category_type = Some(value);
+                self.new_category_parent_options = self.parent_options(value == 0, None);
+                self.new_category_parent = Some(0);
+            }
+            CategoriesMessage::NewCategoryParentChanged(value) => {
+                self.new_category_parent = Some(value);
             }
             CategoriesMessage::NewCategoryCancel => {
                 self.add_category_view_active = false;
@@ -422,24 +836,134 @@ impl Categories {
                     .find(|c| c.id == category_id);
                 self.edit_category_id = Some(category_id);
                 if let Some(category) = category {
+                    self.edit_category_type = Some(if category.is_income { 0 } else { 1 });
+                    self.edit_category_parent_options =
+                        self.parent_options(category.is_income, Some(category_id));
+                    self.edit_category_parent = self
+                        .edit_category_parent_options
+                        .iter()
+                        .position(|option| option.id == category.parent_category);
                     self.edit_category_form_name = category.name;
                     self.edit_category_form_description = category.category_description;
                 }
             }
+            CategoriesMessage::EditBudget(category_id) => {
+                let (month_start, _) = self.get_month_start_and_end();
+                commands.push(data::request(
+                    &self.store,
+                    move |store| store.get_budget(category_id, &month_start),
+                    move |budget| {
+                        Message::Categories(CategoriesMessage::BudgetLoaded(category_id, budget))
+                    },
+                ));
+            }
+            CategoriesMessage::BudgetLoaded(_, Err(e)) => commands.push(app::error_toast(e)),
+            CategoriesMessage::BudgetLoaded(category_id, Ok(budget)) => {
+                self.form_budget_amount = budget
+                    .as_ref()
+                    .map(|b| b.amount(self.currency_scale).to_string())
+                    .unwrap_or_default();
+                self.form_budget_rollover = Some(match budget {
+                    Some(b) if b.rollover => 1,
+                    _ => 0,
+                });
+                self.edit_budget_category_id = Some(category_id);
+            }
+            CategoriesMessage::BudgetAmountChanged(value) => {
+                if value.is_empty() || Money::parse(&value, self.currency_scale).is_some() {
+                    self.form_budget_amount = value;
+                }
+            }
+            CategoriesMessage::BudgetRolloverChanged(value) => {
+                self.form_budget_rollover = Some(value);
+            }
+            CategoriesMessage::BudgetCancel => {
+                self.edit_budget_category_id = None;
+            }
+            CategoriesMessage::BudgetSubmitted => {
+                if let Some(category_id) = self.edit_budget_category_id {
+                    let (month_start, _) = self.get_month_start_and_end();
+                    // clearing the amount removes the budget for the month
+                    let amount = if self.form_budget_amount.trim().is_empty() {
+                        None
+                    } else {
+                        match Money::parse(&self.form_budget_amount, self.currency_scale) {
+                            Some(amount) => Some(amount),
+                            None => return app::error_toast(fl!("invalid-amount")),
+                        }
+                    };
+                    self.edit_budget_category_id = None;
+                    let rollover = self.form_budget_rollover == Some(1);
+                    commands.push(data::execute(&self.store, move |store| match amount {
+                        Some(amount) => store.set_budget(&NewBudget {
+                            budget_category: category_id,
+                            budget_month: month_start,
+                            amount,
+                            rollover,
+                        }),
+                        None => store.delete_budget(category_id, &month_start),
+                    }));
+                    commands.push(self.load());
+                }
+            }
+            CategoriesMessage::DeleteCategory(category_id) => {
+                self.merge_options = self.merge_options(category_id);
+                self.merge_into = Some(0);
+                self.delete_category_id = Some(category_id);
+            }
+            CategoriesMessage::MergeIntoChanged(value) => {
+                self.merge_into = Some(value);
+            }
+            CategoriesMessage::CancelDeleteCategory => {
+                self.delete_category_id = None;
+            }
+            CategoriesMessage::ConfirmDeleteCategory => {
+                let merge_into = self
+                    .merge_into
+                    .and_then(|index| self.merge_options.get(index))
+                    .and_then(|option| option.id);
+                if let (Some(category_id), Some(merge_into)) =
+                    (self.delete_category_id.take(), merge_into)
+                {
+                    commands.push(data::execute(&self.store, move |store| {
+                        store.delete_category(category_id, merge_into)
+                    }));
+                    commands.push(Task::perform(async {}, |_| {
+                        Message::Categories(super::categories::CategoriesMessage::Update)
+                    }));
+                    commands.push(Task::perform(async {}, |_| {
+                        app::Message::Transactions(TransactionMessage::UpdatePage)
+                    }));
+                    commands.push(Task::perform(async {}, |_| {
+                        app::Message::Recurring(super::recurring::RecurringMessage::UpdatePage)
+                    }));
+                    commands.push(Task::perform(async {}, |_| {
+                        app::Message::Accounts(super::accounts::AccountsMessage::Update)
+                    }));
+                }
+            }
             CategoriesMessage::EditCategoryCancel => {
                 self.edit_category_id = None;
             }
             CategoriesMessage::EditCategorySubmitted => {
                 log::info!("update category submitted");
                 if let Some(id) = self.edit_category_id {
-                    let update_category = UpdateCategory {
-                        id,
-                        name: &self.edit_category_form_name,
-                        is_income: self.edit_category_type == Some(0),
-                        category_description: self.edit_category_form_description.clone(),
-                    };
-                    let mut store = STORE.lock().unwrap();
-                    let _ = store.update_category(&update_category);
+                    let category_name = self.edit_category_form_name.clone();
+                    let is_income = self.edit_category_type == Some(0);
+                    let category_description = self.edit_category_form_description.clone();
+                    let parent_category = self
+                        .edit_category_parent
+                        .and_then(|index| self.edit_category_parent_options.get(index))
+                        .and_then(|option| option.id);
+                    commands.push(data::execute(&self.store, move |store| {
+                        store.update_category(&UpdateCategory {
+                            id,
+                            name: &category_name,
+                            is_income,
+                            category_description,
+                            parent_category,
+                        })
+                    }));
                     self.edit_category_id = None;
                     commands.push(Task::perform(async {}, |_| {
                         Message::Categories(super::categories::CategoriesMessage::Update)
@@ -450,13 +974,78 @@ impl Categories {
         Task::batch(commands)
     }
 
-    fn calculate_by_category_id(&self, category_id: i32) -> f32 {
-        let mut store = STORE.lock().unwrap();
-        let (start_date, end_date) = self.get_month_start_and_end();
-        match store.calculate_expense_by_category(category_id, &start_date, &end_date) {
-            Ok(val) => val,
-            Err(_) => 0.,
-        }
+    fn calculate_by_category_id(&self, category_id: i32) -> Money {
+        self.spent
+            .get(&category_id)
+            .copied()
+            .unwrap_or(Money::zero(self.currency_scale))
+    }
+
+    fn calculate_rolled_up_by_category_id(&self, category_id: i32) -> Money {
+        self.rolled_up_spent
+            .get(&category_id)
+            .copied()
+            .unwrap_or(Money::zero(self.currency_scale))
+    }
+
+    /// Categories of the same type that can hold the edited category, leaving out
+    /// the category itself and its subcategories so that no cycle can be formed.
+    fn parent_options(&self, is_income: bool, editing: Option<i32>) -> Vec<CategoryOption> {
+        let excluded = editing
+            .map(|category_id| category_with_descendants(&self.categories, category_id))
+            .unwrap_or_default();
+        let mut options = vec![CategoryOption {
+            id: None,
+            label: fl!("no-parent"),
+        }];
+        options.extend(
+            self.categories
+                .iter()
+                .filter(|c| c.is_income == is_income && !excluded.contains(&c.id))
+                .map(|c| CategoryOption {
+                    id: Some(c.id),
+                    label: category_path(&self.categories, c),
+                }),
+        );
+        options
+    }
+
+    /// Categories of the same type that can take over the transactions of a
+    /// deleted one.
+    fn merge_options(&self, category_id: i32) -> Vec<CategoryOption> {
+        let Some(deleted) = self.categories.iter().find(|c| c.id == category_id) else {
+            return vec![];
+        };
+        self.categories
+            .iter()
+            .filter(|c| c.is_income == deleted.is_income && c.id != category_id)
+            .map(|c| CategoryOption {
+                id: Some(c.id),
+                label: category_path(&self.categories, c),
+            })
+            .collect()
+    }
+
+    fn calculate_by_payee_id(&self, payee_id: i32) -> Money {
+        self.payee_spent
+            .get(&payee_id)
+            .copied()
+            .unwrap_or(Money::zero(self.currency_scale))
+    }
+
+    fn budget_summary(&self, category_id: i32) -> Option<BudgetSummary> {
+        self.budget_summaries.get(&category_id).copied()
+    }
+
+    /// Loads the categories with their totals for the month on view.
+    fn load(&mut self) -> Task<app::Message> {
+        self.loading = true;
+        let (month_start, month_end) = self.get_month_start_and_end();
+        data::request(
+            &self.store,
+            move |store| CategoriesData::load(store, month_start, month_end),
+            |data| Message::Categories(CategoriesMessage::Loaded(data)),
+        )
     }
 
     fn get_month_start_and_end(&self) -> (NaiveDate, NaiveDate) {
@@ -464,7 +1053,7 @@ impl Categories {
             .expect("Data non valida per l'inizio del mese");
 
         let next_month = if self.view_month == 12 {
-            NaiveDate::from_ymd_opt((self.view_month + 1) as i32, 1, 1)
+            NaiveDate::from_ymd_opt(self.view_year + 1, 1, 1)
         } else {
             NaiveDate::from_ymd_opt(self.view_year, self.view_month + 1, 1)
         }
@@ -475,3 +1064,71 @@ impl Categories {
         (month_start, month_end)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::store::Store;
+
+    fn categories_page() -> (Categories, SharedStore) {
+        let store = Arc::new(Mutex::new(Store::in_memory().unwrap()));
+        (Categories::new(store.clone()), store)
+    }
+
+    /// Waits for the queued changes, then shows what the store holds.
+    fn reload(categories: &mut Categories, store: &SharedStore) {
+        data::wait_idle();
+        let (month_start, month_end) = categories.get_month_start_and_end();
+        let loaded = CategoriesData::load(&mut *store.lock().unwrap(), month_start, month_end);
+        let _ = categories.update(CategoriesMessage::Loaded(loaded));
+    }
+
+    fn add_category(categories: &mut Categories, store: &SharedStore, category_name: &str) {
+        let _ = categories.update(CategoriesMessage::AddCategory);
+        let _ = categories.update(CategoriesMessage::NewCategoryNameChanged(
+            category_name.to_string(),
+        ));
+        let _ = categories.update(CategoriesMessage::NewCategorySubmitted);
+        reload(categories, store);
+    }
+
+    #[test]
+    fn submitted_categories_are_stored() {
+        let (mut categories, store) = categories_page();
+        add_category(&mut categories, &store, "Salary");
+
+        assert!(!categories.add_category_view_active);
+        assert!(categories.form_new_category_name.is_empty());
+        assert_eq!(categories.categories.len(), 1);
+        let stored = store.lock().unwrap().get_categories().unwrap();
+        assert_eq!(stored[0].name, "Salary");
+        assert!(stored[0].is_income);
+    }
+
+    #[test]
+    fn deleted_categories_are_merged_into_the_chosen_one() {
+        let (mut categories, store) = categories_page();
+        add_category(&mut categories, &store, "Salary");
+        add_category(&mut categories, &store, "Bonus");
+        let bonus = categories
+            .categories
+            .iter()
+            .find(|c| c.name == "Bonus")
+            .unwrap()
+            .id;
+
+        let _ = categories.update(CategoriesMessage::DeleteCategory(bonus));
+        assert_eq!(categories.delete_category_id, Some(bonus));
+        assert_eq!(categories.merge_options.len(), 1);
+        let _ = categories.update(CategoriesMessage::MergeIntoChanged(0));
+        let _ = categories.update(CategoriesMessage::ConfirmDeleteCategory);
+        assert_eq!(categories.delete_category_id, None);
+        data::wait_idle();
+
+        let stored = store.lock().unwrap().get_categories().unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].name, "Salary");
+    }
+}
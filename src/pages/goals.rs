@@ -0,0 +1,483 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local, Utc};
+use cosmic::{
+    iced::{Alignment, Length, Padding},
+    widget::{self, Space},
+    Element, Task,
+};
+
+use crate::{
+    app,
+    config::Config,
+    data,
+    errors::DataStoreError,
+    fl,
+    models::{open_accounts, Account, Goal, GoalProgress, Money, NewGoal},
+    repository::Repository,
+    store::{SharedStore, Store},
+    widget::date_picker::date_picker,
+    STORE,
+};
+
+const BEHIND_COLOR: cosmic::iced::Color = cosmic::iced::Color::from_rgb(0.85, 0.2, 0.2);
+
+#[derive(Debug, Clone)]
+pub enum GoalsMessage {
+    UpdatePage,
+    Loaded(Result<GoalsData, DataStoreError>),
+    AddGoal,
+    CancelAddGoal,
+    SubmitGoal,
+    FormNameChanged(String),
+    FormTargetAmountChanged(String),
+    FormTargetDateChanged(i64),
+    FormSourceChanged(usize),
+    ContributionAmountChanged(i32, String),
+    AddContribution(i32),
+    DeleteGoal(i32),
+}
+
+pub struct Goals {
+    store: SharedStore,
+    loading: bool,
+    currency_symbol: String,
+    currency_scale: u32,
+    goals: Vec<Goal>,
+    progress: HashMap<i32, GoalProgress>,
+    accounts: Vec<Account>,
+    add_goal_view: bool,
+    source_options: Vec<String>,
+    form_name: String,
+    form_target_amount: String,
+    form_target_date: i64,
+    form_source: Option<usize>,
+    contribution_goal: Option<i32>,
+    form_contribution: String,
+}
+
+impl Default for Goals {
+    fn default() -> Self {
+        Self::new(STORE.clone())
+    }
+}
+
+/// What the goals page shows, read by the data service.
+#[derive(Debug, Clone)]
+pub struct GoalsData {
+    currency_symbol: String,
+    currency_scale: u32,
+    goals: Vec<Goal>,
+    progress: HashMap<i32, GoalProgress>,
+    accounts: Vec<Account>,
+}
+
+impl GoalsData {
+    fn load(store: &mut Store) -> Result<Self, DataStoreError> {
+        let config = Config::load();
+        let reporting_currency_id = config.1.currency_id;
+        let (currency_symbol, currency_scale) =
+            match store.get_currency_by_id(reporting_currency_id) {
+                Ok(currency) => (currency.symbol, currency.scale as u32),
+                Err(_) => ("USD".to_string(), Money::DEFAULT_SCALE),
+            };
+        let goals = store.get_goals()?;
+        let today = Local::now().date_naive();
+        let progress = goals
+            .iter()
+            .map(|goal| {
+                store
+                    .get_goal_progress(goal, &today, reporting_currency_id)
+                    .map(|progress| (goal.id, progress))
+            })
+            .collect::<Result<HashMap<i32, GoalProgress>, DataStoreError>>()?;
+        Ok(Self {
+            currency_symbol,
+            currency_scale,
+            goals,
+            progress,
+            accounts: store.get_accounts()?,
+        })
+    }
+}
+
+/// A goal is fed by manual contributions, listed first, or by an open account.
+fn source_options(accounts: &[Account]) -> Vec<String> {
+    let mut options = vec![fl!("manual-contributions")];
+    options.extend(open_accounts(accounts, &[]).into_iter().map(|a| a.name));
+    options
+}
+
+impl Goals {
+    /// The page starts out empty until `GoalsMessage::UpdatePage` loads it.
+    pub fn new(store: SharedStore) -> Self {
+        Self {
+            store,
+            loading: true,
+            currency_symbol: "USD".to_string(),
+            currency_scale: Money::DEFAULT_SCALE,
+            goals: vec![],
+            progress: HashMap::new(),
+            source_options: source_options(&[]),
+            accounts: vec![],
+            add_goal_view: false,
+            form_name: "".to_string(),
+            form_target_amount: "".to_string(),
+            form_target_date: Utc::now().timestamp(),
+            form_source: Some(0),
+            contribution_goal: None,
+            form_contribution: "".to_string(),
+        }
+    }
+
+    pub fn view<'a>(&'a self) -> Element<'a, GoalsMessage> {
+        let container = widget::container(if self.add_goal_view {
+            self.new_goal_view()
+        } else {
+            self.goals_view()
+        })
+        .padding(Padding::new(15.));
+        widget::scrollable(container).into()
+    }
+
+    fn goals_view<'a>(&'a self) -> Element<'a, GoalsMessage> {
+        let mut element = widget::column()
+            .push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::title1(fl!("page_goals")))
+                            .width(Length::Fill),
+                    )
+                    .push(
+                        widget::column()
+                            .push(
+                                widget::button::text(fl!("add-goal"))
+                                    .on_press(GoalsMessage::AddGoal)
+                                    .class(widget::button::ButtonClass::Suggested),
+                            )
+                            .width(Length::Fill)
+                            .align_x(Alignment::End),
+                    ),
+            )
+            .push(Space::with_height(10))
+            .width(Length::Fill)
+            .align_x(Alignment::Start);
+
+        if self.goals.is_empty() {
+            return element
+                .push(widget::text::text(if self.loading {
+                    fl!("loading")
+                } else {
+                    fl!("no-elements")
+                }))
+                .into();
+        }
+
+        for goal in &self.goals {
+            element = element
+                .push(self.goal_card(goal))
+                .push(Space::with_height(10));
+        }
+
+        element.into()
+    }
+
+    fn goal_card<'a>(&'a self, goal: &Goal) -> Element<'a, GoalsMessage> {
+        let today = Local::now().date_naive();
+        let mut main_col = widget::column().push(
+            widget::row()
+                .push(
+                    widget::column()
+                        .push(widget::text::title4(goal.name.clone()))
+                        .push(widget::text::text(match goal.linked_account {
+                            Some(account_id) => format!(
+                                "{}: {}",
+                                fl!("bank-account"),
+                                self.account_name(account_id)
+                            ),
+                            None => fl!("manual-contributions"),
+                        }))
+                        .width(Length::Fill),
+                )
+                .push(
+                    widget::button::icon(widget::icon::from_name("edit-delete-symbolic"))
+                        .on_press(GoalsMessage::DeleteGoal(goal.id)),
+                ),
+        );
+
+        let Some(progress) = self.goal_progress(goal) else {
+            return widget::container(main_col)
+                .padding(10)
+                .width(Length::Fill)
+                .class(cosmic::theme::Container::Card)
+                .into();
+        };
+
+        main_col = main_col
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::text::text(format!(
+                            "{}: {} / {} {}",
+                            fl!("saved"),
+                            progress.saved,
+                            progress.target,
+                            self.currency_symbol
+                        ))
+                        .width(Length::Fill),
+                    )
+                    .push(
+                        widget::text::text(format!(
+                            "{}: {}",
+                            fl!("target-date"),
+                            goal.target_date.format("%d-%m-%Y")
+                        ))
+                        .width(Length::Fill),
+                    ),
+            )
+            .push(Space::with_height(5))
+            .push(widget::progress_bar(0.0..=1.0, progress.progress()).height(6))
+            .push(Space::with_height(5));
+
+        if progress.is_complete() {
+            main_col = main_col.push(widget::text::text(fl!("goal-reached")));
+        } else {
+            let projected = progress.projected_completion(&today);
+            let projection = match projected {
+                Some(date) => widget::text::text(format!(
+                    "{}: {}",
+                    fl!("projected-completion"),
+                    date.format("%d-%m-%Y")
+                )),
+                None => widget::text::text(fl!("no-contributions-yet")),
+            };
+            main_col = main_col.push(
+                widget::row()
+                    .push(
+                        widget::text::text(format!(
+                            "{}: {} {}",
+                            fl!("needed-per-month"),
+                            progress.needed_per_month(&today, &goal.target_date),
+                            self.currency_symbol
+                        ))
+                        .width(Length::Fill),
+                    )
+                    .push(
+                        if projected.map_or(true, |date| date > goal.target_date) {
+                            projection.class(cosmic::theme::Text::Color(BEHIND_COLOR))
+                        } else {
+                            projection
+                        }
+                        .width(Length::Fill),
+                    ),
+            );
+        }
+
+        if goal.linked_account.is_none() {
+            main_col = main_col.push(Space::with_height(10)).push(
+                widget::row()
+                    .push(
+                        widget::text_input(
+                            fl!("contribution"),
+                            if self.contribution_goal == Some(goal.id) {
+                                &self.form_contribution
+                            } else {
+                                ""
+                            },
+                        )
+                        .on_input({
+                            let goal_id = goal.id;
+                            move |value| GoalsMessage::ContributionAmountChanged(goal_id, value)
+                        }),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::button::text(fl!("add-contribution"))
+                            .on_press(GoalsMessage::AddContribution(goal.id)),
+                    )
+                    .align_y(Alignment::Center),
+            );
+        }
+
+        widget::container(main_col)
+            .padding(10)
+            .width(Length::Fill)
+            .class(cosmic::theme::Container::Card)
+            .into()
+    }
+
+    fn new_goal_view<'a>(&'a self) -> Element<'a, GoalsMessage> {
+        widget::column()
+            .width(Length::Fill)
+            .push(widget::text::title1(fl!("add-goal")))
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("goal-name")))
+                            .push(
+                                widget::text_input(fl!("goal-name"), &self.form_name)
+                                    .on_input(GoalsMessage::FormNameChanged),
+                            )
+                            .width(Length::Fill),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("target-amount")))
+                            .push(
+                                widget::text_input("0", &self.form_target_amount)
+                                    .on_input(GoalsMessage::FormTargetAmountChanged),
+                            )
+                            .width(Length::Fill),
+                    ),
+            )
+            .push(Space::with_height(10))
+            .push(widget::text::text(fl!("goal-source")))
+            .push(widget::dropdown(
+                &self.source_options,
+                self.form_source,
+                GoalsMessage::FormSourceChanged,
+            ))
+            .push(Space::with_height(10))
+            .push(widget::text::title4(fl!("target-date")))
+            .push(date_picker(self.form_target_date, |date| {
+                GoalsMessage::FormTargetDateChanged(date)
+            }))
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::button::text(fl!("add-goal"))
+                            .on_press(GoalsMessage::SubmitGoal)
+                            .class(widget::button::ButtonClass::Suggested),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::button::text(fl!("cancel"))
+                            .on_press(GoalsMessage::CancelAddGoal)
+                            .class(widget::button::ButtonClass::Destructive),
+                    ),
+            )
+            .into()
+    }
+
+    pub fn update(&mut self, message: GoalsMessage) -> Task<crate::app::Message> {
+        match message {
+            GoalsMessage::UpdatePage => {
+                self.loading = true;
+                return data::request(&self.store, GoalsData::load, |data| {
+                    app::Message::Goals(GoalsMessage::Loaded(data))
+                });
+            }
+            GoalsMessage::Loaded(Err(e)) => {
+                self.loading = false;
+                return app::error_toast(e);
+            }
+            GoalsMessage::Loaded(Ok(data)) => {
+                self.loading = false;
+                self.currency_symbol = data.currency_symbol;
+                self.currency_scale = data.currency_scale;
+                self.goals = data.goals;
+                self.progress = data.progress;
+                self.source_options = source_options(&data.accounts);
+                self.accounts = data.accounts;
+            }
+            GoalsMessage::AddGoal => {
+                self.form_name = "".to_string();
+                self.form_target_amount = "".to_string();
+                self.form_target_date = Utc::now().timestamp();
+                self.form_source = Some(0);
+                self.add_goal_view = true;
+            }
+            GoalsMessage::CancelAddGoal => {
+                self.add_goal_view = false;
+            }
+            GoalsMessage::FormNameChanged(name) => {
+                self.form_name = name;
+            }
+            GoalsMessage::FormTargetAmountChanged(value) => {
+                if value.is_empty() || Money::parse(&value, self.currency_scale).is_some() {
+                    self.form_target_amount = value;
+                }
+            }
+            GoalsMessage::FormTargetDateChanged(date) => {
+                self.form_target_date = date;
+            }
+            GoalsMessage::FormSourceChanged(selected) => {
+                self.form_source = Some(selected);
+            }
+            GoalsMessage::SubmitGoal => {
+                let Some(target_amount) =
+                    Money::parse(&self.form_target_amount, self.currency_scale)
+                else {
+                    return app::error_toast(fl!("invalid-amount"));
+                };
+                // the first option is manual contributions
+                let linked_account = self
+                    .form_source
+                    .filter(|index| *index > 0)
+                    .and_then(|index| open_accounts(&self.accounts, &[]).get(index - 1).cloned())
+                    .map(|a| a.id);
+                let new_goal = NewGoal {
+                    name: self.form_name.trim().to_string(),
+                    target_amount,
+                    target_date: DateTime::from_timestamp(self.form_target_date, 0)
+                        .unwrap_or_default()
+                        .date_naive(),
+                    linked_account,
+                };
+                self.add_goal_view = false;
+                return Task::batch([
+                    data::execute(&self.store, move |store| store.create_goal(&new_goal)),
+                    Task::perform(async {}, |_| app::Message::Goals(GoalsMessage::UpdatePage)),
+                ]);
+            }
+            GoalsMessage::ContributionAmountChanged(goal_id, value) => {
+                if value.is_empty()
+                    || value == "-"
+                    || Money::parse(&value, self.currency_scale).is_some()
+                {
+                    self.contribution_goal = Some(goal_id);
+                    self.form_contribution = value;
+                }
+            }
+            GoalsMessage::AddContribution(goal_id) => {
+                if self.contribution_goal != Some(goal_id) {
+                    return Task::none();
+                }
+                if let Some(amount) = Money::parse(&self.form_contribution, self.currency_scale) {
+                    self.contribution_goal = None;
+                    self.form_contribution = "".to_string();
+                    return Task::batch([
+                        data::execute(&self.store, move |store| {
+                            store.add_goal_contribution(goal_id, &Local::now().date_naive(), amount)
+                        }),
+                        Task::perform(async {}, |_| app::Message::Goals(GoalsMessage::UpdatePage)),
+                    ]);
+                }
+                return app::error_toast(fl!("invalid-amount"));
+            }
+            GoalsMessage::DeleteGoal(goal_id) => {
+                self.goals.retain(|goal| goal.id != goal_id);
+                return data::execute(&self.store, move |store| store.delete_goal(goal_id));
+            }
+        }
+        Task::none()
+    }
+
+    fn goal_progress(&self, goal: &Goal) -> Option<GoalProgress> {
+        self.progress.get(&goal.id).copied()
+    }
+
+    fn account_name(&self, account_id: i32) -> String {
+        self.accounts
+            .iter()
+            .find(|a| a.id == account_id)
+            .map(|a| a.name.clone())
+            .unwrap_or_else(|| fl!("not-found"))
+    }
+}
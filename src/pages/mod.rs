@@ -1,5 +1,10 @@
 pub mod accounts;
 pub mod settings;
 pub mod categories;
+pub mod goals;
+pub mod recurring;
+pub mod securities;
 pub mod transactions;
+pub mod recovery;
+pub mod unlock;
 pub mod welcome;
\ No newline at end of file
@@ -0,0 +1,212 @@
+use std::path::PathBuf;
+
+use cosmic::{
+    iced::{Alignment, Length},
+    widget::{self, Space},
+    Element, Task,
+};
+
+use crate::{
+    app,
+    backup::{self, Backup, BackupKind},
+    data,
+    errors::DataStoreError,
+    fl, ledger,
+};
+
+#[derive(Debug, Clone)]
+pub enum RecoveryMessage {
+    Update,
+    Loaded(Result<Vec<Backup>, DataStoreError>),
+    Retry,
+    RestoreBackup(PathBuf),
+    LedgerNameChanged(String),
+    CreateLedger,
+    Finished(Result<(), DataStoreError>),
+}
+
+/// What kept the ledger from opening, which decides the ways out offered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Problem {
+    Corrupt,
+    Migration,
+    ReadOnly,
+    NoDataDirectory,
+    Other,
+}
+
+impl Problem {
+    fn of(error: &DataStoreError) -> Self {
+        match error {
+            DataStoreError::IntegrityError(_) => Self::Corrupt,
+            DataStoreError::MigrationError(_) => Self::Migration,
+            DataStoreError::ReadOnly(_) => Self::ReadOnly,
+            DataStoreError::NoDataDirectory => Self::NoDataDirectory,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Shown instead of the pages when the ledger could not be opened at start.
+/// Trying again fits a directory made writable meanwhile, while a damaged
+/// ledger can be replaced by one of its backups or left for a new ledger.
+pub struct Recovery {
+    error: DataStoreError,
+    backups: Vec<Backup>,
+    form_ledger_name: String,
+    working: bool,
+}
+
+impl Recovery {
+    /// The backups are listed once `RecoveryMessage::Update` loads them.
+    pub fn new(error: DataStoreError) -> Self {
+        Self {
+            error,
+            backups: vec![],
+            form_ledger_name: "".to_string(),
+            working: false,
+        }
+    }
+
+    pub fn view<'a>(&'a self) -> Element<'a, RecoveryMessage> {
+        let name = ledger::current_ledger();
+        let problem = Problem::of(&self.error);
+        let (title, explanation) = match problem {
+            Problem::Corrupt => (
+                fl!("ledger-corrupt"),
+                fl!("ledger-corrupt-message", name = name),
+            ),
+            Problem::Migration => (
+                fl!("migration-failed"),
+                fl!("migration-failed-message", name = name),
+            ),
+            Problem::ReadOnly => (fl!("data-dir-read-only"), fl!("data-dir-read-only-message")),
+            Problem::NoDataDirectory => (fl!("data-dir-missing"), fl!("data-dir-missing-message")),
+            Problem::Other => (
+                fl!("ledger-not-opened"),
+                fl!("ledger-not-opened-message", name = name),
+            ),
+        };
+
+        let mut content = widget::column()
+            .push(widget::text::title3(title))
+            .push(widget::text::text(explanation))
+            .push(widget::text::caption(self.error.to_string()))
+            .push(Space::with_height(10))
+            .push(
+                widget::button::text(fl!("try-again"))
+                    .on_press_maybe((!self.working).then_some(RecoveryMessage::Retry))
+                    .class(widget::button::ButtonClass::Suggested),
+            )
+            .spacing(5)
+            .max_width(600);
+
+        // restoring and creating ledgers both write to the data directory
+        if !matches!(problem, Problem::ReadOnly | Problem::NoDataDirectory) {
+            content = content
+                .push(Space::with_height(10))
+                .push(widget::text::title4(fl!("restore-backup")));
+            if self.backups.is_empty() {
+                content = content.push(widget::text::text(fl!("no-backups")));
+            }
+            for backup in &self.backups {
+                let mut taken_at = backup.taken_at.format("%d-%m-%Y %H:%M:%S").to_string();
+                if backup.kind == BackupKind::Migration {
+                    taken_at = format!("{} ({})", taken_at, fl!("before-migration"));
+                }
+                content = content.push(
+                    widget::row()
+                        .push(widget::text::text(taken_at).width(Length::Fill))
+                        .push(
+                            widget::button::text(fl!("restore")).on_press_maybe(
+                                (!self.working)
+                                    .then(|| RecoveryMessage::RestoreBackup(backup.path.clone())),
+                            ),
+                        )
+                        .spacing(5)
+                        .align_y(Alignment::Center),
+                );
+            }
+
+            content = content
+                .push(Space::with_height(10))
+                .push(widget::text::title4(fl!("start-new-ledger")))
+                .push(
+                    widget::row()
+                        .push(
+                            widget::text_input(fl!("ledger-name"), &self.form_ledger_name)
+                                .on_input(RecoveryMessage::LedgerNameChanged)
+                                .width(Length::Fill),
+                        )
+                        .push(
+                            widget::button::text(fl!("create-ledger")).on_press_maybe(
+                                (!self.working && !self.form_ledger_name.trim().is_empty())
+                                    .then_some(RecoveryMessage::CreateLedger),
+                            ),
+                        )
+                        .spacing(5)
+                        .align_y(Alignment::Center),
+                );
+        }
+
+        widget::container(widget::scrollable(content))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .align_x(Alignment::Center)
+            .align_y(Alignment::Center)
+            .into()
+    }
+
+    pub fn update(&mut self, message: RecoveryMessage) -> Task<app::Message> {
+        match message {
+            // without a data directory there are no backups to look for
+            RecoveryMessage::Update if Problem::of(&self.error) == Problem::NoDataDirectory => {}
+            RecoveryMessage::Update => {
+                return Task::perform(
+                    data::run(|| backup::list_backups(&ledger::current_ledger())),
+                    |backups| app::Message::Recovery(RecoveryMessage::Loaded(backups)),
+                );
+            }
+            RecoveryMessage::Loaded(Ok(backups)) => self.backups = backups,
+            RecoveryMessage::Loaded(Err(e)) => return app::error_toast(e),
+            RecoveryMessage::Retry => return self.attempt(crate::open_database),
+            RecoveryMessage::RestoreBackup(path) => {
+                return self.attempt(move || backup::restore(&path));
+            }
+            RecoveryMessage::LedgerNameChanged(value) => {
+                self.form_ledger_name = value;
+            }
+            RecoveryMessage::CreateLedger => {
+                let name = self.form_ledger_name.trim().to_string();
+                return self.attempt(move || {
+                    ledger::create_ledger(&name)?;
+                    ledger::open_ledger(&name, None)
+                });
+            }
+            RecoveryMessage::Finished(Ok(())) => {
+                self.working = false;
+                return Task::perform(async {}, |_| app::Message::Recovered);
+            }
+            RecoveryMessage::Finished(Err(e)) => {
+                log::error!("Failed to recover the ledger: {}", e);
+                self.working = false;
+                self.error = e;
+                // a restore backs up the damaged ledger first
+                return self.update(RecoveryMessage::Update);
+            }
+        }
+        Task::none()
+    }
+
+    /// Runs a way out on the data service. The ledger functions lock the
+    /// store themselves.
+    fn attempt(
+        &mut self,
+        work: impl FnOnce() -> Result<(), DataStoreError> + Send + 'static,
+    ) -> Task<app::Message> {
+        self.working = true;
+        Task::perform(data::run(work), |result| {
+            app::Message::Recovery(RecoveryMessage::Finished(result))
+        })
+    }
+}
@@ -0,0 +1,710 @@
+use chrono::{DateTime, Datelike, Utc};
+use cosmic::{
+    iced::{Alignment, Length, Padding},
+    widget::{self, Space},
+    Element, Task,
+};
+
+use crate::{
+    app,
+    config::Config,
+    data,
+    errors::DataStoreError,
+    fl,
+    models::{
+        categories_by_parent, category_labels, open_accounts, Account, Category, Currency,
+        Frequency, Money, NewRecurringTransaction, RecurringTransaction,
+    },
+    repository::Repository,
+    store::{SharedStore, Store},
+    widget::date_picker::date_picker,
+    STORE,
+};
+
+#[derive(Debug, Clone)]
+pub enum RecurringMessage {
+    UpdatePage,
+    Loaded(Result<RecurringData, DataStoreError>),
+    AddRecurring,
+    CancelAddRecurring,
+    SubmitRecurring,
+    FormTransactionTypeChanged(widget::segmented_button::Entity),
+    FormCategoryChanged(usize),
+    FormBankAccountChanged(usize),
+    FormNoteChanged(String),
+    FormAmountChanged(String),
+    FormStartDateChanged(i64),
+    FormFrequencyChanged(usize),
+    FormIntervalChanged(String),
+    FormDayOfMonthChanged(String),
+    FormEndChanged(widget::segmented_button::Entity),
+    FormEndDateChanged(i64),
+    FormOccurrencesChanged(String),
+    PostOccurrence(i32),
+    SkipOccurrence(i32),
+    DeleteRecurring(i32),
+}
+
+pub struct Recurring {
+    store: SharedStore,
+    loading: bool,
+    reporting_currency_id: i32,
+    currencies: Vec<Currency>,
+    categories: Vec<Category>,
+    category_labels: Vec<String>,
+    accounts: Vec<Account>,
+    account_options: Vec<Account>,
+    recurring_transactions: Vec<RecurringTransaction>,
+    add_recurring_view: bool,
+    frequency_labels: Vec<String>,
+    form_transaction_type: widget::segmented_button::SingleSelectModel,
+    form_selected_category: Option<usize>,
+    form_selected_bank_account: Option<usize>,
+    form_note: String,
+    form_amount: String,
+    form_start_date: i64,
+    form_selected_frequency: Option<usize>,
+    form_interval: String,
+    form_day_of_month: String,
+    form_end: widget::segmented_button::SingleSelectModel,
+    form_end_date: i64,
+    form_occurrences: String,
+}
+
+impl Default for Recurring {
+    fn default() -> Self {
+        Self::new(STORE.clone())
+    }
+}
+
+/// What the recurring transactions page shows, read by the data service.
+#[derive(Debug, Clone)]
+pub struct RecurringData {
+    reporting_currency_id: i32,
+    currencies: Vec<Currency>,
+    categories: Vec<Category>,
+    accounts: Vec<Account>,
+    recurring_transactions: Vec<RecurringTransaction>,
+}
+
+impl RecurringData {
+    fn load(store: &mut Store) -> Result<Self, DataStoreError> {
+        Ok(Self {
+            reporting_currency_id: Config::load().1.currency_id,
+            currencies: store.get_currencies()?,
+            categories: categories_by_parent(store.get_categories()?),
+            accounts: store.get_accounts()?,
+            recurring_transactions: store.get_recurring_transactions()?,
+        })
+    }
+}
+
+const EXPENSE: u16 = 1;
+const INCOME: u16 = 2;
+
+const END_NEVER: u16 = 1;
+const END_ON_DATE: u16 = 2;
+const END_AFTER_COUNT: u16 = 3;
+
+fn transaction_type_model() -> widget::segmented_button::SingleSelectModel {
+    widget::segmented_button::Model::builder()
+        .insert(|b| b.text(fl!("expense")).data(EXPENSE).activate())
+        .insert(|b| b.text(fl!("income")).data(INCOME))
+        .build()
+}
+
+fn end_model() -> widget::segmented_button::SingleSelectModel {
+    widget::segmented_button::Model::builder()
+        .insert(|b| b.text(fl!("ends-never")).data(END_NEVER).activate())
+        .insert(|b| b.text(fl!("ends-on-date")).data(END_ON_DATE))
+        .insert(|b| b.text(fl!("ends-after-count")).data(END_AFTER_COUNT))
+        .build()
+}
+
+pub fn frequency_label(frequency: Frequency) -> String {
+    match frequency {
+        Frequency::Daily => fl!("frequency-daily"),
+        Frequency::Weekly => fl!("frequency-weekly"),
+        Frequency::Monthly => fl!("frequency-monthly"),
+        Frequency::Yearly => fl!("frequency-yearly"),
+    }
+}
+
+fn rule_description(rule: &RecurringTransaction) -> String {
+    let interval = rule.frequency_interval;
+    match rule.frequency() {
+        Frequency::Daily => fl!("every-n-days", interval = interval),
+        Frequency::Weekly => fl!("every-n-weeks", interval = interval),
+        Frequency::Monthly => fl!(
+            "every-n-months",
+            interval = interval,
+            day = rule.day_of_month.unwrap_or(rule.start_date.day() as i32)
+        ),
+        Frequency::Yearly => fl!("every-n-years", interval = interval),
+    }
+}
+
+impl Recurring {
+    /// The page starts out empty until `RecurringMessage::UpdatePage` loads it.
+    pub fn new(store: SharedStore) -> Self {
+        Self {
+            store,
+            loading: true,
+            reporting_currency_id: Config::load().1.currency_id,
+            currencies: vec![],
+            category_labels: vec![],
+            categories: vec![],
+            account_options: vec![],
+            accounts: vec![],
+            recurring_transactions: vec![],
+            add_recurring_view: false,
+            frequency_labels: Frequency::ALL
+                .iter()
+                .map(|frequency| frequency_label(*frequency))
+                .collect(),
+            form_transaction_type: transaction_type_model(),
+            form_selected_category: Some(0),
+            form_selected_bank_account: Some(0),
+            form_note: "".to_string(),
+            form_amount: "".to_string(),
+            form_start_date: Utc::now().timestamp(),
+            form_selected_frequency: Frequency::ALL
+                .iter()
+                .position(|frequency| *frequency == Frequency::Monthly),
+            form_interval: "1".to_string(),
+            form_day_of_month: "".to_string(),
+            form_end: end_model(),
+            form_end_date: Utc::now().timestamp(),
+            form_occurrences: "".to_string(),
+        }
+    }
+
+    pub fn view<'a>(&'a self) -> Element<'a, RecurringMessage> {
+        let container = widget::container(if self.add_recurring_view {
+            self.new_recurring_view()
+        } else {
+            self.recurring_view()
+        })
+        .padding(Padding::new(15.));
+        widget::scrollable(container).into()
+    }
+
+    fn recurring_view<'a>(&self) -> Element<'a, RecurringMessage> {
+        let mut element = widget::column()
+            .push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::title1(fl!("recurring-transactions")))
+                            .width(Length::Fill),
+                    )
+                    .push(
+                        widget::column()
+                            .push(
+                                widget::button::text(fl!("add-recurring"))
+                                    .on_press(RecurringMessage::AddRecurring)
+                                    .class(widget::button::ButtonClass::Suggested),
+                            )
+                            .width(Length::Fill)
+                            .align_x(Alignment::End),
+                    ),
+            )
+            .push(Space::with_height(10))
+            .width(Length::Fill)
+            .align_x(Alignment::Start);
+
+        if self.recurring_transactions.is_empty() {
+            return element
+                .push(widget::text::text(if self.loading {
+                    fl!("loading")
+                } else {
+                    fl!("no-elements")
+                }))
+                .into();
+        }
+
+        // rules that ended sort first on `next_date`, so list them last
+        let upcoming = self
+            .recurring_transactions
+            .iter()
+            .filter(|r| r.next_date.is_some());
+        let ended = self
+            .recurring_transactions
+            .iter()
+            .filter(|r| r.next_date.is_none());
+
+        for r in upcoming.chain(ended) {
+            element = element
+                .push(self.recurring_card(r))
+                .push(Space::with_height(10));
+        }
+
+        element.into()
+    }
+
+    fn recurring_card<'a>(&self, r: &RecurringTransaction) -> Element<'a, RecurringMessage> {
+        let category_name = self
+            .categories
+            .iter()
+            .find(|c| c.id == r.transaction_category)
+            .map(|c| c.name.clone())
+            .unwrap_or_else(|| fl!("not-found"));
+
+        let mut actions = widget::row();
+        if r.next_date.is_some() {
+            actions = actions
+                .push(
+                    widget::button::text(fl!("post"))
+                        .on_press(RecurringMessage::PostOccurrence(r.id))
+                        .class(widget::button::ButtonClass::Suggested),
+                )
+                .push(Space::with_width(10))
+                .push(
+                    widget::button::text(fl!("skip"))
+                        .on_press(RecurringMessage::SkipOccurrence(r.id)),
+                )
+                .push(Space::with_width(10));
+        }
+        actions = actions.push(
+            widget::button::icon(widget::icon::from_name("edit-delete-symbolic"))
+                .on_press(RecurringMessage::DeleteRecurring(r.id)),
+        );
+
+        let ends = match (r.next_date, r.end_date, r.remaining_occurrences) {
+            (None, _, _) => fl!("recurring-ended"),
+            (_, _, Some(remaining)) => fl!("occurrences-left", count = remaining),
+            (_, Some(end_date), _) => {
+                format!("{} {}", fl!("ends-on-date"), end_date.format("%d-%m-%Y"))
+            }
+            (_, None, None) => fl!("ends-never"),
+        };
+
+        widget::container(
+            widget::column()
+                .push(
+                    widget::row()
+                        .push(
+                            widget::text::text(if r.description.is_empty() {
+                                category_name.clone()
+                            } else {
+                                r.description.clone()
+                            })
+                            .width(Length::Fill),
+                        )
+                        .push(
+                            widget::text::text(format!(
+                                "{}: {}{} {}",
+                                fl!("amount"),
+                                if r.is_expense { "-" } else { "+" },
+                                r.amount(self.account_scale(r.bank_account)),
+                                self.account_currency(r.bank_account)
+                                    .map(|c| c.symbol.clone())
+                                    .unwrap_or_default()
+                            ))
+                            .width(Length::Fill),
+                        )
+                        .push(
+                            widget::text::text(match r.next_date {
+                                Some(next_date) => format!(
+                                    "{}: {}",
+                                    fl!("next-date"),
+                                    next_date.format("%d-%m-%Y")
+                                ),
+                                None => fl!("recurring-ended"),
+                            })
+                            .width(Length::Fill),
+                        )
+                        .push(actions),
+                )
+                .push(Space::with_height(5))
+                .push(widget::text::text(format!(
+                    "{} · {}: {} · {}: {} · {}",
+                    rule_description(r),
+                    fl!("category"),
+                    category_name,
+                    fl!("bank-account"),
+                    self.account_name(r.bank_account),
+                    ends
+                )))
+                .width(Length::Fill),
+        )
+        .width(Length::Fill)
+        .padding(Padding::new(10.))
+        .class(cosmic::theme::Container::Card)
+        .into()
+    }
+
+    fn new_recurring_view<'a>(&'a self) -> Element<'a, RecurringMessage> {
+        let mut element = widget::column()
+            .width(Length::Fill)
+            .push(widget::text::title1(fl!("add-recurring")))
+            .push(Space::with_height(10))
+            .push(
+                widget::segmented_control::horizontal(&self.form_transaction_type)
+                    .on_activate(RecurringMessage::FormTransactionTypeChanged),
+            )
+            .push(Space::with_height(10))
+            .push(
+                widget::column()
+                    .push(widget::text::text(fl!("amount")))
+                    .push(
+                        widget::text_input(fl!("amount"), &self.form_amount)
+                            .width(Length::Fill)
+                            .on_input(RecurringMessage::FormAmountChanged),
+                    ),
+            )
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("category")))
+                            .push(Space::with_height(5))
+                            .push(widget::dropdown(
+                                &self.category_labels,
+                                self.form_selected_category,
+                                RecurringMessage::FormCategoryChanged,
+                            )),
+                    )
+                    .push(Space::with_width(20))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("bank-account")))
+                            .push(Space::with_height(5))
+                            .push(widget::dropdown(
+                                &self.account_options,
+                                self.form_selected_bank_account,
+                                RecurringMessage::FormBankAccountChanged,
+                            )),
+                    ),
+            )
+            .push(
+                widget::column().push(widget::text::text(fl!("note"))).push(
+                    widget::text_input(fl!("note"), &self.form_note)
+                        .width(Length::Fill)
+                        .on_input(RecurringMessage::FormNoteChanged),
+                ),
+            )
+            .push(Space::with_height(10))
+            .push(widget::text::title4(fl!("first-occurrence")))
+            .push(date_picker(self.form_start_date, |date| {
+                RecurringMessage::FormStartDateChanged(date)
+            }))
+            .push(Space::with_height(10));
+
+        let mut frequency_row = widget::row()
+            .push(
+                widget::column()
+                    .push(widget::text::text(fl!("frequency")))
+                    .push(Space::with_height(5))
+                    .push(widget::dropdown(
+                        &self.frequency_labels,
+                        self.form_selected_frequency,
+                        RecurringMessage::FormFrequencyChanged,
+                    )),
+            )
+            .push(Space::with_width(20))
+            .push(
+                widget::column()
+                    .push(widget::text::text(fl!("repeat-every")))
+                    .push(Space::with_height(5))
+                    .push(
+                        widget::text_input("1", &self.form_interval)
+                            .on_input(RecurringMessage::FormIntervalChanged),
+                    ),
+            );
+        if self.form_frequency() == Frequency::Monthly {
+            frequency_row = frequency_row.push(Space::with_width(20)).push(
+                widget::column()
+                    .push(widget::text::text(fl!("day-of-month")))
+                    .push(Space::with_height(5))
+                    .push(
+                        widget::text_input(
+                            self.form_start_date().day().to_string(),
+                            &self.form_day_of_month,
+                        )
+                        .on_input(RecurringMessage::FormDayOfMonthChanged),
+                    ),
+            );
+        }
+
+        element = element
+            .push(frequency_row)
+            .push(Space::with_height(10))
+            .push(widget::text::title4(fl!("ends")))
+            .push(
+                widget::segmented_control::horizontal(&self.form_end)
+                    .on_activate(RecurringMessage::FormEndChanged),
+            )
+            .push(Space::with_height(10));
+
+        match self.form_end_kind() {
+            END_ON_DATE => {
+                element = element.push(date_picker(self.form_end_date, |date| {
+                    RecurringMessage::FormEndDateChanged(date)
+                }));
+            }
+            END_AFTER_COUNT => {
+                element = element.push(
+                    widget::column()
+                        .push(widget::text::text(fl!("occurrences")))
+                        .push(
+                            widget::text_input("12", &self.form_occurrences)
+                                .on_input(RecurringMessage::FormOccurrencesChanged),
+                        ),
+                );
+            }
+            _ => {}
+        }
+
+        element
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::button::text(fl!("add-recurring"))
+                            .on_press(RecurringMessage::SubmitRecurring)
+                            .class(widget::button::ButtonClass::Suggested),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::button::text(fl!("cancel"))
+                            .on_press(RecurringMessage::CancelAddRecurring)
+                            .class(widget::button::ButtonClass::Destructive),
+                    ),
+            )
+            .into()
+    }
+
+    pub fn update(&mut self, message: RecurringMessage) -> Task<crate::app::Message> {
+        let mut commands = Vec::new();
+        match message {
+            RecurringMessage::UpdatePage => {
+                self.loading = true;
+                commands.push(data::request(&self.store, RecurringData::load, |data| {
+                    app::Message::Recurring(RecurringMessage::Loaded(data))
+                }));
+            }
+            RecurringMessage::Loaded(Err(e)) => {
+                self.loading = false;
+                commands.push(app::error_toast(e));
+            }
+            RecurringMessage::Loaded(Ok(data)) => {
+                self.loading = false;
+                self.recurring_transactions = data.recurring_transactions;
+                self.categories = data.categories;
+                self.category_labels = category_labels(&self.categories);
+                self.account_options = open_accounts(&data.accounts, &[]);
+                self.accounts = data.accounts;
+                self.currencies = data.currencies;
+                self.reporting_currency_id = data.reporting_currency_id;
+            }
+            RecurringMessage::AddRecurring => {
+                self.form_transaction_type = transaction_type_model();
+                self.form_note = "".to_string();
+                self.form_amount = "".to_string();
+                self.form_start_date = Utc::now().timestamp();
+                self.form_interval = "1".to_string();
+                self.form_day_of_month = "".to_string();
+                self.form_end = end_model();
+                self.form_end_date = Utc::now().timestamp();
+                self.form_occurrences = "".to_string();
+                self.add_recurring_view = true;
+            }
+            RecurringMessage::CancelAddRecurring => {
+                self.add_recurring_view = false;
+            }
+            RecurringMessage::FormTransactionTypeChanged(key) => {
+                self.form_transaction_type.activate(key);
+            }
+            RecurringMessage::FormCategoryChanged(selected) => {
+                self.form_selected_category = Some(selected);
+            }
+            RecurringMessage::FormBankAccountChanged(selected) => {
+                self.form_selected_bank_account = Some(selected);
+            }
+            RecurringMessage::FormNoteChanged(note) => {
+                self.form_note = note;
+            }
+            RecurringMessage::FormAmountChanged(new_amount) => {
+                if Money::parse(&new_amount, self.selected_account_scale()).is_some() {
+                    self.form_amount = new_amount;
+                }
+            }
+            RecurringMessage::FormStartDateChanged(date) => {
+                self.form_start_date = date;
+            }
+            RecurringMessage::FormFrequencyChanged(selected) => {
+                self.form_selected_frequency = Some(selected);
+            }
+            RecurringMessage::FormIntervalChanged(interval) => {
+                if interval.is_empty() || interval.parse::<u16>().is_ok() {
+                    self.form_interval = interval;
+                }
+            }
+            RecurringMessage::FormDayOfMonthChanged(day) => {
+                if day.is_empty() || day.parse::<u32>().is_ok_and(|day| (1..=31).contains(&day)) {
+                    self.form_day_of_month = day;
+                }
+            }
+            RecurringMessage::FormEndChanged(key) => {
+                self.form_end.activate(key);
+            }
+            RecurringMessage::FormEndDateChanged(date) => {
+                self.form_end_date = date;
+            }
+            RecurringMessage::FormOccurrencesChanged(occurrences) => {
+                if occurrences.is_empty() || occurrences.parse::<u16>().is_ok() {
+                    self.form_occurrences = occurrences;
+                }
+            }
+            RecurringMessage::SubmitRecurring => {
+                let (Some(bank_account), Some(category)) = (
+                    self.form_selected_bank_account
+                        .and_then(|index| self.account_options.get(index)),
+                    self.form_selected_category
+                        .and_then(|index| self.categories.get(index)),
+                ) else {
+                    log::error!("a recurring transaction needs an account and a category");
+                    return Task::none();
+                };
+                let scale = self.account_scale(bank_account.id);
+                let Some(amount) = Money::parse(&self.form_amount, scale) else {
+                    return app::error_toast(fl!("invalid-amount"));
+                };
+                let frequency = self.form_frequency();
+                let start_date = self.form_start_date();
+                let end_kind = self.form_end_kind();
+                let new_recurring_transaction = NewRecurringTransaction {
+                    bank_account: bank_account.id,
+                    transaction_category: category.id,
+                    description: self.form_note.clone(),
+                    is_expense: self.form_transaction_kind() != INCOME,
+                    amount,
+                    frequency: frequency.as_str().to_string(),
+                    frequency_interval: self.form_interval.parse::<i32>().unwrap_or(1).max(1),
+                    day_of_month: if frequency == Frequency::Monthly {
+                        Some(
+                            self.form_day_of_month
+                                .parse::<i32>()
+                                .unwrap_or(start_date.day() as i32),
+                        )
+                    } else {
+                        None
+                    },
+                    start_date,
+                    end_date: if end_kind == END_ON_DATE {
+                        DateTime::from_timestamp(self.form_end_date, 0)
+                            .map(|date| date.date_naive())
+                    } else {
+                        None
+                    },
+                    remaining_occurrences: if end_kind == END_AFTER_COUNT {
+                        Some(self.form_occurrences.parse::<i32>().unwrap_or(1).max(1))
+                    } else {
+                        None
+                    },
+                };
+                commands.push(data::execute(&self.store, move |store| {
+                    store.create_recurring_transaction(&new_recurring_transaction)
+                }));
+                commands.push(Self::refresh_pages());
+                self.add_recurring_view = false;
+            }
+            RecurringMessage::PostOccurrence(id) => {
+                commands.push(data::execute(&self.store, move |store| {
+                    store.post_recurring_transaction(id)
+                }));
+                commands.push(Self::refresh_pages());
+            }
+            RecurringMessage::SkipOccurrence(id) => {
+                commands.push(data::execute(&self.store, move |store| {
+                    store.skip_recurring_transaction(id)
+                }));
+                commands.push(Self::refresh_pages());
+            }
+            RecurringMessage::DeleteRecurring(id) => {
+                commands.push(data::execute(&self.store, move |store| {
+                    store.delete_recurring_transaction(id)
+                }));
+                commands.push(Self::refresh_pages());
+            }
+        }
+        Task::batch(commands)
+    }
+
+    fn refresh_pages() -> Task<app::Message> {
+        Task::batch(vec![
+            Task::perform(async {}, |_| {
+                app::Message::Recurring(RecurringMessage::UpdatePage)
+            }),
+            Task::perform(async {}, |_| {
+                app::Message::Accounts(super::accounts::AccountsMessage::Update)
+            }),
+            Task::perform(async {}, |_| {
+                app::Message::Categories(super::categories::CategoriesMessage::Update)
+            }),
+            Task::perform(async {}, |_| {
+                app::Message::Transactions(super::transactions::TransactionMessage::UpdatePage)
+            }),
+        ])
+    }
+
+    fn form_transaction_kind(&self) -> u16 {
+        self.form_transaction_type
+            .data::<u16>(self.form_transaction_type.active())
+            .copied()
+            .unwrap_or(EXPENSE)
+    }
+
+    fn form_end_kind(&self) -> u16 {
+        self.form_end
+            .data::<u16>(self.form_end.active())
+            .copied()
+            .unwrap_or(END_NEVER)
+    }
+
+    fn form_frequency(&self) -> Frequency {
+        self.form_selected_frequency
+            .and_then(|index| Frequency::ALL.get(index))
+            .copied()
+            .unwrap_or(Frequency::Monthly)
+    }
+
+    fn form_start_date(&self) -> chrono::NaiveDate {
+        DateTime::from_timestamp(self.form_start_date, 0)
+            .unwrap_or_default()
+            .date_naive()
+    }
+
+    fn account_name(&self, account_id: i32) -> String {
+        self.accounts
+            .iter()
+            .find(|a| a.id == account_id)
+            .map(|a| a.name.clone())
+            .unwrap_or_else(|| fl!("not-found"))
+    }
+
+    fn account_currency(&self, account_id: i32) -> Option<&Currency> {
+        let currency_id = self
+            .accounts
+            .iter()
+            .find(|a| a.id == account_id)
+            .and_then(|a| a.currency_id)
+            .unwrap_or(self.reporting_currency_id);
+        self.currencies.iter().find(|c| c.id == currency_id)
+    }
+
+    fn account_scale(&self, account_id: i32) -> u32 {
+        self.account_currency(account_id)
+            .map(|c| c.scale as u32)
+            .unwrap_or(Money::DEFAULT_SCALE)
+    }
+
+    fn selected_account_scale(&self) -> u32 {
+        self.form_selected_bank_account
+            .and_then(|index| self.account_options.get(index))
+            .map(|a| self.account_scale(a.id))
+            .unwrap_or(Money::DEFAULT_SCALE)
+    }
+}
@@ -0,0 +1,449 @@
+use chrono::{DateTime, Utc};
+use cosmic::{
+    iced::{Alignment, Length, Padding},
+    widget::{self, Space},
+    Element, Task,
+};
+
+use crate::{
+    app, data,
+    errors::DataStoreError,
+    fl,
+    models::{parse_price_csv, NewSecurity, Security, SecurityPrice},
+    store::{SharedStore, Store},
+    widget::date_picker::date_picker,
+    STORE,
+};
+
+#[derive(Debug, Clone)]
+pub enum SecuritiesMessage {
+    UpdatePage,
+    Loaded(Result<SecuritiesData, DataStoreError>),
+    AddSecurity,
+    CancelAddSecurity,
+    SubmitSecurity,
+    FormSymbolChanged(String),
+    FormNameChanged(String),
+    SelectSecurity(i32),
+    PriceDateChanged(i64),
+    PriceChanged(String),
+    SubmitPrice,
+    PricesSaved(Option<String>),
+    DeletePrice(i32),
+    ImportPathChanged(String),
+    ImportPrices,
+}
+
+pub struct Securities {
+    store: SharedStore,
+    loading: bool,
+    securities: Vec<Security>,
+    add_security_view: bool,
+    form_symbol: String,
+    form_name: String,
+    selected_security: Option<i32>,
+    prices: Vec<SecurityPrice>,
+    form_price_date: i64,
+    form_price: String,
+    import_path: String,
+    message: Option<String>,
+}
+
+impl Default for Securities {
+    fn default() -> Self {
+        Self::new(STORE.clone())
+    }
+}
+
+/// What the securities page shows, read by the data service.
+#[derive(Debug, Clone)]
+pub struct SecuritiesData {
+    securities: Vec<Security>,
+    selected_security: Option<i32>,
+    prices: Vec<SecurityPrice>,
+}
+
+impl SecuritiesData {
+    fn load(store: &mut Store, selected_security: Option<i32>) -> Result<Self, DataStoreError> {
+        Ok(Self {
+            securities: store.get_securities()?,
+            selected_security,
+            prices: match selected_security {
+                Some(security_id) => store.get_security_prices(security_id)?,
+                None => vec![],
+            },
+        })
+    }
+}
+
+impl Securities {
+    /// The page starts out empty until `SecuritiesMessage::UpdatePage` loads it.
+    pub fn new(store: SharedStore) -> Self {
+        Self {
+            store,
+            loading: true,
+            securities: vec![],
+            add_security_view: false,
+            form_symbol: "".to_string(),
+            form_name: "".to_string(),
+            selected_security: None,
+            prices: vec![],
+            form_price_date: Utc::now().timestamp(),
+            form_price: "".to_string(),
+            import_path: "".to_string(),
+            message: None,
+        }
+    }
+
+    pub fn view<'a>(&'a self) -> Element<'a, SecuritiesMessage> {
+        let mut element = widget::column()
+            .push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::title1(fl!("page_securities")))
+                            .width(Length::Fill),
+                    )
+                    .push(
+                        widget::column()
+                            .push(
+                                widget::button::text(fl!("add-security"))
+                                    .on_press(SecuritiesMessage::AddSecurity)
+                                    .class(widget::button::ButtonClass::Suggested),
+                            )
+                            .width(Length::Fill)
+                            .align_x(Alignment::End),
+                    ),
+            )
+            .push(Space::with_height(10))
+            .width(Length::Fill);
+
+        if self.add_security_view {
+            element = element
+                .push(self.new_security_view())
+                .push(Space::with_height(10));
+        }
+
+        if self.securities.is_empty() {
+            element = element.push(widget::text::text(if self.loading {
+                fl!("loading")
+            } else {
+                fl!("no-elements")
+            }));
+        }
+
+        for security in &self.securities {
+            let selected = self.selected_security == Some(security.id);
+            let mut card = widget::column().push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::title4(security.symbol.clone()))
+                            .push(widget::text::text(security.name.clone()))
+                            .width(Length::Fill),
+                    )
+                    .push(
+                        widget::button::text(if selected {
+                            fl!("hide-prices")
+                        } else {
+                            fl!("show-prices")
+                        })
+                        .on_press(SecuritiesMessage::SelectSecurity(security.id)),
+                    )
+                    .align_y(Alignment::Center),
+            );
+            if selected {
+                card = card.push(Space::with_height(10)).push(self.prices_view());
+            }
+            element = element
+                .push(
+                    widget::container(card)
+                        .padding(10)
+                        .width(Length::Fill)
+                        .class(cosmic::theme::Container::Card),
+                )
+                .push(Space::with_height(10));
+        }
+
+        widget::scrollable(widget::container(element).padding(Padding::new(15.))).into()
+    }
+
+    fn new_security_view<'a>(&'a self) -> Element<'a, SecuritiesMessage> {
+        widget::container(
+            widget::column()
+                .push(
+                    widget::row()
+                        .push(
+                            widget::column()
+                                .push(widget::text::text(fl!("symbol")))
+                                .push(
+                                    widget::text_input("ACME", &self.form_symbol)
+                                        .on_input(SecuritiesMessage::FormSymbolChanged),
+                                )
+                                .width(Length::Fill),
+                        )
+                        .push(Space::with_width(10))
+                        .push(
+                            widget::column()
+                                .push(widget::text::text(fl!("security-name")))
+                                .push(
+                                    widget::text_input(fl!("security-name"), &self.form_name)
+                                        .on_input(SecuritiesMessage::FormNameChanged),
+                                )
+                                .width(Length::Fill),
+                        ),
+                )
+                .push(Space::with_height(10))
+                .push(
+                    widget::row()
+                        .push(
+                            widget::button::text(fl!("cancel"))
+                                .on_press(SecuritiesMessage::CancelAddSecurity)
+                                .class(widget::button::ButtonClass::Destructive),
+                        )
+                        .push(Space::with_width(10))
+                        .push(
+                            widget::button::text(fl!("add"))
+                                .on_press_maybe(
+                                    (!self.form_symbol.trim().is_empty())
+                                        .then_some(SecuritiesMessage::SubmitSecurity),
+                                )
+                                .class(widget::button::ButtonClass::Suggested),
+                        ),
+                ),
+        )
+        .padding(10)
+        .width(Length::Fill)
+        .class(cosmic::theme::Container::Card)
+        .into()
+    }
+
+    /// The price history of the selected security, with forms to record a
+    /// price or import a CSV file of `date,price` lines.
+    fn prices_view<'a>(&'a self) -> Element<'a, SecuritiesMessage> {
+        let mut element = widget::column()
+            .push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("date")))
+                            .push(date_picker(self.form_price_date, |date| {
+                                SecuritiesMessage::PriceDateChanged(date)
+                            }))
+                            .width(Length::Fill),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("price")))
+                            .push(
+                                widget::text_input("0", &self.form_price)
+                                    .on_input(SecuritiesMessage::PriceChanged),
+                            )
+                            .push(Space::with_height(10))
+                            .push(
+                                widget::button::text(fl!("add-price"))
+                                    .on_press_maybe(
+                                        self.form_price
+                                            .parse::<f64>()
+                                            .ok()
+                                            .map(|_| SecuritiesMessage::SubmitPrice),
+                                    )
+                                    .class(widget::button::ButtonClass::Suggested),
+                            )
+                            .width(Length::Fill),
+                    ),
+            )
+            .push(Space::with_height(10))
+            .push(widget::text::text(fl!("import-prices")))
+            .push(
+                widget::row()
+                    .push(
+                        widget::text_input("/path/to/prices.csv", &self.import_path)
+                            .on_input(SecuritiesMessage::ImportPathChanged),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::button::text(fl!("import")).on_press_maybe(
+                            (!self.import_path.trim().is_empty())
+                                .then_some(SecuritiesMessage::ImportPrices),
+                        ),
+                    )
+                    .align_y(Alignment::Center),
+            )
+            .push_maybe(self.message.as_ref().map(|m| widget::text::text(m.clone())))
+            .push(Space::with_height(10));
+
+        if self.prices.is_empty() {
+            return element.push(widget::text::text(fl!("no-prices"))).into();
+        }
+
+        for price in &self.prices {
+            element = element.push(
+                widget::row()
+                    .push(
+                        widget::text::text(price.price_date.format("%d-%m-%Y").to_string())
+                            .width(Length::Fill),
+                    )
+                    .push(widget::text::text(price.price.to_string()).width(Length::Fill))
+                    .push(
+                        widget::button::icon(widget::icon::from_name("edit-delete-symbolic"))
+                            .on_press(SecuritiesMessage::DeletePrice(price.id)),
+                    )
+                    .align_y(Alignment::Center),
+            );
+        }
+
+        element.into()
+    }
+
+    pub fn update(&mut self, message: SecuritiesMessage) -> Task<crate::app::Message> {
+        let mut commands = vec![];
+        match message {
+            SecuritiesMessage::UpdatePage => {
+                self.loading = true;
+                commands.push(self.load());
+            }
+            SecuritiesMessage::Loaded(Err(e)) => {
+                self.loading = false;
+                commands.push(app::error_toast(e));
+            }
+            SecuritiesMessage::Loaded(Ok(data)) => {
+                self.loading = false;
+                self.securities = data.securities;
+                // prices of a security the user moved away from are stale
+                if data.selected_security == self.selected_security {
+                    self.prices = data.prices;
+                }
+            }
+            SecuritiesMessage::AddSecurity => {
+                self.form_symbol = "".to_string();
+                self.form_name = "".to_string();
+                self.add_security_view = true;
+            }
+            SecuritiesMessage::CancelAddSecurity => {
+                self.add_security_view = false;
+            }
+            SecuritiesMessage::FormSymbolChanged(value) => {
+                self.form_symbol = value.to_uppercase();
+            }
+            SecuritiesMessage::FormNameChanged(value) => {
+                self.form_name = value;
+            }
+            SecuritiesMessage::SubmitSecurity => {
+                let new_security = NewSecurity {
+                    symbol: self.form_symbol.trim().to_string(),
+                    name: self.form_name.trim().to_string(),
+                };
+                commands.push(data::execute(&self.store, move |store| {
+                    store.create_security(&new_security)
+                }));
+                self.add_security_view = false;
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Securities(SecuritiesMessage::UpdatePage)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Accounts(super::accounts::AccountsMessage::Update)
+                }));
+            }
+            SecuritiesMessage::SelectSecurity(security_id) => {
+                self.selected_security = if self.selected_security == Some(security_id) {
+                    None
+                } else {
+                    Some(security_id)
+                };
+                self.message = None;
+                self.prices = vec![];
+                commands.push(self.load());
+            }
+            SecuritiesMessage::PriceDateChanged(date) => {
+                self.form_price_date = date;
+            }
+            SecuritiesMessage::PriceChanged(value) => {
+                if value.is_empty() || value.parse::<f64>().is_ok_and(|price| price >= 0.) {
+                    self.form_price = value;
+                }
+            }
+            SecuritiesMessage::SubmitPrice => {
+                if let (Some(security_id), Ok(price)) =
+                    (self.selected_security, self.form_price.parse::<f64>())
+                {
+                    let date = DateTime::from_timestamp(self.form_price_date, 0)
+                        .unwrap_or_default()
+                        .date_naive();
+                    commands.push(self.save_prices(security_id, vec![(date, price)], None));
+                    self.form_price = "".to_string();
+                }
+            }
+            SecuritiesMessage::PricesSaved(message) => {
+                self.message = message;
+                commands.push(self.load());
+                commands.push(Self::refresh_accounts());
+            }
+            SecuritiesMessage::DeletePrice(price_id) => {
+                commands.push(data::execute(&self.store, move |store| {
+                    store.delete_security_price(price_id)
+                }));
+                commands.push(self.load());
+                commands.push(Self::refresh_accounts());
+            }
+            SecuritiesMessage::ImportPathChanged(value) => {
+                self.import_path = value;
+            }
+            SecuritiesMessage::ImportPrices => {
+                let Some(security_id) = self.selected_security else {
+                    return Task::none();
+                };
+                let prices = std::fs::read_to_string(self.import_path.trim())
+                    .map_err(|e| e.to_string())
+                    .and_then(|content| {
+                        parse_price_csv(&content)
+                            .map_err(|line| fl!("import-prices-error", line = line))
+                    });
+                match prices {
+                    Ok(prices) => {
+                        let done = fl!("import-prices-done", count = prices.len());
+                        commands.push(self.save_prices(security_id, prices, Some(done)));
+                    }
+                    Err(e) => self.message = Some(e),
+                }
+            }
+        }
+        Task::batch(commands)
+    }
+
+    /// Saves prices, then shows the error if saving failed, or else `done`.
+    fn save_prices(
+        &self,
+        security_id: i32,
+        prices: Vec<(chrono::NaiveDate, f64)>,
+        done: Option<String>,
+    ) -> Task<app::Message> {
+        data::request(
+            &self.store,
+            move |store| store.set_security_prices(security_id, &prices),
+            |saved| {
+                app::Message::Securities(SecuritiesMessage::PricesSaved(match saved {
+                    Ok(_) => done,
+                    Err(e) => Some(e.to_string()),
+                }))
+            },
+        )
+    }
+
+    fn load(&self) -> Task<app::Message> {
+        let selected_security = self.selected_security;
+        data::request(
+            &self.store,
+            move |store| SecuritiesData::load(store, selected_security),
+            |data| app::Message::Securities(SecuritiesMessage::Loaded(data)),
+        )
+    }
+
+    /// Market values on the Accounts page follow the latest prices.
+    fn refresh_accounts() -> Task<app::Message> {
+        Task::perform(async {}, |_| {
+            app::Message::Accounts(super::accounts::AccountsMessage::Update)
+        })
+    }
+}
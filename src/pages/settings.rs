@@ -1,54 +1,577 @@
-use crate::{app, config::Config, fl, models::Currency, STORE};
-use cosmic::{iced::Length, widget, Element, Task};
+use std::path::PathBuf;
+
+use crate::{
+    app,
+    backup::{self, Backup, BackupKind},
+    config::Config,
+    data,
+    errors::DataStoreError,
+    fl, ledger,
+    models::{Currency, ExchangeRate, LedgerSummary, NewExchangeRate},
+    repository::Repository,
+    store::{SharedStore, Store},
+    widget::date_picker::date_picker,
+    STORE,
+};
+use chrono::{DateTime, Utc};
+use cosmic::{
+    iced::{Alignment, Length},
+    widget::{self, Space},
+    Element, Task,
+};
 
 #[derive(Debug, Clone)]
 pub enum SettingsMessage {
     Update,
+    Loaded(Result<SettingsData, DataStoreError>),
     CurrencyChanged(usize),
+    RateFromChanged(usize),
+    RateToChanged(usize),
+    RateValueChanged(String),
+    RateDateChanged(i64),
+    AddExchangeRate,
+    DeleteExchangeRate(i32),
+    LedgerNameChanged(String),
+    CreateLedger,
+    LedgerCreated(Result<(), String>),
+    // whether the open ledger was replaced, so the pages show other data
+    LedgerChanged(Result<bool, String>),
+    OpenLedger(String),
+    OpenLedgerPassphraseChanged(String),
+    SubmitOpenLedger,
+    CancelOpenLedger,
+    RenameLedger(String),
+    RenameLedgerNameChanged(String),
+    SubmitRenameLedger,
+    CancelRenameLedger,
+    DeleteLedger(String),
+    ConfirmDeleteLedger,
+    CancelDeleteLedger,
+    PassphraseChanged(String),
+    PassphraseConfirmChanged(String),
+    SetPassphrase,
+    RemovePassphrase,
+    PassphraseSet(Result<bool, String>),
+    BackupsDailyChanged(String),
+    BackupsWeeklyChanged(String),
+    BackupsMonthlyChanged(String),
+    BackupPathChanged(String),
+    BackUp,
+    BackupFinished(String),
+    ToggleBackups,
+    RestoreBackup(PathBuf),
+    ConfirmRestoreBackup,
+    Restored(Result<(), String>),
+    CancelRestoreBackup,
 }
 
 pub struct Settings {
+    store: SharedStore,
+    loading: bool,
     currency_list: Vec<Currency>,
     selected_currency: Option<usize>,
+    exchange_rates: Vec<ExchangeRate>,
+    form_rate_from: Option<usize>,
+    form_rate_to: Option<usize>,
+    form_rate_value: String,
+    form_rate_date: i64,
+    ledgers: Vec<String>,
+    current_ledger: String,
+    form_ledger_name: String,
+    renaming_ledger: Option<String>,
+    form_rename_ledger: String,
+    deleting_ledger: Option<String>,
+    ledger_error: Option<String>,
+    opening_ledger: Option<String>,
+    form_open_passphrase: String,
+    encrypted: bool,
+    form_passphrase: String,
+    form_passphrase_confirm: String,
+    form_backups_daily: String,
+    form_backups_weekly: String,
+    form_backups_monthly: String,
+    backup_path: String,
+    show_backups: bool,
+    /// The backups of the open ledger with their row counts, or why they
+    /// failed their integrity check.
+    backups: Vec<(Backup, Result<LedgerSummary, String>)>,
+    restoring_backup: Option<PathBuf>,
+    backup_message: Option<String>,
 }
 
 impl Default for Settings {
     fn default() -> Self {
-        let mut store = STORE.lock().unwrap();
-        let currencies = store.get_currencies().unwrap_or_else(|_| vec![]);
-        let config = Config::load();
+        Self::new(STORE.clone())
+    }
+}
 
-        let selected_currency_id = config.1.currency_id;
+/// What the settings page shows, read by the data service.
+#[derive(Debug, Clone)]
+pub struct SettingsData {
+    currencies: Vec<Currency>,
+    exchange_rates: Vec<ExchangeRate>,
+    encrypted: bool,
+    ledgers: Vec<String>,
+    current_ledger: String,
+    backups: Vec<(Backup, Result<LedgerSummary, String>)>,
+}
 
-        let selected_currency = currencies
-            .iter()
-            .position(|currency| currency.id == selected_currency_id)
-            .unwrap_or(0);
+impl SettingsData {
+    /// Checking every backup takes a while, so they are only loaded while
+    /// they are shown.
+    fn load(store: &mut Store, show_backups: bool) -> Result<Self, DataStoreError> {
+        let current_ledger = ledger::current_ledger();
+        // backups open with the passphrase the ledger had when they were taken
+        let passphrase = store.passphrase().map(str::to_string);
+        let backups = if show_backups {
+            backup::list_backups(&current_ledger)?
+                .into_iter()
+                .map(|backup| {
+                    let summary = Store::inspect(&backup.path, passphrase.as_deref())
+                        .map_err(|e| e.to_string());
+                    (backup, summary)
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        Ok(Self {
+            currencies: store.get_currencies()?,
+            exchange_rates: store.get_exchange_rates()?,
+            encrypted: passphrase.is_some(),
+            ledgers: ledger::list_ledgers()?,
+            current_ledger,
+            backups,
+        })
+    }
+}
 
+impl Settings {
+    /// The page starts out empty until `SettingsMessage::Update` loads it.
+    pub fn new(store: SharedStore) -> Self {
+        let config = Config::load();
         Self {
-            currency_list: currencies,
-            selected_currency: Some(selected_currency),
+            store,
+            loading: true,
+            currency_list: vec![],
+            selected_currency: None,
+            exchange_rates: vec![],
+            form_rate_from: Some(0),
+            form_rate_to: None,
+            form_rate_value: "".to_string(),
+            form_rate_date: Utc::now().timestamp(),
+            ledgers: vec![],
+            current_ledger: config.1.ledger,
+            form_ledger_name: "".to_string(),
+            renaming_ledger: None,
+            form_rename_ledger: "".to_string(),
+            deleting_ledger: None,
+            ledger_error: None,
+            opening_ledger: None,
+            form_open_passphrase: "".to_string(),
+            encrypted: false,
+            form_passphrase: "".to_string(),
+            form_passphrase_confirm: "".to_string(),
+            form_backups_daily: config.1.backups_daily.to_string(),
+            form_backups_weekly: config.1.backups_weekly.to_string(),
+            form_backups_monthly: config.1.backups_monthly.to_string(),
+            backup_path: "".to_string(),
+            show_backups: false,
+            backups: vec![],
+            restoring_backup: None,
+            backup_message: None,
         }
     }
-}
 
-impl Settings {
     pub fn view<'a>(&'a self) -> Element<'a, SettingsMessage> {
-        let mut settings_col = widget::column().width(Length::Fill);
+        let mut settings_col = widget::column()
+            .width(Length::Fill)
+            .push(self.ledgers_view())
+            .push(Space::with_height(20))
+            .push(self.passphrase_view())
+            .push(Space::with_height(20))
+            .push(self.backups_view())
+            .push(Space::with_height(20));
 
         settings_col = settings_col
-            .push(widget::text::title4(fl!("currency")))
+            .push(widget::text::title4(fl!("reporting-currency")))
             .push(widget::dropdown(
                 &self.currency_list,
                 self.selected_currency,
                 SettingsMessage::CurrencyChanged,
             ));
 
+        settings_col = settings_col
+            .push(Space::with_height(20))
+            .push(widget::text::title4(fl!("exchange-rates")));
+
+        if self.exchange_rates.is_empty() {
+            settings_col = settings_col.push(widget::text::text(fl!("no-elements")));
+        }
+
+        for rate in &self.exchange_rates {
+            settings_col = settings_col.push(
+                widget::row()
+                    .push(
+                        widget::text::text(format!(
+                            "{}: 1 {} = {} {}",
+                            rate.rate_date.format("%d-%m-%Y"),
+                            self.currency_symbol(rate.from_currency),
+                            rate.rate,
+                            self.currency_symbol(rate.to_currency)
+                        ))
+                        .width(Length::Fill),
+                    )
+                    .push(
+                        widget::button::icon(widget::icon::from_name("edit-delete-symbolic"))
+                            .on_press(SettingsMessage::DeleteExchangeRate(rate.id)),
+                    ),
+            );
+        }
+
+        settings_col = settings_col
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("from-currency")))
+                            .push(widget::dropdown(
+                                &self.currency_list,
+                                self.form_rate_from,
+                                SettingsMessage::RateFromChanged,
+                            )),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("to-currency")))
+                            .push(widget::dropdown(
+                                &self.currency_list,
+                                self.form_rate_to,
+                                SettingsMessage::RateToChanged,
+                            )),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(fl!("rate")))
+                            .push(
+                                widget::text_input("1.0", &self.form_rate_value)
+                                    .on_input(SettingsMessage::RateValueChanged),
+                            )
+                            .width(Length::Fill),
+                    ),
+            )
+            .push(Space::with_height(10))
+            .push(date_picker(self.form_rate_date, |date| {
+                SettingsMessage::RateDateChanged(date)
+            }))
+            .push(Space::with_height(10))
+            .push(
+                widget::button::text(fl!("add-exchange-rate"))
+                    .on_press(SettingsMessage::AddExchangeRate)
+                    .class(widget::button::ButtonClass::Suggested),
+            );
+
         let main_container = widget::container(settings_col);
 
         widget::scrollable(main_container).into()
     }
 
+    /// The ledgers in the data directory, with forms to create, rename,
+    /// open and delete them.
+    fn ledgers_view<'a>(&'a self) -> Element<'a, SettingsMessage> {
+        let mut element = widget::column()
+            .push(widget::text::title4(fl!("ledgers")))
+            .spacing(5);
+
+        for name in &self.ledgers {
+            let is_current = *name == self.current_ledger;
+            let row = if self.renaming_ledger.as_ref() == Some(name) {
+                widget::row()
+                    .push(
+                        widget::text_input(fl!("ledger-name"), &self.form_rename_ledger)
+                            .on_input(SettingsMessage::RenameLedgerNameChanged)
+                            .on_submit(SettingsMessage::SubmitRenameLedger),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::button::text(fl!("cancel"))
+                            .on_press(SettingsMessage::CancelRenameLedger),
+                    )
+                    .push(
+                        widget::button::text(fl!("save"))
+                            .on_press(SettingsMessage::SubmitRenameLedger)
+                            .class(widget::button::ButtonClass::Suggested),
+                    )
+            } else if self.opening_ledger.as_ref() == Some(name) {
+                widget::row()
+                    .push(
+                        widget::secure_input(
+                            fl!("passphrase"),
+                            &self.form_open_passphrase,
+                            None,
+                            true,
+                        )
+                        .on_input(SettingsMessage::OpenLedgerPassphraseChanged)
+                        .on_submit(SettingsMessage::SubmitOpenLedger),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::button::text(fl!("cancel"))
+                            .on_press(SettingsMessage::CancelOpenLedger),
+                    )
+                    .push(
+                        widget::button::text(fl!("unlock"))
+                            .on_press(SettingsMessage::SubmitOpenLedger)
+                            .class(widget::button::ButtonClass::Suggested),
+                    )
+            } else if self.deleting_ledger.as_ref() == Some(name) {
+                widget::row()
+                    .push(
+                        widget::text::text(fl!("delete-ledger-confirm", name = name.clone()))
+                            .width(Length::Fill),
+                    )
+                    .push(
+                        widget::button::text(fl!("cancel"))
+                            .on_press(SettingsMessage::CancelDeleteLedger),
+                    )
+                    .push(
+                        widget::button::text(fl!("delete"))
+                            .on_press(SettingsMessage::ConfirmDeleteLedger)
+                            .class(widget::button::ButtonClass::Destructive),
+                    )
+            } else {
+                widget::row()
+                    .push(
+                        widget::text::text(if is_current {
+                            format!("{} ({})", name, fl!("open-ledger"))
+                        } else {
+                            name.clone()
+                        })
+                        .width(Length::Fill),
+                    )
+                    .push_maybe((!is_current).then(|| {
+                        widget::button::text(fl!("open"))
+                            .on_press(SettingsMessage::OpenLedger(name.clone()))
+                    }))
+                    .push(
+                        widget::button::icon(widget::icon::from_name("edit-symbolic"))
+                            .on_press(SettingsMessage::RenameLedger(name.clone())),
+                    )
+                    .push_maybe((!is_current).then(|| {
+                        widget::button::icon(widget::icon::from_name("edit-delete-symbolic"))
+                            .on_press(SettingsMessage::DeleteLedger(name.clone()))
+                    }))
+            };
+            element = element.push(row.spacing(5).align_y(Alignment::Center));
+        }
+
+        element
+            .push(Space::with_height(5))
+            .push(
+                widget::row()
+                    .push(
+                        widget::text_input(fl!("ledger-name"), &self.form_ledger_name)
+                            .on_input(SettingsMessage::LedgerNameChanged)
+                            .on_submit(SettingsMessage::CreateLedger),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::button::text(fl!("create-ledger"))
+                            .on_press_maybe(
+                                (!self.form_ledger_name.trim().is_empty())
+                                    .then_some(SettingsMessage::CreateLedger),
+                            )
+                            .class(widget::button::ButtonClass::Suggested),
+                    )
+                    .align_y(Alignment::Center),
+            )
+            .push_maybe(
+                self.ledger_error
+                    .as_ref()
+                    .map(|e| widget::text::text(e.clone())),
+            )
+            .into()
+    }
+
+    /// Sets, changes or removes the passphrase of the open ledger.
+    fn passphrase_view<'a>(&'a self) -> Element<'a, SettingsMessage> {
+        let matching = !self.form_passphrase.is_empty()
+            && self.form_passphrase == self.form_passphrase_confirm;
+
+        widget::column()
+            .push(widget::text::title4(fl!("encryption")))
+            .push(widget::text::text(if self.encrypted {
+                fl!("ledger-encrypted")
+            } else {
+                fl!("ledger-not-encrypted")
+            }))
+            .push(
+                widget::row()
+                    .push(
+                        widget::secure_input(
+                            fl!("new-passphrase"),
+                            &self.form_passphrase,
+                            None,
+                            true,
+                        )
+                        .on_input(SettingsMessage::PassphraseChanged),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::secure_input(
+                            fl!("confirm-passphrase"),
+                            &self.form_passphrase_confirm,
+                            None,
+                            true,
+                        )
+                        .on_input(SettingsMessage::PassphraseConfirmChanged)
+                        .on_submit(SettingsMessage::SetPassphrase),
+                    ),
+            )
+            .push(
+                widget::row()
+                    .push(
+                        widget::button::text(if self.encrypted {
+                            fl!("change-passphrase")
+                        } else {
+                            fl!("set-passphrase")
+                        })
+                        .on_press_maybe(matching.then_some(SettingsMessage::SetPassphrase))
+                        .class(widget::button::ButtonClass::Suggested),
+                    )
+                    .push_maybe(self.encrypted.then(|| {
+                        widget::button::text(fl!("remove-passphrase"))
+                            .on_press(SettingsMessage::RemovePassphrase)
+                            .class(widget::button::ButtonClass::Destructive)
+                    }))
+                    .spacing(10),
+            )
+            .spacing(5)
+            .into()
+    }
+
+    /// How many automatic backups are kept, manual backups and the backups
+    /// of the open ledger, which can be restored.
+    fn backups_view<'a>(&'a self) -> Element<'a, SettingsMessage> {
+        let retention = |label, value, on_input: fn(String) -> SettingsMessage| {
+            widget::column()
+                .push(widget::text::text(label))
+                .push(widget::text_input("0", value).on_input(on_input))
+                .width(Length::Fill)
+        };
+
+        let mut element = widget::column()
+            .push(widget::text::title4(fl!("backups")))
+            .push(widget::text::text(fl!("keep-backups")))
+            .push(
+                widget::row()
+                    .push(retention(
+                        fl!("backup-days"),
+                        &self.form_backups_daily,
+                        SettingsMessage::BackupsDailyChanged,
+                    ))
+                    .push(retention(
+                        fl!("backup-weeks"),
+                        &self.form_backups_weekly,
+                        SettingsMessage::BackupsWeeklyChanged,
+                    ))
+                    .push(retention(
+                        fl!("backup-months"),
+                        &self.form_backups_monthly,
+                        SettingsMessage::BackupsMonthlyChanged,
+                    ))
+                    .spacing(10),
+            )
+            .push(
+                widget::row()
+                    .push(
+                        widget::text_input("/path/to/backup.db", &self.backup_path)
+                            .on_input(SettingsMessage::BackupPathChanged)
+                            .on_submit(SettingsMessage::BackUp),
+                    )
+                    .push(Space::with_width(10))
+                    .push(widget::button::text(fl!("back-up")).on_press_maybe(
+                        (!self.backup_path.trim().is_empty()).then_some(SettingsMessage::BackUp),
+                    ))
+                    .align_y(Alignment::Center),
+            )
+            .push(
+                widget::button::text(if self.show_backups {
+                    fl!("hide-backups")
+                } else {
+                    fl!("show-backups")
+                })
+                .on_press(SettingsMessage::ToggleBackups),
+            )
+            .spacing(5);
+
+        if self.show_backups && self.backups.is_empty() {
+            element = element.push(widget::text::text(if self.loading {
+                fl!("loading")
+            } else {
+                fl!("no-backups")
+            }));
+        }
+
+        for (backup, summary) in self.backups.iter().filter(|_| self.show_backups) {
+            let mut taken_at = backup.taken_at.format("%d-%m-%Y %H:%M:%S").to_string();
+            if backup.kind == BackupKind::Migration {
+                taken_at = format!("{} ({})", taken_at, fl!("before-migration"));
+            }
+            let row = if self.restoring_backup.as_ref() == Some(&backup.path) {
+                widget::row()
+                    .push(
+                        widget::text::text(fl!("restore-backup-confirm", date = taken_at))
+                            .width(Length::Fill),
+                    )
+                    .push(
+                        widget::button::text(fl!("cancel"))
+                            .on_press(SettingsMessage::CancelRestoreBackup),
+                    )
+                    .push(
+                        widget::button::text(fl!("restore"))
+                            .on_press(SettingsMessage::ConfirmRestoreBackup)
+                            .class(widget::button::ButtonClass::Destructive),
+                    )
+            } else {
+                widget::row()
+                    .push(
+                        widget::column()
+                            .push(widget::text::text(taken_at))
+                            .push(widget::text::caption(match summary {
+                                Ok(summary) => fl!(
+                                    "backup-summary",
+                                    accounts = summary.accounts,
+                                    categories = summary.categories,
+                                    transactions = summary.transactions
+                                ),
+                                Err(e) => e.clone(),
+                            }))
+                            .width(Length::Fill),
+                    )
+                    .push(
+                        widget::button::text(fl!("restore")).on_press_maybe(
+                            summary
+                                .is_ok()
+                                .then(|| SettingsMessage::RestoreBackup(backup.path.clone())),
+                        ),
+                    )
+            };
+            element = element.push(row.spacing(5).align_y(Alignment::Center));
+        }
+
+        element
+            .push_maybe(
+                self.backup_message
+                    .as_ref()
+                    .map(|m| widget::text::text(m.clone())),
+            )
+            .into()
+    }
+
     pub fn update(&mut self, message: SettingsMessage) -> Task<crate::app::Message> {
         let mut commands = vec![];
         match message {
@@ -60,31 +583,364 @@ impl Settings {
                         .1
                         .set_currency_id(&config.0.unwrap(), selected_currency.id);
                 }
-                commands.push(Task::perform(async {}, |_| {
-                    app::Message::Accounts(super::accounts::AccountsMessage::Update)
-                }));
-                commands.push(Task::perform(async {}, |_| {
-                    app::Message::Categories(super::categories::CategoriesMessage::Update)
-                }));
-                commands.push(Task::perform(async {}, |_| {
-                    app::Message::Transactions(super::transactions::TransactionMessage::UpdatePage)
-                }));
+                commands.push(Self::refresh_pages());
             }
-            SettingsMessage::Update => {
-                let mut store = STORE.lock().unwrap();
-                let currencies = store.get_currencies().unwrap_or_else(|_| vec![]);
-                let config = Config::load();
-
-                let selected_currency_id = config.1.currency_id;
+            SettingsMessage::Update => commands.push(self.load()),
+            SettingsMessage::Loaded(Err(e)) => {
+                self.loading = false;
+                commands.push(app::error_toast(e));
+            }
+            SettingsMessage::Loaded(Ok(data)) => {
+                self.loading = false;
+                let selected_currency_id = Config::load().1.currency_id;
 
-                let selected_currency = currencies
+                let selected_currency = data
+                    .currencies
                     .iter()
                     .position(|currency| currency.id == selected_currency_id)
                     .unwrap_or(0);
 
                 self.selected_currency = Some(selected_currency);
+                self.form_rate_to = self.form_rate_to.or(Some(selected_currency));
+                self.currency_list = data.currencies;
+                self.exchange_rates = data.exchange_rates;
+                self.encrypted = data.encrypted;
+                self.ledgers = data.ledgers;
+                self.current_ledger = data.current_ledger;
+                // the backups may have been hidden while they loaded
+                if self.show_backups {
+                    self.backups = data.backups;
+                }
+            }
+            SettingsMessage::RateFromChanged(index) => {
+                self.form_rate_from = Some(index);
+            }
+            SettingsMessage::RateToChanged(index) => {
+                self.form_rate_to = Some(index);
+            }
+            SettingsMessage::RateValueChanged(value) => {
+                if value.is_empty() || value.parse::<f64>().is_ok() {
+                    self.form_rate_value = value;
+                }
+            }
+            SettingsMessage::RateDateChanged(date) => {
+                self.form_rate_date = date;
+            }
+            SettingsMessage::AddExchangeRate => {
+                let from = self.form_rate_from.and_then(|i| self.currency_list.get(i));
+                let to = self.form_rate_to.and_then(|i| self.currency_list.get(i));
+                let rate = self.form_rate_value.parse::<f64>();
+                let rate_date = DateTime::from_timestamp(self.form_rate_date, 0);
+                match (from, to, rate, rate_date) {
+                    (Some(from), Some(to), Ok(rate), Some(rate_date))
+                        if from.id != to.id && rate > 0. =>
+                    {
+                        let new_exchange_rate = NewExchangeRate {
+                            from_currency: from.id,
+                            to_currency: to.id,
+                            rate,
+                            rate_date: rate_date.date_naive(),
+                        };
+                        commands.push(data::execute(&self.store, move |store| {
+                            store.create_exchange_rate(&new_exchange_rate)
+                        }));
+                        self.form_rate_value = "".to_string();
+                        commands.push(Task::perform(async {}, |_| {
+                            app::Message::Settings(SettingsMessage::Update)
+                        }));
+                        commands.push(Self::refresh_pages());
+                    }
+                    _ => log::error!("invalid exchange rate"),
+                }
+            }
+            SettingsMessage::DeleteExchangeRate(id) => {
+                commands.push(data::execute(&self.store, move |store| {
+                    store.delete_exchange_rate(id)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Settings(SettingsMessage::Update)
+                }));
+                commands.push(Self::refresh_pages());
+            }
+            SettingsMessage::LedgerNameChanged(value) => {
+                self.form_ledger_name = value;
+            }
+            SettingsMessage::CreateLedger => {
+                let name = self.form_ledger_name.clone();
+                commands.push(Task::perform(
+                    data::run(move || ledger::create_ledger(&name)),
+                    |created| {
+                        app::Message::Settings(SettingsMessage::LedgerCreated(
+                            created.map_err(|e| e.to_string()),
+                        ))
+                    },
+                ));
+            }
+            SettingsMessage::LedgerCreated(created) => {
+                match created {
+                    Ok(()) => {
+                        self.form_ledger_name = "".to_string();
+                        self.ledger_error = None;
+                    }
+                    Err(e) => self.ledger_error = Some(e),
+                }
+                commands.push(self.load());
+            }
+            SettingsMessage::LedgerChanged(changed) => {
+                match changed {
+                    Ok(reopened) => {
+                        self.ledger_error = None;
+                        if reopened {
+                            commands.push(Task::perform(async {}, |_| app::Message::LedgerOpened));
+                        }
+                    }
+                    Err(e) => self.ledger_error = Some(e),
+                }
+                commands.push(self.load());
+            }
+            SettingsMessage::OpenLedger(name) => {
+                if ledger::is_encrypted(&name) {
+                    self.form_open_passphrase = "".to_string();
+                    self.opening_ledger = Some(name);
+                    self.renaming_ledger = None;
+                    self.deleting_ledger = None;
+                } else {
+                    commands.push(Self::open_ledger(name, None));
+                }
+            }
+            SettingsMessage::OpenLedgerPassphraseChanged(value) => {
+                self.form_open_passphrase = value;
+            }
+            SettingsMessage::SubmitOpenLedger => {
+                if let Some(name) = self.opening_ledger.take() {
+                    let passphrase = std::mem::take(&mut self.form_open_passphrase);
+                    commands.push(Self::open_ledger(name, Some(passphrase)));
+                }
+            }
+            SettingsMessage::CancelOpenLedger => {
+                self.opening_ledger = None;
+                self.form_open_passphrase = "".to_string();
+            }
+            SettingsMessage::RenameLedger(name) => {
+                self.form_rename_ledger = name.clone();
+                self.renaming_ledger = Some(name);
+                self.deleting_ledger = None;
+                self.opening_ledger = None;
+            }
+            SettingsMessage::RenameLedgerNameChanged(value) => {
+                self.form_rename_ledger = value;
+            }
+            SettingsMessage::SubmitRenameLedger => {
+                if let Some(name) = self.renaming_ledger.take() {
+                    let was_current = name == self.current_ledger;
+                    let new_name = self.form_rename_ledger.clone();
+                    commands.push(Self::change_ledger(move || {
+                        ledger::rename_ledger(&name, &new_name).map(|_| was_current)
+                    }));
+                }
+            }
+            SettingsMessage::CancelRenameLedger => {
+                self.renaming_ledger = None;
+            }
+            SettingsMessage::DeleteLedger(name) => {
+                self.deleting_ledger = Some(name);
+                self.renaming_ledger = None;
+                self.opening_ledger = None;
+            }
+            SettingsMessage::ConfirmDeleteLedger => {
+                if let Some(name) = self.deleting_ledger.take() {
+                    commands.push(Self::change_ledger(move || {
+                        ledger::delete_ledger(&name).map(|_| false)
+                    }));
+                }
+            }
+            SettingsMessage::CancelDeleteLedger => {
+                self.deleting_ledger = None;
+            }
+            SettingsMessage::PassphraseChanged(value) => {
+                self.form_passphrase = value;
+            }
+            SettingsMessage::PassphraseConfirmChanged(value) => {
+                self.form_passphrase_confirm = value;
+            }
+            SettingsMessage::SetPassphrase => {
+                if !self.form_passphrase.is_empty()
+                    && self.form_passphrase == self.form_passphrase_confirm
+                {
+                    commands.push(Self::change_passphrase(Some(self.form_passphrase.clone())));
+                }
+            }
+            SettingsMessage::RemovePassphrase => {
+                commands.push(Self::change_passphrase(None));
+            }
+            SettingsMessage::PassphraseSet(set) => match set {
+                Ok(encrypted) => {
+                    self.encrypted = encrypted;
+                    self.form_passphrase = "".to_string();
+                    self.form_passphrase_confirm = "".to_string();
+                    self.ledger_error = None;
+                }
+                Err(e) => self.ledger_error = Some(e),
+            },
+            SettingsMessage::BackupsDailyChanged(value) => {
+                if let Some(count) = Self::backup_count(&mut self.form_backups_daily, value) {
+                    let (handler, mut config) = Config::load();
+                    if let Some(handler) = handler {
+                        let _ = config.set_backups_daily(&handler, count);
+                    }
+                }
+            }
+            SettingsMessage::BackupsWeeklyChanged(value) => {
+                if let Some(count) = Self::backup_count(&mut self.form_backups_weekly, value) {
+                    let (handler, mut config) = Config::load();
+                    if let Some(handler) = handler {
+                        let _ = config.set_backups_weekly(&handler, count);
+                    }
+                }
+            }
+            SettingsMessage::BackupsMonthlyChanged(value) => {
+                if let Some(count) = Self::backup_count(&mut self.form_backups_monthly, value) {
+                    let (handler, mut config) = Config::load();
+                    if let Some(handler) = handler {
+                        let _ = config.set_backups_monthly(&handler, count);
+                    }
+                }
+            }
+            SettingsMessage::BackupPathChanged(value) => {
+                self.backup_path = value;
+            }
+            SettingsMessage::BackUp => {
+                let target = PathBuf::from(self.backup_path.trim());
+                let path = target.display().to_string();
+                commands.push(Task::perform(
+                    data::run(move || backup::back_up_to(&target)),
+                    move |backed_up| {
+                        let message = match backed_up {
+                            Ok(()) => fl!("backup-done", path = path),
+                            Err(e) => e.to_string(),
+                        };
+                        app::Message::Settings(SettingsMessage::BackupFinished(message))
+                    },
+                ));
+            }
+            SettingsMessage::BackupFinished(message) => {
+                self.backup_message = Some(message);
+            }
+            SettingsMessage::ToggleBackups => {
+                self.show_backups = !self.show_backups;
+                self.restoring_backup = None;
+                if self.show_backups {
+                    self.loading = true;
+                    commands.push(self.load());
+                } else {
+                    self.backups = vec![];
+                }
+            }
+            SettingsMessage::RestoreBackup(path) => {
+                self.restoring_backup = Some(path);
+            }
+            SettingsMessage::ConfirmRestoreBackup => {
+                if let Some(path) = self.restoring_backup.take() {
+                    commands.push(Task::perform(
+                        data::run(move || backup::restore(&path)),
+                        |restored| {
+                            app::Message::Settings(SettingsMessage::Restored(
+                                restored.map_err(|e| e.to_string()),
+                            ))
+                        },
+                    ));
+                }
+            }
+            SettingsMessage::Restored(restored) => {
+                match restored {
+                    Ok(()) => {
+                        self.backup_message = None;
+                        commands.push(Task::perform(async {}, |_| app::Message::LedgerOpened));
+                    }
+                    Err(e) => self.backup_message = Some(e),
+                }
+                commands.push(self.load());
+            }
+            SettingsMessage::CancelRestoreBackup => {
+                self.restoring_backup = None;
             }
         }
         Task::batch(commands)
     }
+
+    fn open_ledger(name: String, passphrase: Option<String>) -> Task<app::Message> {
+        Self::change_ledger(move || ledger::open_ledger(&name, passphrase.as_deref()).map(|_| true))
+    }
+
+    /// Runs a change to the ledger files, which returns whether it replaced
+    /// the open ledger.
+    fn change_ledger(
+        change: impl FnOnce() -> Result<bool, DataStoreError> + Send + 'static,
+    ) -> Task<app::Message> {
+        Task::perform(data::run(change), |changed| {
+            app::Message::Settings(SettingsMessage::LedgerChanged(
+                changed.map_err(|e| e.to_string()),
+            ))
+        })
+    }
+
+    /// Encrypting or decrypting rewrites the whole ledger.
+    fn change_passphrase(passphrase: Option<String>) -> Task<app::Message> {
+        let encrypted = passphrase.is_some();
+        Task::perform(
+            data::run(move || ledger::set_passphrase(passphrase.as_deref()).map(|_| encrypted)),
+            |set| {
+                app::Message::Settings(SettingsMessage::PassphraseSet(
+                    set.map_err(|e| e.to_string()),
+                ))
+            },
+        )
+    }
+
+    /// Keeps `value` in `form` when it is a number of backups, or empty while
+    /// one is typed, and returns that number.
+    fn backup_count(form: &mut String, value: String) -> Option<u32> {
+        if !value.is_empty() && value.parse::<u32>().is_err() {
+            return None;
+        }
+        *form = value;
+        form.parse().ok()
+    }
+
+    fn load(&self) -> Task<app::Message> {
+        let show_backups = self.show_backups;
+        data::request(
+            &self.store,
+            move |store| SettingsData::load(store, show_backups),
+            |data| app::Message::Settings(SettingsMessage::Loaded(data)),
+        )
+    }
+
+    fn currency_symbol(&self, currency_id: i32) -> String {
+        self.currency_list
+            .iter()
+            .find(|currency| currency.id == currency_id)
+            .map(|currency| currency.symbol.clone())
+            .unwrap_or_else(|| fl!("not-found"))
+    }
+
+    fn refresh_pages() -> Task<app::Message> {
+        Task::batch(vec![
+            Task::perform(async {}, |_| {
+                app::Message::Accounts(super::accounts::AccountsMessage::Update)
+            }),
+            Task::perform(async {}, |_| {
+                app::Message::Categories(super::categories::CategoriesMessage::Update)
+            }),
+            Task::perform(async {}, |_| {
+                app::Message::Transactions(super::transactions::TransactionMessage::UpdatePage)
+            }),
+            Task::perform(async {}, |_| {
+                app::Message::Recurring(super::recurring::RecurringMessage::UpdatePage)
+            }),
+            Task::perform(async {}, |_| {
+                app::Message::Goals(super::goals::GoalsMessage::UpdatePage)
+            }),
+        ])
+    }
 }
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use cosmic::{
     iced::{Alignment, Length, Padding},
@@ -8,68 +10,286 @@ use cosmic::{
 use crate::{
     app,
     config::Config,
+    data,
+    errors::DataStoreError,
     fl,
-    models::{Account, Category, MoneyTransaction, NewMoneyTransaction},
+    models::{
+        categories_by_parent, category_labels, highlighted_parts, open_accounts, Account,
+        AuditAction, AuditEntry, Category, ClearedStatus, Currency, InvestmentTrade, Money,
+        MoneyTransaction, NewMoneyTransaction, NewTransactionSplit, NewTransfer, Payee, Security,
+        Tag, TradeKind, TransactionEntry, TransactionMatch, TransactionSplit, TransactionTag,
+        MATCH_START,
+    },
+    repository::Repository,
+    store::{SharedStore, Store},
     widget::date_picker::date_picker,
     STORE,
 };
 
+use super::accounts::trade_kind_label;
+
 #[derive(Debug, Clone)]
 pub enum TransactionMessage {
     UpdatePage,
+    Loaded(Result<TransactionsData, DataStoreError>),
     AddTransaction,
     FormCategoryChanged(usize),
     FormBankAccountChanged(usize),
+    FormDestinationAccountChanged(usize),
     FormTransactionTypeChanged(widget::segmented_button::Entity),
     FormNoteChanged(String),
     FormAmountChanged(String),
     FormDateChanged(i64),
+    FormTagsChanged(String),
+    FormTagSuggestionSelected(String),
+    TagFilterChanged(usize),
+    TagTotalsLoaded(Option<i32>, Result<Option<(Money, Money)>, DataStoreError>),
+    FormPayeeChanged(String),
+    FormPayeeSelected(i32),
+    AddSplitLine,
+    RemoveSplitLine(usize),
+    SplitCategoryChanged(usize, usize),
+    SplitAmountChanged(usize, String),
+    SplitNoteChanged(usize, String),
     CandellAddTransaction,
     SubmitTransaction,
+    Saved(Result<(), String>),
+    EditTransaction(i32),
+    DeleteTransaction(i32),
+    ConfirmDeleteTransaction,
+    CancelDeleteTransaction,
+    ShowHistory(i32),
+    HistoryLoaded(i32, Result<Vec<AuditEntry>, DataStoreError>),
+    SearchChanged(String),
+    SearchResultsLoaded(String, Result<Vec<TransactionMatch>, DataStoreError>),
 }
 
 pub struct Transactions {
+    store: SharedStore,
+    loading: bool,
+    reporting_currency_id: i32,
     currency_symbol: String,
+    currencies: Vec<Currency>,
     add_transaction_view: bool,
     categories: Vec<Category>,
+    category_labels: Vec<String>,
     accounts: Vec<Account>,
+    account_options: Vec<Account>,
     form_transaction_type: widget::segmented_button::SingleSelectModel,
     form_selectected_category: Option<usize>,
     transactions: Vec<MoneyTransaction>,
     form_selected_bank_account: Option<usize>,
+    form_selected_destination_account: Option<usize>,
     form_note: String,
     form_amount: String,
     form_date: i64,
-    new_transaction_amount: f32,
+    editing_transaction: Option<i32>,
+    deleting_transaction: Option<i32>,
+    tags: Vec<Tag>,
+    transaction_tags: Vec<TransactionTag>,
+    tag_filter_options: Vec<String>,
+    selected_tag_filter: Option<usize>,
+    tag_totals: Option<(Money, Money)>,
+    form_tags: String,
+    splits: Vec<TransactionSplit>,
+    trades: Vec<InvestmentTrade>,
+    securities: Vec<Security>,
+    form_splits: Vec<SplitLine>,
+    form_error: Option<String>,
+    payees: Vec<Payee>,
+    form_payee: String,
+    history_transaction: Option<i32>,
+    history: Vec<AuditEntry>,
+    search: String,
+    search_results: Option<Vec<TransactionMatch>>, // `None` while not searching
+}
+
+/// A split line being edited in the form.
+#[derive(Debug, Clone, Default)]
+struct SplitLine {
+    category: Option<usize>,
+    amount: String,
+    note: String,
+}
+
+/// What the transactions page shows, read by the data service.
+#[derive(Debug, Clone)]
+pub struct TransactionsData {
+    reporting_currency_id: i32,
+    currency_symbol: String,
+    currencies: Vec<Currency>,
+    transactions: Vec<MoneyTransaction>,
+    categories: Vec<Category>,
+    accounts: Vec<Account>,
+    tags: Vec<Tag>,
+    transaction_tags: Vec<TransactionTag>,
+    tag_totals: Option<(Money, Money)>,
+    splits: Vec<TransactionSplit>,
+    trades: Vec<InvestmentTrade>,
+    securities: Vec<Security>,
+    payees: Vec<Payee>,
+    history: Option<Vec<AuditEntry>>,
+}
+
+impl TransactionsData {
+    fn load(
+        store: &mut Store,
+        filter_tag_id: Option<i32>,
+        history_transaction: Option<i32>,
+    ) -> Result<Self, DataStoreError> {
+        let reporting_currency_id = Config::load().1.currency_id;
+        Ok(Self {
+            reporting_currency_id,
+            currency_symbol: store
+                .get_currency_by_id(reporting_currency_id)
+                .map(|c| c.symbol)
+                .unwrap_or_default(),
+            currencies: store.get_currencies()?,
+            transactions: store.get_money_transactions()?,
+            categories: categories_by_parent(store.get_categories()?),
+            accounts: store.get_accounts()?,
+            tags: store.get_tags()?,
+            transaction_tags: store.get_transaction_tags()?,
+            tag_totals: tag_totals(store, filter_tag_id, reporting_currency_id)?,
+            splits: store.get_transaction_splits()?,
+            trades: store.get_investment_trades()?,
+            securities: store.get_securities()?,
+            payees: store.get_payees()?,
+            history: history_transaction
+                .map(|transaction_id| store.get_transaction_history(transaction_id))
+                .transpose()?,
+        })
+    }
+}
+
+/// Income and expenses of the transactions with a tag, in the reporting currency.
+fn tag_totals(
+    store: &mut Store,
+    tag_id: Option<i32>,
+    reporting_currency_id: i32,
+) -> Result<Option<(Money, Money)>, DataStoreError> {
+    tag_id
+        .map(|tag_id| store.calculate_totals_by_tag(tag_id, reporting_currency_id))
+        .transpose()
 }
 
 impl Default for Transactions {
     fn default() -> Self {
-        let mut store = STORE.lock().unwrap();
-        let config = Config::load();
-        let transactions = store.get_money_transactions().unwrap_or_else(|_| vec![]);
-        let currency_symbol = store.get_currency_symbol_by_id(config.1.currency_id);
+        Self::new(STORE.clone())
+    }
+}
+
+const EXPENSE: u16 = 1;
+const INCOME: u16 = 2;
+const TRANSFER: u16 = 3;
+
+fn transaction_type_model(selected: u16) -> widget::segmented_button::SingleSelectModel {
+    let mut model = widget::segmented_button::Model::builder()
+        .insert(|b| b.text(fl!("expense")).data(EXPENSE))
+        .insert(|b| b.text(fl!("income")).data(INCOME))
+        .insert(|b| b.text(fl!("transfer")).data(TRANSFER))
+        .build();
+    let entity = model
+        .iter()
+        .find(|entity| model.data::<u16>(*entity) == Some(&selected));
+    if let Some(entity) = entity {
+        model.activate(entity);
+    }
+    model
+}
+
+pub fn cleared_status_label(status: ClearedStatus) -> String {
+    match status {
+        ClearedStatus::Uncleared => fl!("uncleared"),
+        ClearedStatus::Cleared => fl!("cleared"),
+        ClearedStatus::Reconciled => fl!("reconciled"),
+    }
+}
+
+fn audit_field_label(field: &str) -> String {
+    match field {
+        "bank_account" => fl!("bank-account"),
+        "transaction_category" => fl!("category"),
+        "description" => fl!("note"),
+        "amount" => fl!("amount"),
+        "transaction_date" => fl!("date"),
+        "is_expense" => fl!("transaction-type"),
+        "transfer_peer" => fl!("transfer"),
+        "payee_id" => fl!("payee"),
+        "cleared_status" => fl!("status"),
+        _ => field.to_string(),
+    }
+}
+
+/// A labelled field of a search result, its matched words in the accent color.
+fn highlighted<'a>(label: String, text: &str) -> Element<'a, TransactionMessage> {
+    highlighted_parts(text)
+        .into_iter()
+        .fold(
+            widget::row().push(widget::text::text(format!("{}: ", label))),
+            |row, (part, matched)| {
+                let part = widget::text::text(part.to_string());
+                row.push(if matched {
+                    part.class(cosmic::theme::Text::Accent)
+                } else {
+                    part
+                })
+            },
+        )
+        .into()
+}
+
+/// The tag filter lists every tag after the "all tags" entry.
+fn tag_filter_options(tags: &[Tag]) -> Vec<String> {
+    let mut options = vec![fl!("all-tags")];
+    options.extend(tags.iter().map(|tag| tag.name.clone()));
+    options
+}
+
+impl Transactions {
+    /// The page starts out empty until `TransactionMessage::UpdatePage` loads it.
+    pub fn new(store: SharedStore) -> Self {
         Self {
-            currency_symbol: currency_symbol.unwrap_or_else(|_| "USD".to_string()),
+            store,
+            loading: true,
+            reporting_currency_id: Config::load().1.currency_id,
+            currency_symbol: "".to_string(),
+            currencies: vec![],
             add_transaction_view: false,
-            categories: store.get_categories().unwrap_or_else(|_| vec![]),
-            accounts: store.get_accounts().unwrap_or_else(|_| vec![]),
-            form_transaction_type: widget::segmented_button::Model::builder()
-                .insert(|b| b.text(fl!("expense")).data(1u16).activate())
-                .insert(|b| b.text(fl!("income")).data(2u16))
-                .build(),
+            category_labels: vec![],
+            categories: vec![],
+            account_options: vec![],
+            accounts: vec![],
+            form_transaction_type: transaction_type_model(EXPENSE),
             form_note: "".to_string(),
             form_selectected_category: Some(0),
             form_selected_bank_account: Some(0),
-            transactions,
+            form_selected_destination_account: Some(0),
+            transactions: vec![],
             form_amount: "".to_string(),
             form_date: Utc::now().timestamp(),
-            new_transaction_amount: 0.,
+            editing_transaction: None,
+            deleting_transaction: None,
+            tag_filter_options: tag_filter_options(&[]),
+            tags: vec![],
+            transaction_tags: vec![],
+            selected_tag_filter: Some(0),
+            tag_totals: None,
+            form_tags: "".to_string(),
+            splits: vec![],
+            trades: vec![],
+            securities: vec![],
+            form_splits: vec![],
+            form_error: None,
+            payees: vec![],
+            form_payee: "".to_string(),
+            history_transaction: None,
+            history: vec![],
+            search: "".to_string(),
+            search_results: None,
         }
     }
-}
 
-impl Transactions {
     pub fn view<'a>(&'a self) -> Element<'a, TransactionMessage> {
         let container = widget::container(if self.add_transaction_view {
             self.new_transaction_view()
@@ -80,7 +300,7 @@ impl Transactions {
         widget::scrollable(container).into()
     }
 
-    pub fn transactions_view<'a>(&self) -> Element<'a, TransactionMessage> {
+    pub fn transactions_view<'a>(&'a self) -> Element<'a, TransactionMessage> {
         let mut element = widget::column()
             .push(
                 widget::row()
@@ -100,9 +320,33 @@ impl Transactions {
                             .align_x(Alignment::End),
                     ),
             )
+            .push(Space::with_height(10))
+            .push(
+                widget::search_input(fl!("search-transactions"), &self.search)
+                    .on_input(TransactionMessage::SearchChanged)
+                    .on_clear(TransactionMessage::SearchChanged("".to_string())),
+            )
             .width(Length::Fill)
             .align_x(Alignment::Start);
 
+        if !self.tags.is_empty() {
+            element = element
+                .push(Space::with_height(10))
+                .push(
+                    widget::row()
+                        .push(widget::text::text(fl!("tag")))
+                        .push(Space::with_width(10))
+                        .push(widget::dropdown(
+                            &self.tag_filter_options,
+                            self.selected_tag_filter,
+                            TransactionMessage::TagFilterChanged,
+                        ))
+                        .align_y(Alignment::Center),
+                )
+                .push_maybe(self.tag_totals_view())
+                .push(Space::with_height(10));
+        }
+
         let month_names = vec![
             fl!("month-1"),  // January
             fl!("month-2"),  // February
@@ -118,12 +362,41 @@ impl Transactions {
             fl!("month-12"), // December
         ];
 
-        if !self.transactions.is_empty() {
+        // search results are listed best match first, so without date headers
+        let listed = match &self.search_results {
+            Some(results) => {
+                let transactions = self
+                    .transactions
+                    .iter()
+                    .map(|t| (t.id, t))
+                    .collect::<HashMap<i32, &MoneyTransaction>>();
+                results
+                    .iter()
+                    .filter_map(|found| {
+                        transactions
+                            .get(&found.transaction_id)
+                            .map(|t| (*t, Some(found)))
+                    })
+                    .collect::<Vec<(&MoneyTransaction, Option<&TransactionMatch>)>>()
+            }
+            None => self.transactions.iter().map(|t| (t, None)).collect(),
+        };
+
+        if !listed.is_empty() {
             let mut last_date: NaiveDateTime = NaiveDate::from_ymd(1970, 1, 1).and_hms(0, 0, 0);
 
-            for t in &self.transactions {
+            for (t, found) in listed {
+                // a transfer is listed once, through its outgoing row
+                if t.is_transfer() && !t.is_expense {
+                    continue;
+                }
+                if let Some(tag_id) = self.filter_tag_id() {
+                    if !self.has_tag(t.id, tag_id) {
+                        continue;
+                    }
+                }
                 let mut date_row: Option<Element<'a, TransactionMessage>> = None;
-                if t.transaction_date.date().ne(&last_date.date()) {
+                if found.is_none() && t.transaction_date.date().ne(&last_date.date()) {
                     let month = t.transaction_date.month();
 
                     date_row = Some(
@@ -146,22 +419,41 @@ impl Transactions {
                                     widget::text::text(format!(
                                         "{}: {}{} {}",
                                         fl!("amount"),
-                                        if t.is_expense { "-" } else { "+" },
-                                        t.amount,
-                                        self.currency_symbol
+                                        if t.is_transfer() {
+                                            ""
+                                        } else if t.is_expense {
+                                            "-"
+                                        } else {
+                                            "+"
+                                        },
+                                        t.amount(self.account_scale(t.bank_account)),
+                                        self.account_currency(t.bank_account)
+                                            .map(|c| c.symbol.clone())
+                                            .unwrap_or_default()
                                     ))
                                     .width(Length::Fill),
                                 )
                                 .push(
-                                    widget::text::text(format!(
-                                        "{}: {}",
-                                        fl!("category"),
-                                        self.categories
-                                            .iter()
-                                            .find(|c| c.id == t.transaction_category)
-                                            .map(|c| c.name.clone())
-                                            .unwrap_or_else(|| fl!("not-found"))
-                                    ))
+                                    widget::text::text(if t.is_transfer() {
+                                        format!(
+                                            "{}: {} → {}",
+                                            fl!("transfer"),
+                                            self.account_name(t.bank_account),
+                                            self.transfer_destination(t)
+                                                .map(|peer| self.account_name(peer.bank_account))
+                                                .unwrap_or_else(|| fl!("not-found"))
+                                        )
+                                    } else if let Some(trade) = self.trade_of(t.id) {
+                                        self.trade_description(trade)
+                                    } else if self.has_splits(t.id) {
+                                        format!("{}: {}", fl!("category"), fl!("split"))
+                                    } else {
+                                        format!(
+                                            "{}: {}",
+                                            fl!("category"),
+                                            self.category_name(t.transaction_category)
+                                        )
+                                    })
                                     .width(Length::Fill),
                                 )
                                 .push(
@@ -175,15 +467,94 @@ impl Transactions {
                                     ))
                                     .width(Length::Fill),
                                 )
+                                .push(
+                                    widget::button::icon(widget::icon::from_name(
+                                        "document-open-recent-symbolic",
+                                    ))
+                                    .on_press(TransactionMessage::ShowHistory(t.id)),
+                                )
+                                .push(if t.is_reconciled() {
+                                    // reconciled rows are locked
+                                    Element::from(
+                                        widget::icon::from_name("changes-prevent-symbolic")
+                                            .size(16)
+                                            .icon(),
+                                    )
+                                } else {
+                                    // trades are booked from their investment account
+                                    widget::row()
+                                        .push_maybe(self.trade_of(t.id).is_none().then(|| {
+                                            widget::button::icon(widget::icon::from_name(
+                                                "edit-symbolic",
+                                            ))
+                                            .on_press(TransactionMessage::EditTransaction(t.id))
+                                        }))
+                                        .push(
+                                            widget::button::icon(widget::icon::from_name(
+                                                "edit-delete-symbolic",
+                                            ))
+                                            .on_press(TransactionMessage::DeleteTransaction(t.id)),
+                                        )
+                                        .into()
+                                })
                                 .width(Length::Fill),
                         )
                         .push(Space::with_height(5))
                         .push_maybe(if !t.description.is_empty() {
-                            Some(widget::row().push(widget::text::text(format!(
+                            Some(match found {
+                                Some(found) => highlighted(fl!("note"), &found.description),
+                                None => widget::row()
+                                    .push(widget::text::text(format!(
+                                        "{}: {}",
+                                        fl!("note"),
+                                        t.description
+                                    )))
+                                    .into(),
+                            })
+                        } else {
+                            None
+                        })
+                        .push_maybe(self.payee_name(t.payee_id).map(|payee_name| {
+                            match found {
+                                Some(found) => highlighted(fl!("payee"), &found.payee),
+                                None => widget::row()
+                                    .push(widget::text::text(format!(
+                                        "{}: {}",
+                                        fl!("payee"),
+                                        payee_name
+                                    )))
+                                    .into(),
+                            }
+                        }))
+                        // the category of a split or a trade is not shown above
+                        .push_maybe(
+                            found
+                                .filter(|found| found.category.contains(MATCH_START))
+                                .map(|found| highlighted(fl!("category"), &found.category)),
+                        )
+                        .push_maybe(match t.cleared_status {
+                            ClearedStatus::Uncleared => None,
+                            status => Some(widget::row().push(widget::text::text(format!(
                                 "{}: {}",
-                                fl!("note"),
-                                t.description
-                            ))))
+                                fl!("status"),
+                                cleared_status_label(status)
+                            )))),
+                        })
+                        .push(self.split_lines_view(t))
+                        .push_maybe({
+                            let tag_names = self.tag_names(t.id);
+                            if !tag_names.is_empty() {
+                                Some(widget::row().push(widget::text::text(format!(
+                                    "{}: {}",
+                                    fl!("tags"),
+                                    tag_names.join(", ")
+                                ))))
+                            } else {
+                                None
+                            }
+                        })
+                        .push_maybe(if self.deleting_transaction == Some(t.id) {
+                            Some(self.delete_confirmation_view())
                         } else {
                             None
                         })
@@ -195,6 +566,10 @@ impl Transactions {
 
                 element = element.push(container).push(Space::with_height(10))
             }
+        } else if self.loading {
+            element = element.push(widget::text::text(fl!("loading")))
+        } else if self.search_results.is_some() {
+            element = element.push(widget::text::text(fl!("no-search-results")))
         } else {
             element = element.push(widget::text::text(fl!("no-elements")))
         }
@@ -202,10 +577,436 @@ impl Transactions {
         element.into()
     }
 
+    /// The audit log of the transaction picked with its history button,
+    /// newest change first, for the context drawer.
+    pub fn history_view<'a>(&'a self) -> Element<'a, TransactionMessage> {
+        if self.history.is_empty() {
+            return widget::text::text(fl!("no-history")).into();
+        }
+
+        let mut element = widget::column().spacing(10).width(Length::Fill);
+        // the fields changed by one action share its time
+        for change in self
+            .history
+            .chunk_by(|a, b| a.changed_at == b.changed_at && a.action == b.action)
+        {
+            let first = &change[0];
+            let mut card = widget::column()
+                .push(widget::text::title4(match first.action {
+                    AuditAction::Create => fl!("created"),
+                    AuditAction::Update => fl!("updated"),
+                    AuditAction::Delete => fl!("deleted"),
+                }))
+                .push(widget::text::caption(format!(
+                    "{} · {}",
+                    Local
+                        .from_utc_datetime(&first.changed_at)
+                        .format("%d-%m-%Y %H:%M"),
+                    first.changed_by
+                )));
+            for entry in change {
+                let Some(field) = &entry.field_name else {
+                    continue;
+                };
+                let old = self.audit_value(field, entry.old_value.as_deref());
+                card = card.push(widget::text::text(match &entry.new_value {
+                    Some(new) => format!(
+                        "{}: {} → {}",
+                        audit_field_label(field),
+                        old,
+                        self.audit_value(field, Some(new))
+                    ),
+                    None => format!("{}: {}", audit_field_label(field), old),
+                }));
+            }
+            element = element.push(
+                widget::container(card)
+                    .padding(10)
+                    .width(Length::Fill)
+                    .class(cosmic::theme::Container::Card),
+            );
+        }
+
+        element.into()
+    }
+
+    /// An audited value as the user knows it: names instead of ids,
+    /// amounts in the account currency and dates in local time.
+    fn audit_value(&self, field: &str, value: Option<&str>) -> String {
+        let value = value.unwrap_or_default();
+        let id = value.parse::<i32>().ok();
+        match field {
+            _ if value.is_empty() => "—".to_string(),
+            "bank_account" => id.map(|id| self.account_name(id)).unwrap_or_default(),
+            "transaction_category" => self.category_name(id),
+            "payee_id" => self.payee_name(id).unwrap_or_else(|| fl!("not-found")),
+            "amount" => {
+                let scale = self
+                    .history_transaction
+                    .and_then(|id| self.transactions.iter().find(|t| t.id == id))
+                    .map(|t| self.account_scale(t.bank_account))
+                    .unwrap_or(Money::DEFAULT_SCALE);
+                value
+                    .parse::<i64>()
+                    .map(|minor_units| Money::new(minor_units, scale).to_string())
+                    .unwrap_or_else(|_| value.to_string())
+            }
+            "transaction_date" => NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                .map(|date| {
+                    Local
+                        .from_utc_datetime(&date)
+                        .format("%d-%m-%Y %H:%M")
+                        .to_string()
+                })
+                .unwrap_or_else(|_| value.to_string()),
+            "is_expense" if value == "true" => fl!("expense"),
+            "is_expense" => fl!("income"),
+            "cleared_status" => ClearedStatus::from_str(value)
+                .map(cleared_status_label)
+                .unwrap_or_else(|| value.to_string()),
+            _ => value.to_string(),
+        }
+    }
+
+    fn category_name(&self, category_id: Option<i32>) -> String {
+        self.categories
+            .iter()
+            .find(|c| Some(c.id) == category_id)
+            .map(|c| c.name.clone())
+            .unwrap_or_else(|| fl!("not-found"))
+    }
+
+    fn trade_of(&self, transaction_id: i32) -> Option<&InvestmentTrade> {
+        self.trades
+            .iter()
+            .find(|trade| trade.cash_transaction == transaction_id)
+    }
+
+    fn trade_description(&self, trade: &InvestmentTrade) -> String {
+        let symbol = self
+            .securities
+            .iter()
+            .find(|s| s.id == trade.security_id)
+            .map(|s| s.symbol.clone())
+            .unwrap_or_else(|| fl!("not-found"));
+        match trade.trade_kind {
+            TradeKind::Dividend => format!("{}: {}", trade_kind_label(trade.trade_kind), symbol),
+            kind => format!(
+                "{}: {} {} @ {}",
+                trade_kind_label(kind),
+                trade.quantity,
+                symbol,
+                trade.price
+            ),
+        }
+    }
+
+    fn has_splits(&self, transaction_id: i32) -> bool {
+        self.splits
+            .iter()
+            .any(|split| split.parent_transaction == transaction_id)
+    }
+
+    fn split_lines_view<'a>(&self, t: &MoneyTransaction) -> Element<'a, TransactionMessage> {
+        let scale = self.account_scale(t.bank_account);
+        let mut lines = widget::column();
+        for split in self
+            .splits
+            .iter()
+            .filter(|split| split.parent_transaction == t.id)
+        {
+            let mut line = format!(
+                "{}: {}",
+                self.category_name(Some(split.split_category)),
+                split.amount(scale)
+            );
+            if !split.note.is_empty() {
+                line = format!("{} ({})", line, split.note);
+            }
+            lines = lines.push(widget::text::text(line));
+        }
+        lines.into()
+    }
+
+    fn split_lines_editor<'a>(&'a self) -> Element<'a, TransactionMessage> {
+        let mut element = widget::column()
+            .push(Space::with_height(10))
+            .push(widget::text::title4(fl!("split")));
+
+        for (index, line) in self.form_splits.iter().enumerate() {
+            element = element.push(
+                widget::row()
+                    .push(widget::dropdown(
+                        &self.category_labels,
+                        line.category,
+                        move |selected| TransactionMessage::SplitCategoryChanged(index, selected),
+                    ))
+                    .push(Space::with_width(10))
+                    .push(
+                        text_input(fl!("amount"), &line.amount)
+                            .width(Length::Fill)
+                            .on_input(move |value| {
+                                TransactionMessage::SplitAmountChanged(index, value)
+                            }),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        text_input(fl!("note"), &line.note)
+                            .width(Length::Fill)
+                            .on_input(move |value| {
+                                TransactionMessage::SplitNoteChanged(index, value)
+                            }),
+                    )
+                    .push(
+                        widget::button::icon(widget::icon::from_name("edit-delete-symbolic"))
+                            .on_press(TransactionMessage::RemoveSplitLine(index)),
+                    )
+                    .align_y(Alignment::Center),
+            );
+        }
+
+        let mut actions = widget::row()
+            .push(
+                widget::button::text(fl!("add-split-line"))
+                    .on_press(TransactionMessage::AddSplitLine),
+            )
+            .align_y(Alignment::Center);
+        if !self.form_splits.is_empty() {
+            let scale = self.selected_account_scale();
+            let amount = Money::parse(&self.form_amount, scale).unwrap_or(Money::zero(scale));
+            let unassigned = self
+                .form_splits
+                .iter()
+                .filter_map(|line| Money::parse(&line.amount, scale))
+                .fold(amount, |left, split| left - split);
+            actions = actions
+                .push(Space::with_width(10))
+                .push(widget::text::text(format!(
+                    "{}: {}",
+                    fl!("unassigned"),
+                    unassigned
+                )));
+        }
+
+        element.push(Space::with_height(5)).push(actions).into()
+    }
+
+    /// The split lines of the form, checked against the transaction amount.
+    fn form_split_lines(&self, amount: Money) -> Result<Vec<NewTransactionSplit>, String> {
+        let scale = self.selected_account_scale();
+        let mut splits = vec![];
+        for line in &self.form_splits {
+            let category = line
+                .category
+                .and_then(|index| self.categories.get(index))
+                .ok_or_else(|| fl!("split-missing-category"))?;
+            let split_amount =
+                Money::parse(&line.amount, scale).ok_or_else(|| fl!("split-mismatch"))?;
+            splits.push(NewTransactionSplit {
+                split_category: category.id,
+                amount: split_amount,
+                note: line.note.clone(),
+            });
+        }
+        let total = splits
+            .iter()
+            .fold(Money::zero(scale), |total, split| total + split.amount);
+        if !splits.is_empty() && total != amount {
+            return Err(fl!("split-mismatch"));
+        }
+        Ok(splits)
+    }
+
+    /// Runs the search typed in the search box. The results carry it, so
+    /// the ones of an older search can be told apart.
+    fn search(&self) -> Task<app::Message> {
+        let search = self.search.clone();
+        let query = search.clone();
+        data::request(
+            &self.store,
+            move |store| store.search_transactions(&query),
+            move |results| {
+                app::Message::Transactions(TransactionMessage::SearchResultsLoaded(search, results))
+            },
+        )
+    }
+
+    fn filter_tag_id(&self) -> Option<i32> {
+        // index 0 is "all tags"
+        self.selected_tag_filter
+            .filter(|index| *index > 0)
+            .and_then(|index| self.tags.get(index - 1))
+            .map(|tag| tag.id)
+    }
+
+    fn has_tag(&self, transaction_id: i32, tag_id: i32) -> bool {
+        self.transaction_tags
+            .iter()
+            .any(|link| link.transaction_id == transaction_id && link.tag_id == tag_id)
+    }
+
+    fn tag_names(&self, transaction_id: i32) -> Vec<String> {
+        self.tags
+            .iter()
+            .filter(|tag| self.has_tag(transaction_id, tag.id))
+            .map(|tag| tag.name.clone())
+            .collect()
+    }
+
+    fn tag_totals_view<'a>(&self) -> Option<Element<'a, TransactionMessage>> {
+        self.filter_tag_id()?;
+        let (income, expenses) = self.tag_totals?;
+        Some(
+            widget::text::text(format!(
+                "{}: +{} {} · {}: -{} {}",
+                fl!("income"),
+                income,
+                self.currency_symbol,
+                fl!("expense"),
+                expenses,
+                self.currency_symbol
+            ))
+            .into(),
+        )
+    }
+
+    /// Tags typed in the form, split on commas.
+    fn form_tag_names(&self) -> Vec<String> {
+        self.form_tags
+            .split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    }
+
+    fn payee_name(&self, payee_id: Option<i32>) -> Option<String> {
+        self.payees
+            .iter()
+            .find(|p| Some(p.id) == payee_id)
+            .map(|p| p.name.clone())
+    }
+
+    /// Existing payees that complete the name being typed.
+    fn payee_suggestions(&self) -> Vec<&Payee> {
+        let partial = self.form_payee.trim().to_lowercase();
+        if partial.is_empty() {
+            return vec![];
+        }
+        self.payees
+            .iter()
+            .filter(|p| p.name.to_lowercase().starts_with(&partial))
+            .filter(|p| p.name != self.form_payee.trim())
+            .take(5)
+            .collect()
+    }
+
+    /// Existing tags that complete the tag being typed.
+    fn tag_suggestions(&self) -> Vec<&Tag> {
+        let partial = self
+            .form_tags
+            .rsplit(',')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        if partial.is_empty() {
+            return vec![];
+        }
+        let typed = self.form_tag_names();
+        self.tags
+            .iter()
+            .filter(|tag| tag.name.to_lowercase().starts_with(&partial))
+            .filter(|tag| !typed.contains(&tag.name))
+            .take(5)
+            .collect()
+    }
+
+    fn account_name(&self, account_id: i32) -> String {
+        self.accounts
+            .iter()
+            .find(|a| a.id == account_id)
+            .map(|a| a.name.clone())
+            .unwrap_or_else(|| fl!("not-found"))
+    }
+
+    fn transfer_destination(&self, t: &MoneyTransaction) -> Option<&MoneyTransaction> {
+        self.transactions
+            .iter()
+            .find(|peer| Some(peer.id) == t.transfer_peer)
+    }
+
+    fn account_currency_id(&self, account_id: i32) -> i32 {
+        self.accounts
+            .iter()
+            .find(|a| a.id == account_id)
+            .and_then(|a| a.currency_id)
+            .unwrap_or(self.reporting_currency_id)
+    }
+
+    fn account_currency(&self, account_id: i32) -> Option<&Currency> {
+        let currency_id = self.account_currency_id(account_id);
+        self.currencies.iter().find(|c| c.id == currency_id)
+    }
+
+    fn account_scale(&self, account_id: i32) -> u32 {
+        self.account_currency(account_id)
+            .map(|c| c.scale as u32)
+            .unwrap_or(Money::DEFAULT_SCALE)
+    }
+
+    fn selected_account_scale(&self) -> u32 {
+        self.form_selected_bank_account
+            .and_then(|index| self.account_options.get(index))
+            .map(|a| self.account_scale(a.id))
+            .unwrap_or(Money::DEFAULT_SCALE)
+    }
+
+    fn form_account_id(&self, selected: Option<usize>) -> Option<i32> {
+        selected
+            .and_then(|index| self.account_options.get(index))
+            .map(|a| a.id)
+    }
+
+    fn form_transaction_kind(&self) -> u16 {
+        self.form_transaction_type
+            .data::<u16>(self.form_transaction_type.active())
+            .copied()
+            .unwrap_or(EXPENSE)
+    }
+
+    fn delete_confirmation_view<'a>(&self) -> Element<'a, TransactionMessage> {
+        widget::column()
+            .push(Space::with_height(10))
+            .push(widget::divider::horizontal::default())
+            .push(Space::with_height(10))
+            .push(widget::text::text(fl!("delete-transaction-confirm")))
+            .push(Space::with_height(10))
+            .push(
+                widget::row()
+                    .push(
+                        widget::button::text(fl!("delete"))
+                            .on_press(TransactionMessage::ConfirmDeleteTransaction)
+                            .class(widget::button::ButtonClass::Destructive),
+                    )
+                    .push(Space::with_width(10))
+                    .push(
+                        widget::button::text(fl!("cancel"))
+                            .on_press(TransactionMessage::CancelDeleteTransaction),
+                    ),
+            )
+            .into()
+    }
+
     pub fn new_transaction_view<'a>(&'a self) -> Element<'a, TransactionMessage> {
         let mut element = widget::column().width(Length::Fill);
 
-        element = element.push(widget::text::title1(fl!("add-transaction")));
+        element = element.push(widget::text::title1(
+            if self.editing_transaction.is_some() {
+                fl!("edit-transaction")
+            } else {
+                fl!("add-transaction")
+            },
+        ));
 
         element = element.push(Space::with_height(10));
 
@@ -234,46 +1035,132 @@ impl Transactions {
 
         element = element.push(Space::with_height(10));
 
-        element = element
-            .push(
-                widget::row()
+        if self.form_transaction_kind() != TRANSFER {
+            let mut suggestions = widget::row();
+            for payee in self.payee_suggestions() {
+                suggestions = suggestions
                     .push(
+                        widget::button::text(payee.name.clone())
+                            .on_press(TransactionMessage::FormPayeeSelected(payee.id)),
+                    )
+                    .push(Space::with_width(5));
+            }
+            element = element
+                .push(
+                    column()
+                        .push(widget::text::text(fl!("payee")))
+                        .push(
+                            text_input(fl!("payee"), &self.form_payee)
+                                .width(Length::Fill)
+                                .on_input(TransactionMessage::FormPayeeChanged),
+                        )
+                        .push(suggestions),
+                )
+                .push(Space::with_height(10));
+        }
+
+        let accounts_row = if self.form_transaction_kind() == TRANSFER {
+            widget::row()
+                .push(
+                    widget::column()
+                        .push(widget::text::text(fl!("from-account")))
+                        .push(Space::with_height(Length::from(5)))
+                        .push(widget::dropdown(
+                            &self.account_options,
+                            self.form_selected_bank_account,
+                            TransactionMessage::FormBankAccountChanged,
+                        )),
+                )
+                .push(Space::with_width(Length::from(20)))
+                .push(
+                    widget::column()
+                        .push(widget::text::text(fl!("to-account")))
+                        .push(Space::with_height(Length::from(5)))
+                        .push(widget::dropdown(
+                            &self.account_options,
+                            self.form_selected_destination_account,
+                            TransactionMessage::FormDestinationAccountChanged,
+                        )),
+                )
+        } else {
+            widget::row()
+                .push_maybe(if self.form_splits.is_empty() {
+                    Some(
                         widget::column()
                             .push(widget::text::text(fl!("category")))
                             .push(Space::with_height(Length::from(5)))
                             .push(widget::dropdown(
-                                &self.categories,
+                                &self.category_labels,
                                 self.form_selectected_category,
                                 TransactionMessage::FormCategoryChanged,
                             )),
                     )
-                    .push(Space::with_width(Length::from(20)))
+                } else {
+                    None
+                })
+                .push(Space::with_width(Length::from(20)))
+                .push(
+                    widget::column()
+                        .push(widget::text::text(fl!("bank-account")))
+                        .push(widget::dropdown(
+                            &self.account_options,
+                            self.form_selected_bank_account,
+                            TransactionMessage::FormBankAccountChanged,
+                        )),
+                )
+        };
+
+        element = element.push(accounts_row).push(
+            column().push(widget::text::text(fl!("note"))).push(
+                text_input(fl!("note"), &self.form_note)
+                    .width(Length::Fill)
+                    .on_input(TransactionMessage::FormNoteChanged),
+            ),
+        );
+
+        if self.form_transaction_kind() != TRANSFER {
+            let mut suggestions = widget::row();
+            for tag in self.tag_suggestions() {
+                suggestions = suggestions
+                    .push(widget::button::text(tag.name.clone()).on_press(
+                        TransactionMessage::FormTagSuggestionSelected(tag.name.clone()),
+                    ))
+                    .push(Space::with_width(5));
+            }
+            element = element.push(
+                column()
+                    .push(widget::text::text(fl!("tags")))
                     .push(
-                        widget::column()
-                            .push(widget::text::text(fl!("bank-account")))
-                            .push(widget::dropdown(
-                                &self.accounts,
-                                self.form_selected_bank_account,
-                                TransactionMessage::FormBankAccountChanged,
-                            )),
-                    ),
-            )
-            .push(
-                column().push(widget::text::text(fl!("note"))).push(
-                    text_input(fl!("note"), &self.form_note)
-                        .width(Length::Fill)
-                        .on_input(TransactionMessage::FormNoteChanged),
-                ),
+                        text_input(fl!("tags-placeholder"), &self.form_tags)
+                            .width(Length::Fill)
+                            .on_input(TransactionMessage::FormTagsChanged),
+                    )
+                    .push(suggestions),
             );
+            element = element.push(self.split_lines_editor());
+        }
+
+        if let Some(form_error) = &self.form_error {
+            element = element
+                .push(Space::with_height(10))
+                .push(widget::text::text(form_error.clone()));
+        }
 
         element = element
             .push(widget::vertical_space().height(Length::from(10)))
             .push(
                 widget::row()
                     .push(
-                        widget::button::text(fl!("add-transaction"))
-                            .on_press(TransactionMessage::SubmitTransaction)
-                            .class(widget::button::ButtonClass::Suggested),
+                        widget::button::text(if self.editing_transaction.is_some() {
+                            fl!("save")
+                        } else {
+                            fl!("add-transaction")
+                        })
+                        .on_press_maybe(
+                            (!self.account_options.is_empty())
+                                .then_some(TransactionMessage::SubmitTransaction),
+                        )
+                        .class(widget::button::ButtonClass::Suggested),
                     )
                     .push(widget::horizontal_space().width(Length::from(10)))
                     .push(
@@ -291,15 +1178,63 @@ impl Transactions {
         match message {
             TransactionMessage::UpdatePage => {
                 log::info!("updating page");
-                let mut store = STORE.lock().unwrap();
-                let config = Config::load();
-                let currency_symbol = store.get_currency_symbol_by_id(config.1.currency_id);
-                self.transactions = store.get_money_transactions().unwrap_or_else(|_| vec![]);
-                self.categories = store.get_categories().unwrap_or_else(|_| vec![]);
-                self.accounts = store.get_accounts().unwrap_or_else(|_| vec![]);
-                self.currency_symbol = currency_symbol.unwrap_or_else(|_| "USD".to_string());
+                self.loading = true;
+                let filter_tag_id = self.filter_tag_id();
+                let history_transaction = self.history_transaction;
+                commands.push(data::request(
+                    &self.store,
+                    move |store| TransactionsData::load(store, filter_tag_id, history_transaction),
+                    |data| app::Message::Transactions(TransactionMessage::Loaded(data)),
+                ));
+                // the results follow the changes too
+                if self.search_results.is_some() {
+                    commands.push(self.search());
+                }
+            }
+            TransactionMessage::Loaded(Err(e)) => {
+                self.loading = false;
+                commands.push(app::error_toast(e));
+            }
+            TransactionMessage::Loaded(Ok(data)) => {
+                self.loading = false;
+                let filter_tag_id = self.filter_tag_id();
+                self.reporting_currency_id = data.reporting_currency_id;
+                self.currency_symbol = data.currency_symbol;
+                self.currencies = data.currencies;
+                self.transactions = data.transactions;
+                self.categories = data.categories;
+                self.category_labels = category_labels(&self.categories);
+                self.account_options = open_accounts(&data.accounts, &[]);
+                self.accounts = data.accounts;
+                self.tags = data.tags;
+                self.transaction_tags = data.transaction_tags;
+                self.tag_totals = data.tag_totals;
+                self.splits = data.splits;
+                self.trades = data.trades;
+                self.securities = data.securities;
+                self.payees = data.payees;
+                if let Some(history) = data.history {
+                    self.history = history;
+                }
+                self.tag_filter_options = tag_filter_options(&self.tags);
+                self.selected_tag_filter = Some(
+                    filter_tag_id
+                        .and_then(|tag_id| self.tags.iter().position(|tag| tag.id == tag_id))
+                        .map(|index| index + 1)
+                        .unwrap_or(0),
+                );
             }
             TransactionMessage::AddTransaction => {
+                self.editing_transaction = None;
+                self.form_transaction_type = transaction_type_model(EXPENSE);
+                self.form_note = "".to_string();
+                self.form_amount = "".to_string();
+                self.form_date = Utc::now().timestamp();
+                self.form_tags = "".to_string();
+                self.form_splits = vec![];
+                self.form_error = None;
+                self.form_payee = "".to_string();
+                self.account_options = open_accounts(&self.accounts, &[]);
                 self.add_transaction_view = true;
             }
             TransactionMessage::FormCategoryChanged(selected) => {
@@ -311,53 +1246,341 @@ impl Transactions {
             TransactionMessage::FormBankAccountChanged(selected) => {
                 self.form_selected_bank_account = Some(selected);
             }
+            TransactionMessage::FormDestinationAccountChanged(selected) => {
+                self.form_selected_destination_account = Some(selected);
+            }
             TransactionMessage::FormNoteChanged(note) => {
                 self.form_note = note;
             }
-            TransactionMessage::FormAmountChanged(new_amount) => match new_amount.parse::<f32>() {
-                Ok(parsed_amount) => {
-                    self.new_transaction_amount = parsed_amount;
+            TransactionMessage::FormAmountChanged(new_amount) => {
+                let scale = self.selected_account_scale();
+                // an empty field is allowed while editing, submitting checks it;
+                // anything else that is not an amount is not typed in
+                if new_amount.is_empty() || Money::parse(&new_amount, scale).is_some() {
                     self.form_amount = new_amount;
                 }
-                Err(_) => {
-                    eprintln!("Failed to parse the amount: {}", new_amount);
-                }
-            },
+            }
             TransactionMessage::SubmitTransaction => {
-                let mut is_expense: bool = true;
-                if let Some(id) = self
-                    .form_transaction_type
-                    .data::<u16>(self.form_transaction_type.active())
-                {
-                    if id == &2 {
-                        is_expense = false;
-                    }
+                let kind = self.form_transaction_kind();
+                // every account can be archived, leaving nothing to choose from
+                let (Some(bank_account), Some(to_account)) = (
+                    self.form_account_id(self.form_selected_bank_account),
+                    self.form_account_id(self.form_selected_destination_account),
+                ) else {
+                    return app::error_toast(fl!("missing-account-or-category"));
+                };
+                if kind == TRANSFER && to_account == bank_account {
+                    return app::error_toast(fl!("transfer-same-account"));
                 }
-                let mut store = STORE.lock().unwrap();
-                let new_transaction = NewMoneyTransaction {
-                    bank_account: self
-                        .accounts
-                        .get(self.form_selected_bank_account.unwrap())
-                        .unwrap()
-                        .id,
-                    transaction_category: self
-                        .categories
-                        .get(self.form_selectected_category.unwrap())
-                        .unwrap()
-                        .id,
-                    description: self.form_note.clone(),
-                    amount: self.new_transaction_amount,
-                    transaction_date: NaiveDateTime::from_timestamp(self.form_date, 0),
-                    is_expense,
+                let transaction_date = NaiveDateTime::from_timestamp(self.form_date, 0);
+                let scale = self.selected_account_scale();
+                let Some(amount) = Money::parse(&self.form_amount, scale) else {
+                    return app::error_toast(fl!("invalid-amount"));
+                };
+                let splits = if kind == TRANSFER {
+                    vec![]
+                } else {
+                    match self.form_split_lines(amount) {
+                        Ok(splits) => splits,
+                        Err(e) => {
+                            self.form_error = Some(e);
+                            return Task::none();
+                        }
+                    }
+                };
+                let editing_transaction = self.editing_transaction;
+                let description = self.form_note.clone();
+                let saved = |saved: Result<(), DataStoreError>| {
+                    app::Message::Transactions(TransactionMessage::Saved(
+                        saved.map_err(|e| e.to_string()),
+                    ))
                 };
-                let _ = store.create_money_transaction(&new_transaction);
+
+                // the form stays open until the transaction is saved
+                if kind == TRANSFER {
+                    let from_currency = self.account_currency_id(bank_account);
+                    let to_currency = self.account_currency_id(to_account);
+                    commands.push(data::request(
+                        &self.store,
+                        move |store| {
+                            // a transfer across currencies credits the converted amount
+                            let to_amount = store.convert_money(
+                                amount,
+                                from_currency,
+                                to_currency,
+                                &transaction_date.date(),
+                            )?;
+                            let transfer = NewTransfer {
+                                from_account: bank_account,
+                                to_account,
+                                description,
+                                amount,
+                                to_amount,
+                                transaction_date,
+                            };
+                            store.save_transaction(
+                                editing_transaction,
+                                &TransactionEntry::Transfer(transfer),
+                            )
+                        },
+                        saved,
+                    ));
+                } else {
+                    let is_expense = kind != INCOME;
+                    // a split transaction files under the category of its first line
+                    let category = self
+                        .form_selectected_category
+                        .and_then(|index| self.categories.get(index));
+                    let transaction_category = match (splits.first(), category) {
+                        (Some(split), _) => split.split_category,
+                        (None, Some(category)) => category.id,
+                        (None, None) => {
+                            return app::error_toast(fl!("missing-account-or-category"));
+                        }
+                    };
+                    let payee_name = self.form_payee.trim();
+                    let entry = TransactionEntry::Regular {
+                        transaction: NewMoneyTransaction {
+                            bank_account,
+                            transaction_category,
+                            description,
+                            amount,
+                            transaction_date,
+                            is_expense,
+                            payee_id: None,
+                        },
+                        payee: (!payee_name.is_empty()).then(|| payee_name.to_string()),
+                        tag_names: self.form_tag_names(),
+                        splits,
+                    };
+                    commands.push(data::request(
+                        &self.store,
+                        move |store| store.save_transaction(editing_transaction, &entry),
+                        saved,
+                    ));
+                }
+            }
+            TransactionMessage::Saved(saved) => {
+                match saved {
+                    Ok(()) => {
+                        self.add_transaction_view = false;
+                        self.editing_transaction = None;
+                        self.form_error = None;
+                    }
+                    Err(e) => self.form_error = Some(e),
+                }
                 commands.push(Task::perform(async {}, |_| {
                     app::Message::Transactions(TransactionMessage::UpdatePage)
                 }));
-                self.add_transaction_view = false;
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Categories(super::categories::CategoriesMessage::Update)
+                }));
             }
             TransactionMessage::CandellAddTransaction => {
                 self.add_transaction_view = false;
+                self.editing_transaction = None;
+                self.form_error = None;
+            }
+            TransactionMessage::EditTransaction(id) => {
+                if let Some(t) = self.transactions.iter().find(|t| t.id == id) {
+                    let amount = t.amount(self.account_scale(t.bank_account));
+                    let (kind, bank_account, destination_account) = if t.is_transfer() {
+                        (
+                            TRANSFER,
+                            t.bank_account,
+                            self.transfer_destination(t).map(|peer| peer.bank_account),
+                        )
+                    } else if t.is_expense {
+                        (EXPENSE, t.bank_account, None)
+                    } else {
+                        (INCOME, t.bank_account, None)
+                    };
+                    self.form_transaction_type = transaction_type_model(kind);
+                    // an archived account stays selectable for its own transactions
+                    self.account_options = open_accounts(
+                        &self.accounts,
+                        &[bank_account]
+                            .into_iter()
+                            .chain(destination_account)
+                            .collect::<Vec<i32>>(),
+                    );
+                    self.form_selectected_category = self
+                        .categories
+                        .iter()
+                        .position(|c| Some(c.id) == t.transaction_category)
+                        .or(Some(0));
+                    self.form_selected_bank_account = self
+                        .account_options
+                        .iter()
+                        .position(|a| a.id == bank_account)
+                        .or(Some(0));
+                    self.form_selected_destination_account = self
+                        .account_options
+                        .iter()
+                        .position(|a| Some(a.id) == destination_account)
+                        .or(Some(0));
+                    self.form_note = t.description.clone();
+                    self.form_amount = amount.to_string();
+                    self.form_date = t.transaction_date.and_utc().timestamp();
+                    self.form_tags = self.tag_names(t.id).join(", ");
+                    self.form_payee = self.payee_name(t.payee_id).unwrap_or_default();
+                    self.form_splits = self
+                        .splits
+                        .iter()
+                        .filter(|split| split.parent_transaction == t.id)
+                        .map(|split| SplitLine {
+                            category: self
+                                .categories
+                                .iter()
+                                .position(|c| c.id == split.split_category),
+                            amount: split.amount(self.account_scale(t.bank_account)).to_string(),
+                            note: split.note.clone(),
+                        })
+                        .collect();
+                    self.form_error = None;
+                    self.editing_transaction = Some(id);
+                    self.deleting_transaction = None;
+                    self.add_transaction_view = true;
+                }
+            }
+            TransactionMessage::DeleteTransaction(id) => {
+                self.deleting_transaction = Some(id);
+            }
+            TransactionMessage::ShowHistory(id) => {
+                commands.push(data::request(
+                    &self.store,
+                    move |store| store.get_transaction_history(id),
+                    move |history| {
+                        app::Message::Transactions(TransactionMessage::HistoryLoaded(id, history))
+                    },
+                ));
+            }
+            TransactionMessage::HistoryLoaded(_, Err(e)) => commands.push(app::error_toast(e)),
+            TransactionMessage::HistoryLoaded(id, Ok(history)) => {
+                self.history = history;
+                self.history_transaction = Some(id);
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::ShowContextPage(app::ContextPage::TransactionHistory)
+                }));
+            }
+            TransactionMessage::ConfirmDeleteTransaction => {
+                if let Some(id) = self.deleting_transaction.take() {
+                    commands.push(data::execute(&self.store, move |store| {
+                        store.delete_money_transaction(id)
+                    }));
+                    commands.push(Task::perform(async {}, |_| {
+                        app::Message::Transactions(TransactionMessage::UpdatePage)
+                    }));
+                }
+            }
+            TransactionMessage::CancelDeleteTransaction => {
+                self.deleting_transaction = None;
+            }
+            TransactionMessage::FormTagsChanged(tags) => {
+                self.form_tags = tags;
+            }
+            TransactionMessage::FormTagSuggestionSelected(tag_name) => {
+                // replace the partially typed tag with the suggestion
+                let mut tag_names = self.form_tag_names();
+                tag_names.pop();
+                tag_names.push(tag_name);
+                self.form_tags = format!("{}, ", tag_names.join(", "));
+            }
+            TransactionMessage::FormPayeeChanged(payee_name) => {
+                self.form_payee = payee_name;
+            }
+            TransactionMessage::FormPayeeSelected(payee_id) => {
+                if let Some(payee) = self.payees.iter().find(|p| p.id == payee_id) {
+                    self.form_payee = payee.name.clone();
+                    if let Some(index) = self
+                        .categories
+                        .iter()
+                        .position(|c| Some(c.id) == payee.default_category)
+                    {
+                        self.form_selectected_category = Some(index);
+                    }
+                    if let Some(index) = self
+                        .account_options
+                        .iter()
+                        .position(|a| Some(a.id) == payee.default_account)
+                    {
+                        self.form_selected_bank_account = Some(index);
+                    }
+                }
+            }
+            TransactionMessage::AddSplitLine => {
+                // the first line starts from the category picked so far
+                let category = if self.form_splits.is_empty() {
+                    self.form_selectected_category
+                } else {
+                    None
+                };
+                self.form_splits.push(SplitLine {
+                    category,
+                    ..Default::default()
+                });
+            }
+            TransactionMessage::RemoveSplitLine(index) => {
+                if index < self.form_splits.len() {
+                    self.form_splits.remove(index);
+                }
+            }
+            TransactionMessage::SplitCategoryChanged(index, selected) => {
+                if let Some(line) = self.form_splits.get_mut(index) {
+                    line.category = Some(selected);
+                }
+            }
+            TransactionMessage::SplitAmountChanged(index, value) => {
+                let scale = self.selected_account_scale();
+                if let Some(line) = self.form_splits.get_mut(index) {
+                    if value.is_empty() || Money::parse(&value, scale).is_some() {
+                        line.amount = value;
+                    }
+                }
+            }
+            TransactionMessage::SplitNoteChanged(index, value) => {
+                if let Some(line) = self.form_splits.get_mut(index) {
+                    line.note = value;
+                }
+            }
+            TransactionMessage::TagFilterChanged(selected) => {
+                self.selected_tag_filter = Some(selected);
+                self.tag_totals = None;
+                let tag_id = self.filter_tag_id();
+                let reporting_currency_id = self.reporting_currency_id;
+                commands.push(data::request(
+                    &self.store,
+                    move |store| tag_totals(store, tag_id, reporting_currency_id),
+                    move |totals| {
+                        app::Message::Transactions(TransactionMessage::TagTotalsLoaded(
+                            tag_id, totals,
+                        ))
+                    },
+                ));
+            }
+            TransactionMessage::TagTotalsLoaded(_, Err(e)) => commands.push(app::error_toast(e)),
+            TransactionMessage::TagTotalsLoaded(tag_id, Ok(totals)) => {
+                // totals of a tag the user already moved away from
+                if tag_id == self.filter_tag_id() {
+                    self.tag_totals = totals;
+                }
+            }
+            TransactionMessage::SearchChanged(value) => {
+                self.search = value;
+                if self.search.trim().is_empty() {
+                    self.search_results = None;
+                } else {
+                    commands.push(self.search());
+                }
+            }
+            TransactionMessage::SearchResultsLoaded(_, Err(e)) => {
+                commands.push(app::error_toast(e))
+            }
+            TransactionMessage::SearchResultsLoaded(search, Ok(results)) => {
+                // results of a search the user already typed past
+                if search == self.search {
+                    self.search_results = Some(results);
+                }
             }
             TransactionMessage::FormDateChanged(date) => {
                 log::info!("form date changed: {:?}", date);
@@ -367,3 +1590,132 @@ impl Transactions {
         Task::batch(commands)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        models::{AccountType, NewAccount, NewCategory},
+        store::Store,
+    };
+
+    fn transactions_page() -> (Transactions, SharedStore) {
+        let store = Arc::new(Mutex::new(Store::in_memory().unwrap()));
+        {
+            let mut store = store.lock().unwrap();
+            store
+                .create_account(&NewAccount {
+                    name: "Bank".to_string(),
+                    account_type: AccountType::Checking,
+                    initial_balance: Money::new(10000, 2),
+                    account_description: "".to_string(),
+                    currency_id: None,
+                })
+                .unwrap();
+            store
+                .create_category(&NewCategory {
+                    name: "Groceries".to_string(),
+                    is_income: false,
+                    category_description: "".to_string(),
+                    parent_category: None,
+                })
+                .unwrap();
+        }
+        let mut transactions = Transactions::new(store.clone());
+        reload(&mut transactions, &store);
+        (transactions, store)
+    }
+
+    /// Waits for the queued changes, then shows what the store holds.
+    fn reload(transactions: &mut Transactions, store: &SharedStore) {
+        data::wait_idle();
+        let loaded = TransactionsData::load(&mut *store.lock().unwrap(), None, None);
+        let _ = transactions.update(TransactionMessage::Loaded(loaded));
+    }
+
+    #[test]
+    fn submitted_expenses_are_stored() {
+        let (mut transactions, store) = transactions_page();
+        let _ = transactions.update(TransactionMessage::AddTransaction);
+        let _ = transactions.update(TransactionMessage::FormAmountChanged("12.50".to_string()));
+        let _ = transactions.update(TransactionMessage::FormNoteChanged("Market".to_string()));
+        let _ = transactions.update(TransactionMessage::SubmitTransaction);
+        reload(&mut transactions, &store);
+
+        assert_eq!(transactions.transactions.len(), 1);
+        let stored = store
+            .lock()
+            .unwrap()
+            .get_money_transactions()
+            .unwrap()
+            .remove(0);
+        let scale = transactions.selected_account_scale();
+        assert_eq!(stored.description, "Market");
+        assert!(stored.is_expense);
+        assert_eq!(
+            Some(stored.amount),
+            Money::parse("12.50", scale).map(|amount| amount.minor_units())
+        );
+    }
+
+    #[test]
+    fn results_of_an_older_search_are_dropped() {
+        let (mut transactions, store) = transactions_page();
+        let _ = transactions.update(TransactionMessage::AddTransaction);
+        let _ = transactions.update(TransactionMessage::FormAmountChanged("12.50".to_string()));
+        let _ = transactions.update(TransactionMessage::FormNoteChanged("Market".to_string()));
+        let _ = transactions.update(TransactionMessage::SubmitTransaction);
+        reload(&mut transactions, &store);
+
+        let _ = transactions.update(TransactionMessage::SearchChanged("mar".to_string()));
+        let _ = transactions.update(TransactionMessage::SearchChanged("rent".to_string()));
+        let found = store.lock().unwrap().search_transactions("mar");
+        let _ = transactions.update(TransactionMessage::SearchResultsLoaded(
+            "mar".to_string(),
+            found,
+        ));
+        assert!(transactions.search_results.is_none());
+
+        let found = store.lock().unwrap().search_transactions("rent");
+        let _ = transactions.update(TransactionMessage::SearchResultsLoaded(
+            "rent".to_string(),
+            found,
+        ));
+        assert_eq!(transactions.search_results.as_ref().map(Vec::len), Some(0));
+
+        let _ = transactions.update(TransactionMessage::SearchChanged("".to_string()));
+        assert!(transactions.search_results.is_none());
+    }
+
+    #[test]
+    fn unparsable_amounts_are_ignored() {
+        let (mut transactions, _) = transactions_page();
+        let _ = transactions.update(TransactionMessage::AddTransaction);
+        let _ = transactions.update(TransactionMessage::FormAmountChanged("12".to_string()));
+        let _ = transactions.update(TransactionMessage::FormAmountChanged("12x".to_string()));
+        assert_eq!(transactions.form_amount, "12");
+    }
+
+    #[test]
+    fn submitting_without_accounts_stores_nothing() {
+        let store = Arc::new(Mutex::new(Store::in_memory().unwrap()));
+        let mut transactions = Transactions::new(store.clone());
+        reload(&mut transactions, &store);
+        let _ = transactions.update(TransactionMessage::AddTransaction);
+        let _ = transactions.update(TransactionMessage::FormAmountChanged("12".to_string()));
+        let _ = transactions.update(TransactionMessage::SubmitTransaction);
+        reload(&mut transactions, &store);
+        assert!(transactions.transactions.is_empty());
+    }
+
+    #[test]
+    fn the_amount_can_be_cleared() {
+        let (mut transactions, _) = transactions_page();
+        let _ = transactions.update(TransactionMessage::AddTransaction);
+        let _ = transactions.update(TransactionMessage::FormAmountChanged("12".to_string()));
+        let _ = transactions.update(TransactionMessage::FormAmountChanged(String::new()));
+        assert_eq!(transactions.form_amount, "");
+    }
+}
@@ -0,0 +1,78 @@
+use cosmic::{
+    iced::{Alignment, Length},
+    widget::{self, Space},
+    Element, Task,
+};
+
+use crate::{app, data, fl, ledger};
+
+#[derive(Debug, Clone)]
+pub enum UnlockMessage {
+    PassphraseChanged(String),
+    Submit,
+    Opened(Result<(), String>),
+}
+
+/// Asks for the passphrase of an encrypted ledger before any page reads it.
+#[derive(Default)]
+pub struct Unlock {
+    passphrase: String,
+    error: Option<String>,
+}
+
+impl Unlock {
+    pub fn view<'a>(&'a self) -> Element<'a, UnlockMessage> {
+        let content = widget::column()
+            .push(widget::text::title3(fl!("ledger-locked")))
+            .push(widget::text::text(fl!(
+                "ledger-locked-message",
+                name = ledger::current_ledger()
+            )))
+            .push(Space::with_height(10))
+            .push(
+                widget::secure_input(fl!("passphrase"), &self.passphrase, None, true)
+                    .on_input(UnlockMessage::PassphraseChanged)
+                    .on_submit(UnlockMessage::Submit),
+            )
+            .push_maybe(self.error.as_ref().map(|e| widget::text::text(e.clone())))
+            .push(Space::with_height(10))
+            .push(
+                widget::button::text(fl!("unlock"))
+                    .on_press_maybe((!self.passphrase.is_empty()).then_some(UnlockMessage::Submit))
+                    .class(widget::button::ButtonClass::Suggested),
+            )
+            .spacing(5)
+            .max_width(400)
+            .align_x(Alignment::Center);
+
+        widget::container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .align_x(Alignment::Center)
+            .align_y(Alignment::Center)
+            .into()
+    }
+
+    pub fn update(&mut self, message: UnlockMessage) -> Task<crate::app::Message> {
+        match message {
+            UnlockMessage::PassphraseChanged(value) => {
+                self.passphrase = value;
+                self.error = None;
+            }
+            UnlockMessage::Submit => {
+                let passphrase = self.passphrase.clone();
+                return Task::perform(data::run(move || ledger::unlock(&passphrase)), |result| {
+                    app::Message::Unlock(UnlockMessage::Opened(result.map_err(|e| e.to_string())))
+                });
+            }
+            UnlockMessage::Opened(result) => match result {
+                Ok(()) => {
+                    self.passphrase = "".to_string();
+                    return Task::perform(async {}, |_| app::Message::Unlocked);
+                }
+                Err(e) => self.error = Some(e),
+            },
+        }
+        Task::none()
+    }
+}
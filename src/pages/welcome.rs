@@ -1,6 +1,15 @@
 use crate::app;
-use crate::models::{NewAccount, NewCategory};
-use crate::{config::Config, fl, models::Currency, STORE};
+use crate::models::{AccountType, Money, NewAccount, NewCategory};
+use crate::{
+    config::Config,
+    data,
+    errors::DataStoreError,
+    fl,
+    models::Currency,
+    repository::Repository,
+    store::{SharedStore, Store},
+    STORE,
+};
 use cosmic::iced::alignment::Horizontal;
 use cosmic::iced::{Alignment, Padding};
 use cosmic::{
@@ -16,6 +25,8 @@ use super::transactions::TransactionMessage;
 
 #[derive(Debug, Clone)]
 pub enum WelcomeMessage {
+    Update,
+    Loaded(Result<Vec<Currency>, DataStoreError>),
     CurrencyChanged(usize),
     AddCategoryToggle(bool),
     NewCategoryNameChanged(String),
@@ -33,7 +44,8 @@ pub enum WelcomeMessage {
     Setup,
 }
 
-pub struct Welcome {
+pub struct Welcome<R = Store> {
+    store: SharedStore<R>,
     currency_list: Vec<Currency>,
     selected_currency: Option<usize>,
     income_categories: Vec<NewCategory>,
@@ -51,20 +63,17 @@ pub struct Welcome {
 
 impl Default for Welcome {
     fn default() -> Self {
-        let mut store = STORE.lock().unwrap();
-        let currencies = store.get_currencies().unwrap_or_else(|_| vec![]);
-        let config = Config::load();
-
-        let selected_currency_id = config.1.currency_id;
-
-        let selected_currency = currencies
-            .iter()
-            .position(|currency| currency.id == selected_currency_id)
-            .unwrap_or(0);
+        Self::new(STORE.clone())
+    }
+}
 
+impl<R: Repository> Welcome<R> {
+    /// The currencies are listed once `WelcomeMessage::Update` loads them.
+    pub fn new(store: SharedStore<R>) -> Self {
         Self {
-            currency_list: currencies,
-            selected_currency: Some(selected_currency),
+            store,
+            currency_list: vec![],
+            selected_currency: None,
             income_categories: vec![],
             expense_categories: vec![],
             add_income_toogled: false,
@@ -78,9 +87,7 @@ impl Default for Welcome {
             form_new_account_balance: "0".to_string(),
         }
     }
-}
 
-impl Welcome {
     pub fn view<'a>(&'a self) -> Element<'a, WelcomeMessage> {
         let mut main_col = widget::column().width(Length::Fill);
 
@@ -374,9 +381,35 @@ impl Welcome {
         element.into()
     }
 
+    fn currency_scale(&self) -> u32 {
+        self.selected_currency
+            .and_then(|index| self.currency_list.get(index))
+            .map(|currency| currency.scale as u32)
+            .unwrap_or(Money::DEFAULT_SCALE)
+    }
+
     pub fn update(&mut self, message: WelcomeMessage) -> Task<crate::app::Message> {
         let mut commands = vec![];
         match message {
+            WelcomeMessage::Update => {
+                commands.push(data::request(
+                    &self.store,
+                    |store| store.get_currencies(),
+                    |currencies| app::Message::Welcome(WelcomeMessage::Loaded(currencies)),
+                ));
+            }
+            WelcomeMessage::Loaded(Err(e)) => commands.push(app::error_toast(e)),
+            WelcomeMessage::Loaded(Ok(currencies)) => {
+                let selected_currency_id = Config::load().1.currency_id;
+
+                let selected_currency = currencies
+                    .iter()
+                    .position(|currency| currency.id == selected_currency_id)
+                    .unwrap_or(0);
+
+                self.currency_list = currencies;
+                self.selected_currency = Some(selected_currency);
+            }
             WelcomeMessage::CurrencyChanged(index) => {
                 self.selected_currency = Some(index);
                 if let Some(selected_currency) = self.currency_list.get(index).clone() {
@@ -405,6 +438,7 @@ impl Welcome {
                         name: self.form_new_category_name.clone(),
                         is_income,
                         category_description: self.form_new_category_description.clone(),
+                        parent_category: None,
                     };
                     if is_income {
                         self.income_categories.push(new_category);
@@ -442,21 +476,23 @@ impl Welcome {
                 self.form_new_account_description = description;
             }
             WelcomeMessage::NewAccountBalanceChanged(balance) => {
-                if balance.parse::<f32>().is_ok() || balance == "" {
+                if Money::parse(&balance, self.currency_scale()).is_some() || balance == "" {
                     self.form_new_account_balance = balance;
                 }
             }
             WelcomeMessage::NewAccountSubmitted => {
-                let balance = if let Ok(balance) = self.form_new_account_balance.parse::<f32>() {
-                    balance
-                } else {
-                    0.
+                let scale = self.currency_scale();
+                let Some(balance) = Money::parse_or_zero(&self.form_new_account_balance, scale)
+                else {
+                    return app::error_toast(fl!("invalid-amount"));
                 };
 
                 let new_account = NewAccount {
                     name: self.form_new_account_name.clone(),
+                    account_type: AccountType::Checking,
                     initial_balance: balance,
                     account_description: self.form_new_account_description.clone(),
+                    currency_id: None,
                 };
 
                 self.accounts.push(new_account);
@@ -475,14 +511,24 @@ impl Welcome {
                 self.accounts.retain(|a| a.name != delete_account.name);
             }
             WelcomeMessage::Setup => {
-                let mut store = STORE.lock().unwrap();
-                store.create_accounts(&self.accounts);
-                store.create_categories(&self.income_categories);
-                store.create_categories(&self.expense_categories);
+                let selected_currency_id = self
+                    .selected_currency
+                    .and_then(|index| self.currency_list.get(index))
+                    .map(|currency| currency.id);
+                for account in &mut self.accounts {
+                    account.currency_id = selected_currency_id;
+                }
+                let accounts = self.accounts.clone();
+                let income_categories = self.income_categories.clone();
+                let expense_categories = self.expense_categories.clone();
+                commands.push(data::execute(&self.store, move |store| {
+                    store.create_accounts(&accounts)?;
+                    store.create_categories(&income_categories)?;
+                    store.create_categories(&expense_categories)
+                }));
                 if let Some(selected_currency) = self
-                    .currency_list
-                    .get(self.selected_currency.unwrap())
-                    .clone()
+                    .selected_currency
+                    .and_then(|index| self.currency_list.get(index))
                 {
                     let mut config = Config::load();
                     let _ = config
@@ -494,6 +540,12 @@ impl Welcome {
                 commands.push(Task::perform(async {}, |_| {
                     app::Message::Transactions(TransactionMessage::UpdatePage)
                 }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Recurring(super::recurring::RecurringMessage::UpdatePage)
+                }));
+                commands.push(Task::perform(async {}, |_| {
+                    app::Message::Goals(super::goals::GoalsMessage::UpdatePage)
+                }));
                 commands.push(Task::perform(async {}, |_| {
                     app::Message::Accounts(AccountsMessage::Update)
                 }));
@@ -0,0 +1,95 @@
+//! The data the pages are built on: accounts, categories, transactions and
+//! currencies. `Store` implements it on top of a SQLite ledger file, or an
+//! empty ledger kept in memory with `Store::in_memory`. The data service
+//! runs the work of the pages on another thread, so a repository has to be
+//! `Send`.
+
+use crate::{
+    errors::DataStoreError,
+    models::{
+        Account, Category, Currency, Money, MoneyTransaction, NewAccount, NewCategory,
+        NewMoneyTransaction, NewTransfer, TransactionEntry, UpdateAccount, UpdateCategory,
+        UpdateMoneyTransaction, UpdateTransfer,
+    },
+};
+
+pub trait Repository: Send + 'static {
+    // accounts
+    fn get_accounts(&mut self) -> Result<Vec<Account>, DataStoreError>;
+
+    fn create_account(&mut self, new_account: &NewAccount) -> Result<(), DataStoreError>;
+
+    fn create_accounts(&mut self, new_accounts: &[NewAccount]) -> Result<(), DataStoreError>;
+
+    fn update_account(&mut self, update_account: &UpdateAccount) -> Result<(), DataStoreError>;
+
+    /// Archived accounts keep their history and balance but are hidden when
+    /// picking an account for new transactions.
+    fn set_account_archived(
+        &mut self,
+        account_id: i32,
+        archived: bool,
+    ) -> Result<(), DataStoreError>;
+
+    /// Returns the balance of an account in the account currency. Liabilities
+    /// have a negative balance while money is owed on them.
+    fn get_account_balance(
+        &mut self,
+        account_id: i32,
+        reporting_currency_id: i32,
+    ) -> Result<Money, DataStoreError>;
+
+    // categories
+    fn get_categories(&mut self) -> Result<Vec<Category>, DataStoreError>;
+
+    fn create_category(&mut self, new_category: &NewCategory) -> Result<(), DataStoreError>;
+
+    fn create_categories(&mut self, new_categories: &[NewCategory]) -> Result<(), DataStoreError>;
+
+    fn update_category(&mut self, update_category: &UpdateCategory) -> Result<(), DataStoreError>;
+
+    /// Deletes a category after moving everything that refers to it into
+    /// `merge_into`: transactions, split lines, recurring rules, payee
+    /// defaults, loan interest and budgets (amounts are added up when both
+    /// categories have a budget for the same month). Subcategories move up
+    /// to the parent of the deleted category.
+    fn delete_category(&mut self, category_id: i32, merge_into: i32) -> Result<(), DataStoreError>;
+
+    // transactions
+    fn get_money_transactions(&mut self) -> Result<Vec<MoneyTransaction>, DataStoreError>;
+
+    /// Creates a transaction and returns its id.
+    fn create_money_transaction(
+        &mut self,
+        new_money_transaction: &NewMoneyTransaction,
+    ) -> Result<i32, DataStoreError>;
+
+    fn update_money_transaction(
+        &mut self,
+        update_money_transaction: &UpdateMoneyTransaction,
+    ) -> Result<(), DataStoreError>;
+
+    /// Deletes a transaction. Deleting either side of a transfer removes both
+    /// rows, deleting part of a loan payment removes the whole payment and
+    /// deleting the cash of an investment trade removes the trade.
+    fn delete_money_transaction(&mut self, transaction_id: i32) -> Result<(), DataStoreError>;
+
+    fn create_transfer(&mut self, new_transfer: &NewTransfer) -> Result<(), DataStoreError>;
+
+    fn update_transfer(&mut self, update_transfer: &UpdateTransfer) -> Result<(), DataStoreError>;
+
+    /// Saves a transaction entered in the form, replacing the one with the
+    /// `editing` id when given, as a single change that is kept or rolled
+    /// back as a whole. Switching between a transfer and a regular
+    /// transaction replaces the rows of the edited one.
+    fn save_transaction(
+        &mut self,
+        editing: Option<i32>,
+        entry: &TransactionEntry,
+    ) -> Result<(), DataStoreError>;
+
+    // currencies
+    fn get_currencies(&mut self) -> Result<Vec<Currency>, DataStoreError>;
+
+    fn get_currency_by_id(&mut self, target_id: i32) -> Result<Currency, DataStoreError>;
+}
@@ -6,7 +6,36 @@ diesel::table! {
         name -> Text,
         account_type -> Text,
         account_description -> Text,
-        initial_balance -> Float,
+        initial_balance -> BigInt,
+        currency_id -> Nullable<Integer>,
+        is_archived -> Bool,
+        cost_basis_method -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Integer,
+        audited_table -> Text,
+        row_id -> Integer,
+        action -> Text,
+        field_name -> Nullable<Text>,
+        old_value -> Nullable<Text>,
+        new_value -> Nullable<Text>,
+        changed_by -> Text,
+        changed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    budget (id) {
+        id -> Integer,
+        budget_category -> Integer,
+        budget_month -> Date,
+        amount -> BigInt,
+        rollover -> Bool,
     }
 }
 
@@ -16,6 +45,18 @@ diesel::table! {
         name -> Text,
         category_description -> Text,
         is_income -> Bool,
+        parent_category -> Nullable<Integer>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    change_journal (id) {
+        id -> Integer,
+        first_seq -> Integer,
+        last_seq -> Integer,
+        is_undone -> Bool,
     }
 }
 
@@ -24,6 +65,78 @@ diesel::table! {
         id -> Integer,
         label -> Text,
         symbol -> Text,
+        scale -> Integer,
+    }
+}
+
+diesel::table! {
+    exchange_rate (id) {
+        id -> Integer,
+        from_currency -> Integer,
+        to_currency -> Integer,
+        rate -> Double,
+        rate_date -> Date,
+    }
+}
+
+diesel::table! {
+    goal (id) {
+        id -> Integer,
+        name -> Text,
+        target_amount -> BigInt,
+        target_date -> Date,
+        linked_account -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    goal_contribution (id) {
+        id -> Integer,
+        goal_id -> Integer,
+        contribution_date -> Date,
+        amount -> BigInt,
+    }
+}
+
+diesel::table! {
+    investment_trade (id) {
+        id -> Integer,
+        bank_account -> Integer,
+        security_id -> Integer,
+        trade_kind -> Text,
+        trade_date -> Date,
+        quantity -> Double,
+        price -> Double,
+        fees -> BigInt,
+        amount -> BigInt,
+        cash_transaction -> Integer,
+    }
+}
+
+diesel::table! {
+    loan (id) {
+        id -> Integer,
+        loan_account -> Integer,
+        principal -> BigInt,
+        annual_rate -> Double,
+        term -> Integer,
+        frequency -> Text,
+        first_payment -> Date,
+        payment_account -> Integer,
+        interest_category -> Integer,
+    }
+}
+
+diesel::table! {
+    loan_payment (id) {
+        id -> Integer,
+        loan_id -> Integer,
+        payment_number -> Integer,
+        payment_date -> Date,
+        principal -> BigInt,
+        interest -> BigInt,
+        transfer_transaction -> Integer,
+        interest_transaction -> Nullable<Integer>,
     }
 }
 
@@ -31,20 +144,146 @@ diesel::table! {
     money_transaction (id) {
         id -> Integer,
         bank_account -> Integer,
-        transaction_category -> Integer,
+        transaction_category -> Nullable<Integer>,
         description -> Text,
-        amount -> Float,
         transaction_date -> Timestamp,
         is_expense -> Bool,
+        amount -> BigInt,
+        transfer_peer -> Nullable<Integer>,
+        payee_id -> Nullable<Integer>,
+        cleared_status -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    payee (id) {
+        id -> Integer,
+        name -> Text,
+        default_category -> Nullable<Integer>,
+        default_account -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    reconciliation (id) {
+        id -> Integer,
+        bank_account -> Integer,
+        statement_date -> Date,
+        statement_balance -> BigInt,
+    }
+}
+
+diesel::table! {
+    recurring_transaction (id) {
+        id -> Integer,
+        bank_account -> Integer,
+        transaction_category -> Integer,
+        description -> Text,
+        is_expense -> Bool,
+        amount -> BigInt,
+        frequency -> Text,
+        frequency_interval -> Integer,
+        day_of_month -> Nullable<Integer>,
+        next_date -> Nullable<Date>,
+        end_date -> Nullable<Date>,
+        remaining_occurrences -> Nullable<Integer>,
+        start_date -> Date,
+        occurrence_index -> Integer,
+    }
+}
+
+diesel::table! {
+    security (id) {
+        id -> Integer,
+        symbol -> Text,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    security_price (id) {
+        id -> Integer,
+        security_id -> Integer,
+        price_date -> Date,
+        price -> Double,
+    }
+}
+
+diesel::table! {
+    tag (id) {
+        id -> Integer,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    transaction_split (id) {
+        id -> Integer,
+        parent_transaction -> Integer,
+        split_category -> Integer,
+        amount -> BigInt,
+        note -> Text,
+    }
+}
+
+diesel::table! {
+    transaction_tag (transaction_id, tag_id) {
+        transaction_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
+diesel::table! {
+    undo_log (seq) {
+        seq -> Integer,
+        sql_text -> Text,
     }
 }
 
+diesel::joinable!(account -> currency (currency_id));
+diesel::joinable!(budget -> category (budget_category));
+diesel::joinable!(goal -> account (linked_account));
+diesel::joinable!(goal_contribution -> goal (goal_id));
+diesel::joinable!(investment_trade -> account (bank_account));
+diesel::joinable!(investment_trade -> money_transaction (cash_transaction));
+diesel::joinable!(investment_trade -> security (security_id));
+diesel::joinable!(loan -> category (interest_category));
+diesel::joinable!(loan_payment -> loan (loan_id));
 diesel::joinable!(money_transaction -> account (bank_account));
 diesel::joinable!(money_transaction -> category (transaction_category));
+diesel::joinable!(money_transaction -> payee (payee_id));
+diesel::joinable!(reconciliation -> account (bank_account));
+diesel::joinable!(recurring_transaction -> account (bank_account));
+diesel::joinable!(recurring_transaction -> category (transaction_category));
+diesel::joinable!(security_price -> security (security_id));
+diesel::joinable!(transaction_split -> category (split_category));
+diesel::joinable!(transaction_split -> money_transaction (parent_transaction));
+diesel::joinable!(transaction_tag -> money_transaction (transaction_id));
+diesel::joinable!(transaction_tag -> tag (tag_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     account,
+    audit_log,
+    budget,
     category,
+    change_journal,
     currency,
+    exchange_rate,
+    goal,
+    goal_contribution,
+    investment_trade,
+    loan,
+    loan_payment,
     money_transaction,
+    payee,
+    reconciliation,
+    recurring_transaction,
+    security,
+    security_price,
+    tag,
+    transaction_split,
+    transaction_tag,
+    undo_log,
 );
@@ -1,257 +1,3719 @@
 use crate::{
     errors::DataStoreError,
-    get_database_url,
     models::{self, Account, NewAccount},
+    repository::Repository,
     schema::{self, account, category, money_transaction},
 };
-use chrono::NaiveDate;
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use diesel::connection::{AnsiTransactionManager, SimpleConnection, TransactionManager};
 use diesel::prelude::*;
 use diesel::result::Error as DieselError;
 use diesel::{Connection, RunQueryDsl, SelectableHelper, SqliteConnection};
+use diesel_migrations::MigrationHarness;
 use models::*;
 use schema::account::dsl::*;
 use schema::category::dsl::*;
 use schema::currency::dsl::*;
 use schema::money_transaction::dsl::*;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// How many months of history the goal projections are based on.
+const GOAL_HISTORY_MONTHS: u32 = 6;
+
+/// How many actions are kept in the undo history.
+const UNDO_STEPS: i64 = 100;
+
+/// How many transactions a search returns at most.
+const SEARCH_RESULTS: i64 = 200;
+
+/// The error SQLite reports for a file that is not a database, which is
+/// how an encrypted ledger looks without its key.
+const NOT_A_DATABASE: &str = "file is not a database";
+
+/// A repository shared by the pages. The application hands them
+/// `crate::STORE`, tests an in-memory one.
+pub type SharedStore<R = Store> = Arc<Mutex<R>>;
 
 pub struct Store {
     connection: SqliteConnection,
+    /// The passphrase of an encrypted ledger, kept to reopen it.
+    passphrase: Option<String>,
 }
 
+/// An empty database kept in memory, standing in until a ledger is opened.
 impl Default for Store {
     fn default() -> Self {
-        let database_url = get_database_url();
         Self {
-            connection: SqliteConnection::establish(database_url.to_str().unwrap()).unwrap_or_else(
-                |_| panic!("Error connecting to {}", database_url.to_str().unwrap()),
-            ),
+            connection: SqliteConnection::establish(":memory:")
+                .expect("SQLite could not open an in-memory database"),
+            passphrase: None,
         }
     }
 }
 
 impl Store {
-    pub fn create_account(&mut self, new_account: &NewAccount) -> Result<(), DataStoreError> {
-        let res = diesel::insert_into(account::table)
-            .values(new_account)
-            .returning(Account::as_returning())
-            .get_result(&mut self.connection);
+    /// Opens the ledger stored at `database_path`, creating it when missing,
+    /// with its migrations applied and its change journal installed. An
+    /// encrypted ledger needs the `passphrase` its key is derived from.
+    /// Amounts written before they had a currency are converted with the
+    /// scale of `legacy_currency_id`, or two decimal places without one.
+    pub fn open(
+        database_path: &Path,
+        passphrase: Option<&str>,
+        legacy_currency_id: Option<i32>,
+    ) -> Result<Self, DataStoreError> {
+        let mut connection = connect(database_path, passphrase)?;
+        migrate(&mut connection, legacy_currency_id)?;
+        Self::with_connection(connection, passphrase)
+    }
 
-        if let Err(e) = res {
-            return Err(DataStoreError::InsertError(e.to_string()));
-        }
+    /// An empty ledger kept in memory, with its migrations applied. It
+    /// works like a ledger file but is gone once the store is dropped.
+    pub fn in_memory() -> Result<Self, DataStoreError> {
+        Self::open(Path::new(":memory:"), None, None)
+    }
 
-        Ok(())
+    /// A store working on a connection to a migrated ledger.
+    fn with_connection(
+        connection: SqliteConnection,
+        passphrase: Option<&str>,
+    ) -> Result<Self, DataStoreError> {
+        let mut store = Self {
+            connection,
+            passphrase: passphrase.map(str::to_string),
+        };
+        store.install_change_journal()?;
+        Ok(store)
     }
 
-    pub fn create_accounts(&mut self, new_accounts: &[NewAccount]) -> Result<(), DataStoreError> {
-        self.connection
-            .transaction::<_, DieselError, _>(|conn| {
-                for new_account in new_accounts {
-                    diesel::insert_into(account::table)
-                        .values(new_account)
-                        .execute(conn)?;
-                }
-                Ok(())
-            })
-            .map_err(|e| DataStoreError::InsertError(e.to_string()))
+    /// Checks the integrity of the database at `database_path`, without
+    /// migrating it, and counts its rows.
+    pub fn inspect(
+        database_path: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<LedgerSummary, DataStoreError> {
+        let mut connection = connect(database_path, passphrase)?;
+        integrity_check(&mut connection, "integrity_check")?;
+
+        let count = |connection: &mut SqliteConnection, table: &str| {
+            diesel::sql_query(format!("SELECT count(*) AS row_count FROM \"{}\"", table))
+                .get_result::<RowCount>(connection)
+                .map(|count| count.row_count)
+                .map_err(DataStoreError::query)
+        };
+        Ok(LedgerSummary {
+            accounts: count(&mut connection, "account")?,
+            categories: count(&mut connection, "category")?,
+            transactions: count(&mut connection, "money_transaction")?,
+        })
     }
 
-    pub fn update_account(&mut self, update_account: &UpdateAccount) -> Result<(), DataStoreError> {
-        use schema::account::dsl::*;
+    /// Quickly checks that the database at `database_path` can be read
+    /// before it is opened, so a damaged file is not backed up or migrated.
+    pub fn check(database_path: &Path, passphrase: Option<&str>) -> Result<(), DataStoreError> {
+        let mut connection = connect(database_path, passphrase)?;
+        integrity_check(&mut connection, "quick_check")
+    }
 
-        let res = diesel::update(account.filter(id.eq(update_account.id)))
-            .set((
-                name.eq(&update_account.name),
-                account_description.eq(&update_account.account_description),
-                initial_balance.eq(&update_account.initial_balance),
-            ))
-            .execute(&mut self.connection);
+    /// Whether the database at `database_path` needs a key to be read.
+    /// SQLCipher pages look like random bytes, so SQLite does not take the
+    /// file for a database at all, while a damaged plain file fails for
+    /// another reason.
+    pub fn is_encrypted(database_path: &Path) -> bool {
+        SqliteConnection::establish(&database_path.to_string_lossy()).is_ok_and(|mut connection| {
+            matches!(
+                connection.batch_execute("SELECT count(*) FROM sqlite_master;"),
+                Err(DieselError::DatabaseError(_, info)) if info.message() == NOT_A_DATABASE
+            )
+        })
+    }
 
-        if let Err(e) = res {
-            return Err(DataStoreError::UpdateError(e.to_string()));
+    /// Whether the database at `database_path` is behind the migrations, so
+    /// opening it would change its schema.
+    pub fn has_pending_migrations(
+        database_path: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<bool, DataStoreError> {
+        connect(database_path, passphrase)?
+            .has_pending_migration(crate::MIGRATIONS)
+            .map_err(|e| DataStoreError::MigrationError(e.to_string()))
+    }
+
+    /// Writes a copy of the ledger to `target`, encrypted like the ledger.
+    pub fn backup_to(&mut self, target: &Path) -> Result<(), DataStoreError> {
+        if target.exists() {
+            return Err(DataStoreError::LedgerFileError(format!(
+                "{} already exists",
+                target.display()
+            )));
         }
+        export(&mut self.connection, target, self.passphrase.as_deref())
+    }
+
+    /// Rewrites the ledger this store has open at `database_path`, encrypted
+    /// with a key derived from `passphrase` or in plain text when it is
+    /// `None`, then reopens it.
+    pub fn set_passphrase(
+        &mut self,
+        database_path: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<(), DataStoreError> {
+        rewrite(&mut self.connection, database_path, passphrase)?;
 
+        // the ledger was migrated when it was opened
+        *self = Self::with_connection(connect(database_path, passphrase)?, passphrase)?;
         Ok(())
     }
 
-    pub fn get_accounts(&mut self) -> Result<Vec<Account>, DataStoreError> {
-        let results = account
-            .select(Account::as_select())
-            .load(&mut self.connection);
+    /// Rewrites the database at `database_path`, such as a backup, from
+    /// `old_passphrase` to `passphrase` without migrating it.
+    pub fn rekey(
+        database_path: &Path,
+        old_passphrase: Option<&str>,
+        passphrase: Option<&str>,
+    ) -> Result<(), DataStoreError> {
+        let mut connection = connect(database_path, old_passphrase)?;
+        rewrite(&mut connection, database_path, passphrase)
+    }
 
-        match results {
-            Ok(results) => return Ok(results),
-            Err(e) => return Err(DataStoreError::QueryError(e.to_string())),
+    /// Runs `work` as one database transaction, so the changes of the store
+    /// methods it calls are kept or rolled back together. Their own
+    /// transactions become savepoints of this one.
+    fn atomically<T>(
+        &mut self,
+        work: impl FnOnce(&mut Self) -> Result<T, DataStoreError>,
+    ) -> Result<T, DataStoreError> {
+        AnsiTransactionManager::begin_transaction(&mut self.connection)
+            .map_err(DataStoreError::update)?;
+        match work(self) {
+            Ok(value) => {
+                AnsiTransactionManager::commit_transaction(&mut self.connection)
+                    .map_err(DataStoreError::update)?;
+                Ok(value)
+            }
+            Err(e) => {
+                AnsiTransactionManager::rollback_transaction(&mut self.connection)
+                    .map_err(DataStoreError::update)?;
+                Err(e)
+            }
         }
     }
 
-    pub fn get_account_balance(&mut self, account_id: i32) -> Result<f32, DataStoreError> {
+    /// Returns the currency of an account, falling back to the reporting
+    /// currency for accounts that have none.
+    pub fn get_account_currency(
+        &mut self,
+        account_id: i32,
+        reporting_currency_id: i32,
+    ) -> Result<Currency, DataStoreError> {
+        let account_currency = account
+            .filter(account::id.eq(account_id))
+            .select(account::currency_id)
+            .first::<Option<i32>>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+
+        self.get_currency_by_id(account_currency.unwrap_or(reporting_currency_id))
+    }
+
+    /// Computes an account balance, restricted to the transactions cleared by
+    /// `cleared_until` when it is given.
+    fn account_balance(
+        &mut self,
+        account_id: i32,
+        reporting_currency_id: i32,
+        cleared_until: Option<&NaiveDate>,
+    ) -> Result<Money, DataStoreError> {
+        let currency_scale = self
+            .get_account_currency(account_id, reporting_currency_id)?
+            .scale as u32;
+
         // read account initial balance
-        let target_initial_balance = account
+        let (target_initial_balance, target_type) = account
             .filter(account::id.eq(account_id))
-            .select(account::initial_balance)
-            .first::<f32>(&mut self.connection);
+            .select((account::initial_balance, account::account_type))
+            .first::<(i64, AccountType)>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
 
-        let mut total: f32 = target_initial_balance.unwrap_or(0.);
+        // the initial balance of a liability is the amount owed
+        let mut total = Money::new(target_initial_balance, currency_scale);
+        if target_type.is_liability() {
+            total = -total;
+        }
 
         // read all transaction on that account
         let transactions = money_transaction
             .filter(money_transaction::bank_account.eq(account_id))
             .select(MoneyTransaction::as_select())
-            .load(&mut self.connection);
+            .load(&mut self.connection)
+            .map_err(DataStoreError::query)?;
 
-        if let Ok(transactions) = transactions {
-            for t in transactions {
-                if t.is_expense {
-                    total -= t.amount;
-                } else {
-                    total += t.amount;
+        for t in transactions {
+            if let Some(date) = cleared_until {
+                if t.cleared_status == ClearedStatus::Uncleared || t.transaction_date.date() > *date
+                {
+                    continue;
                 }
             }
+            if t.is_expense {
+                total = total.checked_sub(t.amount(currency_scale))?;
+            } else {
+                total = total.checked_add(t.amount(currency_scale))?;
+            }
         }
 
         Ok(total)
     }
 
-    pub fn get_categories(&mut self) -> Result<Vec<Category>, DataStoreError> {
-        let results = category
-            .select(Category::as_select())
-            .load(&mut self.connection);
+    /// Adds up `(amount, date, account currency)` rows, converting each one
+    /// into the reporting currency at its own date. The currencies and the
+    /// rates of the reporting currency are loaded once for all the rows.
+    fn sum_in_reporting_currency(
+        &mut self,
+        rows: impl IntoIterator<Item = (i64, chrono::NaiveDateTime, Option<i32>)>,
+        reporting_currency_id: i32,
+    ) -> Result<Money, DataStoreError> {
+        let currencies: HashMap<i32, Currency> = self
+            .get_currencies()?
+            .into_iter()
+            .map(|c| (c.id, c))
+            .collect();
+        let rates = {
+            use schema::exchange_rate::dsl::*;
 
-        match results {
-            Ok(results) => return Ok(results),
-            Err(e) => return Err(DataStoreError::QueryError(e.to_string())),
+            exchange_rate
+                .filter(
+                    from_currency
+                        .eq(reporting_currency_id)
+                        .or(to_currency.eq(reporting_currency_id)),
+                )
+                .select(ExchangeRate::as_select())
+                .load(&mut self.connection)
+                .map_err(DataStoreError::query)?
+        };
+        let currency_of = |target: i32| currencies.get(&target).ok_or(DataStoreError::NotFound);
+
+        let reporting_currency = currency_of(reporting_currency_id)?;
+        let reporting_scale = reporting_currency.scale as u32;
+        let mut total = Money::zero(reporting_scale);
+        for (minor_units, date, account_currency) in rows {
+            let account_currency = currency_of(account_currency.unwrap_or(reporting_currency_id))?;
+            let money = Money::new(minor_units, account_currency.scale as u32);
+            let converted = if account_currency.id == reporting_currency_id {
+                money.rescale(reporting_scale)?
+            } else {
+                let rate = pick_exchange_rate(
+                    &rates,
+                    account_currency.id,
+                    reporting_currency_id,
+                    &date.date(),
+                )
+                .ok_or_else(|| {
+                    DataStoreError::MissingExchangeRate(
+                        account_currency.symbol.clone(),
+                        reporting_currency.symbol.clone(),
+                    )
+                })?;
+                money.convert(rate, reporting_scale)
+            };
+            total = total.checked_add(converted)?;
         }
+
+        Ok(total)
     }
 
-    pub fn calculate_expense_by_category(
+    /// Finds the rate to convert `from` into `to` at `date`.
+    ///
+    /// The latest rate on or before `date` wins, otherwise the earliest one
+    /// after it. A rate stored in the opposite direction is inverted.
+    pub fn get_exchange_rate(
         &mut self,
-        category_id: i32,
-        start_date: &NaiveDate,
-        end_date: &NaiveDate,
-    ) -> Result<f32, DataStoreError> {
-        use diesel::dsl::sum;
-        use schema::money_transaction::dsl::*;
-
-        let total_expense = money_transaction
-            .filter(transaction_category.eq(category_id))
-            .filter(
-                transaction_date.between(start_date.and_hms(0, 0, 0), end_date.and_hms(23, 59, 59)),
-            )
-            .filter(is_expense.eq(true))
-            .select(sum(amount))
-            .first::<Option<f32>>(&mut self.connection);
+        from: i32,
+        to: i32,
+        date: &NaiveDate,
+    ) -> Result<f64, DataStoreError> {
+        if from == to {
+            return Ok(1.);
+        }
 
-        match total_expense {
-            Ok(Some(total)) => Ok(total),
-            Ok(None) => Ok(0.0),
-            Err(e) => Err(DataStoreError::QueryError(e.to_string())),
+        if let Some(direct) = self.find_exchange_rate(from, to, date)? {
+            return Ok(direct);
+        }
+        if let Some(inverse) = self.find_exchange_rate(to, from, date)? {
+            if inverse != 0. {
+                return Ok(1. / inverse);
+            }
         }
+
+        let from_symbol = self.get_currency_by_id(from)?.symbol;
+        let to_symbol = self.get_currency_by_id(to)?.symbol;
+        Err(DataStoreError::MissingExchangeRate(from_symbol, to_symbol))
     }
 
-    pub fn create_category(&mut self, new_category: &NewCategory) -> Result<(), DataStoreError> {
-        let res = diesel::insert_into(category::table)
-            .values(new_category)
-            .returning(Category::as_returning())
-            .get_result(&mut self.connection);
+    fn find_exchange_rate(
+        &mut self,
+        from: i32,
+        to: i32,
+        date: &NaiveDate,
+    ) -> Result<Option<f64>, DataStoreError> {
+        use schema::exchange_rate::dsl::*;
 
-        if let Err(e) = res {
-            return Err(DataStoreError::InsertError(e.to_string()));
+        let before = exchange_rate
+            .filter(from_currency.eq(from))
+            .filter(to_currency.eq(to))
+            .filter(rate_date.le(date))
+            .order(rate_date.desc())
+            .select(rate)
+            .first::<f64>(&mut self.connection)
+            .optional()
+            .map_err(DataStoreError::query)?;
+        if before.is_some() {
+            return Ok(before);
         }
 
-        Ok(())
+        exchange_rate
+            .filter(from_currency.eq(from))
+            .filter(to_currency.eq(to))
+            .filter(rate_date.gt(date))
+            .order(rate_date.asc())
+            .select(rate)
+            .first::<f64>(&mut self.connection)
+            .optional()
+            .map_err(DataStoreError::query)
     }
 
-    pub fn create_categories(
+    /// Posts every occurrence due on or before `today`, returning how many were posted.
+    pub fn post_due_recurring_transactions(
         &mut self,
-        new_categories: &[NewCategory],
-    ) -> Result<(), DataStoreError> {
+        today: NaiveDate,
+    ) -> Result<usize, DataStoreError> {
+        use schema::recurring_transaction::dsl::*;
+
         self.connection
             .transaction::<_, DieselError, _>(|conn| {
-                for new_category in new_categories {
-                    diesel::insert_into(category::table)
-                        .values(new_category)
-                        .execute(conn)?;
+                let mut posted = 0;
+                let due = recurring_transaction
+                    .filter(next_date.le(today))
+                    .select(RecurringTransaction::as_select())
+                    .load(conn)?;
+                for mut rule in due {
+                    while rule.next_date.is_some_and(|date| date <= today) {
+                        Self::advance_recurring_transaction(conn, &rule, true)?;
+                        rule = rule.advanced();
+                        posted += 1;
+                    }
                 }
-                Ok(())
+                Ok(posted)
             })
-            .map_err(|e| DataStoreError::InsertError(e.to_string()))
+            .map_err(DataStoreError::insert)
     }
 
-    pub fn update_category(
-        &mut self,
-        update_category: &UpdateCategory,
-    ) -> Result<(), DataStoreError> {
-        use schema::category::dsl::*;
+    fn find_recurring_transaction(
+        conn: &mut SqliteConnection,
+        recurring_transaction_id: i32,
+    ) -> Result<RecurringTransaction, DieselError> {
+        use schema::recurring_transaction::dsl::*;
 
-        let res = diesel::update(category.filter(id.eq(update_category.id)))
+        recurring_transaction
+            .filter(id.eq(recurring_transaction_id))
+            .select(RecurringTransaction::as_select())
+            .first(conn)
+    }
+
+    fn advance_recurring_transaction(
+        conn: &mut SqliteConnection,
+        rule: &RecurringTransaction,
+        post: bool,
+    ) -> Result<(), DieselError> {
+        use schema::recurring_transaction::dsl::*;
+
+        let Some(occurrence) = rule.next_date else {
+            return Ok(());
+        };
+
+        if post {
+            // noon keeps the local date stable across time zones
+            let noon = NaiveTime::from_hms_opt(12, 0, 0).unwrap_or_default();
+            let posted = diesel::insert_into(money_transaction::table)
+                .values((
+                    money_transaction::bank_account.eq(rule.bank_account),
+                    money_transaction::transaction_category.eq(rule.transaction_category),
+                    money_transaction::description.eq(&rule.description),
+                    money_transaction::amount.eq(rule.amount),
+                    money_transaction::transaction_date.eq(occurrence.and_time(noon)),
+                    money_transaction::is_expense.eq(rule.is_expense),
+                ))
+                .returning(money_transaction::id)
+                .get_result::<i32>(conn)?;
+            audit_created::<MoneyTransaction>(conn, posted)?;
+        }
+
+        let following = rule.advanced();
+        diesel::update(recurring_transaction.filter(id.eq(rule.id)))
             .set((
-                name.eq(&update_category.name),
-                category_description.eq(&update_category.category_description),
-                is_income.eq(&update_category.is_income),
+                next_date.eq(following.next_date),
+                occurrence_index.eq(following.occurrence_index),
+                remaining_occurrences.eq(following.remaining_occurrences),
             ))
-            .execute(&mut self.connection);
+            .execute(conn)?;
 
-        if let Err(e) = res {
-            return Err(DataStoreError::UpdateError(e.to_string()));
+        Ok(())
+    }
+
+    /// Fails when the transaction, or the other side of its transfer, has
+    /// been reconciled.
+    fn ensure_not_reconciled(&mut self, transaction_id: i32) -> Result<(), DataStoreError> {
+        let (status, peer) = money_transaction
+            .filter(money_transaction::id.eq(transaction_id))
+            .select((
+                money_transaction::cleared_status,
+                money_transaction::transfer_peer,
+            ))
+            .first::<(ClearedStatus, Option<i32>)>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+
+        let peer_status = match peer {
+            Some(peer) => money_transaction
+                .filter(money_transaction::id.eq(peer))
+                .select(money_transaction::cleared_status)
+                .first::<ClearedStatus>(&mut self.connection)
+                .optional()
+                .map_err(DataStoreError::query)?,
+            None => None,
+        };
+
+        if status == ClearedStatus::Reconciled || peer_status == Some(ClearedStatus::Reconciled) {
+            return Err(DataStoreError::TransactionLocked);
         }
 
         Ok(())
     }
 
-    pub fn get_money_transactions(&mut self) -> Result<Vec<MoneyTransaction>, DataStoreError> {
-        let results = money_transaction
-            .select(MoneyTransaction::as_select())
-            .order(transaction_date.desc())
-            .load(&mut self.connection);
+    /// The latest price of a security on or before `date`, falling back to
+    /// the price of its latest buy or sell when none was recorded.
+    fn latest_security_price(
+        &mut self,
+        target_security: i32,
+        date: &NaiveDate,
+    ) -> Result<Option<f64>, DataStoreError> {
+        use schema::{investment_trade, security_price};
 
-        match results {
-            Ok(results) => return Ok(results),
-            Err(e) => return Err(DataStoreError::QueryError(e.to_string())),
+        let recorded = security_price::table
+            .filter(security_price::security_id.eq(target_security))
+            .filter(security_price::price_date.le(date))
+            .order(security_price::price_date.desc())
+            .select(security_price::price)
+            .first::<f64>(&mut self.connection)
+            .optional()
+            .map_err(DataStoreError::query)?;
+        if recorded.is_some() {
+            return Ok(recorded);
         }
+
+        investment_trade::table
+            .filter(investment_trade::security_id.eq(target_security))
+            .filter(investment_trade::trade_kind.ne(TradeKind::Dividend))
+            .order((
+                investment_trade::trade_date.desc(),
+                investment_trade::id.desc(),
+            ))
+            .select(investment_trade::price)
+            .first::<f64>(&mut self.connection)
+            .optional()
+            .map_err(DataStoreError::query)
     }
 
-    pub fn create_money_transaction(
+    fn account_holdings(
         &mut self,
-        new_money_transaction: &NewMoneyTransaction,
-    ) -> Result<(), DataStoreError> {
-        let res = diesel::insert_into(money_transaction::table)
-            .values(new_money_transaction)
-            .returning(MoneyTransaction::as_returning())
-            .get_result(&mut self.connection);
+        account_id: i32,
+        reporting_currency_id: i32,
+    ) -> Result<Vec<Holding>, DataStoreError> {
+        use schema::investment_trade;
 
-        if let Err(e) = res {
-            return Err(DataStoreError::InsertError(e.to_string()));
+        let account_scale = self
+            .get_account_currency(account_id, reporting_currency_id)?
+            .scale as u32;
+        let method = account
+            .filter(account::id.eq(account_id))
+            .select(account::cost_basis_method)
+            .first::<CostBasisMethod>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+        let trades = investment_trade::table
+            .filter(investment_trade::bank_account.eq(account_id))
+            .select(InvestmentTrade::as_select())
+            .load(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+
+        Ok(holdings(&trades, method, account_scale))
+    }
+
+    /// Investment trades keep their cash transaction in step with their
+    /// quantities, so it can only be deleted, not edited.
+    fn ensure_not_trade(&mut self, transaction_id: i32) -> Result<(), DataStoreError> {
+        use schema::investment_trade;
+
+        let trades = investment_trade::table
+            .filter(investment_trade::cash_transaction.eq(transaction_id))
+            .count()
+            .get_result::<i64>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+
+        if trades > 0 {
+            return Err(DataStoreError::TradeLocked);
         }
 
         Ok(())
     }
 
-    pub fn get_currencies(&mut self) -> Result<Vec<Currency>, DataStoreError> {
-        let results = currency
-            .select(Currency::as_select())
+    /// Installs the triggers that journal every change to the ledger as the
+    /// statement that reverses it. They are rebuilt on each start so they
+    /// follow the columns added by migrations. Statements journaled outside
+    /// of a checkpoint, by migrations or a session that ended abruptly, are
+    /// dropped as they belong to no action.
+    pub fn install_change_journal(&mut self) -> Result<(), DataStoreError> {
+        use schema::undo_log;
+
+        // the search index follows the tables through its own triggers, and
+        // its virtual and shadow tables cannot be journaled. The audit log
+        // keeps the history of the changes that were undone as well.
+        let tables = diesel::sql_query(
+            "SELECT name FROM pragma_table_list WHERE schema = 'main' AND type = 'table' \
+             AND name NOT LIKE 'sqlite_%' \
+             AND name NOT IN \
+             ('__diesel_schema_migrations', 'undo_log', 'change_journal', 'audit_log')",
+        )
+        .load::<SqlName>(&mut self.connection)
+        .map_err(DataStoreError::query)?;
+
+        let mut triggers = String::from(
+            "DROP TRIGGER IF EXISTS \"audit_log_journal_insert\";
+            DROP TRIGGER IF EXISTS \"audit_log_journal_update\";
+            DROP TRIGGER IF EXISTS \"audit_log_journal_delete\";
+            ",
+        );
+        for table in tables {
+            let columns =
+                diesel::sql_query(format!("PRAGMA table_info(\"{}\")", table.object_name))
+                    .load::<SqlName>(&mut self.connection)
+                    .map_err(DataStoreError::query)?
+                    .into_iter()
+                    .map(|column| column.object_name)
+                    .collect::<Vec<String>>();
+            triggers.push_str(&journal_triggers(&table.object_name, &columns));
+        }
+        self.connection
+            .batch_execute(&triggers)
+            .map_err(DataStoreError::update)?;
+
+        let recorded = last_recorded_seq(&mut self.connection).map_err(DataStoreError::query)?;
+        diesel::delete(undo_log::table.filter(undo_log::seq.gt(recorded)))
+            .execute(&mut self.connection)
+            .map_err(DataStoreError::delete)?;
+
+        Ok(())
+    }
+
+    /// Groups the changes journaled since the last checkpoint into one
+    /// undoable step. A new step drops the steps that could be redone and
+    /// the oldest ones beyond [`UNDO_STEPS`]. Returns whether anything
+    /// changed.
+    pub fn checkpoint(&mut self) -> Result<bool, DataStoreError> {
+        use schema::{change_journal, undo_log};
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let recorded = last_recorded_seq(conn)?;
+                let Some(first) = undo_log::table
+                    .filter(undo_log::seq.gt(recorded))
+                    .select(diesel::dsl::min(undo_log::seq))
+                    .first::<Option<i32>>(conn)?
+                else {
+                    return Ok(false);
+                };
+                let latest = undo_log::table
+                    .select(diesel::dsl::max(undo_log::seq))
+                    .first::<Option<i32>>(conn)?
+                    .unwrap_or(first);
+
+                let undone = change_journal::table
+                    .filter(change_journal::is_undone.eq(true))
+                    .select(ChangeStep::as_select())
+                    .load(conn)?;
+                forget_steps(conn, &undone)?;
+
+                diesel::insert_into(change_journal::table)
+                    .values((
+                        change_journal::first_seq.eq(first),
+                        change_journal::last_seq.eq(latest),
+                    ))
+                    .execute(conn)?;
+
+                let expired = change_journal::table
+                    .order(change_journal::id.desc())
+                    .limit(-1)
+                    .offset(UNDO_STEPS)
+                    .select(ChangeStep::as_select())
+                    .load(conn)?;
+                forget_steps(conn, &expired)?;
+
+                Ok(true)
+            })
+            .map_err(DataStoreError::insert)
+    }
+
+    pub fn can_undo(&mut self) -> Result<bool, DataStoreError> {
+        Ok(self.next_step(false)?.is_some())
+    }
+
+    pub fn can_redo(&mut self) -> Result<bool, DataStoreError> {
+        Ok(self.next_step(true)?.is_some())
+    }
+
+    /// Reverses the latest step. Returns false when there is nothing to undo.
+    pub fn undo(&mut self) -> Result<bool, DataStoreError> {
+        match self.next_step(false)? {
+            Some(step) => self.replay(&step, true).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Applies again the latest undone step. Returns false when there is
+    /// nothing to redo.
+    pub fn redo(&mut self) -> Result<bool, DataStoreError> {
+        match self.next_step(true)? {
+            Some(step) => self.replay(&step, false).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// The step an undo (or a redo, when `undone`) would replay. Steps are
+    /// undone from the newest back, so the latest undone one has the lowest
+    /// id among them.
+    fn next_step(&mut self, undone: bool) -> Result<Option<ChangeStep>, DataStoreError> {
+        use schema::change_journal::dsl::*;
+
+        let steps = change_journal.filter(is_undone.eq(undone));
+        if undone {
+            steps
+                .order(id.asc())
+                .select(ChangeStep::as_select())
+                .first(&mut self.connection)
+        } else {
+            steps
+                .order(id.desc())
+                .select(ChangeStep::as_select())
+                .first(&mut self.connection)
+        }
+        .optional()
+        .map_err(DataStoreError::query)
+    }
+
+    /// Runs the statements of a step from the newest back, which reverses
+    /// it. The triggers journal the statements that reverse them in turn,
+    /// and those replace the step's so it can be replayed the other way.
+    /// The audited rows it changes are recorded in the audit log.
+    fn replay(&mut self, step: &ChangeStep, undone: bool) -> Result<(), DataStoreError> {
+        use schema::{change_journal, undo_log};
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                // rows referring to each other, like the two sides of a
+                // transfer, are only consistent once all are restored
+                conn.batch_execute("PRAGMA defer_foreign_keys = ON;")?;
+                let statements = undo_log::table
+                    .filter(undo_log::seq.between(step.first_seq, step.last_seq))
+                    .order(undo_log::seq.desc())
+                    .select(undo_log::sql_text)
+                    .load::<String>(conn)?;
+                let start = undo_log::table
+                    .select(diesel::dsl::max(undo_log::seq))
+                    .first::<Option<i32>>(conn)?
+                    .unwrap_or(0);
+                let mut touched = statements
+                    .iter()
+                    .filter_map(|statement| journaled_row(statement))
+                    .collect::<Vec<_>>();
+                touched.sort();
+                touched.dedup();
+                let accounts = replayed_rows::<Account>(conn, &touched)?;
+                let categories = replayed_rows::<Category>(conn, &touched)?;
+                let transactions = replayed_rows::<MoneyTransaction>(conn, &touched)?;
+
+                for statement in &statements {
+                    diesel::sql_query(statement).execute(conn)?;
+                }
+                audit_replayed(conn, accounts)?;
+                audit_replayed(conn, categories)?;
+                audit_replayed(conn, transactions)?;
+                let end = undo_log::table
+                    .select(diesel::dsl::max(undo_log::seq))
+                    .first::<Option<i32>>(conn)?
+                    .unwrap_or(start);
+
+                diesel::delete(
+                    undo_log::table.filter(undo_log::seq.between(step.first_seq, step.last_seq)),
+                )
+                .execute(conn)?;
+                diesel::update(change_journal::table.find(step.id))
+                    .set((
+                        change_journal::first_seq.eq(start + 1),
+                        change_journal::last_seq.eq(end),
+                        change_journal::is_undone.eq(undone),
+                    ))
+                    .execute(conn)?;
+
+                Ok(())
+            })
+            .map_err(DataStoreError::update)
+    }
+}
+
+impl Repository for Store {
+    fn get_accounts(&mut self) -> Result<Vec<Account>, DataStoreError> {
+        let results = account
+            .select(Account::as_select())
             .load(&mut self.connection);
 
         match results {
             Ok(results) => return Ok(results),
-            Err(e) => return Err(DataStoreError::QueryError(e.to_string())),
+            Err(e) => return Err(DataStoreError::query(e)),
         }
     }
 
-    pub fn get_currency_symbol_by_id(
+    fn create_account(&mut self, new_account: &NewAccount) -> Result<(), DataStoreError> {
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let created = diesel::insert_into(account::table)
+                    .values(new_account)
+                    .returning(account::id)
+                    .get_result::<i32>(conn)?;
+                audit_created::<Account>(conn, created)
+            })
+            .map_err(DataStoreError::insert)
+    }
+
+    fn create_accounts(&mut self, new_accounts: &[NewAccount]) -> Result<(), DataStoreError> {
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                for new_account in new_accounts {
+                    let created = diesel::insert_into(account::table)
+                        .values(new_account)
+                        .returning(account::id)
+                        .get_result::<i32>(conn)?;
+                    audit_created::<Account>(conn, created)?;
+                }
+                Ok(())
+            })
+            .map_err(DataStoreError::insert)
+    }
+
+    fn update_account(&mut self, update_account: &UpdateAccount) -> Result<(), DataStoreError> {
+        use schema::account::dsl::*;
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let before = Account::find(conn, update_account.id)?;
+                diesel::update(account.filter(id.eq(update_account.id)))
+                    .set((
+                        name.eq(&update_account.name),
+                        account_type.eq(&update_account.account_type),
+                        account_description.eq(&update_account.account_description),
+                        initial_balance.eq(&update_account.initial_balance),
+                        currency_id.eq(&update_account.currency_id),
+                    ))
+                    .execute(conn)?;
+                audit_updated(conn, &before)
+            })
+            .map_err(DataStoreError::update)
+    }
+
+    fn set_account_archived(
+        &mut self,
+        account_id: i32,
+        archived: bool,
+    ) -> Result<(), DataStoreError> {
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let before = Account::find(conn, account_id)?;
+                diesel::update(account.filter(account::id.eq(account_id)))
+                    .set(account::is_archived.eq(archived))
+                    .execute(conn)?;
+                audit_updated(conn, &before)
+            })
+            .map_err(DataStoreError::update)
+    }
+
+    fn get_account_balance(
         &mut self,
-        currency_id: i32,
-    ) -> Result<String, DataStoreError> {
-        use crate::schema::currency::dsl::{currency, id, symbol};
+        account_id: i32,
+        reporting_currency_id: i32,
+    ) -> Result<Money, DataStoreError> {
+        self.account_balance(account_id, reporting_currency_id, None)
+    }
 
-        let result = currency
-            .filter(id.eq(currency_id))
-            .select(symbol)
-            .first::<String>(&mut self.connection);
+    fn get_categories(&mut self) -> Result<Vec<Category>, DataStoreError> {
+        let results = category
+            .select(Category::as_select())
+            .load(&mut self.connection);
 
-        match result {
-            Ok(currency_symbol) => Ok(currency_symbol),
-            Err(e) => Err(DataStoreError::QueryError(e.to_string())),
+        match results {
+            Ok(results) => return Ok(results),
+            Err(e) => return Err(DataStoreError::query(e)),
+        }
+    }
+
+    fn create_category(&mut self, new_category: &NewCategory) -> Result<(), DataStoreError> {
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let created = diesel::insert_into(category::table)
+                    .values(new_category)
+                    .returning(category::id)
+                    .get_result::<i32>(conn)?;
+                audit_created::<Category>(conn, created)
+            })
+            .map_err(DataStoreError::insert)
+    }
+
+    fn create_categories(&mut self, new_categories: &[NewCategory]) -> Result<(), DataStoreError> {
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                for new_category in new_categories {
+                    let created = diesel::insert_into(category::table)
+                        .values(new_category)
+                        .returning(category::id)
+                        .get_result::<i32>(conn)?;
+                    audit_created::<Category>(conn, created)?;
+                }
+                Ok(())
+            })
+            .map_err(DataStoreError::insert)
+    }
+
+    fn update_category(&mut self, update_category: &UpdateCategory) -> Result<(), DataStoreError> {
+        use schema::category::dsl::*;
+
+        if let Some(new_parent) = update_category.parent_category {
+            let categories = self.get_categories()?;
+            if category_with_descendants(&categories, update_category.id).contains(&new_parent) {
+                return Err(DataStoreError::CategoryCycle);
+            }
+        }
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let before = Category::find(conn, update_category.id)?;
+                diesel::update(category.filter(id.eq(update_category.id)))
+                    .set((
+                        name.eq(&update_category.name),
+                        category_description.eq(&update_category.category_description),
+                        is_income.eq(&update_category.is_income),
+                        parent_category.eq(&update_category.parent_category),
+                    ))
+                    .execute(conn)?;
+                audit_updated(conn, &before)
+            })
+            .map_err(DataStoreError::update)
+    }
+
+    fn delete_category(&mut self, category_id: i32, merge_into: i32) -> Result<(), DataStoreError> {
+        use schema::{budget, loan, payee, recurring_transaction, transaction_split};
+
+        if category_id == merge_into {
+            return Err(DataStoreError::DeleteError(
+                "A category cannot be merged into itself".to_string(),
+            ));
+        }
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let deleted = category
+                    .filter(category::id.eq(category_id))
+                    .select(Category::as_select())
+                    .first(conn)?;
+
+                let moved = money_transaction
+                    .filter(money_transaction::transaction_category.eq(category_id))
+                    .select(MoneyTransaction::as_select())
+                    .load(conn)?;
+                diesel::update(
+                    money_transaction
+                        .filter(money_transaction::transaction_category.eq(category_id)),
+                )
+                .set(money_transaction::transaction_category.eq(merge_into))
+                .execute(conn)?;
+                for moved_transaction in &moved {
+                    audit_updated(conn, moved_transaction)?;
+                }
+                diesel::update(
+                    transaction_split::table
+                        .filter(transaction_split::split_category.eq(category_id)),
+                )
+                .set(transaction_split::split_category.eq(merge_into))
+                .execute(conn)?;
+                diesel::update(
+                    recurring_transaction::table
+                        .filter(recurring_transaction::transaction_category.eq(category_id)),
+                )
+                .set(recurring_transaction::transaction_category.eq(merge_into))
+                .execute(conn)?;
+                diesel::update(payee::table.filter(payee::default_category.eq(category_id)))
+                    .set(payee::default_category.eq(merge_into))
+                    .execute(conn)?;
+                diesel::update(loan::table.filter(loan::interest_category.eq(category_id)))
+                    .set(loan::interest_category.eq(merge_into))
+                    .execute(conn)?;
+
+                let budgets = budget::table
+                    .filter(budget::budget_category.eq(category_id))
+                    .select(Budget::as_select())
+                    .load(conn)?;
+                for b in budgets {
+                    let merged = diesel::update(
+                        budget::table
+                            .filter(budget::budget_category.eq(merge_into))
+                            .filter(budget::budget_month.eq(b.budget_month)),
+                    )
+                    .set(budget::amount.eq(budget::amount + b.amount))
+                    .execute(conn)?;
+                    if merged > 0 {
+                        diesel::delete(budget::table.filter(budget::id.eq(b.id))).execute(conn)?;
+                    } else {
+                        diesel::update(budget::table.filter(budget::id.eq(b.id)))
+                            .set(budget::budget_category.eq(merge_into))
+                            .execute(conn)?;
+                    }
+                }
+
+                let children = category
+                    .filter(category::parent_category.eq(category_id))
+                    .select(Category::as_select())
+                    .load(conn)?;
+                diesel::update(category.filter(category::parent_category.eq(category_id)))
+                    .set(category::parent_category.eq(deleted.parent_category))
+                    .execute(conn)?;
+                for child in &children {
+                    audit_updated(conn, child)?;
+                }
+                diesel::delete(category.filter(category::id.eq(category_id))).execute(conn)?;
+                audit_deleted(conn, &deleted)?;
+
+                Ok(())
+            })
+            .map_err(DataStoreError::delete)
+    }
+
+    fn get_money_transactions(&mut self) -> Result<Vec<MoneyTransaction>, DataStoreError> {
+        let results = money_transaction
+            .select(MoneyTransaction::as_select())
+            .order(transaction_date.desc())
+            .load(&mut self.connection);
+
+        match results {
+            Ok(results) => return Ok(results),
+            Err(e) => return Err(DataStoreError::query(e)),
         }
     }
+
+    fn create_money_transaction(
+        &mut self,
+        new_money_transaction: &NewMoneyTransaction,
+    ) -> Result<i32, DataStoreError> {
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let created = diesel::insert_into(money_transaction::table)
+                    .values(new_money_transaction)
+                    .returning(money_transaction::id)
+                    .get_result::<i32>(conn)?;
+                audit_created::<MoneyTransaction>(conn, created)?;
+                Ok(created)
+            })
+            .map_err(DataStoreError::insert)
+    }
+
+    fn update_money_transaction(
+        &mut self,
+        update_money_transaction: &UpdateMoneyTransaction,
+    ) -> Result<(), DataStoreError> {
+        use schema::money_transaction::dsl::*;
+
+        self.atomically(|store| {
+            store.ensure_not_reconciled(update_money_transaction.id)?;
+            store.ensure_not_trade(update_money_transaction.id)?;
+
+            store
+                .connection
+                .transaction::<_, DieselError, _>(|conn| {
+                    let before = MoneyTransaction::find(conn, update_money_transaction.id)?;
+                    diesel::update(money_transaction.filter(id.eq(update_money_transaction.id)))
+                        .set((
+                            bank_account.eq(&update_money_transaction.bank_account),
+                            transaction_category.eq(&update_money_transaction.transaction_category),
+                            description.eq(&update_money_transaction.description),
+                            amount.eq(&update_money_transaction.amount),
+                            transaction_date.eq(&update_money_transaction.transaction_date),
+                            is_expense.eq(&update_money_transaction.is_expense),
+                            payee_id.eq(&update_money_transaction.payee_id),
+                        ))
+                        .execute(conn)?;
+                    audit_updated(conn, &before)
+                })
+                .map_err(DataStoreError::update)
+        })
+    }
+
+    fn delete_money_transaction(&mut self, transaction_id: i32) -> Result<(), DataStoreError> {
+        use schema::loan_payment;
+        use schema::money_transaction::dsl::*;
+
+        self.atomically(|store| {
+            store.ensure_not_reconciled(transaction_id)?;
+
+            store
+                .connection
+                .transaction::<_, DieselError, _>(|conn| {
+                    let peer = money_transaction
+                        .filter(id.eq(transaction_id))
+                        .select(transfer_peer)
+                        .first::<Option<i32>>(conn)?;
+
+                    let mut deleted: Vec<i32> =
+                        [Some(transaction_id), peer].into_iter().flatten().collect();
+                    let payments = loan_payment::table
+                        .filter(
+                            loan_payment::transfer_transaction
+                                .eq_any(&deleted)
+                                .or(loan_payment::interest_transaction.eq_any(&deleted)),
+                        )
+                        .select(LoanPayment::as_select())
+                        .load(conn)?;
+                    for payment in &payments {
+                        let payment_peer = money_transaction
+                            .filter(id.eq(payment.transfer_transaction))
+                            .select(transfer_peer)
+                            .first::<Option<i32>>(conn)?;
+                        deleted.extend(
+                            [
+                                Some(payment.transfer_transaction),
+                                payment_peer,
+                                payment.interest_transaction,
+                            ]
+                            .into_iter()
+                            .flatten(),
+                        );
+                    }
+                    deleted.sort();
+                    deleted.dedup();
+
+                    diesel::delete(
+                        loan_payment::table
+                            .filter(loan_payment::id.eq_any(payments.iter().map(|p| p.id))),
+                    )
+                    .execute(conn)?;
+                    diesel::delete(
+                        schema::investment_trade::table
+                            .filter(schema::investment_trade::cash_transaction.eq_any(&deleted)),
+                    )
+                    .execute(conn)?;
+                    diesel::delete(
+                        schema::transaction_tag::table
+                            .filter(schema::transaction_tag::transaction_id.eq_any(&deleted)),
+                    )
+                    .execute(conn)?;
+                    diesel::delete(
+                        schema::transaction_split::table
+                            .filter(schema::transaction_split::parent_transaction.eq_any(&deleted)),
+                    )
+                    .execute(conn)?;
+                    let removed = money_transaction
+                        .filter(id.eq_any(&deleted))
+                        .select(MoneyTransaction::as_select())
+                        .load(conn)?;
+                    diesel::delete(money_transaction.filter(id.eq_any(&deleted))).execute(conn)?;
+                    for removed_transaction in &removed {
+                        audit_deleted(conn, removed_transaction)?;
+                    }
+                    Ok(())
+                })
+                .map_err(DataStoreError::delete)
+        })
+    }
+
+    fn create_transfer(&mut self, new_transfer: &NewTransfer) -> Result<(), DataStoreError> {
+        use schema::money_transaction::dsl::*;
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let outgoing = diesel::insert_into(money_transaction)
+                    .values((
+                        bank_account.eq(new_transfer.from_account),
+                        description.eq(&new_transfer.description),
+                        amount.eq(new_transfer.amount),
+                        transaction_date.eq(new_transfer.transaction_date),
+                        is_expense.eq(true),
+                    ))
+                    .returning(id)
+                    .get_result::<i32>(conn)?;
+                let incoming = diesel::insert_into(money_transaction)
+                    .values((
+                        bank_account.eq(new_transfer.to_account),
+                        description.eq(&new_transfer.description),
+                        amount.eq(new_transfer.to_amount),
+                        transaction_date.eq(new_transfer.transaction_date),
+                        is_expense.eq(false),
+                        transfer_peer.eq(outgoing),
+                    ))
+                    .returning(id)
+                    .get_result::<i32>(conn)?;
+                diesel::update(money_transaction.filter(id.eq(outgoing)))
+                    .set(transfer_peer.eq(incoming))
+                    .execute(conn)?;
+                audit_created::<MoneyTransaction>(conn, outgoing)?;
+                audit_created::<MoneyTransaction>(conn, incoming)
+            })
+            .map_err(DataStoreError::insert)
+    }
+
+    fn update_transfer(&mut self, update_transfer: &UpdateTransfer) -> Result<(), DataStoreError> {
+        use schema::money_transaction::dsl::*;
+
+        self.atomically(|store| {
+            store.ensure_not_reconciled(update_transfer.id)?;
+
+            store
+                .connection
+                .transaction::<_, DieselError, _>(|conn| {
+                    let (peer, expense) = money_transaction
+                        .filter(id.eq(update_transfer.id))
+                        .select((transfer_peer, is_expense))
+                        .first::<(Option<i32>, bool)>(conn)?;
+                    let peer = peer.ok_or(DieselError::NotFound)?;
+                    let (outgoing, incoming) = if expense {
+                        (update_transfer.id, peer)
+                    } else {
+                        (peer, update_transfer.id)
+                    };
+
+                    for (transaction_id, account_id, transfer_amount) in [
+                        (
+                            outgoing,
+                            update_transfer.from_account,
+                            update_transfer.amount,
+                        ),
+                        (
+                            incoming,
+                            update_transfer.to_account,
+                            update_transfer.to_amount,
+                        ),
+                    ] {
+                        let before = MoneyTransaction::find(conn, transaction_id)?;
+                        diesel::update(money_transaction.filter(id.eq(transaction_id)))
+                            .set((
+                                bank_account.eq(account_id),
+                                description.eq(&update_transfer.description),
+                                amount.eq(transfer_amount),
+                                transaction_date.eq(update_transfer.transaction_date),
+                            ))
+                            .execute(conn)?;
+                        audit_updated(conn, &before)?;
+                    }
+                    Ok(())
+                })
+                .map_err(DataStoreError::update)
+        })
+    }
+
+    fn save_transaction(
+        &mut self,
+        editing: Option<i32>,
+        entry: &TransactionEntry,
+    ) -> Result<(), DataStoreError> {
+        self.atomically(|store| {
+            let is_transfer = matches!(entry, TransactionEntry::Transfer(_));
+            let kept = match editing {
+                Some(edited_id) => {
+                    let was_transfer = money_transaction
+                        .filter(money_transaction::id.eq(edited_id))
+                        .select(money_transaction::transfer_peer)
+                        .first::<Option<i32>>(&mut store.connection)
+                        .map_err(DataStoreError::query)?
+                        .is_some();
+                    if was_transfer == is_transfer {
+                        Some(edited_id)
+                    } else {
+                        store.delete_money_transaction(edited_id)?;
+                        None
+                    }
+                }
+                None => None,
+            };
+
+            match entry {
+                TransactionEntry::Transfer(transfer) => match kept {
+                    Some(kept_id) => store.update_transfer(&UpdateTransfer {
+                        id: kept_id,
+                        from_account: transfer.from_account,
+                        to_account: transfer.to_account,
+                        description: transfer.description.clone(),
+                        amount: transfer.amount,
+                        to_amount: transfer.to_amount,
+                        transaction_date: transfer.transaction_date,
+                    }),
+                    None => store.create_transfer(transfer),
+                },
+                TransactionEntry::Regular {
+                    transaction,
+                    payee,
+                    tag_names,
+                    splits,
+                } => {
+                    // remember the category and account used with the payee
+                    let saved_payee = match payee {
+                        Some(payee_name) => Some(store.save_payee(&NewPayee {
+                            name: payee_name.clone(),
+                            default_category: Some(transaction.transaction_category),
+                            default_account: Some(transaction.bank_account),
+                        })?),
+                        None => None,
+                    };
+                    let saved_id = match kept {
+                        Some(kept_id) => {
+                            store.update_money_transaction(&UpdateMoneyTransaction {
+                                id: kept_id,
+                                bank_account: transaction.bank_account,
+                                transaction_category: transaction.transaction_category,
+                                description: transaction.description.clone(),
+                                amount: transaction.amount,
+                                transaction_date: transaction.transaction_date,
+                                is_expense: transaction.is_expense,
+                                payee_id: saved_payee,
+                            })?;
+                            kept_id
+                        }
+                        None => store.create_money_transaction(&NewMoneyTransaction {
+                            bank_account: transaction.bank_account,
+                            transaction_category: transaction.transaction_category,
+                            description: transaction.description.clone(),
+                            amount: transaction.amount,
+                            transaction_date: transaction.transaction_date,
+                            is_expense: transaction.is_expense,
+                            payee_id: saved_payee,
+                        })?,
+                    };
+                    store.set_transaction_tags(saved_id, tag_names)?;
+                    store.set_transaction_splits(saved_id, splits)
+                }
+            }
+        })
+    }
+
+    fn get_currencies(&mut self) -> Result<Vec<Currency>, DataStoreError> {
+        let results = currency
+            .select(Currency::as_select())
+            .load(&mut self.connection);
+
+        match results {
+            Ok(results) => return Ok(results),
+            Err(e) => return Err(DataStoreError::query(e)),
+        }
+    }
+
+    fn get_currency_by_id(&mut self, target_id: i32) -> Result<Currency, DataStoreError> {
+        use crate::schema::currency::dsl::{currency, id};
+
+        let result = currency
+            .filter(id.eq(target_id))
+            .select(Currency::as_select())
+            .first(&mut self.connection);
+
+        match result {
+            Ok(result) => Ok(result),
+            Err(e) => Err(DataStoreError::query(e)),
+        }
+    }
+}
+
+impl Store {
+    /// The passphrase the ledger is encrypted with, if it is.
+    pub fn passphrase(&self) -> Option<&str> {
+        self.passphrase.as_deref()
+    }
+
+    /// Returns the balance of an account at a statement date, counting only
+    /// the cleared and reconciled transactions.
+    pub fn get_cleared_balance(
+        &mut self,
+        account_id: i32,
+        statement_date: &NaiveDate,
+        reporting_currency_id: i32,
+    ) -> Result<Money, DataStoreError> {
+        self.account_balance(account_id, reporting_currency_id, Some(statement_date))
+    }
+
+    /// Sums the balances of all accounts converted into the reporting currency.
+    pub fn get_net_worth(&mut self, reporting_currency_id: i32) -> Result<Money, DataStoreError> {
+        let reporting_currency = self.get_currency_by_id(reporting_currency_id)?;
+        let mut total = Money::zero(reporting_currency.scale as u32);
+
+        for (_, type_total) in self.get_account_type_totals(reporting_currency_id)? {
+            total = total.checked_add(type_total)?;
+        }
+
+        Ok(total)
+    }
+
+    /// Sums the balances of the accounts of each type, in the reporting currency.
+    /// Investment accounts add the market value of their holdings to their
+    /// cash. Types without accounts are left out.
+    pub fn get_account_type_totals(
+        &mut self,
+        reporting_currency_id: i32,
+    ) -> Result<Vec<(AccountType, Money)>, DataStoreError> {
+        let reporting_scale = self.get_currency_by_id(reporting_currency_id)?.scale as u32;
+        let today = Utc::now().date_naive();
+        let accounts = self.get_accounts()?;
+        let mut totals = vec![];
+
+        for account_kind in AccountType::ALL {
+            let mut total = Money::zero(reporting_scale);
+            let mut has_accounts = false;
+            for a in accounts.iter().filter(|a| a.account_type == account_kind) {
+                let mut balance = self.get_account_balance(a.id, reporting_currency_id)?;
+                if account_kind == AccountType::Investment {
+                    balance = balance.checked_add(
+                        self.get_investment_summary(a.id, &today, reporting_currency_id)?
+                            .market_value,
+                    )?;
+                }
+                total = total.checked_add(self.convert_money(
+                    balance,
+                    a.currency_id.unwrap_or(reporting_currency_id),
+                    reporting_currency_id,
+                    &today,
+                )?)?;
+                has_accounts = true;
+            }
+            if has_accounts {
+                totals.push((account_kind, total));
+            }
+        }
+
+        Ok(totals)
+    }
+
+    /// Sums the expenses of a category between two dates, converting each
+    /// transaction into the reporting currency at its own date. A split
+    /// transaction counts through its split lines.
+    pub fn calculate_expense_by_category(
+        &mut self,
+        category_id: i32,
+        start_date: &NaiveDate,
+        end_date: &NaiveDate,
+        reporting_currency_id: i32,
+    ) -> Result<Money, DataStoreError> {
+        let rows = money_transaction
+            .inner_join(account)
+            .filter(money_transaction::transaction_category.eq(category_id))
+            .filter(
+                money_transaction::transaction_date
+                    .between(start_date.and_hms(0, 0, 0), end_date.and_hms(23, 59, 59)),
+            )
+            .filter(money_transaction::is_expense.eq(true))
+            .filter(money_transaction::transfer_peer.is_null())
+            .filter(diesel::dsl::not(diesel::dsl::exists(
+                schema::transaction_split::table.filter(
+                    schema::transaction_split::parent_transaction.eq(money_transaction::id),
+                ),
+            )))
+            .select((
+                money_transaction::amount,
+                money_transaction::transaction_date,
+                account::currency_id,
+            ))
+            .load::<(i64, chrono::NaiveDateTime, Option<i32>)>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+
+        let split_rows = schema::transaction_split::table
+            .inner_join(money_transaction.inner_join(account))
+            .filter(schema::transaction_split::split_category.eq(category_id))
+            .filter(
+                money_transaction::transaction_date
+                    .between(start_date.and_hms(0, 0, 0), end_date.and_hms(23, 59, 59)),
+            )
+            .filter(money_transaction::is_expense.eq(true))
+            .select((
+                schema::transaction_split::amount,
+                money_transaction::transaction_date,
+                account::currency_id,
+            ))
+            .load::<(i64, chrono::NaiveDateTime, Option<i32>)>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+
+        self.sum_in_reporting_currency(rows.into_iter().chain(split_rows), reporting_currency_id)
+    }
+
+    /// Sums the expenses of a category and of all its subcategories.
+    pub fn calculate_rolled_up_expense_by_category(
+        &mut self,
+        category_id: i32,
+        start_date: &NaiveDate,
+        end_date: &NaiveDate,
+        reporting_currency_id: i32,
+    ) -> Result<Money, DataStoreError> {
+        let categories = self.get_categories()?;
+        let reporting_scale = self.get_currency_by_id(reporting_currency_id)?.scale as u32;
+
+        let mut total = Money::zero(reporting_scale);
+        for nested_id in category_with_descendants(&categories, category_id) {
+            total = total.checked_add(self.calculate_expense_by_category(
+                nested_id,
+                start_date,
+                end_date,
+                reporting_currency_id,
+            )?)?;
+        }
+
+        Ok(total)
+    }
+
+    /// Sums the expenses paid to a payee between two dates, in the reporting currency.
+    pub fn calculate_expense_by_payee(
+        &mut self,
+        target_payee_id: i32,
+        start_date: &NaiveDate,
+        end_date: &NaiveDate,
+        reporting_currency_id: i32,
+    ) -> Result<Money, DataStoreError> {
+        let rows = money_transaction
+            .inner_join(account)
+            .filter(money_transaction::payee_id.eq(target_payee_id))
+            .filter(
+                money_transaction::transaction_date
+                    .between(start_date.and_hms(0, 0, 0), end_date.and_hms(23, 59, 59)),
+            )
+            .filter(money_transaction::is_expense.eq(true))
+            .filter(money_transaction::transfer_peer.is_null())
+            .select((
+                money_transaction::amount,
+                money_transaction::transaction_date,
+                account::currency_id,
+            ))
+            .load::<(i64, chrono::NaiveDateTime, Option<i32>)>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+
+        self.sum_in_reporting_currency(rows, reporting_currency_id)
+    }
+
+    /// The audit log of a transaction, newest first.
+    pub fn get_transaction_history(
+        &mut self,
+        transaction_id: i32,
+    ) -> Result<Vec<AuditEntry>, DataStoreError> {
+        use schema::audit_log;
+
+        audit_log::table
+            .filter(audit_log::audited_table.eq(MoneyTransaction::TABLE))
+            .filter(audit_log::row_id.eq(transaction_id))
+            .order(audit_log::id.desc())
+            .select(AuditEntry::as_select())
+            .load(&mut self.connection)
+            .map_err(DataStoreError::query)
+    }
+
+    /// The transactions whose note, payee or category names hold every word
+    /// of `query`, each word also matching the start of longer ones. The
+    /// best matches come first, then the newest.
+    pub fn search_transactions(
+        &mut self,
+        query: &str,
+    ) -> Result<Vec<TransactionMatch>, DataStoreError> {
+        let Some(expression) = match_expression(query) else {
+            return Ok(vec![]);
+        };
+        let start = MATCH_START.to_string();
+        let end = MATCH_END.to_string();
+
+        diesel::sql_query(
+            "SELECT transaction_search.rowid AS transaction_id, \
+             highlight(transaction_search, 0, ?1, ?2) AS description, \
+             highlight(transaction_search, 1, ?1, ?2) AS payee, \
+             highlight(transaction_search, 2, ?1, ?2) AS category \
+             FROM transaction_search \
+             JOIN money_transaction ON money_transaction.id = transaction_search.rowid \
+             WHERE transaction_search MATCH ?3 \
+             ORDER BY transaction_search.rank, money_transaction.transaction_date DESC \
+             LIMIT ?4",
+        )
+        .bind::<diesel::sql_types::Text, _>(start)
+        .bind::<diesel::sql_types::Text, _>(end)
+        .bind::<diesel::sql_types::Text, _>(expression)
+        .bind::<diesel::sql_types::BigInt, _>(SEARCH_RESULTS)
+        .load::<TransactionMatch>(&mut self.connection)
+        .map_err(DataStoreError::query)
+    }
+
+    pub fn get_exchange_rates(&mut self) -> Result<Vec<ExchangeRate>, DataStoreError> {
+        use schema::exchange_rate::dsl::*;
+
+        let results = exchange_rate
+            .select(ExchangeRate::as_select())
+            .order(rate_date.desc())
+            .load(&mut self.connection);
+
+        match results {
+            Ok(results) => Ok(results),
+            Err(e) => Err(DataStoreError::query(e)),
+        }
+    }
+
+    pub fn create_exchange_rate(
+        &mut self,
+        new_exchange_rate: &NewExchangeRate,
+    ) -> Result<(), DataStoreError> {
+        let res = diesel::insert_into(schema::exchange_rate::table)
+            .values(new_exchange_rate)
+            .execute(&mut self.connection);
+
+        if let Err(e) = res {
+            return Err(DataStoreError::insert(e));
+        }
+
+        Ok(())
+    }
+
+    pub fn delete_exchange_rate(&mut self, exchange_rate_id: i32) -> Result<(), DataStoreError> {
+        use schema::exchange_rate::dsl::*;
+
+        let res = diesel::delete(exchange_rate.filter(id.eq(exchange_rate_id)))
+            .execute(&mut self.connection);
+
+        if let Err(e) = res {
+            return Err(DataStoreError::delete(e));
+        }
+
+        Ok(())
+    }
+
+    pub fn convert_money(
+        &mut self,
+        money: Money,
+        from: i32,
+        to: i32,
+        date: &NaiveDate,
+    ) -> Result<Money, DataStoreError> {
+        let to_scale = self.get_currency_by_id(to)?.scale as u32;
+        if from == to {
+            return money.rescale(to_scale);
+        }
+        let rate = self.get_exchange_rate(from, to, date)?;
+        Ok(money.convert(rate, to_scale))
+    }
+
+    pub fn get_recurring_transactions(
+        &mut self,
+    ) -> Result<Vec<RecurringTransaction>, DataStoreError> {
+        use schema::recurring_transaction::dsl::*;
+
+        let results = recurring_transaction
+            .select(RecurringTransaction::as_select())
+            .order(next_date.asc())
+            .load(&mut self.connection);
+
+        match results {
+            Ok(results) => Ok(results),
+            Err(e) => Err(DataStoreError::query(e)),
+        }
+    }
+
+    pub fn create_recurring_transaction(
+        &mut self,
+        new_recurring_transaction: &NewRecurringTransaction,
+    ) -> Result<(), DataStoreError> {
+        use schema::recurring_transaction::dsl::*;
+
+        // the first occurrence to post is the start date
+        let res = diesel::insert_into(recurring_transaction)
+            .values((
+                new_recurring_transaction,
+                next_date.eq(Some(new_recurring_transaction.start_date)),
+            ))
+            .execute(&mut self.connection);
+
+        if let Err(e) = res {
+            return Err(DataStoreError::insert(e));
+        }
+
+        Ok(())
+    }
+
+    /// Deletes a rule. Transactions it already posted are kept.
+    pub fn delete_recurring_transaction(
+        &mut self,
+        recurring_transaction_id: i32,
+    ) -> Result<(), DataStoreError> {
+        use schema::recurring_transaction::dsl::*;
+
+        let res = diesel::delete(recurring_transaction.filter(id.eq(recurring_transaction_id)))
+            .execute(&mut self.connection);
+
+        if let Err(e) = res {
+            return Err(DataStoreError::delete(e));
+        }
+
+        Ok(())
+    }
+
+    /// Posts the next occurrence of a rule as a transaction and moves the rule forward.
+    pub fn post_recurring_transaction(
+        &mut self,
+        recurring_transaction_id: i32,
+    ) -> Result<(), DataStoreError> {
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let rule = Self::find_recurring_transaction(conn, recurring_transaction_id)?;
+                Self::advance_recurring_transaction(conn, &rule, true)
+            })
+            .map_err(DataStoreError::update)
+    }
+
+    /// Moves a rule past its next occurrence without posting it.
+    pub fn skip_recurring_transaction(
+        &mut self,
+        recurring_transaction_id: i32,
+    ) -> Result<(), DataStoreError> {
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let rule = Self::find_recurring_transaction(conn, recurring_transaction_id)?;
+                Self::advance_recurring_transaction(conn, &rule, false)
+            })
+            .map_err(DataStoreError::update)
+    }
+
+    pub fn get_budget(
+        &mut self,
+        category_id: i32,
+        month: &NaiveDate,
+    ) -> Result<Option<Budget>, DataStoreError> {
+        use schema::budget::dsl::*;
+
+        budget
+            .filter(budget_category.eq(category_id))
+            .filter(budget_month.eq(first_of_month(month)))
+            .select(Budget::as_select())
+            .first(&mut self.connection)
+            .optional()
+            .map_err(DataStoreError::query)
+    }
+
+    /// Creates the budget of a category for a month, replacing any existing one.
+    pub fn set_budget(&mut self, new_budget: &NewBudget) -> Result<(), DataStoreError> {
+        use schema::budget::dsl::*;
+
+        let res = diesel::insert_into(budget)
+            .values((
+                budget_category.eq(new_budget.budget_category),
+                budget_month.eq(first_of_month(&new_budget.budget_month)),
+                amount.eq(&new_budget.amount),
+                rollover.eq(new_budget.rollover),
+            ))
+            .on_conflict((budget_category, budget_month))
+            .do_update()
+            .set((
+                amount.eq(&new_budget.amount),
+                rollover.eq(new_budget.rollover),
+            ))
+            .execute(&mut self.connection);
+
+        if let Err(e) = res {
+            return Err(DataStoreError::insert(e));
+        }
+
+        Ok(())
+    }
+
+    pub fn delete_budget(
+        &mut self,
+        category_id: i32,
+        month: &NaiveDate,
+    ) -> Result<(), DataStoreError> {
+        use schema::budget::dsl::*;
+
+        let res = diesel::delete(
+            budget
+                .filter(budget_category.eq(category_id))
+                .filter(budget_month.eq(first_of_month(month))),
+        )
+        .execute(&mut self.connection);
+
+        if let Err(e) = res {
+            return Err(DataStoreError::delete(e));
+        }
+
+        Ok(())
+    }
+
+    /// Compares the budget of a category in `month` with what was spent.
+    ///
+    /// When the budget rolls over, the unspent part of the previous month is
+    /// added, following the chain back as long as each month rolls over.
+    /// Overspending is not carried forward.
+    pub fn get_budget_summary(
+        &mut self,
+        category_id: i32,
+        month: &NaiveDate,
+        reporting_currency_id: i32,
+    ) -> Result<Option<BudgetSummary>, DataStoreError> {
+        let reporting_scale = self.get_currency_by_id(reporting_currency_id)?.scale as u32;
+
+        let mut month = first_of_month(month);
+        let mut chain = vec![];
+        while let Some(month_budget) = self.get_budget(category_id, &month)? {
+            let rollover = month_budget.rollover;
+            chain.push(month_budget);
+            match month.checked_sub_months(Months::new(1)) {
+                Some(previous) if rollover => month = previous,
+                _ => break,
+            }
+        }
+
+        let mut summary: Option<BudgetSummary> = None;
+        for month_budget in chain.into_iter().rev() {
+            let (start, end) = month_bounds(&month_budget.budget_month);
+            let rolled_over = match summary {
+                Some(previous) if month_budget.rollover && !previous.is_overspent() => {
+                    previous.remaining()
+                }
+                _ => Money::zero(reporting_scale),
+            };
+            summary = Some(BudgetSummary {
+                budgeted: month_budget.amount(reporting_scale),
+                rolled_over,
+                spent: self.calculate_expense_by_category(
+                    category_id,
+                    &start,
+                    &end,
+                    reporting_currency_id,
+                )?,
+                rollover: month_budget.rollover,
+            });
+        }
+
+        Ok(summary)
+    }
+
+    pub fn get_tags(&mut self) -> Result<Vec<Tag>, DataStoreError> {
+        use schema::tag::dsl::*;
+
+        let results = tag
+            .select(Tag::as_select())
+            .order(name.asc())
+            .load(&mut self.connection);
+
+        match results {
+            Ok(results) => Ok(results),
+            Err(e) => Err(DataStoreError::query(e)),
+        }
+    }
+
+    /// Every link between a transaction and a tag.
+    pub fn get_transaction_tags(&mut self) -> Result<Vec<TransactionTag>, DataStoreError> {
+        let results = schema::transaction_tag::table
+            .select(TransactionTag::as_select())
+            .load(&mut self.connection);
+
+        match results {
+            Ok(results) => Ok(results),
+            Err(e) => Err(DataStoreError::query(e)),
+        }
+    }
+
+    /// Replaces the tags of a transaction, creating the tags that do not exist yet.
+    pub fn set_transaction_tags(
+        &mut self,
+        target_transaction_id: i32,
+        tag_names: &[String],
+    ) -> Result<(), DataStoreError> {
+        use schema::transaction_tag::dsl::*;
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                diesel::delete(transaction_tag.filter(transaction_id.eq(target_transaction_id)))
+                    .execute(conn)?;
+
+                for tag_name in tag_names {
+                    let tag_name = tag_name.trim();
+                    if tag_name.is_empty() {
+                        continue;
+                    }
+                    diesel::insert_into(schema::tag::table)
+                        .values(&NewTag {
+                            name: tag_name.to_string(),
+                        })
+                        .on_conflict_do_nothing()
+                        .execute(conn)?;
+                    let target_tag_id = schema::tag::table
+                        .filter(schema::tag::name.eq(tag_name))
+                        .select(schema::tag::id)
+                        .first::<i32>(conn)?;
+                    diesel::insert_into(transaction_tag)
+                        .values((
+                            transaction_id.eq(target_transaction_id),
+                            tag_id.eq(target_tag_id),
+                        ))
+                        .on_conflict_do_nothing()
+                        .execute(conn)?;
+                }
+                Ok(())
+            })
+            .map_err(DataStoreError::update)
+    }
+
+    /// Sums the income and the expenses tagged with `target_tag_id`, in the
+    /// reporting currency. Transfers are left out.
+    pub fn calculate_totals_by_tag(
+        &mut self,
+        target_tag_id: i32,
+        reporting_currency_id: i32,
+    ) -> Result<(Money, Money), DataStoreError> {
+        let reporting_currency = self.get_currency_by_id(reporting_currency_id)?;
+
+        let rows = money_transaction
+            .inner_join(account)
+            .inner_join(schema::transaction_tag::table)
+            .filter(schema::transaction_tag::tag_id.eq(target_tag_id))
+            .filter(money_transaction::transfer_peer.is_null())
+            .select((
+                money_transaction::amount,
+                money_transaction::is_expense,
+                money_transaction::transaction_date,
+                account::currency_id,
+            ))
+            .load::<(i64, bool, chrono::NaiveDateTime, Option<i32>)>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+
+        let mut income = Money::zero(reporting_currency.scale as u32);
+        let mut expenses = Money::zero(reporting_currency.scale as u32);
+        for (minor_units, expense, date, account_currency) in rows {
+            let account_currency = account_currency.unwrap_or(reporting_currency_id);
+            let account_scale = self.get_currency_by_id(account_currency)?.scale as u32;
+            let converted = self.convert_money(
+                Money::new(minor_units, account_scale),
+                account_currency,
+                reporting_currency_id,
+                &date.date(),
+            )?;
+            if expense {
+                expenses = expenses.checked_add(converted)?;
+            } else {
+                income = income.checked_add(converted)?;
+            }
+        }
+
+        Ok((income, expenses))
+    }
+
+    pub fn get_transaction_splits(&mut self) -> Result<Vec<TransactionSplit>, DataStoreError> {
+        use schema::transaction_split::dsl::*;
+
+        let results = transaction_split
+            .select(TransactionSplit::as_select())
+            .order(id.asc())
+            .load(&mut self.connection);
+
+        match results {
+            Ok(results) => Ok(results),
+            Err(e) => Err(DataStoreError::query(e)),
+        }
+    }
+
+    /// Replaces the split lines of a transaction. The lines must add up to
+    /// the transaction amount; no lines at all removes the split.
+    pub fn set_transaction_splits(
+        &mut self,
+        parent_id: i32,
+        splits: &[NewTransactionSplit],
+    ) -> Result<(), DataStoreError> {
+        use schema::transaction_split::dsl::*;
+
+        let parent_amount = money_transaction
+            .filter(money_transaction::id.eq(parent_id))
+            .select(money_transaction::amount)
+            .first::<i64>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+        let split_total: i64 = splits.iter().map(|split| split.amount.minor_units()).sum();
+        if !splits.is_empty() && split_total != parent_amount {
+            return Err(DataStoreError::SplitMismatch);
+        }
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                diesel::delete(transaction_split.filter(parent_transaction.eq(parent_id)))
+                    .execute(conn)?;
+                for split in splits {
+                    diesel::insert_into(transaction_split)
+                        .values((
+                            parent_transaction.eq(parent_id),
+                            split_category.eq(split.split_category),
+                            amount.eq(&split.amount),
+                            note.eq(&split.note),
+                        ))
+                        .execute(conn)?;
+                }
+                Ok(())
+            })
+            .map_err(DataStoreError::update)
+    }
+
+    pub fn get_payees(&mut self) -> Result<Vec<Payee>, DataStoreError> {
+        use schema::payee::dsl::*;
+
+        let results = payee
+            .select(Payee::as_select())
+            .order(name.asc())
+            .load(&mut self.connection);
+
+        match results {
+            Ok(results) => Ok(results),
+            Err(e) => Err(DataStoreError::query(e)),
+        }
+    }
+
+    /// Creates the payee if needed and remembers its default category and
+    /// account. Returns the payee id.
+    pub fn save_payee(&mut self, new_payee: &NewPayee) -> Result<i32, DataStoreError> {
+        use schema::payee::dsl::*;
+
+        let payee_name = new_payee.name.trim();
+        diesel::insert_into(payee)
+            .values((
+                name.eq(payee_name),
+                default_category.eq(new_payee.default_category),
+                default_account.eq(new_payee.default_account),
+            ))
+            .on_conflict(name)
+            .do_update()
+            .set((
+                default_category.eq(new_payee.default_category),
+                default_account.eq(new_payee.default_account),
+            ))
+            .execute(&mut self.connection)
+            .map_err(DataStoreError::insert)?;
+
+        payee
+            .filter(name.eq(payee_name))
+            .select(id)
+            .first::<i32>(&mut self.connection)
+            .map_err(DataStoreError::query)
+    }
+
+    /// Marks a transaction as cleared or uncleared while reconciling. Both
+    /// sides of a transfer change together.
+    pub fn set_cleared_status(
+        &mut self,
+        transaction_id: i32,
+        status: ClearedStatus,
+    ) -> Result<(), DataStoreError> {
+        self.atomically(|store| {
+            store.ensure_not_reconciled(transaction_id)?;
+
+            store
+                .connection
+                .transaction::<_, DieselError, _>(|conn| {
+                    let peer = money_transaction
+                        .filter(money_transaction::id.eq(transaction_id))
+                        .select(money_transaction::transfer_peer)
+                        .first::<Option<i32>>(conn)?;
+                    for changed in [Some(transaction_id), peer].into_iter().flatten() {
+                        let before = MoneyTransaction::find(conn, changed)?;
+                        diesel::update(money_transaction.filter(money_transaction::id.eq(changed)))
+                            .set(money_transaction::cleared_status.eq(status))
+                            .execute(conn)?;
+                        audit_updated(conn, &before)?;
+                    }
+                    Ok(())
+                })
+                .map_err(DataStoreError::update)
+        })
+    }
+
+    /// Returns the most recent reconciliation of an account, if any.
+    pub fn get_last_reconciliation(
+        &mut self,
+        account_id: i32,
+    ) -> Result<Option<Reconciliation>, DataStoreError> {
+        use schema::reconciliation::dsl::*;
+
+        reconciliation
+            .filter(bank_account.eq(account_id))
+            .order((statement_date.desc(), id.desc()))
+            .select(Reconciliation::as_select())
+            .first(&mut self.connection)
+            .optional()
+            .map_err(DataStoreError::query)
+    }
+
+    /// Completes the reconciliation of an account against a statement: the
+    /// cleared balance has to match the statement balance, then every cleared
+    /// transaction up to the statement date becomes reconciled.
+    pub fn finish_reconciliation(
+        &mut self,
+        account_id: i32,
+        date: &NaiveDate,
+        balance: Money,
+        reporting_currency_id: i32,
+    ) -> Result<(), DataStoreError> {
+        use schema::reconciliation::dsl::*;
+
+        if self.get_cleared_balance(account_id, date, reporting_currency_id)? != balance {
+            return Err(DataStoreError::ReconciliationMismatch);
+        }
+
+        let end_of_day = date
+            .and_hms_opt(23, 59, 59)
+            .ok_or_else(|| DataStoreError::UpdateError("Invalid statement date".to_string()))?;
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let cleared = money_transaction
+                    .filter(money_transaction::bank_account.eq(account_id))
+                    .filter(money_transaction::cleared_status.eq(ClearedStatus::Cleared))
+                    .filter(money_transaction::transaction_date.le(end_of_day));
+                let reconciled = cleared.select(MoneyTransaction::as_select()).load(conn)?;
+                diesel::update(cleared)
+                    .set(money_transaction::cleared_status.eq(ClearedStatus::Reconciled))
+                    .execute(conn)?;
+                for reconciled_transaction in &reconciled {
+                    audit_updated(conn, reconciled_transaction)?;
+                }
+
+                diesel::insert_into(reconciliation)
+                    .values((
+                        bank_account.eq(account_id),
+                        statement_date.eq(date),
+                        statement_balance.eq(balance),
+                    ))
+                    .execute(conn)?;
+
+                Ok(())
+            })
+            .map_err(DataStoreError::update)
+    }
+
+    pub fn get_goals(&mut self) -> Result<Vec<Goal>, DataStoreError> {
+        use schema::goal::dsl::*;
+
+        goal.select(Goal::as_select())
+            .order(target_date.asc())
+            .load(&mut self.connection)
+            .map_err(DataStoreError::query)
+    }
+
+    pub fn create_goal(&mut self, new_goal: &NewGoal) -> Result<(), DataStoreError> {
+        diesel::insert_into(schema::goal::table)
+            .values(new_goal)
+            .execute(&mut self.connection)
+            .map_err(DataStoreError::insert)?;
+
+        Ok(())
+    }
+
+    /// Deletes a goal together with its contributions.
+    pub fn delete_goal(&mut self, goal_id: i32) -> Result<(), DataStoreError> {
+        use schema::{goal, goal_contribution};
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                diesel::delete(
+                    goal_contribution::table.filter(goal_contribution::goal_id.eq(goal_id)),
+                )
+                .execute(conn)?;
+                diesel::delete(goal::table.filter(goal::id.eq(goal_id))).execute(conn)?;
+                Ok(())
+            })
+            .map_err(DataStoreError::delete)
+    }
+
+    /// Records money put aside for a goal without a linked account.
+    pub fn add_goal_contribution(
+        &mut self,
+        contribution_goal: i32,
+        date: &NaiveDate,
+        contribution: Money,
+    ) -> Result<(), DataStoreError> {
+        use schema::goal_contribution::dsl::*;
+
+        diesel::insert_into(goal_contribution)
+            .values((
+                goal_id.eq(contribution_goal),
+                contribution_date.eq(date),
+                amount.eq(contribution),
+            ))
+            .execute(&mut self.connection)
+            .map_err(DataStoreError::insert)?;
+
+        Ok(())
+    }
+
+    /// Computes how much has been saved for a goal and the average monthly
+    /// contribution over the last `GOAL_HISTORY_MONTHS` months. A linked
+    /// account counts its balance and its net movements; other goals count
+    /// their recorded contributions.
+    pub fn get_goal_progress(
+        &mut self,
+        target: &Goal,
+        today: &NaiveDate,
+        reporting_currency_id: i32,
+    ) -> Result<GoalProgress, DataStoreError> {
+        use schema::goal_contribution;
+
+        let reporting_scale = self.get_currency_by_id(reporting_currency_id)?.scale as u32;
+        let history_start = first_of_month(today)
+            .checked_sub_months(Months::new(GOAL_HISTORY_MONTHS - 1))
+            .unwrap_or(*today);
+
+        let (saved, history) = match target.linked_account {
+            Some(account_id) => {
+                let balance = self.get_account_balance(account_id, reporting_currency_id)?;
+                let account_currency = account
+                    .filter(account::id.eq(account_id))
+                    .select(account::currency_id)
+                    .first::<Option<i32>>(&mut self.connection)
+                    .map_err(DataStoreError::query)?;
+                let saved = self.convert_money(
+                    balance,
+                    account_currency.unwrap_or(reporting_currency_id),
+                    reporting_currency_id,
+                    today,
+                )?;
+
+                let rows = money_transaction
+                    .filter(money_transaction::bank_account.eq(account_id))
+                    .filter(
+                        money_transaction::transaction_date
+                            .ge(history_start.and_time(NaiveTime::MIN)),
+                    )
+                    .select((
+                        money_transaction::amount,
+                        money_transaction::is_expense,
+                        money_transaction::transaction_date,
+                    ))
+                    .load::<(i64, bool, chrono::NaiveDateTime)>(&mut self.connection)
+                    .map_err(DataStoreError::query)?;
+                let first_date = rows.iter().map(|(_, _, date)| date.date()).min();
+                let movements = self.sum_in_reporting_currency(
+                    rows.into_iter().map(|(minor_units, expense, date)| {
+                        let signed = if expense { -minor_units } else { minor_units };
+                        (signed, date, account_currency)
+                    }),
+                    reporting_currency_id,
+                )?;
+                (saved, first_date.map(|date| (date, movements)))
+            }
+            None => {
+                let contributions = goal_contribution::table
+                    .filter(goal_contribution::goal_id.eq(target.id))
+                    .select((
+                        goal_contribution::amount,
+                        goal_contribution::contribution_date,
+                    ))
+                    .load::<(i64, NaiveDate)>(&mut self.connection)
+                    .map_err(DataStoreError::query)?;
+                let saved: i64 = contributions
+                    .iter()
+                    .map(|(minor_units, _)| minor_units)
+                    .sum();
+                let recent: Vec<&(i64, NaiveDate)> = contributions
+                    .iter()
+                    .filter(|(_, date)| *date >= history_start)
+                    .collect();
+                let first_date = recent.iter().map(|(_, date)| *date).min();
+                let movements: i64 = recent.iter().map(|(minor_units, _)| minor_units).sum();
+                (
+                    Money::new(saved, reporting_scale),
+                    first_date.map(|date| (date, Money::new(movements, reporting_scale))),
+                )
+            }
+        };
+
+        // average over the months since the first recent movement
+        let monthly_contribution = match history {
+            Some((first_date, movements)) => {
+                let months = (today.year() - first_date.year()) * 12 + today.month() as i32
+                    - first_date.month() as i32
+                    + 1;
+                Money::new(
+                    movements.minor_units() / months.max(1) as i64,
+                    reporting_scale,
+                )
+            }
+            None => Money::zero(reporting_scale),
+        };
+
+        Ok(GoalProgress {
+            target: target.target_amount(reporting_scale),
+            saved,
+            monthly_contribution,
+        })
+    }
+
+    pub fn get_loans(&mut self) -> Result<Vec<Loan>, DataStoreError> {
+        use schema::loan::dsl::*;
+
+        loan.select(Loan::as_select())
+            .load(&mut self.connection)
+            .map_err(DataStoreError::query)
+    }
+
+    /// Opens a loan account together with its terms. The initial balance of
+    /// the account is the principal.
+    pub fn create_loan(
+        &mut self,
+        new_account: &NewAccount,
+        new_loan: &NewLoan,
+    ) -> Result<(), DataStoreError> {
+        use schema::loan;
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let created = diesel::insert_into(account::table)
+                    .values(new_account)
+                    .returning(account::id)
+                    .get_result::<i32>(conn)?;
+                audit_created::<Account>(conn, created)?;
+                diesel::insert_into(loan::table)
+                    .values((
+                        loan::loan_account.eq(created),
+                        loan::principal.eq(new_account.initial_balance),
+                        loan::annual_rate.eq(new_loan.annual_rate),
+                        loan::term.eq(new_loan.term),
+                        loan::frequency.eq(&new_loan.frequency),
+                        loan::first_payment.eq(new_loan.first_payment),
+                        loan::payment_account.eq(new_loan.payment_account),
+                        loan::interest_category.eq(new_loan.interest_category),
+                    ))
+                    .execute(conn)?;
+                Ok(())
+            })
+            .map_err(DataStoreError::insert)
+    }
+
+    pub fn get_loan_payments(
+        &mut self,
+        target_loan: i32,
+    ) -> Result<Vec<LoanPayment>, DataStoreError> {
+        use schema::loan_payment::dsl::*;
+
+        loan_payment
+            .filter(loan_id.eq(target_loan))
+            .select(LoanPayment::as_select())
+            .order(payment_number.asc())
+            .load(&mut self.connection)
+            .map_err(DataStoreError::query)
+    }
+
+    /// The next payment of a loan, split according to what is still owed on
+    /// the loan account, or `None` once it is paid off.
+    pub fn get_next_loan_installment(
+        &mut self,
+        target: &Loan,
+        reporting_currency_id: i32,
+    ) -> Result<Option<Installment>, DataStoreError> {
+        use schema::loan_payment;
+
+        let owed = -self.get_account_balance(target.loan_account, reporting_currency_id)?;
+        if owed.is_negative() || owed.is_zero() {
+            return Ok(None);
+        }
+
+        let posted = loan_payment::table
+            .filter(loan_payment::loan_id.eq(target.id))
+            .count()
+            .get_result::<i64>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+
+        Ok(Some(target.installment(posted as i32 + 1, owed)))
+    }
+
+    /// Posts the next payment of a loan on its due date: the principal is
+    /// transferred from the payment account to the loan account and the
+    /// interest is booked as an expense under the interest category.
+    pub fn post_loan_payment(
+        &mut self,
+        target: &Loan,
+        reporting_currency_id: i32,
+    ) -> Result<(), DataStoreError> {
+        use schema::loan_payment;
+
+        let installment = self
+            .get_next_loan_installment(target, reporting_currency_id)?
+            .ok_or(DataStoreError::LoanPaidOff)?;
+        let loan_currency =
+            self.get_account_currency(target.loan_account, reporting_currency_id)?;
+        let payment_currency =
+            self.get_account_currency(target.payment_account, reporting_currency_id)?;
+        let principal_paid = self.convert_money(
+            installment.principal,
+            loan_currency.id,
+            payment_currency.id,
+            &installment.payment_date,
+        )?;
+        let interest_paid = self.convert_money(
+            installment.interest,
+            loan_currency.id,
+            payment_currency.id,
+            &installment.payment_date,
+        )?;
+        let loan_name = account
+            .filter(account::id.eq(target.loan_account))
+            .select(account::name)
+            .first::<String>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+        let payment_time = installment.payment_date.and_time(NaiveTime::MIN);
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                use schema::money_transaction::dsl::*;
+
+                let outgoing = diesel::insert_into(money_transaction)
+                    .values((
+                        bank_account.eq(target.payment_account),
+                        description.eq(&loan_name),
+                        amount.eq(principal_paid),
+                        transaction_date.eq(payment_time),
+                        is_expense.eq(true),
+                    ))
+                    .returning(id)
+                    .get_result::<i32>(conn)?;
+                let incoming = diesel::insert_into(money_transaction)
+                    .values((
+                        bank_account.eq(target.loan_account),
+                        description.eq(&loan_name),
+                        amount.eq(installment.principal),
+                        transaction_date.eq(payment_time),
+                        is_expense.eq(false),
+                        transfer_peer.eq(outgoing),
+                    ))
+                    .returning(id)
+                    .get_result::<i32>(conn)?;
+                diesel::update(money_transaction.filter(id.eq(outgoing)))
+                    .set(transfer_peer.eq(incoming))
+                    .execute(conn)?;
+
+                let interest_transaction = if interest_paid.is_zero() {
+                    None
+                } else {
+                    Some(
+                        diesel::insert_into(money_transaction)
+                            .values((
+                                bank_account.eq(target.payment_account),
+                                transaction_category.eq(target.interest_category),
+                                description.eq(&loan_name),
+                                amount.eq(interest_paid),
+                                transaction_date.eq(payment_time),
+                                is_expense.eq(true),
+                            ))
+                            .returning(id)
+                            .get_result::<i32>(conn)?,
+                    )
+                };
+                for created in [Some(outgoing), Some(incoming), interest_transaction]
+                    .into_iter()
+                    .flatten()
+                {
+                    audit_created::<MoneyTransaction>(conn, created)?;
+                }
+
+                diesel::insert_into(loan_payment::table)
+                    .values((
+                        loan_payment::loan_id.eq(target.id),
+                        loan_payment::payment_number.eq(installment.number),
+                        loan_payment::payment_date.eq(installment.payment_date),
+                        loan_payment::principal.eq(installment.principal),
+                        loan_payment::interest.eq(installment.interest),
+                        loan_payment::transfer_transaction.eq(incoming),
+                        loan_payment::interest_transaction.eq(interest_transaction),
+                    ))
+                    .execute(conn)?;
+                Ok(())
+            })
+            .map_err(DataStoreError::insert)
+    }
+
+    pub fn get_securities(&mut self) -> Result<Vec<Security>, DataStoreError> {
+        use schema::security::dsl::*;
+
+        security
+            .select(Security::as_select())
+            .order(symbol.asc())
+            .load(&mut self.connection)
+            .map_err(DataStoreError::query)
+    }
+
+    pub fn create_security(&mut self, new_security: &NewSecurity) -> Result<(), DataStoreError> {
+        diesel::insert_into(schema::security::table)
+            .values(new_security)
+            .execute(&mut self.connection)
+            .map_err(DataStoreError::insert)?;
+
+        Ok(())
+    }
+
+    /// Returns the price history of a security, latest first.
+    pub fn get_security_prices(
+        &mut self,
+        target_security: i32,
+    ) -> Result<Vec<SecurityPrice>, DataStoreError> {
+        use schema::security_price::dsl::*;
+
+        security_price
+            .filter(security_id.eq(target_security))
+            .select(SecurityPrice::as_select())
+            .order(price_date.desc())
+            .load(&mut self.connection)
+            .map_err(DataStoreError::query)
+    }
+
+    /// Records the prices of a security, replacing the ones already known
+    /// for the same dates.
+    pub fn set_security_prices(
+        &mut self,
+        target_security: i32,
+        prices: &[(NaiveDate, f64)],
+    ) -> Result<(), DataStoreError> {
+        use schema::security_price::dsl::*;
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                for (date, new_price) in prices {
+                    diesel::insert_into(security_price)
+                        .values((
+                            security_id.eq(target_security),
+                            price_date.eq(date),
+                            price.eq(new_price),
+                        ))
+                        .on_conflict((security_id, price_date))
+                        .do_update()
+                        .set(price.eq(new_price))
+                        .execute(conn)?;
+                }
+                Ok(())
+            })
+            .map_err(DataStoreError::insert)
+    }
+
+    pub fn delete_security_price(&mut self, price_id: i32) -> Result<(), DataStoreError> {
+        use schema::security_price::dsl::*;
+
+        diesel::delete(security_price.filter(id.eq(price_id)))
+            .execute(&mut self.connection)
+            .map_err(DataStoreError::delete)?;
+
+        Ok(())
+    }
+
+    pub fn get_investment_trades(&mut self) -> Result<Vec<InvestmentTrade>, DataStoreError> {
+        use schema::investment_trade::dsl::*;
+
+        investment_trade
+            .select(InvestmentTrade::as_select())
+            .order((trade_date.desc(), id.desc()))
+            .load(&mut self.connection)
+            .map_err(DataStoreError::query)
+    }
+
+    /// Books a buy, sell or dividend together with its cash transaction on
+    /// the investment account.
+    pub fn create_trade(
+        &mut self,
+        new_trade: &NewTrade,
+        reporting_currency_id: i32,
+    ) -> Result<(), DataStoreError> {
+        use schema::investment_trade;
+
+        if new_trade.trade_kind == TradeKind::Sell {
+            let held = self
+                .account_holdings(new_trade.bank_account, reporting_currency_id)?
+                .iter()
+                .find(|h| h.security_id == new_trade.security_id)
+                .map(|h| h.quantity())
+                .unwrap_or(0.);
+            if new_trade.quantity > held + 1e-9 {
+                return Err(DataStoreError::InsufficientQuantity);
+            }
+        }
+        let security_symbol = schema::security::table
+            .filter(schema::security::id.eq(new_trade.security_id))
+            .select(schema::security::symbol)
+            .first::<String>(&mut self.connection)
+            .map_err(DataStoreError::query)?;
+        let cash = new_trade.amount();
+
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                use schema::money_transaction::dsl::*;
+
+                let cash_transaction = diesel::insert_into(money_transaction)
+                    .values((
+                        bank_account.eq(new_trade.bank_account),
+                        transaction_category.eq(new_trade
+                            .dividend_category
+                            .filter(|_| new_trade.trade_kind == TradeKind::Dividend)),
+                        description.eq(&security_symbol),
+                        amount.eq(cash),
+                        transaction_date.eq(new_trade.trade_date.and_time(NaiveTime::MIN)),
+                        is_expense.eq(new_trade.trade_kind == TradeKind::Buy),
+                    ))
+                    .returning(id)
+                    .get_result::<i32>(conn)?;
+                audit_created::<MoneyTransaction>(conn, cash_transaction)?;
+                diesel::insert_into(investment_trade::table)
+                    .values((
+                        investment_trade::bank_account.eq(new_trade.bank_account),
+                        investment_trade::security_id.eq(new_trade.security_id),
+                        investment_trade::trade_kind.eq(new_trade.trade_kind),
+                        investment_trade::trade_date.eq(new_trade.trade_date),
+                        investment_trade::quantity.eq(new_trade.quantity),
+                        investment_trade::price.eq(new_trade.price),
+                        investment_trade::fees.eq(new_trade.fees),
+                        investment_trade::amount.eq(cash),
+                        investment_trade::cash_transaction.eq(cash_transaction),
+                    ))
+                    .execute(conn)?;
+                Ok(())
+            })
+            .map_err(DataStoreError::insert)
+    }
+
+    pub fn set_cost_basis_method(
+        &mut self,
+        account_id: i32,
+        method: CostBasisMethod,
+    ) -> Result<(), DataStoreError> {
+        self.connection
+            .transaction::<_, DieselError, _>(|conn| {
+                let before = Account::find(conn, account_id)?;
+                diesel::update(account.filter(account::id.eq(account_id)))
+                    .set(account::cost_basis_method.eq(method))
+                    .execute(conn)?;
+                audit_updated(conn, &before)
+            })
+            .map_err(DataStoreError::update)
+    }
+
+    /// Values the holdings of an investment account at their latest price on
+    /// `today`, in the account currency.
+    pub fn get_investment_summary(
+        &mut self,
+        account_id: i32,
+        today: &NaiveDate,
+        reporting_currency_id: i32,
+    ) -> Result<InvestmentSummary, DataStoreError> {
+        let account_scale = self
+            .get_account_currency(account_id, reporting_currency_id)?
+            .scale as u32;
+        let mut summary = InvestmentSummary {
+            holdings: vec![],
+            market_value: Money::zero(account_scale),
+            cost_basis: Money::zero(account_scale),
+            unrealized_gain: Money::zero(account_scale),
+            realized_gain: Money::zero(account_scale),
+            dividends: Money::zero(account_scale),
+        };
+
+        for holding in self.account_holdings(account_id, reporting_currency_id)? {
+            let latest_price = self.latest_security_price(holding.security_id, today)?;
+            let price = latest_price.unwrap_or(0.);
+            summary.market_value = summary
+                .market_value
+                .checked_add(holding.market_value(price))?;
+            summary.cost_basis = summary.cost_basis.checked_add(holding.cost_basis())?;
+            summary.unrealized_gain = summary
+                .unrealized_gain
+                .checked_add(holding.unrealized_gain(price))?;
+            summary.realized_gain = summary.realized_gain.checked_add(holding.realized_gain)?;
+            summary.dividends = summary.dividends.checked_add(holding.dividends)?;
+            summary.holdings.push((holding, latest_price));
+        }
+
+        Ok(summary)
+    }
+}
+
+/// Connects to the database at `database_path` and makes sure it can be
+/// read, which an encrypted one only can with the right `passphrase`.
+fn connect(
+    database_path: &Path,
+    passphrase: Option<&str>,
+) -> Result<SqliteConnection, DataStoreError> {
+    let mut connection = SqliteConnection::establish(&database_path.to_string_lossy())
+        .map_err(|_| DataStoreError::Connection)?;
+    if let Some(passphrase) = passphrase {
+        // the key has to be set before anything else reads the file
+        connection
+            .batch_execute(&format!("PRAGMA key = {};", sql_literal(passphrase)))
+            .map_err(|_| DataStoreError::Connection)?;
+    }
+    connection
+        .batch_execute("SELECT count(*) FROM sqlite_master;")
+        .map_err(|e| match passphrase {
+            Some(_) => DataStoreError::WrongPassphrase,
+            // a plain file that cannot be read is not a database anymore
+            None => DataStoreError::IntegrityError(e.to_string()),
+        })?;
+    // SQLite leaves foreign keys unchecked unless it is built otherwise,
+    // while the ledger relies on them to refuse dangling references
+    connection
+        .batch_execute("PRAGMA foreign_keys = ON;")
+        .map_err(|_| DataStoreError::Connection)?;
+    Ok(connection)
+}
+
+/// Copies the whole database of `connection` into a new database file,
+/// encrypted with `passphrase` or in plain text.
+fn export(
+    connection: &mut SqliteConnection,
+    target: &Path,
+    passphrase: Option<&str>,
+) -> Result<(), DataStoreError> {
+    connection
+        .batch_execute(&format!(
+            "ATTACH DATABASE {} AS export KEY {};
+            SELECT sqlcipher_export('export');
+            DETACH DATABASE export;",
+            sql_literal(&target.to_string_lossy()),
+            sql_literal(passphrase.unwrap_or_default()),
+        ))
+        .map_err(|e| DataStoreError::LedgerFileError(e.to_string()))
+}
+
+/// Replaces the database file at `database_path`, which `connection` has
+/// open, with a copy encrypted with `passphrase` or in plain text.
+fn rewrite(
+    connection: &mut SqliteConnection,
+    database_path: &Path,
+    passphrase: Option<&str>,
+) -> Result<(), DataStoreError> {
+    let exported = database_path.with_extension("rekey");
+    let _ = std::fs::remove_file(&exported);
+    export(connection, &exported, passphrase)?;
+    std::fs::rename(&exported, database_path)
+        .map_err(|e| DataStoreError::LedgerFileError(e.to_string()))
+}
+
+/// Runs the `integrity_check` or the faster `quick_check` pragma, which list
+/// the problems found or a single "ok".
+fn integrity_check(connection: &mut SqliteConnection, check: &str) -> Result<(), DataStoreError> {
+    let problems = diesel::sql_query(format!(
+        "SELECT {check} AS integrity_check FROM pragma_{check}"
+    ))
+    .load::<IntegrityCheck>(connection)
+    .map_err(|e| DataStoreError::IntegrityError(e.to_string()))?
+    .into_iter()
+    .map(|row| row.integrity_check)
+    .filter(|row| row != "ok")
+    .collect::<Vec<String>>();
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(DataStoreError::IntegrityError(problems.join(", ")))
+    }
+}
+
+/// A string quoted as an SQL literal, for the statements that cannot take
+/// bound parameters.
+fn sql_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Applies the pending migrations. Amounts written before they had a
+/// currency are converted with the scale of `legacy_currency_id`, which the
+/// migrations read from a temporary table of the connection.
+fn migrate(
+    connection: &mut SqliteConnection,
+    legacy_currency_id: Option<i32>,
+) -> Result<(), DataStoreError> {
+    let legacy_currency_id =
+        legacy_currency_id.map_or("NULL".to_string(), |legacy| legacy.to_string());
+    connection
+        .batch_execute(&format!(
+            "CREATE TEMP TABLE IF NOT EXISTS migration_context (reporting_currency_id INTEGER); \
+             DELETE FROM migration_context; \
+             INSERT INTO migration_context VALUES ({legacy_currency_id});"
+        ))
+        .map_err(|e| DataStoreError::MigrationError(e.to_string()))?;
+    connection
+        .run_pending_migrations(crate::MIGRATIONS)
+        .map(|_| ())
+        .map_err(|e| DataStoreError::MigrationError(e.to_string()))?;
+    connection
+        .batch_execute("DROP TABLE migration_context;")
+        .map_err(|e| DataStoreError::MigrationError(e.to_string()))
+}
+
+/// An FTS5 query matching the words of `query`, each as a prefix. Words
+/// are quoted, so the operators of the query syntax are searched for as
+/// they are typed.
+fn match_expression(query: &str) -> Option<String> {
+    let words = query
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<String>>();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// The `name` column of `pragma_table_list` and `PRAGMA table_info`.
+#[derive(QueryableByName)]
+struct SqlName {
+    #[diesel(sql_type = diesel::sql_types::Text, column_name = name)]
+    object_name: String,
+}
+
+/// The triggers that journal the statement reversing each insert, update
+/// and delete on `table`. Rows are addressed by rowid, which every ledger
+/// table has.
+fn journal_triggers(table: &str, columns: &[String]) -> String {
+    let names = columns
+        .iter()
+        .map(|column| format!("\"{column}\""))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let values = columns
+        .iter()
+        .map(|column| format!("quote(old.\"{column}\")"))
+        .collect::<Vec<String>>()
+        .join(" || ', ' || ");
+    let assignments = columns
+        .iter()
+        .map(|column| format!("'\"{column}\" = ' || quote(old.\"{column}\")"))
+        .collect::<Vec<String>>()
+        .join(" || ', ' || ");
+
+    format!(
+        "DROP TRIGGER IF EXISTS \"{table}_journal_insert\";
+        CREATE TRIGGER \"{table}_journal_insert\" AFTER INSERT ON \"{table}\" BEGIN
+            INSERT INTO undo_log (sql_text)
+            VALUES ('DELETE FROM \"{table}\" WHERE rowid = ' || new.rowid);
+        END;
+        DROP TRIGGER IF EXISTS \"{table}_journal_update\";
+        CREATE TRIGGER \"{table}_journal_update\" AFTER UPDATE ON \"{table}\" BEGIN
+            INSERT INTO undo_log (sql_text)
+            VALUES ('UPDATE \"{table}\" SET ' || {assignments} || ' WHERE rowid = ' || new.rowid);
+        END;
+        DROP TRIGGER IF EXISTS \"{table}_journal_delete\";
+        CREATE TRIGGER \"{table}_journal_delete\" BEFORE DELETE ON \"{table}\" BEGIN
+            INSERT INTO undo_log (sql_text)
+            VALUES ('INSERT INTO \"{table}\" (rowid, {names}) VALUES (' || old.rowid || ', ' || {values} || ')');
+        END;
+        "
+    )
+}
+
+/// The table and rowid a journaled statement changes, read back from the
+/// statements written by [`journal_triggers`].
+fn journaled_row(statement: &str) -> Option<(&str, i32)> {
+    let (_, rest) = statement.split_once('"')?;
+    let (table, rest) = rest.split_once('"')?;
+    let row = match statement.split_once(' ')?.0 {
+        "INSERT" => rest.split_once("VALUES (")?.1.split_once(',')?.0,
+        _ => rest.rsplit_once("WHERE rowid = ")?.1,
+    };
+    Some((table, row.trim().parse().ok()?))
+}
+
+/// The last journaled statement that belongs to a step.
+fn last_recorded_seq(conn: &mut SqliteConnection) -> QueryResult<i32> {
+    use schema::change_journal;
+
+    change_journal::table
+        .select(diesel::dsl::max(change_journal::last_seq))
+        .first::<Option<i32>>(conn)
+        .map(|seq| seq.unwrap_or(0))
+}
+
+/// Removes steps from the journal together with their statements.
+fn forget_steps(conn: &mut SqliteConnection, steps: &[ChangeStep]) -> QueryResult<()> {
+    use schema::{change_journal, undo_log};
+
+    for step in steps {
+        diesel::delete(
+            undo_log::table.filter(undo_log::seq.between(step.first_seq, step.last_seq)),
+        )
+        .execute(conn)?;
+        diesel::delete(change_journal::table.find(step.id)).execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Picks the rate to convert `from` into `to` at `date` out of `rates`, the
+/// way [`Store::get_exchange_rate`] picks it out of the ledger.
+fn pick_exchange_rate(rates: &[ExchangeRate], from: i32, to: i32, date: &NaiveDate) -> Option<f64> {
+    let find = |from: i32, to: i32| {
+        let matching = || {
+            rates
+                .iter()
+                .filter(move |r| r.from_currency == from && r.to_currency == to)
+        };
+        matching()
+            .filter(|r| r.rate_date <= *date)
+            .max_by_key(|r| r.rate_date)
+            .or_else(|| {
+                matching()
+                    .filter(|r| r.rate_date > *date)
+                    .min_by_key(|r| r.rate_date)
+            })
+            .map(|r| r.rate)
+    };
+
+    find(from, to).or_else(|| {
+        find(to, from)
+            .filter(|inverse| *inverse != 0.)
+            .map(|inverse| 1. / inverse)
+    })
+}
+
+fn first_of_month(date: &NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(*date)
+}
+
+/// The first and the last day of the month of `date`.
+fn month_bounds(date: &NaiveDate) -> (NaiveDate, NaiveDate) {
+    let start = first_of_month(date);
+    let end = start
+        .checked_add_months(Months::new(1))
+        .and_then(|next| next.pred_opt())
+        .unwrap_or(start);
+    (start, end)
+}
+
+/// A table whose changes are written to the audit log.
+trait Audited: Sized {
+    const TABLE: &'static str;
+
+    fn find(conn: &mut SqliteConnection, row_id: i32) -> QueryResult<Self>;
+
+    fn row_id(&self) -> i32;
+
+    /// The audited columns with their values as text.
+    fn audit_fields(&self) -> Vec<(&'static str, String)>;
+
+    /// Sets `updated_at`, and `created_at` as well for a new row.
+    fn stamp(
+        conn: &mut SqliteConnection,
+        row_id: i32,
+        created: bool,
+        now: NaiveDateTime,
+    ) -> QueryResult<usize>;
+}
+
+impl Audited for Account {
+    const TABLE: &'static str = "account";
+
+    fn find(conn: &mut SqliteConnection, row_id: i32) -> QueryResult<Self> {
+        account
+            .filter(account::id.eq(row_id))
+            .select(Account::as_select())
+            .first(conn)
+    }
+
+    fn row_id(&self) -> i32 {
+        self.id
+    }
+
+    fn audit_fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("name", self.name.clone()),
+            ("account_type", self.account_type.as_str().to_string()),
+            ("account_description", self.account_description.clone()),
+            ("initial_balance", self.initial_balance.to_string()),
+            ("currency_id", audit_value(self.currency_id)),
+            ("is_archived", self.is_archived.to_string()),
+            (
+                "cost_basis_method",
+                self.cost_basis_method.as_str().to_string(),
+            ),
+        ]
+    }
+
+    fn stamp(
+        conn: &mut SqliteConnection,
+        row_id: i32,
+        created: bool,
+        now: NaiveDateTime,
+    ) -> QueryResult<usize> {
+        let target = account.filter(account::id.eq(row_id));
+        if created {
+            diesel::update(target)
+                .set((account::created_at.eq(now), account::updated_at.eq(now)))
+                .execute(conn)
+        } else {
+            diesel::update(target)
+                .set(account::updated_at.eq(now))
+                .execute(conn)
+        }
+    }
+}
+
+impl Audited for Category {
+    const TABLE: &'static str = "category";
+
+    fn find(conn: &mut SqliteConnection, row_id: i32) -> QueryResult<Self> {
+        category
+            .filter(category::id.eq(row_id))
+            .select(Category::as_select())
+            .first(conn)
+    }
+
+    fn row_id(&self) -> i32 {
+        self.id
+    }
+
+    fn audit_fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("name", self.name.clone()),
+            ("category_description", self.category_description.clone()),
+            ("is_income", self.is_income.to_string()),
+            ("parent_category", audit_value(self.parent_category)),
+        ]
+    }
+
+    fn stamp(
+        conn: &mut SqliteConnection,
+        row_id: i32,
+        created: bool,
+        now: NaiveDateTime,
+    ) -> QueryResult<usize> {
+        let target = category.filter(category::id.eq(row_id));
+        if created {
+            diesel::update(target)
+                .set((category::created_at.eq(now), category::updated_at.eq(now)))
+                .execute(conn)
+        } else {
+            diesel::update(target)
+                .set(category::updated_at.eq(now))
+                .execute(conn)
+        }
+    }
+}
+
+impl Audited for MoneyTransaction {
+    const TABLE: &'static str = "money_transaction";
+
+    fn find(conn: &mut SqliteConnection, row_id: i32) -> QueryResult<Self> {
+        money_transaction
+            .filter(money_transaction::id.eq(row_id))
+            .select(MoneyTransaction::as_select())
+            .first(conn)
+    }
+
+    fn row_id(&self) -> i32 {
+        self.id
+    }
+
+    fn audit_fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("bank_account", self.bank_account.to_string()),
+            (
+                "transaction_category",
+                audit_value(self.transaction_category),
+            ),
+            ("description", self.description.clone()),
+            ("amount", self.amount.to_string()),
+            (
+                "transaction_date",
+                self.transaction_date
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+            ),
+            ("is_expense", self.is_expense.to_string()),
+            ("transfer_peer", audit_value(self.transfer_peer)),
+            ("payee_id", audit_value(self.payee_id)),
+            ("cleared_status", self.cleared_status.as_str().to_string()),
+        ]
+    }
+
+    fn stamp(
+        conn: &mut SqliteConnection,
+        row_id: i32,
+        created: bool,
+        now: NaiveDateTime,
+    ) -> QueryResult<usize> {
+        let target = money_transaction.filter(money_transaction::id.eq(row_id));
+        if created {
+            diesel::update(target)
+                .set((
+                    money_transaction::created_at.eq(now),
+                    money_transaction::updated_at.eq(now),
+                ))
+                .execute(conn)
+        } else {
+            diesel::update(target)
+                .set(money_transaction::updated_at.eq(now))
+                .execute(conn)
+        }
+    }
+}
+
+fn audit_value(value: Option<i32>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// The name of the session user, recorded with every audited change so
+/// that people sharing a ledger can tell their changes apart.
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+fn write_audit_entry(
+    conn: &mut SqliteConnection,
+    table: &str,
+    audited_row: i32,
+    audit_action: AuditAction,
+    change: Option<(&str, Option<String>, Option<String>)>,
+    now: NaiveDateTime,
+) -> QueryResult<()> {
+    use schema::audit_log::dsl::*;
+
+    let (field, old, new) = match change {
+        Some((field, old, new)) => (Some(field), old, new),
+        None => (None, None, None),
+    };
+    diesel::insert_into(audit_log)
+        .values((
+            audited_table.eq(table),
+            row_id.eq(audited_row),
+            action.eq(audit_action),
+            field_name.eq(field),
+            old_value.eq(old),
+            new_value.eq(new),
+            changed_by.eq(current_user()),
+            changed_at.eq(now),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Stamps a new row and records its creation.
+fn audit_created<T: Audited>(conn: &mut SqliteConnection, created: i32) -> QueryResult<()> {
+    let now = Utc::now().naive_utc();
+    T::stamp(conn, created, true, now)?;
+    write_audit_entry(conn, T::TABLE, created, AuditAction::Create, None, now)
+}
+
+/// Compares a row with its state `before` an update, then stamps it and
+/// records each changed field. Nothing is recorded when nothing changed.
+fn audit_updated<T: Audited>(conn: &mut SqliteConnection, before: &T) -> QueryResult<()> {
+    let after = T::find(conn, before.row_id())?;
+    if before.audit_fields() == after.audit_fields() {
+        return Ok(());
+    }
+
+    let now = Utc::now().naive_utc();
+    T::stamp(conn, before.row_id(), false, now)?;
+    write_field_changes(conn, before, &after, now)
+}
+
+/// Records each field that differs between two states of a row.
+fn write_field_changes<T: Audited>(
+    conn: &mut SqliteConnection,
+    before: &T,
+    after: &T,
+    now: NaiveDateTime,
+) -> QueryResult<()> {
+    let changes = before
+        .audit_fields()
+        .into_iter()
+        .zip(after.audit_fields())
+        .filter(|((_, old), (_, new))| old != new);
+    for ((field, old), (_, new)) in changes {
+        write_audit_entry(
+            conn,
+            T::TABLE,
+            before.row_id(),
+            AuditAction::Update,
+            Some((field, Some(old), Some(new))),
+            now,
+        )?;
+    }
+    Ok(())
+}
+
+/// The rows of `T` among the `touched` ones, with their state before an
+/// undo or redo replays them.
+fn replayed_rows<T: Audited>(
+    conn: &mut SqliteConnection,
+    touched: &[(&str, i32)],
+) -> QueryResult<Vec<(i32, Option<T>)>> {
+    touched
+        .iter()
+        .filter(|(table, _)| *table == T::TABLE)
+        .map(|(_, row)| Ok((*row, T::find(conn, *row).optional()?)))
+        .collect()
+}
+
+/// Records what an undo or redo did to the rows `replayed_rows` returned.
+/// The rows keep the stamps they are restored with.
+fn audit_replayed<T: Audited>(
+    conn: &mut SqliteConnection,
+    rows: Vec<(i32, Option<T>)>,
+) -> QueryResult<()> {
+    let now = Utc::now().naive_utc();
+    for (row, before) in rows {
+        match (before, T::find(conn, row).optional()?) {
+            (None, Some(_)) => {
+                write_audit_entry(conn, T::TABLE, row, AuditAction::Create, None, now)?
+            }
+            (Some(before), None) => audit_deleted(conn, &before)?,
+            (Some(before), Some(after)) => write_field_changes(conn, &before, &after, now)?,
+            (None, None) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Records the deletion of a row with the values it had, one field at a
+/// time.
+fn audit_deleted<T: Audited>(conn: &mut SqliteConnection, before: &T) -> QueryResult<()> {
+    let now = Utc::now().naive_utc();
+    for (field, old) in before.audit_fields() {
+        write_audit_entry(
+            conn,
+            T::TABLE,
+            before.row_id(),
+            AuditAction::Delete,
+            Some((field, Some(old), None)),
+            now,
+        )?;
+    }
+    Ok(())
+}
+
+/// A row of an integrity check, which is "ok" for a sound database.
+#[derive(QueryableByName)]
+struct IntegrityCheck {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    integrity_check: String,
+}
+
+#[derive(QueryableByName)]
+struct RowCount {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    row_count: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_store() -> Store {
+        Store::in_memory().expect("in-memory store")
+    }
+
+    fn new_account(account_name: &str, minor_units: i64) -> NewAccount {
+        NewAccount {
+            name: account_name.to_string(),
+            account_type: AccountType::Checking,
+            initial_balance: Money::new(minor_units, 2),
+            account_description: "".to_string(),
+            currency_id: None,
+        }
+    }
+
+    fn new_category(category_name: &str, income: bool) -> NewCategory {
+        NewCategory {
+            name: category_name.to_string(),
+            is_income: income,
+            category_description: "".to_string(),
+            parent_category: None,
+        }
+    }
+
+    fn new_transaction(account_id: i32, category_id: i32, minor_units: i64) -> NewMoneyTransaction {
+        NewMoneyTransaction {
+            bank_account: account_id,
+            transaction_category: category_id,
+            description: "".to_string(),
+            amount: Money::new(minor_units, 2),
+            transaction_date: Utc::now().naive_utc(),
+            is_expense: true,
+            payee_id: None,
+        }
+    }
+
+    /// Creates an account and an expense category, returning their ids.
+    fn seed(store: &mut Store) -> (i32, i32) {
+        store.create_account(&new_account("Bank", 10000)).unwrap();
+        store
+            .create_category(&new_category("Groceries", false))
+            .unwrap();
+        (
+            store.get_accounts().unwrap()[0].id,
+            store.get_categories().unwrap()[0].id,
+        )
+    }
+
+    #[test]
+    fn migrations_seed_currencies() {
+        let mut store = test_store();
+        let currencies = store.get_currencies().unwrap();
+        assert!(!currencies.is_empty());
+        let first = store.get_currency_by_id(currencies[0].id).unwrap();
+        assert_eq!(first.symbol, currencies[0].symbol);
+        assert!(matches!(
+            store.get_currency_by_id(-1),
+            Err(DataStoreError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn transactions_need_an_existing_account() {
+        let mut store = test_store();
+        let (_, category_id) = seed(&mut store);
+        assert!(matches!(
+            store.create_money_transaction(&new_transaction(-1, category_id, 100)),
+            Err(DataStoreError::ConstraintViolation(_))
+        ));
+    }
+
+    #[test]
+    fn overflowing_amounts_are_errors() {
+        let largest = Money::new(i64::MAX, 0);
+        assert!(matches!(
+            largest.rescale(2),
+            Err(DataStoreError::AmountOverflow)
+        ));
+        assert!(matches!(
+            largest.checked_add(Money::new(1, 0)),
+            Err(DataStoreError::AmountOverflow)
+        ));
+        assert_eq!(Money::new(-125, 2).rescale(1).unwrap(), Money::new(-13, 1));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "amount overflow")]
+    fn overflowing_operators_panic_in_debug_builds() {
+        let _ = Money::new(i64::MAX, 0) + Money::new(1, 0);
+    }
+
+    #[test]
+    fn accounts_are_created_updated_and_archived() {
+        let mut store = test_store();
+        store
+            .create_accounts(&[new_account("Bank", 10000), new_account("Wallet", 0)])
+            .unwrap();
+        let bank = store
+            .get_accounts()
+            .unwrap()
+            .into_iter()
+            .find(|a| a.name == "Bank")
+            .unwrap();
+        assert_eq!(bank.initial_balance, 10000);
+
+        store
+            .update_account(&UpdateAccount {
+                id: bank.id,
+                name: "Main bank".to_string(),
+                account_type: AccountType::Savings,
+                initial_balance: Money::new(5000, 2),
+                account_description: "".to_string(),
+                currency_id: None,
+            })
+            .unwrap();
+        store.set_account_archived(bank.id, true).unwrap();
+
+        let bank = store
+            .get_accounts()
+            .unwrap()
+            .into_iter()
+            .find(|a| a.id == bank.id)
+            .unwrap();
+        assert_eq!(bank.name, "Main bank");
+        assert_eq!(bank.account_type, AccountType::Savings);
+        assert_eq!(bank.initial_balance, 5000);
+        assert!(bank.is_archived);
+    }
+
+    #[test]
+    fn expenses_are_converted_into_the_reporting_currency() {
+        let mut store = test_store();
+        let currencies: Vec<i32> = store
+            .get_currencies()
+            .unwrap()
+            .into_iter()
+            .filter(|c| c.scale == 2)
+            .map(|c| c.id)
+            .collect();
+        let (reporting, foreign) = (currencies[0], currencies[1]);
+        let (bank, groceries) = seed(&mut store);
+        store
+            .create_account(&NewAccount {
+                currency_id: Some(foreign),
+                ..new_account("Abroad", 0)
+            })
+            .unwrap();
+        let abroad = store.get_accounts().unwrap()[1].id;
+        store
+            .create_money_transaction(&new_transaction(bank, groceries, 1000))
+            .unwrap();
+        store
+            .create_money_transaction(&new_transaction(abroad, groceries, 1000))
+            .unwrap();
+
+        let today = Utc::now().date_naive();
+        let expenses = |store: &mut Store| {
+            store.calculate_expense_by_category(groceries, &today, &today, reporting)
+        };
+        assert!(matches!(
+            expenses(&mut store),
+            Err(DataStoreError::MissingExchangeRate(..))
+        ));
+
+        // only the opposite rate is known, so it is inverted
+        store
+            .create_exchange_rate(&NewExchangeRate {
+                from_currency: reporting,
+                to_currency: foreign,
+                rate: 2.,
+                rate_date: today,
+            })
+            .unwrap();
+        assert_eq!(expenses(&mut store).unwrap(), Money::new(1500, 2));
+    }
+
+    #[test]
+    fn transactions_change_the_account_balance() {
+        let mut store = test_store();
+        let (account_id, category_id) = seed(&mut store);
+        let reporting_currency = store.get_currencies().unwrap()[0].id;
+
+        let created = store
+            .create_money_transaction(&new_transaction(account_id, category_id, 1250))
+            .unwrap();
+        let balance = store
+            .get_account_balance(account_id, reporting_currency)
+            .unwrap();
+        assert_eq!(balance.minor_units(), 8750);
+
+        store
+            .update_money_transaction(&UpdateMoneyTransaction {
+                id: created,
+                bank_account: account_id,
+                transaction_category: category_id,
+                description: "".to_string(),
+                amount: Money::new(2000, 2),
+                transaction_date: Utc::now().naive_utc(),
+                is_expense: true,
+                payee_id: None,
+            })
+            .unwrap();
+        let balance = store
+            .get_account_balance(account_id, reporting_currency)
+            .unwrap();
+        assert_eq!(balance.minor_units(), 8000);
+
+        store.delete_money_transaction(created).unwrap();
+        assert!(store.get_money_transactions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn transfers_are_deleted_as_a_whole() {
+        let mut store = test_store();
+        store
+            .create_accounts(&[new_account("Bank", 10000), new_account("Wallet", 0)])
+            .unwrap();
+        let accounts = store.get_accounts().unwrap();
+        store
+            .create_transfer(&NewTransfer {
+                from_account: accounts[0].id,
+                to_account: accounts[1].id,
+                description: "".to_string(),
+                amount: Money::new(3000, 2),
+                to_amount: Money::new(3000, 2),
+                transaction_date: Utc::now().naive_utc(),
+            })
+            .unwrap();
+
+        let transactions = store.get_money_transactions().unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert!(transactions.iter().all(|t| t.is_transfer()));
+
+        store.delete_money_transaction(transactions[0].id).unwrap();
+        assert!(store.get_money_transactions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn transfers_are_cleared_on_both_sides() {
+        let mut store = test_store();
+        store
+            .create_accounts(&[new_account("Bank", 10000), new_account("Wallet", 0)])
+            .unwrap();
+        let accounts = store.get_accounts().unwrap();
+        store
+            .create_transfer(&NewTransfer {
+                from_account: accounts[0].id,
+                to_account: accounts[1].id,
+                description: "".to_string(),
+                amount: Money::new(3000, 2),
+                to_amount: Money::new(3000, 2),
+                transaction_date: Utc::now().naive_utc(),
+            })
+            .unwrap();
+
+        let outgoing = store.get_money_transactions().unwrap()[0].id;
+        store
+            .set_cleared_status(outgoing, ClearedStatus::Cleared)
+            .unwrap();
+        assert!(store
+            .get_money_transactions()
+            .unwrap()
+            .iter()
+            .all(|t| t.cleared_status == ClearedStatus::Cleared));
+    }
+
+    #[test]
+    fn a_failed_save_leaves_the_edited_transaction_alone() {
+        let mut store = test_store();
+        let (account_id, category_id) = seed(&mut store);
+        let edited = store
+            .create_money_transaction(&new_transaction(account_id, category_id, 2500))
+            .unwrap();
+
+        // the split lines do not add up, so nothing of the save is kept
+        let entry = TransactionEntry::Regular {
+            transaction: NewMoneyTransaction {
+                description: "Groceries".to_string(),
+                ..new_transaction(account_id, category_id, 4000)
+            },
+            payee: Some("Market".to_string()),
+            tag_names: vec!["food".to_string()],
+            splits: vec![NewTransactionSplit {
+                split_category: category_id,
+                amount: Money::new(1000, 2),
+                note: "".to_string(),
+            }],
+        };
+        assert!(matches!(
+            store.save_transaction(Some(edited), &entry),
+            Err(DataStoreError::SplitMismatch)
+        ));
+
+        let transactions = store.get_money_transactions().unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].id, edited);
+        assert_eq!(transactions[0].amount, 2500);
+        assert!(store.get_payees().unwrap().is_empty());
+        assert!(store.get_transaction_tags().unwrap().is_empty());
+    }
+
+    #[test]
+    fn deleted_categories_hand_over_their_transactions() {
+        let mut store = test_store();
+        let (account_id, category_id) = seed(&mut store);
+        store.create_category(&new_category("Food", false)).unwrap();
+        let food = store
+            .get_categories()
+            .unwrap()
+            .into_iter()
+            .find(|c| c.name == "Food")
+            .unwrap();
+        store
+            .create_money_transaction(&new_transaction(account_id, category_id, 1250))
+            .unwrap();
+
+        assert!(store.delete_category(category_id, category_id).is_err());
+        store.delete_category(category_id, food.id).unwrap();
+
+        let categories = store.get_categories().unwrap();
+        assert_eq!(categories.len(), 1);
+        let transactions = store.get_money_transactions().unwrap();
+        assert_eq!(transactions[0].transaction_category, Some(food.id));
+    }
+
+    #[test]
+    fn categories_cannot_become_their_own_ancestor() {
+        let mut store = test_store();
+        store.create_category(&new_category("Home", false)).unwrap();
+        let home = store.get_categories().unwrap()[0].id;
+        store
+            .create_category(&NewCategory {
+                parent_category: Some(home),
+                ..new_category("Rent", false)
+            })
+            .unwrap();
+        let rent = store
+            .get_categories()
+            .unwrap()
+            .into_iter()
+            .find(|c| c.name == "Rent")
+            .unwrap()
+            .id;
+
+        let result = store.update_category(&UpdateCategory {
+            id: home,
+            name: "Home",
+            is_income: false,
+            category_description: "".to_string(),
+            parent_category: Some(rent),
+        });
+        assert!(matches!(result, Err(DataStoreError::CategoryCycle)));
+    }
+
+    #[test]
+    fn recurring_dates_do_not_drift_after_short_months() {
+        let mut store = test_store();
+        let (account_id, category_id) = seed(&mut store);
+        let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        for (rule_frequency, start) in [
+            (Frequency::Monthly, date(2026, 1, 31)),
+            (Frequency::Yearly, date(2028, 2, 29)),
+        ] {
+            store
+                .create_recurring_transaction(&NewRecurringTransaction {
+                    bank_account: account_id,
+                    transaction_category: category_id,
+                    description: rule_frequency.as_str().to_string(),
+                    is_expense: true,
+                    amount: Money::new(100, 2),
+                    frequency: rule_frequency.as_str().to_string(),
+                    frequency_interval: 1,
+                    day_of_month: None,
+                    start_date: start,
+                    end_date: None,
+                    remaining_occurrences: None,
+                })
+                .unwrap();
+        }
+
+        store
+            .post_due_recurring_transactions(date(2032, 2, 1))
+            .unwrap();
+        let next_date = |store: &mut Store, rule_frequency: Frequency| {
+            store
+                .get_recurring_transactions()
+                .unwrap()
+                .into_iter()
+                .find(|rule| rule.description == rule_frequency.as_str())
+                .and_then(|rule| rule.next_date)
+        };
+        assert_eq!(
+            next_date(&mut store, Frequency::Monthly),
+            Some(date(2032, 2, 29))
+        );
+        assert_eq!(
+            next_date(&mut store, Frequency::Yearly),
+            Some(date(2032, 2, 29))
+        );
+
+        store.skip_recurring_transaction(1).unwrap();
+        assert_eq!(
+            next_date(&mut store, Frequency::Monthly),
+            Some(date(2032, 3, 31))
+        );
+    }
+
+    #[test]
+    fn changes_are_undone_and_redone_in_steps() {
+        let mut store = test_store();
+        assert!(!store.can_undo().unwrap());
+        store.create_account(&new_account("Bank", 10000)).unwrap();
+        assert!(store.checkpoint().unwrap());
+
+        assert!(store.undo().unwrap());
+        assert!(store.get_accounts().unwrap().is_empty());
+        assert!(store.can_redo().unwrap());
+
+        assert!(store.redo().unwrap());
+        assert_eq!(store.get_accounts().unwrap().len(), 1);
+        assert!(!store.can_redo().unwrap());
+    }
+
+    #[test]
+    fn undone_changes_stay_in_the_history() {
+        let mut store = test_store();
+        let (account_id, category_id) = seed(&mut store);
+        store.checkpoint().unwrap();
+        let transaction_id = store
+            .create_money_transaction(&new_transaction(account_id, category_id, 100))
+            .unwrap();
+        store.checkpoint().unwrap();
+
+        store.undo().unwrap();
+        store.redo().unwrap();
+        let actions = store
+            .get_transaction_history(transaction_id)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.action)
+            .collect::<Vec<_>>();
+        assert_eq!(actions.first(), Some(&AuditAction::Create));
+        assert!(actions.contains(&AuditAction::Delete));
+        assert_eq!(actions.last(), Some(&AuditAction::Create));
+    }
+
+    #[test]
+    fn transactions_are_found_by_word_prefixes() {
+        let mut store = test_store();
+        let (account_id, category_id) = seed(&mut store);
+        let market = store
+            .save_payee(&NewPayee {
+                name: "Corner Market".to_string(),
+                default_category: None,
+                default_account: None,
+            })
+            .unwrap();
+        let mut weekly = new_transaction(account_id, category_id, 4200);
+        weekly.description = "Weekly shopping".to_string();
+        weekly.payee_id = Some(market);
+        let weekly = store.create_money_transaction(&weekly).unwrap();
+        let mut rent = new_transaction(account_id, category_id, 90000);
+        rent.description = "Rent".to_string();
+        store.create_money_transaction(&rent).unwrap();
+
+        let found = store.search_transactions("corn week").unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].transaction_id, weekly);
+        assert_eq!(
+            highlighted_parts(&found[0].payee),
+            vec![("Corner", true), (" Market", false)]
+        );
+        assert_eq!(store.search_transactions("groc").unwrap().len(), 2);
+        // quoted words leave the operators of the query syntax as words
+        assert_eq!(store.search_transactions("\"rent").unwrap().len(), 1);
+        assert!(store
+            .search_transactions("rent OR weekly")
+            .unwrap()
+            .is_empty());
+        assert!(store.search_transactions("  ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn the_search_index_follows_changes() {
+        let mut store = test_store();
+        let (account_id, category_id) = seed(&mut store);
+        let created = store
+            .create_money_transaction(&new_transaction(account_id, category_id, 1250))
+            .unwrap();
+
+        store
+            .update_category(&UpdateCategory {
+                id: category_id,
+                name: "Food",
+                is_income: false,
+                category_description: "".to_string(),
+                parent_category: None,
+            })
+            .unwrap();
+        assert!(store.search_transactions("groceries").unwrap().is_empty());
+        assert_eq!(store.search_transactions("food").unwrap().len(), 1);
+        store.checkpoint().unwrap();
+
+        store.delete_money_transaction(created).unwrap();
+        assert!(store.search_transactions("food").unwrap().is_empty());
+        store.checkpoint().unwrap();
+        assert!(store.undo().unwrap());
+        assert_eq!(store.search_transactions("food").unwrap().len(), 1);
+    }
 }